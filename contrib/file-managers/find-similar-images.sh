@@ -0,0 +1,19 @@
+#!/bin/sh
+# Nautilus script: shows the images looking like the selected one in findimg.
+# Nautilus starts scripts without a terminal, so one is opened for the interface.
+image="$1"
+[ -n "$image" ] || exit 1
+
+for terminal in kgx ptyxis gnome-terminal x-terminal-emulator xterm; do
+    command -v "$terminal" >/dev/null 2>&1 || continue
+    case "$terminal" in
+        kgx | ptyxis | gnome-terminal)
+            exec "$terminal" -- findimg similar "$image" --open-grid
+            ;;
+        *)
+            exec "$terminal" -e findimg similar "$image" --open-grid
+            ;;
+    esac
+done
+echo "No terminal found to open findimg in" >&2
+exit 1
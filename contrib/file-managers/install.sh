@@ -0,0 +1,19 @@
+#!/bin/sh
+# Adds "Find similar images" to the context menu of images in Nautilus and Dolphin.
+# findimg has to be on the PATH and set up with a model.
+set -e
+here=$(dirname "$0")
+data="${XDG_DATA_HOME:-$HOME/.local/share}"
+
+mkdir -p "$data/nautilus/scripts"
+cp "$here/find-similar-images.sh" "$data/nautilus/scripts/Find similar images"
+chmod +x "$data/nautilus/scripts/Find similar images"
+
+# Plasma 6 reads service menus from kio/, Plasma 5 from kservices5/
+for menus in "$data/kio/servicemenus" "$data/kservices5/ServiceMenus"; do
+    mkdir -p "$menus"
+    cp "$here/findimg-similar.desktop" "$menus/"
+    chmod +x "$menus/findimg-similar.desktop"
+done
+
+echo "Installed, restart Nautilus (nautilus -q) or Dolphin to see it"
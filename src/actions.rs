@@ -0,0 +1,167 @@
+use crossterm::event::KeyCode;
+
+/// Something the user can do from anywhere in the interface. Keys and the command palette
+/// both go through this list, so an action added here shows up in the palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    EditSearch,
+    RefineSearch,
+    ClearSearch,
+    ChangeMode,
+    CycleModel,
+    CycleLayout,
+    ToggleTimeline,
+    ToggleSafeSearch,
+    ToggleHideBlurry,
+    ToggleTranslation,
+    UndoDelete,
+    OpenMap,
+    DrawSketch,
+    OpenPinned,
+    ExportPinned,
+    ClearPinned,
+    SetWallpaper,
+    CopyMarkdownLink,
+    CopyCollage,
+    RevealPrivate,
+    ServeResult,
+    VoiceSearch,
+    OpenSettings,
+    CycleTheme,
+    RescanLibrary,
+    ShowStats,
+    ShowJobs,
+    CleanUpLibrary,
+    Redraw,
+    TogglePerformanceHud,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 31] = [
+        Action::EditSearch,
+        Action::RefineSearch,
+        Action::ClearSearch,
+        Action::ChangeMode,
+        Action::CycleModel,
+        Action::CycleLayout,
+        Action::ToggleTimeline,
+        Action::ToggleSafeSearch,
+        Action::ToggleHideBlurry,
+        Action::ToggleTranslation,
+        Action::UndoDelete,
+        Action::OpenMap,
+        Action::DrawSketch,
+        Action::OpenPinned,
+        Action::ExportPinned,
+        Action::ClearPinned,
+        Action::SetWallpaper,
+        Action::CopyMarkdownLink,
+        Action::CopyCollage,
+        Action::RevealPrivate,
+        Action::ServeResult,
+        Action::VoiceSearch,
+        Action::OpenSettings,
+        Action::CycleTheme,
+        Action::RescanLibrary,
+        Action::ShowStats,
+        Action::ShowJobs,
+        Action::CleanUpLibrary,
+        Action::Redraw,
+        Action::TogglePerformanceHud,
+        Action::Quit,
+    ];
+
+    /// Name shown in the command palette
+    pub const fn name(self) -> &'static str {
+        match self {
+            Action::EditSearch => "Edit the search",
+            Action::RefineSearch => "Refine the results with the search",
+            Action::ClearSearch => "Clear the search",
+            Action::ChangeMode => "Change the search mode",
+            Action::CycleModel => "Switch to the next model",
+            Action::CycleLayout => "Change the result layout",
+            Action::ToggleTimeline => "Toggle the timeline",
+            Action::ToggleSafeSearch => "Toggle safe search",
+            Action::ToggleHideBlurry => "Toggle hiding blurry photos",
+            Action::ToggleTranslation => "Toggle translating queries into English",
+            Action::UndoDelete => "Undo the last delete",
+            Action::OpenMap => "Open the embedding map",
+            Action::DrawSketch => "Draw a sketch to search with",
+            Action::OpenPinned => "Open the pinned images",
+            Action::ExportPinned => "Copy the pinned images to a folder",
+            Action::ClearPinned => "Unpin all images",
+            Action::SetWallpaper => "Set the selected result as wallpaper",
+            Action::CopyMarkdownLink => "Copy a Markdown link to the selected result",
+            Action::CopyCollage => "Copy a collage of the best results",
+            Action::RevealPrivate => "Reveal or hide the selected private result",
+            Action::ServeResult => "Serve the selected result over HTTP for a browser",
+            Action::VoiceSearch => "Search by voice",
+            Action::OpenSettings => "Open the settings",
+            Action::CycleTheme => "Change the theme",
+            Action::RescanLibrary => "Rescan the library",
+            Action::ShowStats => "Show the library stats and unreadable files",
+            Action::ShowJobs => "Show the background jobs",
+            Action::CleanUpLibrary => "Clean up duplicates, bursts and blurry photos",
+            Action::Redraw => "Redraw the screen",
+            Action::TogglePerformanceHud => "Toggle the performance HUD",
+            Action::Quit => "Quit",
+        }
+    }
+
+    /// Key the action is bound to, shown next to its name. Some actions only have
+    /// a key while the search bar is focused.
+    pub const fn key(self) -> Option<&'static str> {
+        match self {
+            Action::EditSearch => Some("Enter"),
+            Action::RefineSearch => Some("Alt+Enter"),
+            Action::ClearSearch => Some("c"),
+            Action::ChangeMode => None,
+            Action::CycleModel => Some("]"),
+            Action::CycleLayout => Some("v"),
+            Action::ToggleTimeline => Some("t"),
+            Action::ToggleSafeSearch => Some("n"),
+            Action::UndoDelete => Some("u"),
+            Action::OpenMap => Some("m"),
+            Action::DrawSketch => Some("p"),
+            Action::OpenPinned => Some("o"),
+            Action::ToggleHideBlurry
+            | Action::ToggleTranslation
+            | Action::ExportPinned
+            | Action::ClearPinned => None,
+            Action::SetWallpaper => Some("w"),
+            Action::CopyMarkdownLink => Some("y"),
+            Action::CopyCollage => None,
+            Action::RevealPrivate => Some("b"),
+            Action::ServeResult => Some("g"),
+            Action::VoiceSearch => Some("a"),
+            Action::OpenSettings => Some("s"),
+            Action::CycleTheme
+            | Action::RescanLibrary
+            | Action::ShowStats
+            | Action::ShowJobs
+            | Action::CleanUpLibrary => None,
+            Action::Redraw => Some("r"),
+            Action::TogglePerformanceHud => Some("F2"),
+            Action::Quit => Some("q"),
+        }
+    }
+
+    /// The action bound to a key everywhere but in the mode selector. `p` draws a sketch
+    /// only from the search bar, on a result it pins the result.
+    pub const fn for_key(code: KeyCode) -> Option<Action> {
+        match code {
+            KeyCode::Char(']') => Some(Action::CycleModel),
+            KeyCode::Char('v') => Some(Action::CycleLayout),
+            KeyCode::Char('t') => Some(Action::ToggleTimeline),
+            KeyCode::Char('n') => Some(Action::ToggleSafeSearch),
+            KeyCode::Char('u') => Some(Action::UndoDelete),
+            KeyCode::Char('m') => Some(Action::OpenMap),
+            KeyCode::Char('o') => Some(Action::OpenPinned),
+            KeyCode::Char('a') => Some(Action::VoiceSearch),
+            KeyCode::Char('s') => Some(Action::OpenSettings),
+            KeyCode::F(2) => Some(Action::TogglePerformanceHud),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::expansion::normalized;
+
+#[derive(Deserialize)]
+struct Tensor {
+    dtype: String,
+    shape: Vec<usize>,
+    data_offsets: [usize; 2],
+}
+
+/// A fully connected layer, `weight` holds `outputs` rows of the inputs
+struct Linear {
+    weight: Vec<f32>,
+    bias: Vec<f32>,
+    inputs: usize,
+}
+
+/// The LAION aesthetic predictor: a small stack of linear layers over the CLIP image embedding
+/// that rates how good a photo looks from 1 to 10.
+///
+/// The weights are read from a safetensors file with `<layer>.weight` and `<layer>.bias` for
+/// every layer, applied in the order of the numbers in their names. The layers of the
+/// predictor have no activation between them, so none is applied.
+pub struct AestheticModel {
+    layers: Vec<Linear>,
+}
+
+impl AestheticModel {
+    pub fn load(file: &Path) -> Result<Self, String> {
+        let bytes = fs::read(file).map_err(|e| e.to_string())?;
+        let header_len = bytes
+            .get(..8)
+            .map(|len| u64::from_le_bytes(len.try_into().unwrap_or_default()) as usize)
+            .ok_or("not a safetensors file")?;
+        let header = bytes
+            .get(8..8usize.saturating_add(header_len))
+            .ok_or("not a safetensors file")?;
+        let data = &bytes[8 + header_len..];
+
+        let mut tensors: HashMap<String, serde_json::Value> =
+            serde_json::from_slice(header).map_err(|e| e.to_string())?;
+        tensors.remove("__metadata__");
+        let mut names: Vec<&String> = tensors
+            .keys()
+            .filter(|name| name.ends_with(".weight"))
+            .collect();
+        // `layers.10` comes after `layers.2`
+        names.sort_by_key(|name| {
+            name.split('.')
+                .filter_map(|part| part.parse::<usize>().ok())
+                .collect::<Vec<_>>()
+        });
+
+        let tensor = |name: &str| -> Result<(Vec<usize>, Vec<f32>), String> {
+            let value = tensors
+                .get(name)
+                .ok_or_else(|| format!("{} is missing", name))?;
+            let tensor: Tensor =
+                serde_json::from_value(value.clone()).map_err(|e| e.to_string())?;
+            if tensor.dtype != "F32" {
+                return Err(format!(
+                    "{} is {}, only F32 is supported",
+                    name, tensor.dtype
+                ));
+            }
+            let [start, end] = tensor.data_offsets;
+            let values = data
+                .get(start..end)
+                .ok_or_else(|| format!("{} is cut off", name))?
+                .chunks_exact(4)
+                .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                .collect();
+            Ok((tensor.shape, values))
+        };
+
+        let mut layers = Vec::new();
+        for name in names {
+            let (shape, weight) = tensor(name)?;
+            let (_, bias) = tensor(&name.replace(".weight", ".bias"))?;
+            let [outputs, inputs] = shape[..] else {
+                return Err(format!("{} is not a matrix", name));
+            };
+            if bias.len() != outputs {
+                return Err(format!("the bias of {} doesn't fit its weight", name));
+            }
+            if let Some(previous) = layers.last().map(|layer: &Linear| layer.bias.len())
+                && previous != inputs
+            {
+                return Err(format!("{} doesn't fit the layer before it", name));
+            }
+            layers.push(Linear {
+                weight,
+                bias,
+                inputs,
+            });
+        }
+        if layers.is_empty() {
+            return Err("the file has no layers".into());
+        }
+        Ok(Self { layers })
+    }
+
+    /// Length of the embeddings the predictor was trained on, 768 for ViT-L/14
+    fn dimensions(&self) -> usize {
+        self.layers[0].inputs
+    }
+
+    /// Rates an image embedding, `None` if it was made by a model of another size
+    pub fn score(&self, embedding: &[f32]) -> Option<f32> {
+        if embedding.len() != self.dimensions() {
+            return None;
+        }
+        let mut values = normalized(embedding.to_vec());
+        for layer in &self.layers {
+            values = layer
+                .weight
+                .chunks_exact(layer.inputs)
+                .zip(&layer.bias)
+                .map(|(row, bias)| row.iter().zip(&values).map(|(w, v)| w * v).sum::<f32>() + bias)
+                .collect();
+        }
+        values.first().copied()
+    }
+}
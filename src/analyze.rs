@@ -0,0 +1,189 @@
+use serde_json::json;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    num::NonZeroUsize,
+    path::Path,
+    thread,
+};
+
+use crate::{config::Config, expansion::normalized, index, models, store::VectorStore};
+
+/// Number of nearest neighbors listed per image without `--top`
+pub const DEFAULT_TOP: usize = 5;
+
+/// File formats the nearest neighbor edges can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeFormat {
+    /// `source,target,rank,similarity` with a header line
+    Csv,
+    /// An array of objects with `source`, `target`, `rank` and `similarity`
+    Json,
+}
+
+impl EdgeFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "csv" => Some(EdgeFormat::Csv),
+            "json" => Some(EdgeFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub const fn extension(self) -> &'static str {
+        match self {
+            EdgeFormat::Csv => "csv",
+            EdgeFormat::Json => "json",
+        }
+    }
+}
+
+/// An image and one of its nearest neighbors
+struct Edge<'a> {
+    source: &'a str,
+    target: &'a str,
+    /// 1 for the nearest neighbor
+    rank: usize,
+    similarity: f32,
+}
+
+/// Writes the `top` nearest neighbors of every image in the index of the main model, or of
+/// the additional model with the given name, as an edge list for graph tools and duplicate
+/// analysis. Returns the number of edges.
+pub fn export_pairwise(
+    config: &Config,
+    main_model: Option<&Path>,
+    model: Option<&str>,
+    top: usize,
+    format: EdgeFormat,
+    output: &Path,
+) -> Result<usize, String> {
+    let info = models::find_index(config, main_model, model)?;
+    let database = index::open_database(config, &info);
+    let image_paths = index::library_items(&config.library_dir());
+
+    pairwise(&*database, &image_paths, top, format, output).map_err(|e| e.to_string())
+}
+
+/// Compares every image with every other one by the cosine similarity of their embeddings
+/// and writes the `top` most similar of each to `output`. Images that are not indexed or
+/// whose embedding has an unexpected length are left out.
+pub fn pairwise(
+    database: &dyn VectorStore,
+    image_paths: &[String],
+    top: usize,
+    format: EdgeFormat,
+    output: &Path,
+) -> io::Result<usize> {
+    let data = database.get(image_paths);
+    let dimensions = data
+        .iter()
+        .map(|data| data.vector.len())
+        .find(|length| *length > 0)
+        .unwrap_or_default();
+    let rows: Vec<(&str, Vec<f32>)> = data
+        .iter()
+        .filter(|data| data.vector.len() == dimensions && dimensions > 0)
+        .map(|data| (data.id.as_str(), normalized(data.vector.clone())))
+        .collect();
+
+    // every image is compared with all others, so the rows are split across the cores
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk = rows.len().div_ceil(threads).max(1);
+    let neighbors: Vec<Vec<(usize, f32)>> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..rows.len())
+            .step_by(chunk)
+            .map(|start| {
+                let rows = &rows;
+                scope.spawn(move || {
+                    (start..(start + chunk).min(rows.len()))
+                        .map(|index| nearest(rows, index, top))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_default())
+            .collect()
+    });
+
+    let edges: Vec<Edge> = neighbors
+        .iter()
+        .enumerate()
+        .flat_map(|(source, neighbors)| {
+            neighbors
+                .iter()
+                .enumerate()
+                .map(move |(rank, (target, similarity))| (source, rank, *target, *similarity))
+        })
+        .map(|(source, rank, target, similarity)| Edge {
+            source: rows[source].0,
+            target: rows[target].0,
+            rank: rank + 1,
+            similarity,
+        })
+        .collect();
+
+    let mut file = BufWriter::new(File::create(output)?);
+    match format {
+        EdgeFormat::Csv => {
+            writeln!(file, "source,target,rank,similarity")?;
+            for edge in &edges {
+                writeln!(
+                    file,
+                    "{},{},{},{}",
+                    csv_field(edge.source),
+                    csv_field(edge.target),
+                    edge.rank,
+                    edge.similarity
+                )?;
+            }
+        }
+        EdgeFormat::Json => {
+            let edges: Vec<_> = edges
+                .iter()
+                .map(|edge| {
+                    json!({
+                        "source": edge.source,
+                        "target": edge.target,
+                        "rank": edge.rank,
+                        "similarity": edge.similarity,
+                    })
+                })
+                .collect();
+            serde_json::to_writer(&mut file, &edges)?;
+        }
+    }
+    file.flush()?;
+    Ok(edges.len())
+}
+
+/// The `top` rows most similar to the row at `index`, most similar first
+fn nearest(rows: &[(&str, Vec<f32>)], index: usize, top: usize) -> Vec<(usize, f32)> {
+    let vector = &rows[index].1;
+    let mut similar: Vec<(usize, f32)> = rows
+        .iter()
+        .enumerate()
+        .filter(|(other, _)| *other != index)
+        .map(|(other, (_, other_vector))| {
+            let similarity = vector.iter().zip(other_vector).map(|(a, b)| a * b).sum();
+            (other, similarity)
+        })
+        .collect();
+    if similar.len() > top {
+        similar.select_nth_unstable_by(top, |a, b| b.1.total_cmp(&a.1));
+        similar.truncate(top);
+    }
+    similar.sort_by(|a, b| b.1.total_cmp(&a.1));
+    similar
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
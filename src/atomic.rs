@@ -0,0 +1,122 @@
+use nano_vectordb_rs::NanoVectorDB;
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Writes a file by writing a temporary file next to it and renaming it over the target,
+/// so readers and crashes never see a half-written file.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let temp = with_suffix(path, ".tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(contents.as_ref())?;
+    file.sync_all()?;
+    fs::rename(temp, path)
+}
+
+/// Saves the index stored at `path` without risking a corrupted database.
+///
+/// The current database is moved to a backup first, which is only removed once the new
+/// one has been written completely. A leftover backup therefore marks an interrupted save.
+pub fn save_database(database: &NanoVectorDB, path: &Path) -> io::Result<()> {
+    let backup = with_suffix(path, ".bak");
+
+    if path.exists() {
+        fs::rename(path, &backup)?;
+    }
+    database.save().map_err(io::Error::other)?;
+    File::open(path)?.sync_all()?;
+
+    if backup.exists() {
+        fs::remove_file(backup)?;
+    }
+    Ok(())
+}
+
+/// Restores the backup of a save that got interrupted. Returns whether anything was restored.
+pub fn recover_database(path: &Path) -> io::Result<bool> {
+    let backup = with_suffix(path, ".bak");
+
+    if !backup.exists() {
+        return Ok(false);
+    }
+    fs::rename(backup, path)?;
+    Ok(true)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("findimg-atomic-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn write_replaces_the_file_without_leaving_a_temporary_one() {
+        let dir = temp_dir("write");
+        let file = dir.join("session.json");
+        write(&file, "old").unwrap();
+        write(&file, "new").unwrap();
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+        assert!(!with_suffix(&file, ".tmp").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_finished_save_leaves_no_backup() {
+        let dir = temp_dir("save");
+        let file = dir.join("embeddings.db");
+        let database = NanoVectorDB::new(2, &file.to_string_lossy()).unwrap();
+        fs::write(&file, "old").unwrap();
+        save_database(&database, &file).unwrap();
+
+        assert_ne!(fs::read_to_string(&file).unwrap(), "old");
+        assert!(!with_suffix(&file, ".bak").exists());
+        assert!(!recover_database(&file).unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn an_interrupted_save_is_recovered_from_the_backup() {
+        let dir = temp_dir("recover");
+        let file = dir.join("embeddings.db");
+        // a save that stopped while writing: the database was moved to the backup and only
+        // part of the new one is there
+        fs::write(&file, "old").unwrap();
+        fs::rename(&file, with_suffix(&file, ".bak")).unwrap();
+        fs::write(&file, "{\"embedding_dim\": 2, \"da").unwrap();
+
+        assert!(recover_database(&file).unwrap());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "old");
+        assert!(!with_suffix(&file, ".bak").exists());
+        // nothing left to recover
+        assert!(!recover_database(&file).unwrap());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "old");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_save_interrupted_before_writing_is_recovered() {
+        let dir = temp_dir("unwritten");
+        let file = dir.join("embeddings.db");
+        fs::write(with_suffix(&file, ".bak"), "old").unwrap();
+
+        assert!(recover_database(&file).unwrap());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "old");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
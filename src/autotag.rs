@@ -0,0 +1,99 @@
+use cliprs::ClipModel;
+use std::fs;
+use std::path::Path;
+
+use crate::{
+    curate::Library, expansion::normalized, metadata::Editable, remote, search::vector, sidecar,
+};
+
+/// Cosine similarity an image needs to a label to get it as tag without `--threshold`
+pub const DEFAULT_THRESHOLD: f32 = 0.25;
+
+/// What auto-tagging did to the library
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Images with an embedding that were classified
+    pub images: usize,
+    /// Images that got at least one new tag
+    pub tagged: usize,
+    /// Tags added over all images
+    pub tags: usize,
+    /// Sidecars that couldn't be written, with the reason
+    pub failed_sidecars: Vec<(String, String)>,
+}
+
+/// Reads the label vocabulary, one label per line. Empty lines and lines starting with `#`
+/// are skipped.
+pub fn read_labels(file: &Path) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(file).map_err(|e| e.to_string())?;
+    let labels: Vec<String> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect();
+    if labels.is_empty() {
+        return Err("The labels file has no labels".into());
+    }
+    Ok(labels)
+}
+
+/// Tags every image with the labels its embedding is at least `threshold` similar to.
+///
+/// Zero-shot classification: each label is embedded as `a photo of <label>` and compared with
+/// the stored image embeddings, so no image is decoded again. Tags the image already has are
+/// kept. With `xmp`, the tags of local images are also written to their XMP sidecars.
+pub fn autotag(
+    model: &ClipModel,
+    library: &Library,
+    labels: &[String],
+    threshold: f32,
+    xmp: bool,
+) -> Result<Report, String> {
+    let prompts = labels
+        .iter()
+        .map(|label| {
+            model
+                .embed_text(format!("a photo of {}", label))
+                .map(normalized)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut report = Report::default();
+    for path in library.image_paths {
+        let Some(embedding) = vector(library.database, path) else {
+            continue;
+        };
+        let embedding = normalized(embedding);
+        report.images += 1;
+
+        let mut fields = library.catalog.fields(path);
+        let mut editable = Editable::from_fields(&fields);
+        let mut added = 0;
+        for (label, prompt) in labels.iter().zip(&prompts) {
+            let similarity: f32 = embedding.iter().zip(prompt).map(|(a, b)| a * b).sum();
+            if similarity >= threshold && !editable.tags.contains(label) {
+                editable.tags.push(label.clone());
+                added += 1;
+            }
+        }
+        if added == 0 {
+            continue;
+        }
+        report.tagged += 1;
+        report.tags += added;
+
+        if xmp
+            && !remote::is_remote(path)
+            && let Err(e) = sidecar::write(path, &editable.tags, editable.rating)
+        {
+            report.failed_sidecars.push((path.clone(), e.to_string()));
+        }
+        editable.apply(&mut fields);
+        library
+            .catalog
+            .set_fields(path, &fields)
+            .map_err(|e| format!("Failed to save the tags of {}: {}", path, e))?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,217 @@
+use image::ImageFormat;
+use nano_vectordb_rs::Data;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Cursor,
+    path::Path,
+};
+
+use crate::{
+    cache,
+    catalog::Catalog,
+    config::Config,
+    engine::Similarity,
+    index,
+    models::{self, IndexInfo},
+    paths, privacy, remote,
+    store::StoreConfig,
+};
+
+/// Marks a bundle file
+const MAGIC: &[u8; 4] = b"FIBN";
+/// Version of the bundle format, bumped when the layout changes
+const FORMAT_VERSION: u8 = 1;
+/// Longest side of the thumbnails in a bundle, like the ones of remote items
+const THUMBNAIL_SIZE: u32 = 512;
+/// Written once a bundle is unpacked completely
+const UNPACKED_MARKER: &str = "unpacked";
+
+/// What a bundle holds besides the thumbnails
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    /// Name of the model the embeddings were made with, see `models::main_name`
+    model: String,
+    dimensions: usize,
+    similarity: Similarity,
+    images: Vec<Entry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// File name of the thumbnail
+    name: String,
+    vector: Vec<f32>,
+    fields: HashMap<String, Value>,
+    /// Length of the JPEG thumbnail in bytes
+    thumbnail: usize,
+}
+
+/// Writes the indexed library of the main model to a single file that others can search
+/// with `bundle open`: a thumbnail, the embedding and the metadata of every image, but
+/// not the originals. Images in private directories are left out. Returns the number of images in the bundle.
+///
+/// The file holds the magic bytes `FIBN`, the format version, the length of the JSON
+/// manifest as a little-endian `u64`, the manifest and the JPEG thumbnails one after another.
+pub fn create(config: &Config, main_model: Option<&Path>, output: &Path) -> Result<usize, String> {
+    let info = models::main_index(config, main_model);
+    let database = index::open_database(config, &info);
+    let catalog = Catalog::open(&paths::catalog_file()).map_err(|e| e.to_string())?;
+    let items = index::library_items(&config.library_dir());
+
+    let mut manifest = Manifest {
+        model: main_model.map_or("main".into(), models::main_name),
+        dimensions: info.dimensions,
+        similarity: info.similarity,
+        images: Vec::new(),
+    };
+    let mut thumbnails = Vec::new();
+    let mut names = HashSet::new();
+    for data in database.get(&items) {
+        if privacy::is_private(&config.private_dirs, &data.id) {
+            continue;
+        }
+        let Ok(image) = image::open(remote::local_file(&data.id)) else {
+            continue;
+        };
+        let mut thumbnail = Vec::new();
+        image
+            .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+            .to_rgb8()
+            .write_to(&mut Cursor::new(&mut thumbnail), ImageFormat::Jpeg)
+            .map_err(|e| e.to_string())?;
+
+        manifest.images.push(Entry {
+            name: unique_name(&data.id, &mut names),
+            vector: data.vector.clone(),
+            fields: catalog.fields(&data.id),
+            thumbnail: thumbnail.len(),
+        });
+        thumbnails.extend(thumbnail);
+    }
+
+    let header = serde_json::to_vec(&manifest).map_err(|e| e.to_string())?;
+    let mut bytes = Vec::with_capacity(MAGIC.len() + 9 + header.len() + thumbnails.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(FORMAT_VERSION);
+    bytes.extend_from_slice(&(header.len() as u64).to_le_bytes());
+    bytes.extend(header);
+    bytes.extend(thumbnails);
+    fs::write(output, bytes).map_err(|e| e.to_string())?;
+    Ok(manifest.images.len())
+}
+
+/// File name of the thumbnail of an image, its own name unless another image took it
+fn unique_name(path: &str, names: &mut HashSet<String>) -> String {
+    let stem = if remote::is_remote(path) {
+        path.rsplit('/').find(|part| !part.is_empty())
+    } else {
+        Path::new(path).file_stem().and_then(|stem| stem.to_str())
+    }
+    .unwrap_or("image")
+    .to_string();
+
+    let mut name = format!("{}.jpg", stem);
+    let mut number = 1;
+    while !names.insert(name.clone()) {
+        number += 1;
+        name = format!("{}-{}.jpg", stem, number);
+    }
+    name
+}
+
+/// Opens a bundle in place of the own library: it's unpacked to the cache directory once,
+/// which then stands in for the config and data directories. The config is copied from
+/// `config` without the library directory, the additional models and the vector store.
+/// Returns the name of the model the bundle was made with.
+pub fn open(file: &Path, config: &Config) -> Result<String, String> {
+    let hash = cache::content_hash(&file.to_string_lossy()).map_err(|e| e.to_string())?;
+    let dir = paths::cache_dir().join("bundles").join(hash);
+    let bytes = fs::read(file).map_err(|e| e.to_string())?;
+    let (manifest, thumbnails) = parse(&bytes)?;
+    if manifest.dimensions != index::DIMENSIONS {
+        return Err(format!(
+            "The bundle was made with a model with {} dimensions, the main model has {}",
+            manifest.dimensions,
+            index::DIMENSIONS
+        ));
+    }
+
+    paths::use_bundle_dir(dir.clone());
+    if dir.join(UNPACKED_MARKER).is_file() {
+        return Ok(manifest.model);
+    }
+    unpack(&manifest, thumbnails, config)?;
+    fs::write(dir.join(UNPACKED_MARKER), "").map_err(|e| e.to_string())?;
+    Ok(manifest.model)
+}
+
+fn parse(bytes: &[u8]) -> Result<(Manifest, &[u8]), String> {
+    let invalid = || "Not a findimg bundle".to_string();
+    let rest = bytes.strip_prefix(MAGIC).ok_or_else(invalid)?;
+    let (&version, rest) = rest.split_first().ok_or_else(invalid)?;
+    if version != FORMAT_VERSION {
+        return Err(format!(
+            "The bundle has format version {}, this findimg reads version {}",
+            version, FORMAT_VERSION
+        ));
+    }
+    let (length, rest) = rest.split_first_chunk::<8>().ok_or_else(invalid)?;
+    let length = usize::try_from(u64::from_le_bytes(*length)).map_err(|_| invalid())?;
+    if rest.len() < length {
+        return Err(invalid());
+    }
+    let (header, thumbnails) = rest.split_at(length);
+    let manifest: Manifest = serde_json::from_slice(header).map_err(|e| e.to_string())?;
+    Ok((manifest, thumbnails))
+}
+
+/// Writes the thumbnails, the index and the metadata of a bundle to the directories
+/// of the opened bundle
+fn unpack(manifest: &Manifest, mut thumbnails: &[u8], config: &Config) -> Result<(), String> {
+    let library_dir = paths::default_library_dir();
+    fs::create_dir_all(&library_dir).map_err(|e| e.to_string())?;
+    let config = Config {
+        library_dir: None,
+        models: Vec::new(),
+        vector_store: StoreConfig::Local,
+        ..config.clone()
+    };
+    config.save().map_err(|e| e.to_string())?;
+
+    let info = IndexInfo {
+        database_file: paths::database_file(),
+        dimensions: manifest.dimensions,
+        similarity: manifest.similarity,
+    };
+    let mut database = index::open_database(&config, &info);
+    let catalog = Catalog::open(&paths::catalog_file()).map_err(|e| e.to_string())?;
+    let mut data = Vec::new();
+    for entry in &manifest.images {
+        if thumbnails.len() < entry.thumbnail {
+            return Err("The bundle is cut off".into());
+        }
+        let (thumbnail, rest) = thumbnails.split_at(entry.thumbnail);
+        thumbnails = rest;
+
+        // the name comes from someone else's file, so it must not leave the directory
+        let Some(name) = Path::new(&entry.name).file_name() else {
+            continue;
+        };
+        let path = library_dir.join(name);
+        fs::write(&path, thumbnail).map_err(|e| e.to_string())?;
+        let path = path.display().to_string();
+        catalog
+            .set_fields(&path, &entry.fields)
+            .map_err(|e| e.to_string())?;
+        data.push(Data {
+            id: path,
+            vector: entry.vector.clone(),
+            fields: HashMap::new(),
+        });
+    }
+    database.upsert(data).map_err(|e| e.to_string())?;
+    database.save().map_err(|e| e.to_string())
+}
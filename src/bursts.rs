@@ -0,0 +1,147 @@
+use crate::index::cosine_similarity;
+
+/// A ranked image together with the near-identical shots that were folded into it.
+pub struct Burst {
+    pub representative: (String, f32),
+    pub members: Vec<(String, f32)>,
+}
+
+/// Folds near-identical images into the best ranked image of their burst.
+///
+/// Images are walked from best to worst match. An image whose embedding is at least
+/// `threshold` similar to an earlier representative joins that burst, otherwise it
+/// starts a new one. Stops after `limit` bursts were found.
+///
+/// # Arguments
+///
+/// - ranked: Paths and scores from best match to worst.
+/// - vector: Returns the embedding of a path. Images without one always start a new burst.
+/// - threshold: Cosine similarity above which two images are considered the same shot.
+/// - limit: Maximum number of bursts to return.
+pub fn group_bursts(
+    ranked: &[(String, f32)],
+    vector: impl Fn(&str) -> Option<Vec<f32>>,
+    threshold: f32,
+    limit: usize,
+) -> Vec<Burst> {
+    let mut bursts: Vec<(Burst, Option<Vec<f32>>)> = Vec::new();
+
+    for (path, score) in ranked {
+        let embedding = vector(path);
+
+        let similar = embedding.as_ref().and_then(|embedding| {
+            bursts.iter().position(|(_, other)| {
+                other
+                    .as_ref()
+                    .is_some_and(|other| cosine_similarity(embedding, other) >= threshold)
+            })
+        });
+
+        match similar {
+            Some(i) => bursts[i].0.members.push((path.clone(), *score)),
+            None if bursts.len() < limit => bursts.push((
+                Burst {
+                    representative: (path.clone(), *score),
+                    members: Vec::new(),
+                },
+                embedding,
+            )),
+            None => {}
+        }
+    }
+
+    bursts.into_iter().map(|(burst, _)| burst).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const THRESHOLD: f32 = 0.6;
+
+    fn ranked(paths: &[&str]) -> Vec<(String, f32)> {
+        paths
+            .iter()
+            .enumerate()
+            .map(|(i, path)| (path.to_string(), 1.0 - i as f32 / 10.0))
+            .collect()
+    }
+
+    fn paths(images: &[(String, f32)]) -> Vec<&str> {
+        images.iter().map(|(path, _)| path.as_str()).collect()
+    }
+
+    /// `a` and `a2` are the same shot, `a3` is exactly `THRESHOLD` similar to `a` and `b` is
+    /// a different one. `none` has no embedding.
+    fn vectors() -> HashMap<&'static str, Vec<f32>> {
+        HashMap::from([
+            ("a", vec![1.0, 0.0]),
+            ("a2", vec![0.99, 0.14]),
+            ("a3", vec![3.0, 4.0]),
+            ("b", vec![0.0, 1.0]),
+        ])
+    }
+
+    #[test]
+    fn shots_at_least_threshold_similar_join_the_best_ranked_one() {
+        let vectors = vectors();
+        let bursts = group_bursts(
+            &ranked(&["a", "b", "a2", "a3"]),
+            |path| vectors.get(path).cloned(),
+            THRESHOLD,
+            10,
+        );
+
+        assert_eq!(bursts.len(), 2);
+        assert_eq!(bursts[0].representative, ("a".to_string(), 1.0));
+        assert_eq!(paths(&bursts[0].members), ["a2", "a3"]);
+        assert_eq!(bursts[1].representative.0, "b");
+        assert!(bursts[1].members.is_empty());
+    }
+
+    #[test]
+    fn shots_below_the_threshold_start_their_own_burst() {
+        let vectors = vectors();
+        let bursts = group_bursts(
+            &ranked(&["a", "a3"]),
+            |path| vectors.get(path).cloned(),
+            THRESHOLD + 0.01,
+            10,
+        );
+
+        assert_eq!(bursts.len(), 2);
+    }
+
+    #[test]
+    fn images_without_an_embedding_are_never_folded() {
+        let vectors = vectors();
+        let bursts = group_bursts(
+            &ranked(&["none", "a", "none"]),
+            |path| vectors.get(path).cloned(),
+            THRESHOLD,
+            10,
+        );
+
+        let representatives: Vec<&str> = bursts
+            .iter()
+            .map(|burst| burst.representative.0.as_str())
+            .collect();
+        assert_eq!(representatives, ["none", "a", "none"]);
+    }
+
+    #[test]
+    fn the_limit_caps_the_bursts_but_later_shots_still_join_them() {
+        let vectors = vectors();
+        let bursts = group_bursts(
+            &ranked(&["a", "b", "a2"]),
+            |path| vectors.get(path).cloned(),
+            THRESHOLD,
+            1,
+        );
+
+        assert_eq!(bursts.len(), 1);
+        assert_eq!(bursts[0].representative.0, "a");
+        assert_eq!(paths(&bursts[0].members), ["a2"]);
+    }
+}
@@ -0,0 +1,119 @@
+use sha2::{Digest, Sha256};
+use std::{fs, io, path::PathBuf};
+
+use crate::{atomic, paths};
+
+/// Marks a cached embedding file
+const MAGIC: &[u8; 4] = b"FIEM";
+/// Version of the embedding file format, bumped when the layout changes
+const FORMAT_VERSION: u8 = 1;
+const EXTENSION: &str = "embed";
+
+/// Hex encoded SHA-256 of a file's content
+pub fn content_hash(path: &str) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+    Ok(Sha256::digest(&bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Embeddings stored in the cache directory by content hash, so they survive files being
+/// moved or shares being mounted at a different path.
+///
+/// Every embedding is a `.embed` file: the magic bytes `FIEM`, the format version, the id of
+/// the model that made it as a length-prefixed string, the number of dimensions as a
+/// little-endian `u32` and the values as little-endian `f32`. Embeddings of other models
+/// are ignored, so several models can share the cache.
+pub struct VectorCache {
+    dir: PathBuf,
+    model_id: String,
+}
+
+impl VectorCache {
+    /// Opens the cache for the embeddings of the model with the given id
+    pub fn open(model_id: &str) -> Self {
+        Self {
+            dir: paths::cache_dir().join("vectors"),
+            model_id: model_id.to_string(),
+        }
+    }
+
+    fn file(&self, hash: &str) -> PathBuf {
+        self.dir.join(hash).with_extension(EXTENSION)
+    }
+
+    pub fn get(&self, hash: &str) -> Option<Vec<f32>> {
+        let bytes = fs::read(self.file(hash)).ok()?;
+        let (model_id, vector) = decode(&bytes)?;
+        (model_id == self.model_id).then_some(vector)
+    }
+
+    pub fn put(&self, hash: &str, vector: &[f32]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        atomic::write(&self.file(hash), encode(&self.model_id, vector))
+    }
+
+    /// Converts the JSON embeddings of earlier versions, which didn't record their model,
+    /// to `.embed` files of this cache's model. Returns the number of converted files.
+    pub fn convert_legacy(&self) -> io::Result<usize> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+
+        let mut converted = 0;
+        for entry in entries {
+            let path = entry?.path();
+            // the legacy files are named by the bare hash
+            if path.extension().is_some() || !path.is_file() {
+                continue;
+            }
+            let Ok(vector) = serde_json::from_str::<Vec<f32>>(&fs::read_to_string(&path)?) else {
+                continue;
+            };
+            atomic::write(
+                &path.with_extension(EXTENSION),
+                encode(&self.model_id, &vector),
+            )?;
+            fs::remove_file(&path)?;
+            converted += 1;
+        }
+        Ok(converted)
+    }
+}
+
+fn encode(model_id: &str, vector: &[f32]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(MAGIC.len() + 7 + model_id.len() + vector.len() * 4);
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(FORMAT_VERSION);
+    bytes.extend_from_slice(&(model_id.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(model_id.as_bytes());
+    bytes.extend_from_slice(&(vector.len() as u32).to_le_bytes());
+    for value in vector {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+/// Reads the model id and the vector of an embedding file, `None` if it is damaged
+/// or in an unknown version
+fn decode(bytes: &[u8]) -> Option<(String, Vec<f32>)> {
+    let rest = bytes.strip_prefix(MAGIC)?;
+    let (&version, rest) = rest.split_first()?;
+    if version != FORMAT_VERSION {
+        return None;
+    }
+    let (length, rest) = rest.split_first_chunk::<2>()?;
+    let (model_id, rest) = rest.split_at_checked(u16::from_le_bytes(*length) as usize)?;
+    let (dimensions, rest) = rest.split_first_chunk::<4>()?;
+    if rest.len() != u32::from_le_bytes(*dimensions) as usize * 4 {
+        return None;
+    }
+    let vector = rest
+        .chunks_exact(4)
+        .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+        .collect();
+    Some((String::from_utf8(model_id.to_vec()).ok()?, vector))
+}
@@ -0,0 +1,30 @@
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use std::fs;
+
+#[derive(Deserialize)]
+struct CaptionResponse {
+    caption: String,
+}
+
+/// Generates a caption for an image using a remote captioning service.
+///
+/// The raw image bytes are sent as the body of a POST request to `endpoint`,
+/// which is expected to answer with `{"caption": "..."}`.
+pub fn caption_image(
+    client: &Client,
+    endpoint: &str,
+    path: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let bytes = fs::read(path)?;
+    let response = client
+        .post(endpoint)
+        .header("Content-Type", "application/octet-stream")
+        .body(bytes)
+        .send()?
+        .error_for_status()?
+        .text()?;
+
+    let response: CaptionResponse = serde_json::from_str(&response)?;
+    Ok(response.caption.trim().to_string())
+}
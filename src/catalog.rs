@@ -0,0 +1,613 @@
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter, types::Value as SqlValue};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Bound,
+    path::Path,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{
+    index::{
+        CAPTION_FIELD, COLORS_FIELD, DATE_FIELD, HASH_FIELD, HEIGHT_FIELD, NOTE_FIELD, NSFW_FIELD,
+        QUALITY_FIELD, RATING_FIELD, SHARPNESS_FIELD, SIDECAR_FIELD, TAGS_FIELD, WIDTH_FIELD,
+    },
+    query::{Filter, Orientation, SQUARE_TOLERANCE},
+};
+
+/// Schema changes in the order they were made. The schema version stored in the database
+/// is the number of migrations applied, so only the new ones run on an existing database.
+const MIGRATIONS: &[&str] = &[
+    // 1: images with their metadata, tags in their own table to look them up quickly
+    "CREATE TABLE images (
+        path TEXT PRIMARY KEY,
+        hash TEXT,
+        width INTEGER,
+        height INTEGER,
+        date TEXT,
+        caption TEXT,
+        note TEXT,
+        rating INTEGER,
+        nsfw INTEGER,
+        colors TEXT,
+        sidecar_time INTEGER,
+        extra TEXT NOT NULL DEFAULT '{}'
+    );
+    CREATE TABLE tags (
+        path TEXT NOT NULL REFERENCES images (path) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (path, tag)
+    );
+    CREATE INDEX tags_by_tag ON tags (tag);
+    CREATE INDEX images_by_rating ON images (rating);
+    CREATE INDEX images_by_date ON images (date);",
+    // 2: finding duplicates by content hash
+    "CREATE INDEX images_by_hash ON images (hash);",
+    // 3: quality scores to filter and hide images by, the sharpness the clean-up stored in
+    // `extra` moves to its column
+    "ALTER TABLE images ADD COLUMN sharpness REAL;
+    ALTER TABLE images ADD COLUMN quality REAL;
+    UPDATE images SET
+        sharpness = json_extract(extra, '$.sharpness'),
+        extra = json_remove(extra, '$.sharpness')
+    WHERE json_extract(extra, '$.sharpness') IS NOT NULL;
+    CREATE INDEX images_by_quality ON images (quality);",
+    // 4: files that failed to embed, skipped by indexing until their modification time changes
+    "CREATE TABLE failures (
+        path TEXT PRIMARY KEY,
+        reason TEXT NOT NULL,
+        modified INTEGER
+    );",
+];
+
+/// How a metadata field is stored in its column
+#[derive(Clone, Copy)]
+enum Kind {
+    Text,
+    Integer,
+    Boolean,
+    Real,
+    /// Any JSON value, stored as text
+    Json,
+}
+
+/// Metadata fields with their own column in `images`, named like the field.
+/// Other fields end up in the `extra` JSON object.
+const COLUMNS: [(&str, Kind); 12] = [
+    (HASH_FIELD, Kind::Text),
+    (WIDTH_FIELD, Kind::Integer),
+    (HEIGHT_FIELD, Kind::Integer),
+    (DATE_FIELD, Kind::Text),
+    (CAPTION_FIELD, Kind::Text),
+    (NOTE_FIELD, Kind::Text),
+    (RATING_FIELD, Kind::Integer),
+    (NSFW_FIELD, Kind::Boolean),
+    (COLORS_FIELD, Kind::Json),
+    (SIDECAR_FIELD, Kind::Integer),
+    (SHARPNESS_FIELD, Kind::Real),
+    (QUALITY_FIELD, Kind::Real),
+];
+
+/// A file that failed to embed, see `Catalog::record_failure`
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub path: String,
+    pub reason: String,
+    /// Modification time of the file when it failed, in seconds since the epoch
+    pub modified: Option<u64>,
+}
+
+/// Metadata of the images in an SQLite database, shared by the indexes of all models.
+///
+/// The metadata is handed around as the same field map the indexes used to store,
+/// so filters and the editor don't care where it comes from.
+pub struct Catalog {
+    connection: Mutex<Connection>,
+}
+
+impl Catalog {
+    /// Opens the database, creating it or bringing its schema up to date
+    pub fn open(file: &Path) -> rusqlite::Result<Self> {
+        if let Some(dir) = file.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let mut connection = Connection::open(file)?;
+        connection.execute_batch("PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON;")?;
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The metadata of an image, empty if there is none
+    pub fn fields(&self, path: &str) -> HashMap<String, Value> {
+        self.try_fields(path).unwrap_or_default()
+    }
+
+    fn try_fields(&self, path: &str) -> rusqlite::Result<HashMap<String, Value>> {
+        let connection = self.connection();
+        let columns: Vec<&str> = COLUMNS.iter().map(|(name, _)| *name).collect();
+        let row = connection
+            .query_row(
+                &format!(
+                    "SELECT {}, extra FROM images WHERE path = ?1",
+                    columns.join(", ")
+                ),
+                [path],
+                |row| {
+                    let mut values = Vec::new();
+                    for index in 0..=COLUMNS.len() {
+                        values.push(row.get::<_, SqlValue>(index)?);
+                    }
+                    Ok(values)
+                },
+            )
+            .optional()?;
+        let Some(mut values) = row else {
+            return Ok(HashMap::new());
+        };
+
+        let extra = match values.pop() {
+            Some(SqlValue::Text(extra)) => serde_json::from_str(&extra).unwrap_or_default(),
+            _ => HashMap::new(),
+        };
+        let mut fields: HashMap<String, Value> = extra;
+        for ((name, kind), value) in COLUMNS.iter().zip(values) {
+            if let Some(value) = from_sql(*kind, value) {
+                fields.insert(name.to_string(), value);
+            }
+        }
+
+        let mut statement =
+            connection.prepare_cached("SELECT tag FROM tags WHERE path = ?1 ORDER BY rowid")?;
+        let tags = statement
+            .query_map([path], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        if !tags.is_empty() {
+            fields.insert(TAGS_FIELD.into(), tags.into());
+        }
+        Ok(fields)
+    }
+
+    /// Replaces the metadata of an image
+    pub fn set_fields(&self, path: &str, fields: &HashMap<String, Value>) -> rusqlite::Result<()> {
+        let mut extra = fields.clone();
+        let mut values: Vec<SqlValue> = vec![SqlValue::Text(path.to_string())];
+        for (name, kind) in COLUMNS {
+            values.push(
+                extra
+                    .remove(name)
+                    .map_or(SqlValue::Null, |value| to_sql(kind, value)),
+            );
+        }
+        let tags: Vec<String> = extra
+            .remove(TAGS_FIELD)
+            .and_then(|tags| serde_json::from_value(tags).ok())
+            .unwrap_or_default();
+        values.push(SqlValue::Text(
+            serde_json::to_string(&extra).unwrap_or_else(|_| "{}".into()),
+        ));
+
+        let columns: Vec<&str> = COLUMNS.iter().map(|(name, _)| *name).collect();
+        let placeholders: Vec<String> = (1..=values.len()).map(|i| format!("?{}", i)).collect();
+        let updates: Vec<String> = columns
+            .iter()
+            .chain(["extra"].iter())
+            .map(|column| format!("{0} = excluded.{0}", column))
+            .collect();
+
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            &format!(
+                "INSERT INTO images (path, {}, extra) VALUES ({})
+                 ON CONFLICT (path) DO UPDATE SET {}",
+                columns.join(", "),
+                placeholders.join(", "),
+                updates.join(", ")
+            ),
+            params_from_iter(values),
+        )?;
+        transaction.execute("DELETE FROM tags WHERE path = ?1", [path])?;
+        for tag in &tags {
+            transaction.execute(
+                "INSERT OR IGNORE INTO tags (path, tag) VALUES (?1, ?2)",
+                params![path, tag],
+            )?;
+        }
+        transaction.commit()
+    }
+
+    /// Images flagged as not safe for work
+    pub fn flagged_nsfw(&self) -> HashSet<String> {
+        self.paths("SELECT path FROM images WHERE nsfw = 1", Vec::new())
+            .unwrap_or_default()
+    }
+
+    /// Images whose sharpness is below the threshold, see `quality::Quality`
+    pub fn blurry(&self, threshold: f32) -> HashSet<String> {
+        self.paths(
+            "SELECT path FROM images WHERE sharpness < ?1",
+            vec![SqlValue::Real(threshold as f64)],
+        )
+        .unwrap_or_default()
+    }
+
+    /// Images passing all filters. Most filters are answered by the database, the rest
+    /// are checked on the metadata of the images the database lets through.
+    pub fn matching(&self, filters: &[Filter]) -> HashSet<String> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        let mut remaining = Vec::new();
+        for filter in filters {
+            match condition(filter) {
+                Some((condition, value)) => {
+                    conditions.push(condition);
+                    values.extend(value);
+                }
+                None => remaining.push(filter),
+            }
+        }
+
+        let mut sql = "SELECT path FROM images".to_string();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        let mut paths = self.paths(&sql, values).unwrap_or_default();
+        if !remaining.is_empty() {
+            paths.retain(|path| {
+                let fields = self.fields(path);
+                remaining.iter().all(|filter| filter.matches(path, &fields))
+            });
+        }
+        paths
+    }
+
+    /// Images whose text `field` mentions any of the lowercase `words`, with the text.
+    /// The database only ignores the case of ASCII letters, so a word with other letters
+    /// lets every image with the field through and the caller checks the text.
+    pub fn mentioning(&self, field: &str, words: &[String]) -> Vec<(String, String)> {
+        self.try_mentioning(field, words).unwrap_or_default()
+    }
+
+    fn try_mentioning(
+        &self,
+        field: &str,
+        words: &[String],
+    ) -> rusqlite::Result<Vec<(String, String)>> {
+        let mut values = Vec::new();
+        let text = if COLUMNS.iter().any(|(name, _)| *name == field) {
+            field.to_string()
+        } else {
+            values.push(SqlValue::Text(format!("$.\"{}\"", field)));
+            "json_extract(extra, ?1)".to_string()
+        };
+        let mut conditions = Vec::new();
+        for word in words {
+            if word.is_ascii() {
+                let escaped = word
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
+                values.push(SqlValue::Text(format!("%{}%", escaped)));
+                conditions.push(format!("{} LIKE ?{} ESCAPE '\\'", text, values.len()));
+            } else {
+                conditions.push(format!("{} IS NOT NULL", text));
+            }
+        }
+        if conditions.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let connection = self.connection();
+        let mut statement = connection.prepare(&format!(
+            "SELECT path, {} FROM images WHERE {}",
+            text,
+            conditions.join(" OR ")
+        ))?;
+        let rows = statement.query_map(params_from_iter(values), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, SqlValue>(1)?))
+        })?;
+        let mut mentions = Vec::new();
+        for row in rows {
+            // e.g. a number someone stored in the field
+            if let (path, SqlValue::Text(text)) = row? {
+                mentions.push((path, text));
+            }
+        }
+        Ok(mentions)
+    }
+
+    /// Every image with metadata, including ones that left the library since
+    pub fn all_paths(&self) -> HashSet<String> {
+        self.paths("SELECT path FROM images", Vec::new())
+            .unwrap_or_default()
+    }
+
+    /// Images whose content has the hash, see `cache::content_hash`
+    pub fn paths_with_hash(&self, hash: &str) -> HashSet<String> {
+        self.paths(
+            "SELECT path FROM images WHERE hash = ?1",
+            vec![SqlValue::Text(hash.to_string())],
+        )
+        .unwrap_or_default()
+    }
+
+    /// Removes the metadata of the images, their tags go with them
+    pub fn remove(&self, paths: &[String]) -> rusqlite::Result<()> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        for path in paths {
+            transaction.execute("DELETE FROM images WHERE path = ?1", [path])?;
+        }
+        transaction.commit()
+    }
+
+    /// Remembers that a file failed to embed, so indexing skips it until it changes
+    pub fn record_failure(
+        &self,
+        path: &str,
+        reason: &str,
+        modified: Option<u64>,
+    ) -> rusqlite::Result<()> {
+        self.connection().execute(
+            "INSERT INTO failures (path, reason, modified) VALUES (?1, ?2, ?3)
+             ON CONFLICT (path) DO UPDATE SET reason = ?2, modified = ?3",
+            params![path, reason, modified.map(|modified| modified as i64)],
+        )?;
+        Ok(())
+    }
+
+    /// Every file that failed to embed and wasn't embedded since
+    pub fn failures(&self) -> Vec<Failure> {
+        let connection = self.connection();
+        let failures = connection
+            .prepare("SELECT path, reason, modified FROM failures ORDER BY path")
+            .and_then(|mut statement| {
+                statement
+                    .query_map([], |row| {
+                        Ok(Failure {
+                            path: row.get(0)?,
+                            reason: row.get(1)?,
+                            modified: row.get::<_, Option<i64>>(2)?.map(|time| time as u64),
+                        })
+                    })?
+                    .collect()
+            });
+        failures.unwrap_or_default()
+    }
+
+    /// Forgets the failures of the files, all of them if `paths` is `None`, so the next
+    /// indexing tries them again
+    pub fn forget_failures(&self, paths: Option<&[String]>) -> rusqlite::Result<()> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        match paths {
+            Some(paths) => {
+                for path in paths {
+                    transaction.execute("DELETE FROM failures WHERE path = ?1", [path])?;
+                }
+            }
+            None => {
+                transaction.execute("DELETE FROM failures", [])?;
+            }
+        }
+        transaction.commit()
+    }
+
+    /// Rewrites the database file without the space left by removed rows
+    pub fn vacuum(&self) -> rusqlite::Result<()> {
+        self.connection()
+            .execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")
+    }
+
+    fn paths(&self, sql: &str, values: Vec<SqlValue>) -> rusqlite::Result<HashSet<String>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(sql)?;
+        statement
+            .query_map(params_from_iter(values), |row| row.get(0))?
+            .collect()
+    }
+}
+
+/// Applies the migrations the database hasn't seen yet, each in its own transaction
+fn migrate(connection: &mut Connection) -> rusqlite::Result<()> {
+    let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index + 1)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+/// SQL condition matching exactly the images that pass a filter, `None` if the filter
+/// has to be checked on the metadata. Text filters are left to the metadata because SQLite
+/// only lowercases ASCII.
+fn condition(filter: &Filter) -> Option<(String, Vec<SqlValue>)> {
+    let ratio = "CAST(width AS REAL) / MAX(height, 1)";
+    let condition = match filter {
+        Filter::Tag(tag) => (
+            "EXISTS (SELECT 1 FROM tags WHERE tags.path = images.path AND tag = ?)".into(),
+            vec![SqlValue::Text(tag.clone())],
+        ),
+        Filter::Stars(range) => (
+            "COALESCE(rating, 0) BETWEEN ? AND ?".into(),
+            vec![
+                SqlValue::Integer(*range.start() as i64),
+                SqlValue::Integer(*range.end() as i64),
+            ],
+        ),
+        Filter::MinResolution(width, height) => (
+            "width >= ? AND height >= ?".into(),
+            vec![
+                SqlValue::Integer(*width as i64),
+                SqlValue::Integer(*height as i64),
+            ],
+        ),
+        Filter::Orientation(orientation) => {
+            let comparison = match orientation {
+                Orientation::Square => format!("ABS({} - 1.0) <= ?", ratio),
+                Orientation::Landscape => format!("{} > 1.0 + ?", ratio),
+                Orientation::Portrait => format!("{} < 1.0 - ?", ratio),
+            };
+            (
+                format!(
+                    "width IS NOT NULL AND height IS NOT NULL AND {}",
+                    comparison
+                ),
+                vec![SqlValue::Real(SQUARE_TOLERANCE)],
+            )
+        }
+        Filter::Quality(min, max) => {
+            let mut conditions = vec!["quality IS NOT NULL".to_string()];
+            let mut values = Vec::new();
+            for (bound, inclusive, exclusive) in [(min, ">=", ">"), (max, "<=", "<")] {
+                let (operator, value) = match bound {
+                    Bound::Included(value) => (inclusive, value),
+                    Bound::Excluded(value) => (exclusive, value),
+                    Bound::Unbounded => continue,
+                };
+                conditions.push(format!("quality {} ?", operator));
+                values.push(SqlValue::Real(*value as f64));
+            }
+            (conditions.join(" AND "), values)
+        }
+        Filter::PathPrefix(prefix) => (
+            "substr(path, 1, ?) = ?".into(),
+            vec![
+                SqlValue::Integer(prefix.chars().count() as i64),
+                SqlValue::Text(prefix.clone()),
+            ],
+        ),
+        Filter::Format(extensions) => {
+            let mut conditions = Vec::new();
+            let mut values = Vec::new();
+            for extension in extensions {
+                let suffix = format!(".{}", extension);
+                conditions.push("LOWER(substr(path, ?)) = ?");
+                values.push(SqlValue::Integer(-(suffix.chars().count() as i64)));
+                values.push(SqlValue::Text(suffix));
+            }
+            (format!("({})", conditions.join(" OR ")), values)
+        }
+        Filter::Date(min, max) => {
+            let mut conditions = vec!["date IS NOT NULL".to_string()];
+            let mut values = Vec::new();
+            for (bound, inclusive, exclusive) in [(min, ">=", ">"), (max, "<=", "<")] {
+                let (operator, value) = match bound {
+                    Bound::Included(value) => (inclusive, value),
+                    Bound::Excluded(value) => (exclusive, value),
+                    Bound::Unbounded => continue,
+                };
+                conditions.push(format!("date {} ?", operator));
+                values.push(SqlValue::Text(value.clone()));
+            }
+            (conditions.join(" AND "), values)
+        }
+        Filter::Caption(_) | Filter::Note(_) | Filter::Color(_) | Filter::Aesthetic(..) => {
+            return None;
+        }
+    };
+    Some(condition)
+}
+
+fn to_sql(kind: Kind, value: Value) -> SqlValue {
+    match (kind, value) {
+        (_, Value::Null) => SqlValue::Null,
+        (Kind::Text, Value::String(text)) => SqlValue::Text(text),
+        (Kind::Integer, value) => value.as_i64().map_or(SqlValue::Null, SqlValue::Integer),
+        (Kind::Boolean, value) => value
+            .as_bool()
+            .map_or(SqlValue::Null, |flag| SqlValue::Integer(flag as i64)),
+        (Kind::Real, value) => value.as_f64().map_or(SqlValue::Null, SqlValue::Real),
+        (_, value) => SqlValue::Text(value.to_string()),
+    }
+}
+
+fn from_sql(kind: Kind, value: SqlValue) -> Option<Value> {
+    match (kind, value) {
+        (_, SqlValue::Null) => None,
+        (Kind::Text, SqlValue::Text(text)) => Some(text.into()),
+        (Kind::Integer, SqlValue::Integer(number)) => Some(number.into()),
+        (Kind::Boolean, SqlValue::Integer(flag)) => Some((flag != 0).into()),
+        (Kind::Real, SqlValue::Real(number)) => Some(number.into()),
+        (Kind::Real, SqlValue::Integer(number)) => Some((number as f64).into()),
+        (Kind::Json, SqlValue::Text(json)) => serde_json::from_str(&json).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::WEB_TEXT_FIELD;
+    use serde_json::json;
+
+    fn catalog(name: &str) -> Catalog {
+        let file = std::env::temp_dir().join(format!(
+            "findimg-catalog-{}-{}.sqlite",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&file);
+        Catalog::open(&file).unwrap()
+    }
+
+    fn set(catalog: &Catalog, path: &str, field: &str, text: &str) {
+        let fields = HashMap::from([(field.to_string(), json!(text))]);
+        catalog.set_fields(path, &fields).unwrap();
+    }
+
+    fn paths(mut mentions: Vec<(String, String)>) -> Vec<String> {
+        mentions.sort();
+        mentions.into_iter().map(|(path, _)| path).collect()
+    }
+
+    #[test]
+    fn mentioning_finds_the_words_in_columns_and_extra_fields() {
+        let catalog = catalog("mentioning");
+        set(&catalog, "a.jpg", NOTE_FIELD, "Sunset at the Beach");
+        set(&catalog, "b.jpg", NOTE_FIELD, "mountains");
+        set(&catalog, "c.jpg", WEB_TEXT_FIELD, "beach party");
+        set(&catalog, "d.jpg", WEB_TEXT_FIELD, "100% beach_day");
+
+        let words = ["beach".to_string(), "sunset".to_string()];
+        assert_eq!(paths(catalog.mentioning(NOTE_FIELD, &words)), ["a.jpg"]);
+        assert_eq!(
+            paths(catalog.mentioning(WEB_TEXT_FIELD, &words)),
+            ["c.jpg", "d.jpg"]
+        );
+        // `%` and `_` are matched as they are, not as wildcards
+        assert_eq!(
+            paths(catalog.mentioning(WEB_TEXT_FIELD, &["0%".to_string()])),
+            ["d.jpg"]
+        );
+        assert!(
+            catalog
+                .mentioning(WEB_TEXT_FIELD, &["y_p".to_string()])
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn mentioning_leaves_words_beyond_ascii_to_the_caller() {
+        let catalog = catalog("unicode");
+        set(&catalog, "a.jpg", NOTE_FIELD, "ÜBER den Wolken");
+        set(&catalog, "b.jpg", CAPTION_FIELD, "über");
+
+        let mentions = catalog.mentioning(NOTE_FIELD, &["über".to_string()]);
+        assert_eq!(
+            mentions,
+            [("a.jpg".to_string(), "ÜBER den Wolken".to_string())]
+        );
+    }
+}
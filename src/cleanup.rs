@@ -0,0 +1,422 @@
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    num::NonZeroUsize,
+    thread,
+};
+
+use crate::{
+    catalog::Catalog,
+    index::{DATE_FIELD, HASH_FIELD, QUALITY_FIELD, SHARPNESS_FIELD, cosine_similarity},
+    quality::{self, Quality},
+    remote,
+    store::VectorStore,
+};
+
+/// Burst similarity used when `Config::burst_similarity` turns bursts off
+const DEFAULT_BURST_SIMILARITY: f32 = 0.95;
+/// Most blurry photos of a day shown together, so a group fits on one screen of results
+const BLURRY_GROUP_SIZE: usize = 10;
+
+/// Why the images of a group are up for cleaning
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupKind {
+    /// Copies of the same file
+    Duplicates,
+    /// Near-identical shots taken one after another
+    Burst,
+    /// Blurry photos taken on the same day
+    Blurry,
+}
+
+impl GroupKind {
+    pub const fn name(self) -> &'static str {
+        match self {
+            GroupKind::Duplicates => "Duplicates",
+            GroupKind::Burst => "Burst",
+            GroupKind::Blurry => "Blurry",
+        }
+    }
+}
+
+/// Images to decide on together
+#[derive(Debug, Clone)]
+pub struct Group {
+    pub kind: GroupKind,
+    /// Capture date of the images, if they have one
+    pub date: Option<String>,
+    /// The images, the one suggested to keep first for duplicates and bursts
+    pub paths: Vec<String>,
+}
+
+impl Group {
+    /// Whether an image is suggested for deletion: all but the first of duplicates and
+    /// bursts, every blurry image
+    pub fn suggests_deleting(&self, index: usize) -> bool {
+        self.kind == GroupKind::Blurry || index > 0
+    }
+}
+
+/// Finds what could be cleaned out of the library, in this order: copies of the same file,
+/// then bursts and then blurry photos along the timeline. Every image ends up in one group
+/// at most.
+///
+/// Bursts are shots of the same day next to each other in the timeline whose embeddings are
+/// at least `burst_similarity` similar, the sharpest one is suggested to keep. Photos whose
+/// sharpness is below `blur_threshold` are blurry. Images indexed before their quality was
+/// measured are measured here and the catalog remembers it.
+pub fn find_groups(
+    catalog: &Catalog,
+    database: &dyn VectorStore,
+    paths: &[String],
+    burst_similarity: Option<f32>,
+    blur_threshold: f32,
+) -> Vec<Group> {
+    let local: Vec<&String> = paths
+        .iter()
+        .filter(|path| !remote::is_remote(path))
+        .collect();
+    let fields: HashMap<&str, HashMap<String, Value>> = local
+        .iter()
+        .map(|path| (path.as_str(), catalog.fields(path)))
+        .collect();
+    let text = |path: &str, field: &str| {
+        fields
+            .get(path)
+            .and_then(|fields| fields.get(field))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    let sharpness = measure_sharpness(catalog, &local, &fields);
+    let sharper = |a: &String, b: &String| {
+        let sharpness = |path: &String| sharpness.get(path.as_str()).copied().unwrap_or_default();
+        sharpness(b).total_cmp(&sharpness(a)).then_with(|| a.cmp(b))
+    };
+
+    let mut groups = Vec::new();
+    let mut grouped: HashSet<String> = HashSet::new();
+
+    let mut by_hash: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for path in &local {
+        if let Some(hash) = text(path, HASH_FIELD) {
+            by_hash.entry(hash).or_default().push(path.to_string());
+        }
+    }
+    for mut copies in by_hash.into_values().filter(|copies| copies.len() > 1) {
+        // the copy with the shortest path is most likely the one that was filed away
+        copies.sort_by_key(|path| (path.len(), path.clone()));
+        grouped.extend(copies.iter().cloned());
+        groups.push(Group {
+            kind: GroupKind::Duplicates,
+            date: text(&copies[0], DATE_FIELD),
+            paths: copies,
+        });
+    }
+
+    // the timeline: by capture date, then by name, which orders the shots of a camera
+    let mut timeline: Vec<(Option<String>, &String)> = local
+        .iter()
+        .filter(|path| !grouped.contains(path.as_str()))
+        .map(|path| (text(path, DATE_FIELD), *path))
+        .collect();
+    timeline.sort();
+
+    let threshold = burst_similarity.unwrap_or(DEFAULT_BURST_SIMILARITY);
+    let vectors: HashMap<String, Vec<f32>> = database
+        .get(
+            &timeline
+                .iter()
+                .map(|(_, path)| path.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .into_iter()
+        .map(|data| (data.id.clone(), data.vector.clone()))
+        .collect();
+    let mut burst: Vec<String> = Vec::new();
+    let mut burst_date = None;
+    let mut bursts = Vec::new();
+    for (date, path) in &timeline {
+        let similar = burst.first().is_some_and(|first| {
+            burst_date == *date
+                && match (vectors.get(first), vectors.get(*path)) {
+                    (Some(first), Some(vector)) => cosine_similarity(first, vector) >= threshold,
+                    _ => false,
+                }
+        });
+        if !similar {
+            if burst.len() > 1 {
+                bursts.push((burst_date.clone(), std::mem::take(&mut burst)));
+            }
+            burst.clear();
+            burst_date = date.clone();
+        }
+        burst.push(path.to_string());
+    }
+    if burst.len() > 1 {
+        bursts.push((burst_date, burst));
+    }
+    for (date, mut shots) in bursts {
+        shots.sort_by(&sharper);
+        grouped.extend(shots.iter().cloned());
+        groups.push(Group {
+            kind: GroupKind::Burst,
+            date,
+            paths: shots,
+        });
+    }
+
+    let mut blurry: BTreeMap<Option<String>, Vec<String>> = BTreeMap::new();
+    for (date, path) in &timeline {
+        if !grouped.contains(path.as_str())
+            && sharpness
+                .get(path.as_str())
+                .is_some_and(|sharpness| *sharpness < blur_threshold)
+        {
+            blurry
+                .entry(date.clone())
+                .or_default()
+                .push(path.to_string());
+        }
+    }
+    for (date, paths) in blurry {
+        groups.extend(paths.chunks(BLURRY_GROUP_SIZE).map(|paths| Group {
+            kind: GroupKind::Blurry,
+            date: date.clone(),
+            paths: paths.to_vec(),
+        }));
+    }
+    groups
+}
+
+/// The sharpness of every image, measured on all cores for the images that don't have it
+/// in the catalog yet, see `quality::measure`, and stored there with their quality. Images
+/// that can't be read are left out.
+fn measure_sharpness<'a>(
+    catalog: &Catalog,
+    paths: &[&'a String],
+    fields: &HashMap<&str, HashMap<String, Value>>,
+) -> HashMap<&'a str, f32> {
+    let known = |path: &str| {
+        fields
+            .get(path)
+            .and_then(|fields| fields.get(SHARPNESS_FIELD))
+            .and_then(Value::as_f64)
+            .map(|sharpness| sharpness as f32)
+    };
+    let mut sharpness: HashMap<&str, f32> = paths
+        .iter()
+        .filter_map(|path| Some((path.as_str(), known(path)?)))
+        .collect();
+    let missing: Vec<&String> = paths
+        .iter()
+        .filter(|path| !sharpness.contains_key(path.as_str()))
+        .copied()
+        .collect();
+
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk = missing.len().div_ceil(threads).max(1);
+    let measured: Vec<(&String, Quality)> = thread::scope(|scope| {
+        let workers: Vec<_> = missing
+            .chunks(chunk)
+            .map(|paths| {
+                scope.spawn(move || {
+                    paths
+                        .iter()
+                        .filter_map(|path| {
+                            let image = image::open(path.as_str()).ok()?;
+                            Some((*path, quality::measure(&image)))
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_default())
+            .collect()
+    });
+
+    for (path, quality) in measured {
+        let mut fields = fields.get(path.as_str()).cloned().unwrap_or_default();
+        fields.insert(SHARPNESS_FIELD.into(), quality.sharpness.into());
+        fields.insert(QUALITY_FIELD.into(), quality.score().into());
+        let _ = catalog.set_fields(path, &fields);
+        sharpness.insert(path.as_str(), quality.sharpness);
+    }
+    sharpness
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+    use serde_json::json;
+
+    const BLUR_THRESHOLD: f32 = 100.0;
+    const BURST_SIMILARITY: f32 = 0.9;
+
+    /// A photo of the test library: its path, hash, capture date, sharpness and embedding
+    type Photo = (&'static str, &'static str, &'static str, f32, [f32; 2]);
+
+    fn find(name: &str, photos: &[Photo]) -> Vec<Group> {
+        let file = std::env::temp_dir().join(format!(
+            "findimg-cleanup-{}-{}.sqlite",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&file);
+        let catalog = Catalog::open(&file).unwrap();
+        for (path, hash, date, sharpness, _) in photos {
+            let fields = HashMap::from([
+                (HASH_FIELD.to_string(), json!(hash)),
+                (DATE_FIELD.to_string(), json!(date)),
+                (SHARPNESS_FIELD.to_string(), json!(sharpness)),
+            ]);
+            catalog.set_fields(path, &fields).unwrap();
+        }
+        let database = MemoryStore::new(
+            photos
+                .iter()
+                .map(|(path, _, _, _, vector)| (*path, vector.to_vec())),
+        );
+        let paths: Vec<String> = photos.iter().map(|photo| photo.0.to_string()).collect();
+
+        find_groups(
+            &catalog,
+            &database,
+            &paths,
+            Some(BURST_SIMILARITY),
+            BLUR_THRESHOLD,
+        )
+    }
+
+    fn summary(groups: &[Group]) -> Vec<(GroupKind, Vec<&str>)> {
+        groups
+            .iter()
+            .map(|group| {
+                let paths = group.paths.iter().map(String::as_str).collect();
+                (group.kind, paths)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_copy_with_the_shortest_path_is_kept() {
+        let groups = find(
+            "duplicates",
+            &[
+                (
+                    "/photos/backup/a.jpg",
+                    "h1",
+                    "2023-05-01",
+                    500.0,
+                    [1.0, 0.0],
+                ),
+                ("/photos/a.jpg", "h1", "2023-05-01", 500.0, [1.0, 0.0]),
+                ("/photos/old/a.jpg", "h1", "2023-05-01", 500.0, [1.0, 0.0]),
+                ("/photos/b.jpg", "h2", "2023-05-01", 500.0, [0.0, 1.0]),
+            ],
+        );
+
+        assert_eq!(
+            summary(&groups),
+            [(
+                GroupKind::Duplicates,
+                vec!["/photos/a.jpg", "/photos/old/a.jpg", "/photos/backup/a.jpg"]
+            )]
+        );
+        assert!(!groups[0].suggests_deleting(0));
+        assert!(groups[0].suggests_deleting(1));
+        assert_eq!(groups[0].date.as_deref(), Some("2023-05-01"));
+    }
+
+    #[test]
+    fn bursts_keep_the_sharpest_shot_first() {
+        let groups = find(
+            "bursts",
+            &[
+                ("/photos/1.jpg", "h1", "2023-05-01", 300.0, [1.0, 0.0]),
+                ("/photos/2.jpg", "h2", "2023-05-01", 900.0, [1.0, 0.1]),
+                ("/photos/3.jpg", "h3", "2023-05-01", 600.0, [1.0, 0.2]),
+                // a different picture ends the burst
+                ("/photos/4.jpg", "h4", "2023-05-01", 500.0, [0.0, 1.0]),
+                // the same picture on another day is no burst
+                ("/photos/5.jpg", "h5", "2023-05-02", 500.0, [0.0, 1.0]),
+            ],
+        );
+
+        assert_eq!(
+            summary(&groups),
+            [(
+                GroupKind::Burst,
+                vec!["/photos/2.jpg", "/photos/3.jpg", "/photos/1.jpg"]
+            )]
+        );
+    }
+
+    #[test]
+    fn shots_below_the_burst_similarity_are_no_burst() {
+        // cos(45°) is about 0.71, below the similarity of a burst
+        let groups = find(
+            "threshold",
+            &[
+                ("/photos/1.jpg", "h1", "2023-05-01", 500.0, [1.0, 0.0]),
+                ("/photos/2.jpg", "h2", "2023-05-01", 500.0, [1.0, 1.0]),
+            ],
+        );
+
+        assert!(groups.is_empty());
+    }
+
+    #[test]
+    fn blurry_photos_are_grouped_by_day_unless_they_are_in_another_group() {
+        let groups = find(
+            "blurry",
+            &[
+                ("/photos/a.jpg", "h1", "2023-05-01", 20.0, [1.0, 0.0]),
+                ("/photos/copy/a.jpg", "h1", "2023-05-01", 20.0, [1.0, 0.0]),
+                ("/photos/b.jpg", "h2", "2023-05-01", 50.0, [0.0, 1.0]),
+                ("/photos/c.jpg", "h3", "2023-05-02", 80.0, [-1.0, 0.0]),
+                ("/photos/d.jpg", "h4", "2023-05-02", 400.0, [0.0, -1.0]),
+            ],
+        );
+
+        assert_eq!(
+            summary(&groups),
+            [
+                (
+                    GroupKind::Duplicates,
+                    vec!["/photos/a.jpg", "/photos/copy/a.jpg"]
+                ),
+                (GroupKind::Blurry, vec!["/photos/b.jpg"]),
+                (GroupKind::Blurry, vec!["/photos/c.jpg"]),
+            ]
+        );
+        assert!(groups[1].suggests_deleting(0));
+    }
+
+    #[test]
+    fn remote_items_are_left_alone() {
+        let groups = find(
+            "remote",
+            &[
+                (
+                    "https://example.com/a.jpg",
+                    "h1",
+                    "2023-05-01",
+                    10.0,
+                    [1.0, 0.0],
+                ),
+                (
+                    "https://example.com/b.jpg",
+                    "h1",
+                    "2023-05-01",
+                    10.0,
+                    [1.0, 0.0],
+                ),
+            ],
+        );
+
+        assert!(groups.is_empty());
+    }
+}
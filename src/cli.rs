@@ -0,0 +1,563 @@
+use std::path::PathBuf;
+
+use crate::{
+    analyze::{self, EdgeFormat},
+    autotag,
+    eval::DEFAULT_K,
+    export::VectorFormat,
+    provider::LauncherFormat,
+};
+
+/// Number of photos picked by `curate` without `--count`
+const DEFAULT_CURATE_COUNT: usize = 20;
+
+/// Something to do instead of starting the interface.
+#[derive(Debug)]
+pub enum Command {
+    /// Pick a varied set of photos matching the prompt and copy them into a folder
+    Curate {
+        prompt: String,
+        count: usize,
+        output: PathBuf,
+        /// Also write an HTML gallery of the picked photos
+        html: bool,
+    },
+    /// Set one of the best matches of the prompt as the desktop wallpaper
+    Wallpaper {
+        prompt: String,
+        /// Keep running and pick a new wallpaper every day
+        daily: bool,
+    },
+    /// Write the embeddings of the library to a file for analysis in other tools
+    ExportVectors {
+        format: VectorFormat,
+        output: PathBuf,
+        /// Name of an additional model from the config, the main model if `None`
+        model: Option<String>,
+    },
+    /// Merge embeddings computed elsewhere into the index of a model
+    ImportVectors {
+        input: PathBuf,
+        /// Name of the model the embeddings were made with
+        model_id: String,
+    },
+    /// Rewrite cached embeddings of earlier versions in the current format
+    ConvertCache {
+        /// Model the old embeddings were made with, the main model if `None`
+        model_id: Option<String>,
+    },
+    /// Drop the images that left the library from the indexes and give back their space
+    Compact,
+    /// Encrypt the embeddings and metadata with a passphrase asked for on every start
+    EncryptIndex,
+    /// Store the embeddings and metadata unencrypted again
+    DecryptIndex,
+    /// Write the thumbnails, embeddings and metadata of the library to a single file
+    CreateBundle { output: PathBuf },
+    /// Search a bundle made by `CreateBundle` instead of the own library, read-only
+    OpenBundle { input: PathBuf },
+    /// Keep the library in step with a shared album, see `sync::Albums`
+    SyncAdd { url: String },
+    /// Stop syncing an album, its images stay in the library
+    SyncRemove { url: String },
+    /// Sync every album now
+    SyncRun,
+    /// Write the synthetic test corpus and its labels to a directory
+    Corpus { output: PathBuf },
+    /// Check that the model ranks the expected corpus image first for every query
+    Golden,
+    /// Measure how well the library is searched for queries with known relevant images
+    Eval { dataset: PathBuf, k: usize },
+    /// Rank the image files whose paths are piped in and print them from best match to worst
+    RankStdin { query: String },
+    /// Find the images looking like an image file, for the context menu of a file manager
+    Similar {
+        image: PathBuf,
+        /// Show them in the interface instead of printing them as JSON
+        open_grid: bool,
+    },
+    /// Print the best matches of a query for a launcher like Alfred, Raycast or KRunner
+    Provider {
+        format: LauncherFormat,
+        /// Read from the first line of stdin if `None`
+        query: Option<String>,
+    },
+    /// Write the nearest neighbors of every image as an edge list for graph tools
+    Pairwise {
+        top: usize,
+        format: EdgeFormat,
+        output: PathBuf,
+        /// Name of an additional model from the config, the main model if `None`
+        model: Option<String>,
+    },
+    /// Tag every image with the labels of a vocabulary it matches
+    Autotag {
+        labels: PathBuf,
+        threshold: f32,
+        /// Also write the tags to the XMP sidecars
+        xmp: bool,
+    },
+}
+
+/// Options passed on the command line.
+#[derive(Debug, Default)]
+pub struct Args {
+    /// Path of the CLIP model file, the one from the config is used if there is none
+    pub model_path: Option<String>,
+    /// Google Photos album or web gallery to download before starting
+    pub photos: Option<String>,
+    /// Add the `--photos` album as remote items instead of downloading it
+    pub remote: bool,
+    /// Cookies sent when fetching the `--photos` album, for albums that need a login
+    pub cookies: Option<PathBuf>,
+    /// Try the downloads that failed in an earlier scrape again
+    pub retry_failed: bool,
+    /// Shell command recording a spoken query, overrides `Config::mic_command`
+    pub mic_command: Option<String>,
+    /// Never write to the image library, derived data only goes to the cache directory
+    pub read_only: bool,
+    /// Run the setup wizard even if findimg is set up already
+    pub setup: bool,
+    /// Pick an image like fzf: the interface is drawn on the terminal and the path of the
+    /// selected result is printed on exit. Holds the search to start with, which may be empty.
+    pub picker: Option<String>,
+    pub command: Option<Command>,
+}
+
+impl Args {
+    /// Parses the arguments without the program name. Returns an error message if they are invalid.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut curate = false;
+        let mut prompt = None;
+        let mut count = None;
+        let mut output = None;
+        let mut html = false;
+        let mut wallpaper = false;
+        let mut daily = false;
+        let mut index = false;
+        let mut export_vectors = false;
+        let mut format = None;
+        let mut model = None;
+        let mut import_vectors = false;
+        let mut input = None;
+        let mut model_id = None;
+        let mut convert_cache = false;
+        let mut compact = false;
+        let mut encrypt = false;
+        let mut decrypt = false;
+        let mut bundle = false;
+        let mut bundle_create = false;
+        let mut bundle_open = false;
+        let mut bundle_file = None;
+        let mut sync = false;
+        let mut sync_add = false;
+        let mut sync_remove = false;
+        let mut sync_run = false;
+        let mut album = None;
+        let mut dev = false;
+        let mut corpus = false;
+        let mut golden = false;
+        let mut corpus_dir = None;
+        let mut eval = false;
+        let mut dataset = None;
+        let mut k = None;
+        let mut stdin_query = None;
+        let mut analyze = false;
+        let mut pairwise = false;
+        let mut top = None;
+        let mut autotag = false;
+        let mut labels = None;
+        let mut threshold = None;
+        let mut xmp = false;
+        let mut provider = false;
+        let mut similar = false;
+        let mut similar_image = None;
+        let mut open_grid = false;
+        let mut args = args.into_iter().peekable();
+
+        while let Some(arg) = args.next() {
+            let subcommand = curate
+                || index
+                || dev
+                || eval
+                || wallpaper
+                || bundle
+                || sync
+                || analyze
+                || autotag
+                || provider
+                || similar;
+            let sync_subcommand = sync_add || sync_remove || sync_run;
+            let index_subcommand =
+                export_vectors || import_vectors || convert_cache || compact || encrypt || decrypt;
+            match arg.as_str() {
+                "--photos" => {
+                    parsed.photos = Some(args.next().ok_or("--photos needs an album link")?);
+                }
+                "--cookies" => {
+                    parsed.cookies = Some(PathBuf::from(
+                        args.next().ok_or("--cookies needs a cookies.txt file")?,
+                    ));
+                }
+                "--read-only" => parsed.read_only = true,
+                "--remote" => parsed.remote = true,
+                "--retry-failed" => parsed.retry_failed = true,
+                "--mic-command" => {
+                    parsed.mic_command =
+                        Some(args.next().ok_or("--mic-command needs a shell command")?);
+                }
+                "--setup" => parsed.setup = true,
+                "--picker" => {
+                    parsed.picker = Some(
+                        args.next_if(|query| !query.starts_with("--"))
+                            .unwrap_or_default(),
+                    );
+                }
+                "--count" => {
+                    let value = args.next().ok_or("--count needs a number")?;
+                    count = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid count {}", value))?,
+                    );
+                }
+                "--output" => {
+                    output = Some(PathBuf::from(args.next().ok_or("--output needs a path")?));
+                }
+                "--html" => html = true,
+                "--daily" => daily = true,
+                "--format" => format = Some(args.next().ok_or("--format needs a format")?),
+                "--model" => model = Some(args.next().ok_or("--model needs a model name")?),
+                "--dataset" => {
+                    dataset = Some(PathBuf::from(
+                        args.next().ok_or("--dataset needs a labels file")?,
+                    ));
+                }
+                "--k" => {
+                    let value = args.next().ok_or("--k needs a number")?;
+                    k = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|k| *k > 0)
+                            .ok_or_else(|| format!("Invalid k {}", value))?,
+                    );
+                }
+                "--top" => {
+                    let value = args.next().ok_or("--top needs a number")?;
+                    top = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|top| *top > 0)
+                            .ok_or_else(|| format!("Invalid top {}", value))?,
+                    );
+                }
+                "--labels" => {
+                    labels = Some(PathBuf::from(
+                        args.next().ok_or("--labels needs a labels file")?,
+                    ));
+                }
+                "--threshold" => {
+                    let value = args.next().ok_or("--threshold needs a number")?;
+                    threshold = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|threshold: &f32| (-1.0..=1.0).contains(threshold))
+                            .ok_or_else(|| format!("Invalid threshold {}", value))?,
+                    );
+                }
+                "--xmp" => xmp = true,
+                "--open-grid" => open_grid = true,
+                "--stdin" => stdin_query = Some(args.next().ok_or("--stdin needs a query")?),
+                "--model-id" => {
+                    model_id = Some(args.next().ok_or("--model-id needs a model name")?);
+                }
+                flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+                "curate" if !subcommand => curate = true,
+                "index" if !subcommand => index = true,
+                "dev" if !subcommand => dev = true,
+                "eval" if !subcommand => eval = true,
+                "wallpaper" if !subcommand => wallpaper = true,
+                "bundle" if !subcommand => bundle = true,
+                "sync" if !subcommand => sync = true,
+                "analyze" if !subcommand => analyze = true,
+                "autotag" if !subcommand => autotag = true,
+                "provider" if !subcommand => provider = true,
+                "similar" if !subcommand => similar = true,
+                "pairwise" if analyze && !pairwise => pairwise = true,
+                "create" if bundle && !bundle_create && !bundle_open => bundle_create = true,
+                "open" if bundle && !bundle_create && !bundle_open => bundle_open = true,
+                "add" if sync && !sync_subcommand => sync_add = true,
+                "remove" if sync && !sync_subcommand => sync_remove = true,
+                "run" if sync && !sync_subcommand => sync_run = true,
+                "corpus" if dev && !corpus && !golden => corpus = true,
+                "golden" if dev && !corpus && !golden => golden = true,
+                "export-vectors" if index && !index_subcommand => export_vectors = true,
+                "import-vectors" if index && !index_subcommand => import_vectors = true,
+                "convert-cache" if index && !index_subcommand => convert_cache = true,
+                "compact" if index && !index_subcommand => compact = true,
+                "encrypt" if index && !index_subcommand => encrypt = true,
+                "decrypt" if index && !index_subcommand => decrypt = true,
+                _ if parsed.model_path.is_none() && !subcommand => parsed.model_path = Some(arg),
+                _ if (curate || wallpaper || provider) && prompt.is_none() => prompt = Some(arg),
+                _ if import_vectors && input.is_none() => input = Some(PathBuf::from(arg)),
+                _ if similar && similar_image.is_none() => similar_image = Some(PathBuf::from(arg)),
+                _ if corpus && corpus_dir.is_none() => corpus_dir = Some(PathBuf::from(arg)),
+                _ if (bundle_create || bundle_open) && bundle_file.is_none() => {
+                    bundle_file = Some(PathBuf::from(arg))
+                }
+                _ if (sync_add || sync_remove) && album.is_none() => album = Some(arg),
+                _ => return Err(format!("Unexpected argument {}", arg)),
+            }
+        }
+
+        if !export_vectors && !pairwise && model.is_some() {
+            return Err("--model only works with index export-vectors and analyze pairwise".into());
+        }
+        if !export_vectors && !pairwise && !provider && format.is_some() {
+            return Err(
+                "--format only works with index export-vectors, analyze pairwise and provider"
+                    .into(),
+            );
+        }
+        if !pairwise && top.is_some() {
+            return Err("--top only works with analyze pairwise".into());
+        }
+        if (parsed.remote || parsed.cookies.is_some()) && parsed.photos.is_none() {
+            return Err("--remote and --cookies only work with --photos".into());
+        }
+        if parsed.retry_failed && (parsed.photos.is_none() || parsed.remote) && !sync_run {
+            return Err("--retry-failed only works with --photos and sync run".into());
+        }
+        if sync_run && parsed.read_only {
+            return Err("sync run can not be used together with --read-only".into());
+        }
+        if !wallpaper && daily {
+            return Err("--daily only works with wallpaper".into());
+        }
+        if !eval && (dataset.is_some() || k.is_some()) {
+            return Err("--dataset and --k only work with eval".into());
+        }
+        if !autotag && (labels.is_some() || threshold.is_some() || xmp) {
+            return Err("--labels, --threshold and --xmp only work with autotag".into());
+        }
+        if !similar && open_grid {
+            return Err("--open-grid only works with similar".into());
+        }
+        if xmp && parsed.read_only {
+            return Err("--xmp can not be used together with --read-only".into());
+        }
+        if !import_vectors && !convert_cache && model_id.is_some() {
+            return Err("--model-id only works with index import-vectors and convert-cache".into());
+        }
+
+        if curate {
+            parsed.command = Some(Command::Curate {
+                prompt: prompt.ok_or("curate needs a prompt")?,
+                count: count.unwrap_or(DEFAULT_CURATE_COUNT),
+                output: output.unwrap_or_else(|| PathBuf::from("curated")),
+                html,
+            });
+        } else if wallpaper {
+            if count.is_some() || output.is_some() || html {
+                return Err("--count, --output and --html only work with curate".into());
+            }
+            parsed.command = Some(Command::Wallpaper {
+                prompt: prompt.ok_or("wallpaper needs a prompt")?,
+                daily,
+            });
+        } else if export_vectors {
+            if count.is_some() || html {
+                return Err("--count and --html only work with curate".into());
+            }
+            let format = format.ok_or("export-vectors needs --format npy, parquet or jsonl")?;
+            let format = VectorFormat::parse(&format)
+                .ok_or_else(|| format!("Unknown format {}, use npy, parquet or jsonl", format))?;
+            parsed.command = Some(Command::ExportVectors {
+                format,
+                output: output
+                    .unwrap_or_else(|| PathBuf::from(format!("vectors.{}", format.extension()))),
+                model,
+            });
+        } else if import_vectors {
+            if count.is_some() || output.is_some() || html {
+                return Err("--count, --output and --html only work with curate".into());
+            }
+            parsed.command = Some(Command::ImportVectors {
+                input: input.ok_or("import-vectors needs a file to import")?,
+                model_id: model_id
+                    .ok_or("import-vectors needs the --model-id the embeddings were made with")?,
+            });
+        } else if convert_cache {
+            if count.is_some() || output.is_some() || html {
+                return Err("--count, --output and --html only work with curate".into());
+            }
+            parsed.command = Some(Command::ConvertCache { model_id });
+        } else if compact {
+            if count.is_some() || output.is_some() || html {
+                return Err("--count, --output and --html only work with curate".into());
+            }
+            parsed.command = Some(Command::Compact);
+        } else if encrypt || decrypt {
+            if count.is_some() || output.is_some() || html {
+                return Err("--count, --output and --html only work with curate".into());
+            }
+            parsed.command = Some(if encrypt {
+                Command::EncryptIndex
+            } else {
+                Command::DecryptIndex
+            });
+        } else if eval {
+            if count.is_some() || output.is_some() || html {
+                return Err("--count, --output and --html only work with curate".into());
+            }
+            parsed.command = Some(Command::Eval {
+                dataset: dataset.ok_or("eval needs a --dataset of labeled queries")?,
+                k: k.unwrap_or(DEFAULT_K),
+            });
+        } else if corpus || golden {
+            if count.is_some() || output.is_some() || html {
+                return Err("--count, --output and --html only work with curate".into());
+            }
+            parsed.command = Some(if corpus {
+                Command::Corpus {
+                    output: corpus_dir.ok_or("corpus needs a directory to write to")?,
+                }
+            } else {
+                Command::Golden
+            });
+        } else if bundle_create || bundle_open {
+            if count.is_some() || output.is_some() || html {
+                return Err("--count, --output and --html only work with curate".into());
+            }
+            let file = bundle_file.ok_or("bundle needs a file")?;
+            parsed.command = Some(if bundle_create {
+                Command::CreateBundle { output: file }
+            } else {
+                Command::OpenBundle { input: file }
+            });
+        } else if sync_add || sync_remove || sync_run {
+            if count.is_some() || output.is_some() || html {
+                return Err("--count, --output and --html only work with curate".into());
+            }
+            parsed.command = Some(if sync_run {
+                Command::SyncRun
+            } else {
+                let url = album.ok_or("sync add and remove need an album link")?;
+                if sync_add {
+                    Command::SyncAdd { url }
+                } else {
+                    Command::SyncRemove { url }
+                }
+            });
+        } else if pairwise {
+            if count.is_some() || html {
+                return Err("--count and --html only work with curate".into());
+            }
+            let format = match format {
+                Some(format) => EdgeFormat::parse(&format)
+                    .ok_or_else(|| format!("Unknown format {}, use csv or json", format))?,
+                None => EdgeFormat::Csv,
+            };
+            parsed.command = Some(Command::Pairwise {
+                top: top.unwrap_or(analyze::DEFAULT_TOP),
+                format,
+                output: output
+                    .unwrap_or_else(|| PathBuf::from(format!("pairwise.{}", format.extension()))),
+                model,
+            });
+        } else if autotag {
+            if count.is_some() || output.is_some() || html {
+                return Err("--count, --output and --html only work with curate".into());
+            }
+            parsed.command = Some(Command::Autotag {
+                labels: labels.ok_or("autotag needs --labels with a file of labels")?,
+                threshold: threshold.unwrap_or(autotag::DEFAULT_THRESHOLD),
+                xmp,
+            });
+        } else if provider {
+            if count.is_some() || output.is_some() || html {
+                return Err("--count, --output and --html only work with curate".into());
+            }
+            let format = format.ok_or("provider needs --format raycast, alfred or krunner")?;
+            parsed.command = Some(Command::Provider {
+                format: LauncherFormat::parse(&format).ok_or_else(|| {
+                    format!("Unknown format {}, use raycast, alfred or krunner", format)
+                })?,
+                query: prompt,
+            });
+        } else if similar {
+            if count.is_some() || output.is_some() || html {
+                return Err("--count, --output and --html only work with curate".into());
+            }
+            parsed.command = Some(Command::Similar {
+                image: similar_image.ok_or("similar needs an image")?,
+                open_grid,
+            });
+        } else if dev {
+            return Err("dev needs the subcommand corpus or golden".into());
+        } else if bundle {
+            return Err("bundle needs the subcommand create or open".into());
+        } else if sync {
+            return Err("sync needs the subcommand add, remove or run".into());
+        } else if analyze {
+            return Err("analyze needs the subcommand pairwise".into());
+        } else if index {
+            return Err(
+                "index needs the subcommand export-vectors, import-vectors, convert-cache, compact, encrypt or decrypt".into(),
+            );
+        } else if count.is_some() || output.is_some() || html {
+            return Err("--count, --output and --html only work with curate".into());
+        }
+
+        if let Some(query) = stdin_query {
+            if parsed.command.is_some() {
+                return Err("--stdin can not be combined with a command".into());
+            }
+            parsed.command = Some(Command::RankStdin { query });
+        }
+        if parsed.picker.is_some()
+            && parsed
+                .command
+                .as_ref()
+                .is_some_and(|command| !matches!(command, Command::OpenBundle { .. }))
+        {
+            return Err("--picker only works with the interface, not with commands".into());
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Text printed when the arguments are invalid
+pub fn usage(program: &str) -> String {
+    format!(
+        "Usage: {0} [model_path] [--photos <album or gallery link> [--remote] [--cookies <cookies.txt>] [--retry-failed]] [--mic-command <command>] [--read-only] [--setup]
+       {0} [model_path] --picker [query]
+       {0} [model_path] --stdin <query> < paths
+       {0} [model_path] curate <prompt> [--count <n>] [--output <dir>] [--html]
+       {0} [model_path] wallpaper <prompt> [--daily]
+       {0} index export-vectors --format <npy|parquet|jsonl> [--output <file>] [--model <name>]
+       {0} index import-vectors <file.jsonl> --model-id <name>
+       {0} [model_path] index convert-cache [--model-id <name>]
+       {0} index compact
+       {0} index encrypt|decrypt
+       {0} analyze pairwise [--top <n>] [--format <csv|json>] [--output <file>] [--model <name>]
+       {0} [model_path] bundle create <file.fib>
+       {0} [model_path] bundle open <file.fib>
+       {0} sync add|remove <album or gallery link>
+       {0} [model_path] sync run [--retry-failed]
+       {0} dev corpus <dir>
+       {0} [model_path] dev golden
+       {0} [model_path] eval --dataset <labels.csv> [--k <n>]
+       {0} [model_path] autotag --labels <labels.txt> [--threshold <similarity>] [--xmp]
+       {0} [model_path] provider --format <raycast|alfred|krunner> [query]
+       {0} [model_path] similar <image> [--open-grid]
+
+The model path can be left out once it is saved by the setup wizard.",
+        program
+    )
+}
@@ -0,0 +1,113 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::terminal;
+
+/// Commands that put their input into the clipboard, tried in order
+const COPY_COMMANDS: [&[&str]; 4] = [
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard", "-in"],
+    &["pbcopy"],
+    // clip.exe reads the input in the code page of the console, which garbles UTF-8
+    &[
+        "powershell",
+        "-NoProfile",
+        "-Command",
+        "[Console]::InputEncoding = [Text.Encoding]::UTF8; Set-Clipboard ([Console]::In.ReadToEnd())",
+    ],
+];
+
+/// Commands that put a PNG image from their input into the clipboard, tried in order
+const COPY_IMAGE_COMMANDS: [&[&str]; 2] = [
+    &["wl-copy", "--type", "image/png"],
+    &["xclip", "-selection", "clipboard", "-t", "image/png", "-in"],
+];
+
+/// Puts a PNG file into the clipboard. There is no terminal fallback like for text, OSC 52
+/// only carries text.
+pub fn copy_image(file: &Path) -> io::Result<()> {
+    let png = fs::read(file)?;
+    if COPY_IMAGE_COMMANDS
+        .iter()
+        .any(|command| run(command, &png).is_ok())
+    {
+        return Ok(());
+    }
+    // pbcopy only copies text, AppleScript and .NET read the image from the file
+    let file = file.display().to_string();
+    let script;
+    let command: &[&str] = if cfg!(target_os = "macos") {
+        script = format!(
+            "set the clipboard to (read (POSIX file \"{}\") as «class PNGf»)",
+            file.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+        &["osascript", "-e", &script]
+    } else if cfg!(windows) {
+        script = format!(
+            "Add-Type -AssemblyName System.Windows.Forms; [Windows.Forms.Clipboard]::SetImage([Drawing.Image]::FromFile('{}'))",
+            file.replace('\'', "''")
+        );
+        &["powershell", "-NoProfile", "-STA", "-Command", &script]
+    } else {
+        return Err(io::Error::other(
+            "no clipboard command for images found, install wl-clipboard or xclip",
+        ));
+    };
+    run(command, &[])
+}
+
+/// Puts text into the clipboard. Without any of the clipboard commands the terminal is asked
+/// to do it with OSC 52, which also works over SSH but not in every terminal.
+pub fn copy(text: &str) -> io::Result<()> {
+    if COPY_COMMANDS
+        .iter()
+        .any(|command| run(command, text.as_bytes()).is_ok())
+    {
+        return Ok(());
+    }
+    if terminal::is_legacy_console() {
+        return Err(io::Error::other("no clipboard command found"));
+    }
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()
+}
+
+fn run(command: &[&str], input: &[u8]) -> io::Result<()> {
+    let mut child = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input)?;
+    }
+    if child.wait()?.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{} failed", command[0])))
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let value = chunk.iter().enumerate().fold(0u32, |value, (i, byte)| {
+            value | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(value >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,129 @@
+use image::{GenericImage, ImageFormat, Rgb, RgbImage};
+use std::io::Cursor;
+
+use crate::{locale::Locale, remote};
+
+/// Side of the square each image is fitted into, in pixels
+const CELL: u32 = 320;
+/// Space between the cells and around the collage
+const GAP: u32 = 12;
+/// Height of the caption below each image
+const CAPTION: u32 = 30;
+/// Factor the 5x7 glyphs are scaled up by
+const GLYPH_SCALE: u32 = 3;
+const BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+const TEXT: Rgb<u8> = Rgb([32, 32, 32]);
+/// Fills the cell of an image that can't be read
+const PLACEHOLDER: Rgb<u8> = Rgb([200, 200, 200]);
+
+/// Stitches results into one PNG: a grid of the images in the order given, each with its rank
+/// and confidence below it. Images that can't be read leave a gray cell.
+pub fn make(results: &[(String, f32)], locale: Locale) -> Result<Vec<u8>, String> {
+    if results.is_empty() {
+        return Err("there are no results".into());
+    }
+    let columns = (results.len() as f64).sqrt().ceil() as u32;
+    let rows = (results.len() as u32).div_ceil(columns);
+    let mut collage = RgbImage::from_pixel(
+        GAP + columns * (CELL + GAP),
+        GAP + rows * (CELL + CAPTION + GAP),
+        BACKGROUND,
+    );
+
+    for (index, (path, confidence)) in results.iter().enumerate() {
+        let x = GAP + index as u32 % columns * (CELL + GAP);
+        let y = GAP + index as u32 / columns * (CELL + CAPTION + GAP);
+        let cell = match image::open(remote::local_file(path)) {
+            Ok(image) => image.thumbnail(CELL, CELL).to_rgb8(),
+            Err(_) => RgbImage::from_pixel(CELL, CELL, PLACEHOLDER),
+        };
+        // centered in the cell
+        collage
+            .copy_from(
+                &cell,
+                x + (CELL - cell.width()) / 2,
+                y + (CELL - cell.height()) / 2,
+            )
+            .map_err(|e| e.to_string())?;
+        let caption = format!("#{}  {}", index + 1, locale.percent(*confidence as f64));
+        draw_text(
+            &mut collage,
+            &caption,
+            x,
+            y + CELL + (CAPTION - 7 * GLYPH_SCALE) / 2,
+        );
+    }
+
+    let mut png = Vec::new();
+    collage
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(png)
+}
+
+/// Draws text with the glyphs of `glyph`, characters without one are left blank
+fn draw_text(image: &mut RgbImage, text: &str, x: u32, y: u32) {
+    for (index, character) in text.chars().enumerate() {
+        let left = x + index as u32 * 6 * GLYPH_SCALE;
+        for (row, bits) in glyph(character).iter().enumerate() {
+            for column in 0..5 {
+                if bits & (0b10000 >> column) == 0 {
+                    continue;
+                }
+                for dy in 0..GLYPH_SCALE {
+                    for dx in 0..GLYPH_SCALE {
+                        let px = left + column * GLYPH_SCALE + dx;
+                        let py = y + row as u32 * GLYPH_SCALE + dy;
+                        if px < image.width() && py < image.height() {
+                            image.put_pixel(px, py, TEXT);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rows of a 5x7 glyph, the highest of the 5 bits is the leftmost pixel. Only what captions
+/// need is there: digits, `#` and `%`.
+const fn glyph(character: char) -> [u8; 7] {
+    match character {
+        '0' => [
+            0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110,
+        ],
+        '1' => [
+            0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110,
+        ],
+        '2' => [
+            0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111,
+        ],
+        '3' => [
+            0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110,
+        ],
+        '4' => [
+            0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010,
+        ],
+        '5' => [
+            0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110,
+        ],
+        '6' => [
+            0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110,
+        ],
+        '7' => [
+            0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000,
+        ],
+        '8' => [
+            0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110,
+        ],
+        '9' => [
+            0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100,
+        ],
+        '#' => [
+            0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010,
+        ],
+        '%' => [
+            0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011,
+        ],
+        _ => [0; 7],
+    }
+}
@@ -0,0 +1,174 @@
+use image::DynamicImage;
+use serde_json::{Value, json};
+
+/// Number of dominant colors stored per image
+const DOMINANT_COLORS: usize = 3;
+/// Size the image is reduced to before counting colors
+const SAMPLE_SIZE: u32 = 64;
+/// Largest RGB distance at which two colors are still considered similar
+const MAX_DISTANCE: f32 = 120.0;
+
+/// Named colors understood by `color:` filters and the color search mode
+const NAMED_COLORS: [(&str, [u8; 3]); 12] = [
+    ("red", [220, 40, 40]),
+    ("orange", [240, 140, 30]),
+    ("yellow", [240, 220, 50]),
+    ("green", [60, 170, 60]),
+    ("cyan", [60, 200, 210]),
+    ("blue", [40, 80, 200]),
+    ("purple", [130, 60, 180]),
+    ("pink", [240, 130, 180]),
+    ("brown", [130, 80, 40]),
+    ("black", [15, 15, 15]),
+    ("white", [240, 240, 240]),
+    ("gray", [128, 128, 128]),
+];
+
+/// Finds the most common colors of an image.
+/// Returns up to `DOMINANT_COLORS` colors with the share of pixels they cover.
+pub fn dominant_colors(image: &DynamicImage) -> Vec<([u8; 3], f32)> {
+    let sample = image.thumbnail(SAMPLE_SIZE, SAMPLE_SIZE).to_rgb8();
+    let total = sample.pixels().len().max(1) as f32;
+
+    // 3 bits per channel, each bucket keeps the sum of its pixels for the average color
+    let mut buckets = vec![(0u32, [0u32; 3]); 512];
+    for pixel in sample.pixels() {
+        let [r, g, b] = pixel.0;
+        let index = ((r as usize >> 5) << 6) | ((g as usize >> 5) << 3) | (b as usize >> 5);
+        let bucket = &mut buckets[index];
+        bucket.0 += 1;
+        for (sum, channel) in bucket.1.iter_mut().zip(pixel.0) {
+            *sum += channel as u32;
+        }
+    }
+
+    buckets.sort_by_key(|bucket| std::cmp::Reverse(bucket.0));
+    buckets
+        .into_iter()
+        .take(DOMINANT_COLORS)
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, sum)| {
+            (
+                sum.map(|channel| (channel / count) as u8),
+                count as f32 / total,
+            )
+        })
+        .collect()
+}
+
+/// Converts dominant colors into the JSON stored in the index
+pub fn to_value(colors: &[([u8; 3], f32)]) -> Value {
+    colors
+        .iter()
+        .map(|(color, share)| json!({ "color": to_hex(*color), "share": share }))
+        .collect()
+}
+
+/// Reads dominant colors back from the JSON stored in the index
+pub fn from_value(value: &Value) -> Vec<([u8; 3], f32)> {
+    value
+        .as_array()
+        .map(|colors| {
+            colors
+                .iter()
+                .filter_map(|entry| {
+                    let color = parse_color(entry.get("color")?.as_str()?)?;
+                    let share = entry.get("share")?.as_f64()? as f32;
+                    Some((color, share))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parses `#rrggbb`, `rrggbb` or one of the named colors
+pub fn parse_color(text: &str) -> Option<[u8; 3]> {
+    let text = text.trim().to_lowercase();
+    if let Some((_, color)) = NAMED_COLORS.iter().find(|(name, _)| *name == text) {
+        return Some(*color);
+    }
+
+    let hex = text.strip_prefix('#').unwrap_or(&text);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+pub fn to_hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// How much of an image is covered by colors similar to `target`, between 0 and 1
+pub fn color_score(colors: &[([u8; 3], f32)], target: [u8; 3]) -> f32 {
+    colors
+        .iter()
+        .map(|(color, share)| share * (1.0 - distance(*color, target) / MAX_DISTANCE).max(0.0))
+        .sum()
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (*a as f32 - b as f32).powi(2))
+        .sum::<f32>()
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    const RED: [u8; 3] = [200, 30, 30];
+    const BLUE: [u8; 3] = [30, 30, 200];
+
+    #[test]
+    fn dominant_colors_are_ordered_by_their_share() {
+        // three quarters red, one quarter blue
+        let image = RgbImage::from_fn(64, 64, |x, _| image::Rgb(if x < 48 { RED } else { BLUE }));
+        let colors = dominant_colors(&DynamicImage::ImageRgb8(image));
+
+        assert_eq!(colors, [(RED, 0.75), (BLUE, 0.25)]);
+    }
+
+    #[test]
+    fn colors_survive_the_index() {
+        let colors = vec![(RED, 0.75), (BLUE, 0.25)];
+
+        assert_eq!(from_value(&to_value(&colors)), colors);
+        assert!(from_value(&json!("red")).is_empty());
+        let broken = json!([
+            { "color": "nonsense", "share": 0.5 },
+            { "color": "#1e1ec8", "share": 0.5 }
+        ]);
+        assert_eq!(from_value(&broken), [(BLUE, 0.5)]);
+    }
+
+    #[test]
+    fn parse_color_takes_names_and_hex_codes() {
+        assert_eq!(parse_color(" Blue "), Some([40, 80, 200]));
+        assert_eq!(parse_color("#FF6600"), Some([255, 102, 0]));
+        assert_eq!(parse_color("ff6600"), Some([255, 102, 0]));
+        assert_eq!(parse_color(&to_hex(RED)), Some(RED));
+        assert_eq!(parse_color("#ff660"), None);
+        assert_eq!(parse_color("#gg6600"), None);
+        // six bytes, but not six hex digits
+        assert_eq!(parse_color("aébcd"), None);
+        assert_eq!(parse_color("mauve"), None);
+    }
+
+    #[test]
+    fn color_score_weighs_the_shares_by_closeness() {
+        let colors = [(RED, 0.75), (BLUE, 0.25)];
+
+        assert_eq!(color_score(&colors, RED), 0.75);
+        assert_eq!(color_score(&colors, BLUE), 0.25);
+        // a slightly different red counts a little less
+        let near = color_score(&colors, [200, 30, 60]);
+        assert!(near > 0.5 && near < 0.75, "{near}");
+        assert_eq!(color_score(&colors, [30, 200, 30]), 0.0);
+        assert_eq!(color_score(&[], RED), 0.0);
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cache,
+    catalog::Catalog,
+    config::Config,
+    index,
+    models::{self, IndexInfo},
+    paths,
+    remote::{self, Remotes},
+};
+
+/// Result of compacting the index
+#[derive(Debug, Default)]
+pub struct Compacted {
+    /// Images that left the library and were dropped from the indexes and the catalog
+    pub removed: usize,
+    /// Thumbnails of remote items that are gone, or copies of another thumbnail
+    pub thumbnails: usize,
+    /// Bytes on disk before and after
+    pub before: u64,
+    pub after: u64,
+}
+
+impl Compacted {
+    pub fn reclaimed(&self) -> u64 {
+        self.before.saturating_sub(self.after)
+    }
+}
+
+/// Drops the images that left the library from the indexes of all models and from the
+/// catalog, then rewrites them so the space of the removed entries is given back.
+/// Thumbnails no remote item uses are deleted, identical ones are stored once.
+///
+/// Removed images are found through the catalog, which has a row for every image that
+/// was ever indexed.
+pub fn compact(config: &Config, main_model: Option<&Path>) -> Result<Compacted, String> {
+    let indexes = indexes(config, main_model)?;
+    let mut compacted = Compacted {
+        before: disk_usage(&indexes),
+        ..Default::default()
+    };
+
+    let library: HashSet<String> = index::library_items(&config.library_dir())
+        .into_iter()
+        .collect();
+    let catalog = Catalog::open(&paths::catalog_file()).map_err(|e| e.to_string())?;
+    let removed: Vec<String> = catalog
+        .all_paths()
+        .into_iter()
+        .filter(|path| !library.contains(path))
+        .collect();
+
+    remove_images(config, main_model, &removed)?;
+    catalog.vacuum().map_err(|e| e.to_string())?;
+    compacted.removed = removed.len();
+
+    compacted.thumbnails = compact_thumbnails().map_err(|e| e.to_string())?;
+    compacted.after = disk_usage(&indexes);
+    Ok(compacted)
+}
+
+/// Drops images from the indexes of all models and from the catalog
+pub fn remove_images(
+    config: &Config,
+    main_model: Option<&Path>,
+    removed: &[String],
+) -> Result<(), String> {
+    for info in indexes(config, main_model)? {
+        let mut database = index::open_database(config, &info);
+        database.delete(removed).map_err(|e| e.to_string())?;
+        // saving writes the whole file, without the deleted entries
+        database.save().map_err(|e| e.to_string())?;
+    }
+    let catalog = Catalog::open(&paths::catalog_file()).map_err(|e| e.to_string())?;
+    catalog.remove(removed).map_err(|e| e.to_string())
+}
+
+/// The index of the main model and those of the additional models
+fn indexes(config: &Config, main_model: Option<&Path>) -> Result<Vec<IndexInfo>, String> {
+    let mut indexes = vec![models::main_index(config, main_model)];
+    for model in &config.models {
+        indexes.push(models::find_index(config, main_model, Some(&model.name))?);
+    }
+    Ok(indexes)
+}
+
+/// Deletes the thumbnails of remote items that are gone and hard links identical ones
+/// to a single file. Returns how many were deleted or linked.
+fn compact_thumbnails() -> io::Result<usize> {
+    let remotes = Remotes::load();
+    // items whose thumbnail went missing are dropped by `load`
+    remotes.save()?;
+    let used: HashSet<PathBuf> = remotes
+        .urls()
+        .iter()
+        .map(|url| remote::thumbnail_file(url))
+        .collect();
+
+    let entries = match fs::read_dir(paths::thumbnails_dir()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut compacted = 0;
+    let mut by_content: HashMap<String, PathBuf> = HashMap::new();
+    for entry in entries.flatten() {
+        let file = entry.path();
+        if !used.contains(&file) {
+            fs::remove_file(&file)?;
+            compacted += 1;
+            continue;
+        }
+        let hash = cache::content_hash(&file.to_string_lossy())?;
+        let Some(original) = by_content.get(&hash) else {
+            by_content.insert(hash, file);
+            continue;
+        };
+        if same_file(original, &file) {
+            continue;
+        }
+        // the link replaces the copy in one step, so the item never loses its thumbnail
+        let link = file.with_extension("link");
+        fs::hard_link(original, &link)?;
+        fs::rename(&link, &file)?;
+        compacted += 1;
+    }
+    Ok(compacted)
+}
+
+/// Device and inode of a file, the same for all hard links to it
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Whether both paths are hard links to the same file, linked by an earlier compaction
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => file_id(&a).is_some_and(|id| file_id(&b) == Some(id)),
+        _ => false,
+    }
+}
+
+/// Bytes taken by the local indexes, the catalog with its write-ahead log and the
+/// thumbnails, each linked thumbnail counted once
+fn disk_usage(indexes: &[IndexInfo]) -> u64 {
+    let catalog = paths::catalog_file();
+    let mut wal = catalog.clone().into_os_string();
+    wal.push("-wal");
+    let files = indexes
+        .iter()
+        .map(|info| info.database_file.clone())
+        .chain([catalog, PathBuf::from(wal)]);
+    let mut usage: u64 = files
+        .map(|file| fs::metadata(file).map_or(0, |metadata| metadata.len()))
+        .sum();
+
+    let mut seen = HashSet::new();
+    for entry in fs::read_dir(paths::thumbnails_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if file_id(&metadata).is_none_or(|id| seen.insert(id)) {
+            usage += metadata.len();
+        }
+    }
+    usage
+}
@@ -0,0 +1,276 @@
+use ratatui_image::picker::ProtocolType;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    atomic,
+    engine::Similarity,
+    hooks::Hooks,
+    img_scrape::fetcher::ScraperConfig,
+    index,
+    locale::SizeUnits,
+    paths,
+    store::StoreConfig,
+    throttle::IndexThrottle,
+    ui::{button::ThemeColor, grid::GridLayout, list::SearchEnum, message::MessageTheme},
+};
+
+/// User settings that are remembered between runs.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// CLIP model used when none is passed on the command line, set by the setup wizard
+    pub model_path: Option<PathBuf>,
+    /// Additional models that can be picked per query, each with its own index
+    pub models: Vec<ModelConfig>,
+    /// Directory containing the images to search, defaults to the data directory
+    pub library_dir: Option<PathBuf>,
+    /// Arrangement of the result cells
+    pub layout: GridLayout,
+    /// Number of columns of the uniform grid layout, picked automatically if unset
+    pub grid_columns: Option<usize>,
+    /// URL of a captioning service, images are captioned while indexing if set
+    pub caption_endpoint: Option<String>,
+    /// Classify images as safe or not safe for work while indexing
+    pub nsfw_filter: bool,
+    /// Hide images classified as not safe for work from the results
+    pub hide_nsfw: bool,
+    /// Cosine similarity above which results are folded into one burst, `None` shows every shot
+    pub burst_similarity: Option<f32>,
+    /// Sharpness below which the library clean-up suggests deleting a photo as blurry
+    pub blur_threshold: f32,
+    /// Hide photos whose sharpness is below `blur_threshold` from the results
+    pub hide_blurry: bool,
+    /// Weights of the LAION aesthetic predictor as safetensors, images are rated while indexing if set
+    pub aesthetic_model: Option<PathBuf>,
+    /// Similarity function used for ranking
+    pub similarity: Similarity,
+    /// Similarity function per model file name, overrides `similarity`
+    pub model_similarity: HashMap<String, Similarity>,
+    /// URL of a re-ranking service that re-orders the best candidates of text searches
+    pub rerank_endpoint: Option<String>,
+    /// Number of paraphrases embedded together with a text query, 0 turns expansion off
+    pub query_expansions: usize,
+    /// Additional synonyms used for query expansion, keyed by lowercase word
+    pub synonyms: HashMap<String, Vec<String>>,
+    /// Set when the model is a multilingual CLIP, queries are then not expanded with English prompts
+    pub multilingual: bool,
+    /// URL of a LibreTranslate-compatible service that translates text queries into English
+    pub translation_endpoint: Option<String>,
+    /// Translate queries with `translation_endpoint`, toggled from the command palette
+    pub translate_queries: bool,
+    /// Shell command recording a spoken query and printing it, run when `a` is pressed
+    pub mic_command: Option<String>,
+    /// Re-rank results with the model trained on liked and disliked results
+    pub personal_ranking: bool,
+    /// Group results by the month they were taken in instead of showing them by rank
+    pub timeline: bool,
+    /// Colors of the notifications per severity
+    pub notification_colors: MessageTheme,
+    /// Number of results loaded per search
+    pub result_count: usize,
+    /// Memory in MiB for decoded result images and their terminal graphics. Half of it caches
+    /// decoded images across searches, the other half holds the graphics of the results.
+    /// The least recently shown are dropped beyond it and loaded again when needed.
+    pub memory_budget: usize,
+    /// Most result images handed to the encoding threads per frame, 0 for no limit. The
+    /// others show a placeholder and are encoded in the next frames.
+    pub encodes_per_frame: usize,
+    /// Accent color of the interface
+    pub theme: ThemeColor,
+    /// Locale numbers and dates are written for, e.g. `de_DE`. Taken from `LC_ALL`,
+    /// `LC_NUMERIC` or `LANG` if not set.
+    pub locale: Option<String>,
+    /// Whether sizes are written in MiB or MB
+    pub size_units: SizeUnits,
+    /// Draw the interface without blinking or animation, spinners stand still and running
+    /// work is told by text
+    pub reduced_motion: bool,
+    /// Share of the top bar the search field takes in percent, the buttons get the rest
+    pub search_bar_width: u16,
+    /// Graphics protocol images are drawn with, detected from the terminal by default
+    pub protocol: ImageProtocol,
+    /// Width and height of a terminal cell in pixels, detected from the terminal by default
+    pub font_size: Option<(u16, u16)>,
+    /// Search mode selected on startup
+    pub default_mode: SearchEnum,
+    /// Write tags and ratings changed in findimg to XMP sidecars next to the images
+    pub write_sidecars: bool,
+    /// Where the embeddings are stored, a file per model in the data directory by default
+    pub vector_store: StoreConfig,
+    /// Notes vault, e.g. of Obsidian. Markdown links to images inside it are relative to it.
+    pub vault_root: Option<PathBuf>,
+    /// Directories whose images are not cached and shown pixelated until revealed with `b`.
+    /// They are also left out of bundles.
+    pub private_dirs: Vec<PathBuf>,
+    /// Port on localhost results are served on with `g`, to forward over SSH
+    pub serve_port: u16,
+    /// Commands run on events with a JSON description of the event on stdin
+    pub hooks: Hooks,
+    /// Rate limits, retries and the user agent for downloading from the web
+    pub scraper: ScraperConfig,
+    /// Hours after which synced albums are scraped again on startup, 0 only syncs them
+    /// on `sync run`
+    pub album_sync_hours: u64,
+    /// How indexing in the background holds back, so the machine stays usable meanwhile
+    pub index_throttle: IndexThrottle,
+}
+
+/// An additional model, e.g. a small fast one next to a big accurate one
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModelConfig {
+    /// Name shown in the model selector, also names the index of the model
+    pub name: String,
+    /// Path of the model file
+    pub path: PathBuf,
+    /// Length of the embeddings the model produces
+    #[serde(default = "default_dimensions")]
+    pub dimensions: usize,
+}
+
+const fn default_dimensions() -> usize {
+    index::DIMENSIONS
+}
+
+/// Graphics protocol used to draw images in the terminal
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ImageProtocol {
+    /// Whatever the terminal reports to support
+    #[default]
+    Auto,
+    Halfblocks,
+    Sixel,
+    Kitty,
+    Iterm2,
+}
+
+impl ImageProtocol {
+    pub const ALL: [ImageProtocol; 5] = [
+        ImageProtocol::Auto,
+        ImageProtocol::Halfblocks,
+        ImageProtocol::Sixel,
+        ImageProtocol::Kitty,
+        ImageProtocol::Iterm2,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            ImageProtocol::Auto => "Auto",
+            ImageProtocol::Halfblocks => "Halfblocks",
+            ImageProtocol::Sixel => "Sixel",
+            ImageProtocol::Kitty => "Kitty",
+            ImageProtocol::Iterm2 => "iTerm2",
+        }
+    }
+
+    /// The protocol type of the image picker, `None` for the detected one
+    pub const fn protocol_type(self) -> Option<ProtocolType> {
+        match self {
+            ImageProtocol::Auto => None,
+            ImageProtocol::Halfblocks => Some(ProtocolType::Halfblocks),
+            ImageProtocol::Sixel => Some(ProtocolType::Sixel),
+            ImageProtocol::Kitty => Some(ProtocolType::Kitty),
+            ImageProtocol::Iterm2 => Some(ProtocolType::Iterm2),
+        }
+    }
+}
+
+impl From<ProtocolType> for ImageProtocol {
+    fn from(protocol: ProtocolType) -> Self {
+        match protocol {
+            ProtocolType::Halfblocks => ImageProtocol::Halfblocks,
+            ProtocolType::Sixel => ImageProtocol::Sixel,
+            ProtocolType::Kitty => ImageProtocol::Kitty,
+            ProtocolType::Iterm2 => ImageProtocol::Iterm2,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            model_path: None,
+            models: Vec::new(),
+            library_dir: None,
+            layout: GridLayout::default(),
+            grid_columns: None,
+            caption_endpoint: None,
+            nsfw_filter: false,
+            hide_nsfw: false,
+            burst_similarity: Some(0.95),
+            blur_threshold: 60.0,
+            hide_blurry: false,
+            aesthetic_model: None,
+            similarity: Similarity::default(),
+            model_similarity: HashMap::new(),
+            rerank_endpoint: None,
+            query_expansions: 0,
+            synonyms: HashMap::new(),
+            multilingual: false,
+            translation_endpoint: None,
+            translate_queries: true,
+            mic_command: None,
+            personal_ranking: false,
+            timeline: false,
+            notification_colors: MessageTheme::default(),
+            result_count: 20,
+            memory_budget: 1024,
+            encodes_per_frame: 3,
+            theme: ThemeColor::default(),
+            locale: None,
+            size_units: SizeUnits::default(),
+            reduced_motion: false,
+            search_bar_width: 60,
+            protocol: ImageProtocol::default(),
+            font_size: None,
+            default_mode: SearchEnum::Search,
+            write_sidecars: false,
+            vector_store: StoreConfig::default(),
+            vault_root: None,
+            private_dirs: Vec::new(),
+            serve_port: 8765,
+            hooks: Hooks::default(),
+            scraper: ScraperConfig::default(),
+            album_sync_hours: 24,
+            index_throttle: IndexThrottle::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config from disk. Falls back to the default config if there is none
+    /// or it can not be parsed.
+    pub fn load() -> Self {
+        fs::read_to_string(paths::config_file())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the config to disk
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(paths::config_dir())?;
+        let content = serde_json::to_string_pretty(self)?;
+        atomic::write(&paths::config_file(), content)
+    }
+
+    /// Similarity function to use with the given model
+    pub fn similarity_for(&self, model_path: &str) -> Similarity {
+        Path::new(model_path)
+            .file_name()
+            .and_then(|name| self.model_similarity.get(name.to_string_lossy().as_ref()))
+            .copied()
+            .unwrap_or(self.similarity)
+    }
+
+    /// Directory containing the images to search
+    pub fn library_dir(&self) -> PathBuf {
+        self.library_dir
+            .clone()
+            .unwrap_or_else(paths::default_library_dir)
+    }
+}
@@ -0,0 +1,279 @@
+use cliprs::ClipModel;
+use image::RgbImage;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    engine::{self, Candidates, Similarity},
+    expansion::normalized,
+    store::StoreConfig,
+};
+
+/// Side length of the generated images in pixels
+const SIZE: u32 = 224;
+/// Name of the file listing which image each query should find
+pub const LABELS_FILE: &str = "labels.csv";
+
+/// An image of the corpus together with the query that should rank it first
+struct Sample {
+    file: &'static str,
+    query: &'static str,
+    /// Color of the pixel at the given coordinates
+    draw: fn(u32, u32) -> [u8; 3],
+}
+
+const BLACK: [u8; 3] = [0, 0, 0];
+const WHITE: [u8; 3] = [255, 255, 255];
+
+/// The corpus, solid colors and simple black and white patterns that CLIP models tell apart
+/// reliably. Changing it changes what `dev golden` checks.
+const SAMPLES: [Sample; 10] = [
+    Sample {
+        file: "red.png",
+        query: "a plain red image",
+        draw: |_, _| [220, 30, 30],
+    },
+    Sample {
+        file: "green.png",
+        query: "a plain green image",
+        draw: |_, _| [40, 170, 40],
+    },
+    Sample {
+        file: "blue.png",
+        query: "a plain blue image",
+        draw: |_, _| [30, 60, 220],
+    },
+    Sample {
+        file: "yellow.png",
+        query: "a plain yellow image",
+        draw: |_, _| [245, 220, 40],
+    },
+    Sample {
+        file: "purple.png",
+        query: "a plain purple image",
+        draw: |_, _| [130, 50, 180],
+    },
+    Sample {
+        file: "checkerboard.png",
+        query: "a black and white checkerboard",
+        draw: |x, y| {
+            if (x / 28 + y / 28) % 2 == 0 {
+                BLACK
+            } else {
+                WHITE
+            }
+        },
+    },
+    Sample {
+        file: "stripes.png",
+        query: "black and white vertical stripes",
+        draw: |x, _| if (x / 16) % 2 == 0 { BLACK } else { WHITE },
+    },
+    Sample {
+        file: "circle.png",
+        query: "a black circle on a white background",
+        draw: |x, y| {
+            let (dx, dy) = (x as i64 - 112, y as i64 - 112);
+            if dx * dx + dy * dy <= 70 * 70 {
+                BLACK
+            } else {
+                WHITE
+            }
+        },
+    },
+    Sample {
+        file: "gradient.png",
+        query: "a smooth gradient from black to white",
+        draw: |x, _| [(x * 255 / (SIZE - 1)) as u8; 3],
+    },
+    Sample {
+        file: "cross.png",
+        query: "a white cross on a red background",
+        draw: |x, y| {
+            if (92..132).contains(&x) || (92..132).contains(&y) {
+                WHITE
+            } else {
+                [220, 30, 30]
+            }
+        },
+    },
+];
+
+/// A query of the corpus and the image ranked first for it
+pub struct Outcome {
+    pub query: &'static str,
+    pub expected: &'static str,
+    /// File name of the best match, `None` if nothing was ranked
+    pub ranked_first: Option<String>,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.ranked_first.as_deref() == Some(self.expected)
+    }
+}
+
+/// Draws the corpus into `dir` and writes `labels.csv` with a `query,image` line per sample.
+/// The images are the same on every run. Returns the paths of the images.
+pub fn generate(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+
+    let mut labels = String::from("query,image\n");
+    let mut paths = Vec::new();
+    for sample in &SAMPLES {
+        let path = dir.join(sample.file);
+        RgbImage::from_fn(SIZE, SIZE, |x, y| image::Rgb((sample.draw)(x, y)))
+            .save(&path)
+            .map_err(io::Error::other)?;
+        labels.push_str(&format!("{},{}\n", sample.query, sample.file));
+        paths.push(path);
+    }
+    fs::write(dir.join(LABELS_FILE), labels)?;
+    Ok(paths)
+}
+
+/// Embeds the corpus and ranks it for every query with `engine::rank`, which scores the
+/// images for a search. The rest of a search, like filters, re-ranking and plugins, is left
+/// out, so changes to the preprocessing or the scoring that break retrieval show up as
+/// failed queries.
+pub fn golden(model: &ClipModel, similarity: Similarity) -> Result<Vec<Outcome>, String> {
+    let dir = std::env::temp_dir().join(format!("findimg-golden-{}", std::process::id()));
+    let outcomes = rank_corpus(model, similarity, &dir);
+    let _ = fs::remove_dir_all(&dir);
+    outcomes
+}
+
+fn rank_corpus(
+    model: &ClipModel,
+    similarity: Similarity,
+    dir: &Path,
+) -> Result<Vec<Outcome>, String> {
+    let paths: Vec<String> = generate(dir)
+        .map_err(|e| format!("Failed to write the corpus: {}", e))?
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+
+    let mut data = Vec::new();
+    for path in &paths {
+        data.push(nano_vectordb_rs::Data {
+            id: path.clone(),
+            vector: model.embed_image(path)?,
+            fields: Default::default(),
+        });
+    }
+    let dimensions = data.first().map_or(0, |data| data.vector.len());
+
+    // never saved, the file only names the index
+    let mut database = StoreConfig::Local.open(&dir.join("golden.json"), dimensions, similarity)?;
+    database.upsert(data).map_err(|e| e.to_string())?;
+
+    let mut outcomes = Vec::new();
+    for sample in &SAMPLES {
+        let query = normalized(model.embed_text(sample.query)?);
+        let ranked = engine::rank(&*database, &Candidates::All(&paths), &query, similarity, 1);
+        outcomes.push(Outcome {
+            query: sample.query,
+            expected: sample.file,
+            ranked_first: ranked.first().and_then(|(path, _)| {
+                Path::new(path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+            }),
+        });
+    }
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("findimg-corpus-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn generate_draws_every_sample_and_labels_it() {
+        let dir = corpus_dir("generate");
+        let paths = generate(&dir).unwrap();
+
+        assert_eq!(paths.len(), SAMPLES.len());
+        for (path, sample) in paths.iter().zip(&SAMPLES) {
+            assert_eq!(path, &dir.join(sample.file));
+            let image = image::open(path).unwrap();
+            assert_eq!((image.width(), image.height()), (SIZE, SIZE));
+        }
+
+        let labels = fs::read_to_string(dir.join(LABELS_FILE)).unwrap();
+        let mut lines = labels.lines();
+        assert_eq!(lines.next(), Some("query,image"));
+        let labeled: Vec<(&str, &str)> = lines.map(|line| line.rsplit_once(',').unwrap()).collect();
+        let expected: Vec<(&str, &str)> = SAMPLES
+            .iter()
+            .map(|sample| (sample.query, sample.file))
+            .collect();
+        assert_eq!(labeled, expected);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn generate_draws_the_same_images_every_time() {
+        let (first, second) = (corpus_dir("first"), corpus_dir("second"));
+        for (a, b) in generate(&first)
+            .unwrap()
+            .iter()
+            .zip(generate(&second).unwrap())
+        {
+            assert_eq!(
+                fs::read(a).unwrap(),
+                fs::read(b).unwrap(),
+                "{}",
+                a.display()
+            );
+        }
+        fs::remove_dir_all(first).unwrap();
+        fs::remove_dir_all(second).unwrap();
+    }
+
+    #[test]
+    fn samples_are_told_apart_by_their_pixels_and_names() {
+        for (i, a) in SAMPLES.iter().enumerate() {
+            for b in &SAMPLES[i + 1..] {
+                assert_ne!(a.file, b.file);
+                assert_ne!(a.query, b.query);
+                let differs = (0..SIZE).step_by(8).any(|x| {
+                    (0..SIZE)
+                        .step_by(8)
+                        .any(|y| (a.draw)(x, y) != (b.draw)(x, y))
+                });
+                assert!(differs, "{} and {} look the same", a.file, b.file);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a CLIP model, set FINDIMG_TEST_MODEL to its path"]
+    fn golden_ranks_every_labeled_image_first() {
+        let model_path = std::env::var("FINDIMG_TEST_MODEL").expect("FINDIMG_TEST_MODEL isn't set");
+        let model = ClipModel::new(&model_path);
+
+        let failed: Vec<String> = golden(&model, Similarity::default())
+            .unwrap()
+            .into_iter()
+            .filter(|outcome| !outcome.passed())
+            .map(|outcome| {
+                format!(
+                    "{}: expected {}, ranked {:?} first",
+                    outcome.query, outcome.expected, outcome.ranked_first
+                )
+            })
+            .collect();
+        assert!(failed.is_empty(), "{}", failed.join("\n"));
+    }
+}
@@ -0,0 +1,267 @@
+use cliprs::ClipModel;
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    catalog::Catalog,
+    config::Config,
+    engine::{self, Candidates, Hit, Pipeline},
+    expansion,
+    index::cosine_similarity,
+    query::{self, Query},
+    remote,
+    store::VectorStore,
+};
+
+/// How many candidates per requested photo are considered for the selection
+const CANDIDATES_PER_PHOTO: usize = 5;
+/// Balance between relevance and variety, 1 only looks at the score
+const RELEVANCE_WEIGHT: f32 = 0.7;
+
+/// The indexed library the photos are picked from
+pub struct Library<'a> {
+    pub database: &'a dyn VectorStore,
+    pub catalog: &'a Catalog,
+    pub image_paths: &'a [String],
+}
+
+/// Picks `count` photos that match the prompt well without being near copies of each other.
+///
+/// Uses maximal marginal relevance: each pick maximizes its score minus its similarity to the
+/// closest photo already picked. Filters in the prompt are applied like in the search bar.
+pub fn curate(
+    model: &ClipModel,
+    library: &Library,
+    config: &Config,
+    similarity: engine::Similarity,
+    prompt: &str,
+    count: usize,
+) -> Result<Vec<Hit>, String> {
+    let ranked = best_matches(
+        model,
+        library,
+        config,
+        similarity,
+        &query::parse(prompt),
+        count * CANDIDATES_PER_PHOTO,
+    )?;
+
+    let candidates: Vec<(Hit, Vec<f32>)> = ranked
+        .into_iter()
+        .filter_map(|hit| {
+            let vector = library
+                .database
+                .get(std::slice::from_ref(&hit.path))
+                .first()?
+                .vector
+                .clone();
+            Some((hit, vector))
+        })
+        .collect();
+    Ok(select(candidates, count))
+}
+
+/// Picks `count` of the candidates with their embeddings by maximal marginal relevance, see
+/// `curate`. Returns them in the order they were picked.
+fn select(mut candidates: Vec<(Hit, Vec<f32>)>, count: usize) -> Vec<Hit> {
+    let mut selected: Vec<(Hit, Vec<f32>)> = Vec::new();
+    while selected.len() < count && !candidates.is_empty() {
+        let marginal = |(hit, vector): &(Hit, Vec<f32>)| {
+            let redundancy = selected
+                .iter()
+                .map(|(_, picked)| cosine_similarity(vector, picked))
+                .fold(0.0, f32::max);
+            RELEVANCE_WEIGHT * hit.score - (1.0 - RELEVANCE_WEIGHT) * redundancy
+        };
+        let best = candidates
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| marginal(a).total_cmp(&marginal(b)))
+            .map(|(index, _)| index)
+            .unwrap_or_default();
+        selected.push(candidates.remove(best));
+    }
+
+    selected.into_iter().map(|(hit, _)| hit).collect()
+}
+
+/// The `count` photos matching a query best, with the filters of the query applied and
+/// NSFW photos left out if they are hidden
+pub fn best_matches(
+    model: &ClipModel,
+    library: &Library,
+    config: &Config,
+    similarity: engine::Similarity,
+    query: &Query,
+    count: usize,
+) -> Result<Vec<Hit>, String> {
+    let embedding = expansion::embed_query(model, &query.text, config)?;
+
+    let mut pipeline = Pipeline::default();
+    if !query.filters.is_empty() {
+        pipeline.push(&query.filters);
+    }
+    let candidates = pipeline.candidates(library.catalog, library.image_paths);
+
+    let mut ranked = engine::rank(library.database, &candidates, &embedding, similarity, count);
+    let flagged = if config.hide_nsfw {
+        library.catalog.flagged_nsfw()
+    } else {
+        HashSet::new()
+    };
+    ranked.retain(|(path, _)| !flagged.contains(path));
+    ranked.truncate(count);
+    Ok(engine::hits(ranked, library.catalog))
+}
+
+/// The `count` images looking most like an image file, which doesn't have to be in the
+/// library. The image itself and NSFW images, if they are hidden, are left out.
+pub fn similar_images(
+    model: &ClipModel,
+    library: &Library,
+    config: &Config,
+    similarity: engine::Similarity,
+    image: &Path,
+    count: usize,
+) -> Result<Vec<Hit>, String> {
+    let file = image
+        .to_str()
+        .ok_or("The path of the image is not valid UTF-8")?;
+    let embedding = model.embed_image(file)?;
+
+    let mut ranked = engine::rank(
+        library.database,
+        &Candidates::All(library.image_paths),
+        &embedding,
+        similarity,
+        count + 1,
+    );
+    let flagged = if config.hide_nsfw {
+        library.catalog.flagged_nsfw()
+    } else {
+        HashSet::new()
+    };
+    ranked.retain(|(path, _)| Path::new(path) != image && !flagged.contains(path));
+    ranked.truncate(count);
+    Ok(engine::hits(ranked, library.catalog))
+}
+
+/// Copies the photos into `output`, numbered in the order they were picked,
+/// and optionally writes an `index.html` gallery showing them with their tags.
+/// Returns the paths of the copies.
+pub fn export(photos: &[Hit], output: &Path, html: bool) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(output)?;
+
+    let mut copies = Vec::new();
+    for (index, Hit { path, .. }) in photos.iter().enumerate() {
+        let name = Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let copy = output.join(format!("{:03}_{}", index + 1, name));
+        // only the thumbnail of a remote item is stored
+        fs::copy(remote::local_file(path), &copy)?;
+        copies.push(copy);
+    }
+
+    if html {
+        fs::write(output.join("index.html"), gallery(photos, &copies))?;
+    }
+    Ok(copies)
+}
+
+fn gallery(photos: &[Hit], copies: &[PathBuf]) -> String {
+    let images: String = photos
+        .iter()
+        .zip(copies)
+        .filter_map(|(hit, copy)| Some((hit, copy.file_name()?)))
+        .map(|(hit, name)| {
+            let name = escape(&name.to_string_lossy());
+            let alt = if hit.tags.is_empty() {
+                name.clone()
+            } else {
+                escape(&hit.tags.join(", "))
+            };
+            format!(
+                "    <a href=\"{}\"><img src=\"{}\" alt=\"{}\"></a>\n",
+                name, name, alt
+            )
+        })
+        .collect();
+
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+  <meta charset=\"utf-8\">
+  <title>findimg</title>
+  <style>
+    body {{ background: #111; margin: 0; display: flex; flex-wrap: wrap; gap: 8px; padding: 8px; }}
+    img {{ height: 300px; object-fit: cover; }}
+  </style>
+</head>
+<body>
+{}</body>
+</html>
+",
+        images
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn candidate(path: &str, score: f32, vector: [f32; 2]) -> (Hit, Vec<f32>) {
+        (
+            Hit::new(path.to_string(), score, &HashMap::new()),
+            vector.to_vec(),
+        )
+    }
+
+    fn paths(hits: &[Hit]) -> Vec<&str> {
+        hits.iter().map(|hit| hit.path.as_str()).collect()
+    }
+
+    #[test]
+    fn a_near_copy_of_a_pick_gives_way_to_a_different_photo() {
+        let candidates = vec![
+            candidate("best.jpg", 0.9, [1.0, 0.0]),
+            candidate("copy.jpg", 0.89, [1.0, 0.01]),
+            candidate("other.jpg", 0.8, [0.0, 1.0]),
+        ];
+
+        assert_eq!(paths(&select(candidates, 2)), ["best.jpg", "other.jpg"]);
+    }
+
+    #[test]
+    fn relevance_still_wins_over_a_far_worse_match() {
+        let candidates = vec![
+            candidate("best.jpg", 0.9, [1.0, 0.0]),
+            candidate("similar.jpg", 0.85, [1.0, 1.0]),
+            candidate("unrelated.jpg", 0.2, [0.0, 1.0]),
+        ];
+
+        assert_eq!(paths(&select(candidates, 2)), ["best.jpg", "similar.jpg"]);
+    }
+
+    #[test]
+    fn select_stops_when_the_candidates_run_out() {
+        let candidates = vec![candidate("only.jpg", 0.5, [1.0, 0.0])];
+
+        assert_eq!(paths(&select(candidates.clone(), 3)), ["only.jpg"]);
+        assert!(select(candidates, 0).is_empty());
+        assert!(select(Vec::new(), 3).is_empty());
+    }
+}
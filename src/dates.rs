@@ -0,0 +1,85 @@
+use std::{fs, path::Path, time::UNIX_EPOCH};
+
+use crate::exif::{self, Tiff};
+
+/// EXIF tags used to find the capture date
+const EXIF_IFD_POINTER: u16 = 0x8769;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const DATE_TIME: u16 = 0x0132;
+
+/// When a photo was taken as `YYYY-MM-DD`, from its EXIF data or else the modification time
+pub fn capture_date(path: &str) -> Option<String> {
+    exif_date(path).or_else(|| modified_date(path))
+}
+
+/// Title of the month a `YYYY-MM-DD` date falls in, e.g. `March 2023`
+pub fn month_title(date: &str) -> String {
+    const MONTHS: [&str; 12] = [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ];
+    let month = date
+        .get(5..7)
+        .and_then(|month| month.parse::<usize>().ok())
+        .and_then(|month| MONTHS.get(month.checked_sub(1)?));
+    match (date.get(..4), month) {
+        (Some(year), Some(month)) => format!("{} {}", month, year),
+        _ => date.to_string(),
+    }
+}
+
+fn exif_date(path: &str) -> Option<String> {
+    let exif = exif::read(path)?;
+    let tiff = Tiff::new(&exif)?;
+
+    let ifd0 = tiff.ifd0()?;
+    let original = tiff
+        .entry(ifd0, EXIF_IFD_POINTER)
+        .and_then(|entry| tiff.u32(entry + 8))
+        .and_then(|exif_ifd| tiff.ascii(tiff.entry(exif_ifd as usize, DATE_TIME_ORIGINAL)?));
+    let text = original.or_else(|| tiff.ascii(tiff.entry(ifd0, DATE_TIME)?))?;
+
+    // EXIF dates look like `2023:03:14 15:09:26`
+    let date = text.get(..10)?.replace(':', "-");
+    let valid = date.len() == 10
+        && date.chars().enumerate().all(|(i, c)| {
+            if i == 4 || i == 7 {
+                c == '-'
+            } else {
+                c.is_ascii_digit()
+            }
+        })
+        && !date.starts_with("0000");
+    valid.then_some(date)
+}
+
+fn modified_date(path: &str) -> Option<String> {
+    let modified = fs::metadata(Path::new(path)).ok()?.modified().ok()?;
+    let days = modified.duration_since(UNIX_EPOCH).ok()?.as_secs() / 86_400;
+    let (year, month, day) = civil_from_days(days as i64);
+    Some(format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+/// Converts days since 1970-01-01 to a calendar date (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
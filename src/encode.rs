@@ -0,0 +1,160 @@
+use image::DynamicImage;
+use ratatui::layout::Rect;
+use ratatui_image::{ResizeEncodeRender, picker::Picker, protocol::StatefulProtocol};
+use std::{
+    sync::{
+        Arc, Mutex, PoisonError,
+        mpsc::{self, Sender},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    events::AppEvent,
+    memory::{self, ImageCache},
+    privacy,
+};
+
+/// Most threads encoding images, sixel and kitty payloads of a few large cells at once are
+/// enough to keep up with the frames
+const MAX_THREADS: usize = 4;
+
+/// What an encoding starts from
+pub enum Source {
+    /// Graphics that are loaded, encoded again for another area
+    Loaded(Box<StatefulProtocol>),
+    /// Graphics that were dropped, the image is loaded again with the picker first
+    Reload { picker: Picker, pixelate: bool },
+}
+
+/// What was loaded again for a `Source::Reload`
+pub struct Reloaded {
+    /// Estimated memory of the graphics, see `graphics_bytes`
+    pub bytes: usize,
+    /// Longest side of the image if it is the thumbnail embedded in the file
+    pub reduced: Option<u32>,
+}
+
+struct Request {
+    id: u64,
+    path: String,
+    area: Rect,
+    source: Source,
+}
+
+/// An encoding that is done, posted back to the interface as `AppEvent::Encoded`
+pub struct Encoded {
+    /// What `Encoder::submit` returned for it
+    pub id: u64,
+    pub path: String,
+    /// Area the graphics are encoded for
+    pub area: Rect,
+    /// The graphics, or why the image could not be read
+    pub graphics: Result<StatefulProtocol, String>,
+    /// Set if the graphics were loaded again
+    pub reloaded: Option<Reloaded>,
+    /// How long loading and encoding took
+    pub took: Duration,
+}
+
+/// Threads encoding the results for the terminal, so resizes and new searches don't freeze
+/// the interface while the payloads are built
+pub struct Encoder {
+    sender: Sender<Request>,
+    next_id: u64,
+}
+
+impl Encoder {
+    /// Starts the threads, they load images through `images` and post what they encoded
+    /// to `events`
+    pub fn new(images: Arc<Mutex<ImageCache>>, events: Sender<AppEvent>) -> Self {
+        let (sender, receiver) = mpsc::channel::<Request>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let threads = thread::available_parallelism().map_or(1, |n| n.get().min(MAX_THREADS));
+        for _ in 0..threads {
+            let receiver = Arc::clone(&receiver);
+            let images = Arc::clone(&images);
+            let events = events.clone();
+            thread::spawn(move || {
+                loop {
+                    let request = receiver
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .recv();
+                    // the interface is gone
+                    let Ok(request) = request else {
+                        break;
+                    };
+                    let encoded = encode(request, &images);
+                    if events.send(AppEvent::Encoded(Box::new(encoded))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        Self { sender, next_id: 0 }
+    }
+
+    /// Queues the image of `path` to be encoded for `area`. Returns the id its `Encoded`
+    /// will have, encodings that were superseded in the meantime are told apart by it.
+    pub fn submit(&mut self, path: String, area: Rect, source: Source) -> u64 {
+        self.next_id += 1;
+        let _ = self.sender.send(Request {
+            id: self.next_id,
+            path,
+            area,
+            source,
+        });
+        self.next_id
+    }
+}
+
+fn encode(request: Request, images: &Mutex<ImageCache>) -> Encoded {
+    let started = Instant::now();
+    let (graphics, reloaded) = match request.source {
+        Source::Loaded(graphics) => (Ok(*graphics), None),
+        Source::Reload { picker, pixelate } => {
+            match memory::load(images, &request.path, pixel_side(request.area, &picker)) {
+                Ok(decoded) => {
+                    let image = if pixelate {
+                        privacy::pixelate(&decoded.image)
+                    } else {
+                        decoded.image
+                    };
+                    let reloaded = Reloaded {
+                        bytes: graphics_bytes(&image),
+                        reduced: decoded.reduced.then(|| image.width().max(image.height())),
+                    };
+                    (Ok(picker.new_resize_protocol(image)), Some(reloaded))
+                }
+                // e.g. deleted by another program since the search
+                Err(e) => (Err(e), None),
+            }
+        }
+    };
+    let graphics = graphics.map(|mut graphics| {
+        graphics.resize_encode(&ratatui_image::Resize::Fit(None), request.area);
+        graphics
+    });
+    Encoded {
+        id: request.id,
+        path: request.path,
+        area: request.area,
+        graphics,
+        reloaded,
+        took: started.elapsed(),
+    }
+}
+
+/// Longest side in pixels of an area of terminal cells
+pub fn pixel_side(area: Rect, picker: &Picker) -> u32 {
+    let (width, height) = picker.font_size();
+    (area.width as u32 * width as u32).max(area.height as u32 * height as u32)
+}
+
+/// Estimated memory of the terminal graphics of an image: the graphics keep the decoded
+/// image and its encoding at about the same size
+pub fn graphics_bytes(image: &DynamicImage) -> usize {
+    image.as_bytes().len() * 2
+}
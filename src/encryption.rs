@@ -0,0 +1,393 @@
+use aes_gcm::{
+    Aes256Gcm, Key, Nonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+};
+use argon2::Argon2;
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{atomic, paths};
+
+/// Marks an encrypted index
+const MAGIC: &[u8; 4] = b"FIEN";
+/// Version of the encrypted index format, bumped when the layout changes
+const FORMAT_VERSION: u8 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+/// Passphrase used instead of asking for it, for scripts
+const PASSPHRASE_VARIABLE: &str = "FINDIMG_PASSPHRASE";
+/// Files and directories in the data directory that make up the index
+const INDEX_FILES: [&str; 8] = [
+    "embeddings.db",
+    "embeddings.db.bak",
+    "indexes",
+    "metadata.sqlite",
+    "metadata.sqlite-wal",
+    "metadata.sqlite-shm",
+    "session.json",
+    "feedback.json",
+];
+
+/// Files of the index by their path relative to the directory they are stored in
+type Files = Vec<(String, Vec<u8>)>;
+
+/// The embeddings and the metadata decrypted to a private directory for this run, see
+/// `paths::use_unlocked_dir`. They are encrypted again when this is dropped.
+///
+/// The encrypted index is a single file: the magic bytes `FIEN`, the format version, the
+/// salt the key is derived from with Argon2, the AES-GCM nonce and the encrypted files.
+pub struct Unlocked {
+    key: [u8; 32],
+    salt: [u8; SALT_LENGTH],
+    /// The encrypted index the files are written back to
+    file: PathBuf,
+    dir: PathBuf,
+}
+
+/// Whether the index is stored encrypted
+pub fn is_encrypted() -> bool {
+    paths::encrypted_index_file().is_file()
+}
+
+/// Asks for the passphrase on the terminal, or takes it from `FINDIMG_PASSPHRASE`.
+/// With `confirm` it has to be typed twice, for setting a new one.
+pub fn ask_passphrase(confirm: bool) -> Result<String, String> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_VARIABLE) {
+        return Ok(passphrase);
+    }
+    let passphrase =
+        rpassword::prompt_password("Passphrase of the index: ").map_err(|e| e.to_string())?;
+    if passphrase.is_empty() {
+        return Err("The passphrase is empty".into());
+    }
+    if confirm
+        && rpassword::prompt_password("Repeat the passphrase: ").map_err(|e| e.to_string())?
+            != passphrase
+    {
+        return Err("The passphrases don't match".into());
+    }
+    Ok(passphrase)
+}
+
+/// Decrypts the index to a directory only the user can read, the runtime directory if
+/// there is one, and uses it for this run. Files left there by a run that didn't get to
+/// encrypt them again are newer than the encrypted index, so they are kept.
+pub fn unlock(passphrase: &str) -> Result<Unlocked, String> {
+    let dir = dirs::runtime_dir().map_or_else(
+        || paths::data_dir().join("unlocked"),
+        |dir| dir.join("findimg"),
+    );
+    let unlocked = unlock_at(&paths::encrypted_index_file(), dir, passphrase)?;
+    // the session and the feedback were stored unencrypted in the data directory before,
+    // they are moved to the index so they are encrypted with it
+    for file in [paths::session_file(), paths::feedback_file()] {
+        let Some(name) = file.file_name().filter(|_| file.is_file()) else {
+            continue;
+        };
+        let target = unlocked.dir.join(name);
+        if !target.exists() {
+            // the runtime directory usually is another file system, so it can't be renamed
+            fs::copy(&file, &target)
+                .and_then(|_| fs::remove_file(&file))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    paths::use_unlocked_dir(unlocked.dir.clone());
+    Ok(unlocked)
+}
+
+/// Decrypts the encrypted index `file` to `dir`, see `unlock`
+pub fn unlock_at(file: &Path, dir: PathBuf, passphrase: &str) -> Result<Unlocked, String> {
+    let (salt, files) = read_encrypted(file, passphrase)?;
+    let unlocked = Unlocked {
+        key: derive_key(passphrase, &salt)?,
+        salt,
+        file: file.to_path_buf(),
+        dir,
+    };
+
+    create_private_dir(&unlocked.dir).map_err(|e| e.to_string())?;
+    if files_in(&unlocked.dir).is_empty() {
+        write_files(&unlocked.dir, files).map_err(|e| e.to_string())?;
+    }
+    Ok(unlocked)
+}
+
+impl Unlocked {
+    /// Encrypts the files of the unlocked index and removes them
+    fn lock(&self) -> io::Result<()> {
+        let files = files_in(&self.dir);
+        let mut plaintext = Vec::new();
+        for (name, path) in &files {
+            append_file(&mut plaintext, name, &fs::read(path)?);
+        }
+        let encrypted = encrypt(&self.key, &self.salt, &plaintext).map_err(io::Error::other)?;
+        atomic::write(&self.file, encrypted)?;
+        fs::remove_dir_all(&self.dir)
+    }
+}
+
+impl Drop for Unlocked {
+    fn drop(&mut self) {
+        if let Err(e) = self.lock() {
+            eprintln!(
+                "ERROR: Failed to encrypt the index, it is left decrypted in {}: {}",
+                self.dir.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Encrypts the index in the data directory with a new passphrase and removes the
+/// unencrypted files. Returns the number of encrypted files.
+pub fn encrypt_index(passphrase: &str) -> Result<usize, String> {
+    if is_encrypted() {
+        return Err("The index is encrypted already".into());
+    }
+    let data_dir = paths::data_dir();
+    let files: Vec<(String, PathBuf)> = files_in(&data_dir)
+        .into_iter()
+        .filter(|(name, _)| {
+            let top = name.split('/').next().unwrap_or_default();
+            INDEX_FILES.contains(&top)
+        })
+        .collect();
+    if files.is_empty() {
+        return Err("There is no index to encrypt yet".into());
+    }
+
+    let mut plaintext = Vec::new();
+    for (name, path) in &files {
+        append_file(
+            &mut plaintext,
+            name,
+            &fs::read(path).map_err(|e| e.to_string())?,
+        );
+    }
+    let mut salt = [0; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt)?;
+    let encrypted = encrypt(&key, &salt, &plaintext)?;
+    atomic::write(&paths::encrypted_index_file(), encrypted).map_err(|e| e.to_string())?;
+
+    for (_, path) in &files {
+        fs::remove_file(path).map_err(|e| e.to_string())?;
+    }
+    let _ = fs::remove_dir(data_dir.join("indexes"));
+    Ok(files.len())
+}
+
+/// Decrypts the index back into the data directory and removes the encrypted file.
+/// Returns the number of decrypted files.
+pub fn decrypt_index(passphrase: &str) -> Result<usize, String> {
+    if !is_encrypted() {
+        return Err("The index is not encrypted".into());
+    }
+    let (_, files) = read_encrypted(&paths::encrypted_index_file(), passphrase)?;
+    let count = files.len();
+    write_files(&paths::data_dir(), files).map_err(|e| e.to_string())?;
+    fs::remove_file(paths::encrypted_index_file()).map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+fn encrypt(key: &[u8; 32], salt: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| "Failed to encrypt the index".to_string())?;
+
+    let mut bytes =
+        Vec::with_capacity(MAGIC.len() + 1 + salt.len() + nonce.len() + ciphertext.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(FORMAT_VERSION);
+    bytes.extend_from_slice(salt);
+    bytes.extend_from_slice(&nonce);
+    bytes.extend(ciphertext);
+    Ok(bytes)
+}
+
+/// Decrypts an encrypted index. Returns its salt and its files by relative path.
+fn read_encrypted(file: &Path, passphrase: &str) -> Result<([u8; SALT_LENGTH], Files), String> {
+    let bytes = fs::read(file).map_err(|e| e.to_string())?;
+    let invalid = || "The encrypted index is damaged".to_string();
+    let rest = bytes.strip_prefix(MAGIC).ok_or_else(invalid)?;
+    let (&version, rest) = rest.split_first().ok_or_else(invalid)?;
+    if version != FORMAT_VERSION {
+        return Err(format!(
+            "The index is encrypted with format version {}, this findimg reads version {}",
+            version, FORMAT_VERSION
+        ));
+    }
+    let (salt, rest) = rest
+        .split_first_chunk::<SALT_LENGTH>()
+        .ok_or_else(invalid)?;
+    let (nonce, ciphertext) = rest
+        .split_first_chunk::<NONCE_LENGTH>()
+        .ok_or_else(invalid)?;
+
+    let key = derive_key(passphrase, salt)?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Wrong passphrase".to_string())?;
+    Ok((*salt, read_files(&plaintext).ok_or_else(invalid)?))
+}
+
+/// Adds a file to the plaintext: the length of its path as a little-endian `u32`,
+/// the path, the length of its content as a little-endian `u64` and the content
+fn append_file(plaintext: &mut Vec<u8>, name: &str, content: &[u8]) {
+    plaintext.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    plaintext.extend_from_slice(name.as_bytes());
+    plaintext.extend_from_slice(&(content.len() as u64).to_le_bytes());
+    plaintext.extend_from_slice(content);
+}
+
+fn read_files(mut plaintext: &[u8]) -> Option<Files> {
+    let mut files = Vec::new();
+    while !plaintext.is_empty() {
+        let (length, rest) = plaintext.split_first_chunk::<4>()?;
+        let (name, rest) = rest.split_at_checked(u32::from_le_bytes(*length) as usize)?;
+        let (length, rest) = rest.split_first_chunk::<8>()?;
+        let (content, rest) =
+            rest.split_at_checked(usize::try_from(u64::from_le_bytes(*length)).ok()?)?;
+        files.push((String::from_utf8(name.to_vec()).ok()?, content.to_vec()));
+        plaintext = rest;
+    }
+    Some(files)
+}
+
+fn write_files(dir: &Path, files: Files) -> io::Result<()> {
+    for (name, content) in files {
+        // the names are written by findimg, but a damaged file must not write elsewhere
+        if name.split('/').any(|part| part.is_empty() || part == "..") {
+            continue;
+        }
+        let path = name
+            .split('/')
+            .fold(dir.to_path_buf(), |path, part| path.join(part));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+/// Every file below `dir` with its path relative to it, separated by slashes
+fn files_in(dir: &Path) -> Vec<(String, PathBuf)> {
+    let mut files = Vec::new();
+    let mut pending = vec![(String::new(), dir.to_path_buf())];
+    while let Some((prefix, dir)) = pending.pop() {
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push((format!("{}/", name), path));
+            } else {
+                files.push((name, path));
+            }
+        }
+    }
+    files
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{index::Interrupted, lock::IndexGuard};
+    use std::error::Error;
+
+    /// An encrypted index with the passphrase `secret` holding only `metadata.sqlite`, in a
+    /// new directory named after the test. Returns the directory and the encrypted file.
+    fn encrypted_index(name: &str) -> (PathBuf, PathBuf) {
+        let root = env::temp_dir().join(format!(
+            "findimg-encryption-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let file = root.join("index.enc");
+        let salt = [7; SALT_LENGTH];
+        let mut plaintext = Vec::new();
+        append_file(&mut plaintext, "metadata.sqlite", b"before");
+        let key = derive_key("secret", &salt).unwrap();
+        fs::write(&file, encrypt(&key, &salt, &plaintext).unwrap()).unwrap();
+        (root, file)
+    }
+
+    #[test]
+    fn interrupted_run_leaves_no_unlocked_directory_behind() {
+        let (root, file) = encrypted_index("interrupted");
+        let dir = root.join("unlocked");
+        let unlocked = unlock_at(&file, dir.clone(), "secret").unwrap();
+        let status =
+            IndexGuard::new(None, Some(unlocked), None).run(|_| -> Result<(), Box<dyn Error>> {
+                // indexing changes the index, then a signal stops it
+                fs::write(dir.join("metadata.sqlite"), "after")?;
+                Err(Interrupted { done: 1, total: 2 }.into())
+            });
+
+        assert!(status.unwrap_err().downcast::<Interrupted>().is_ok());
+        assert!(!dir.exists());
+        let (_, files) = read_encrypted(&file, "secret").unwrap();
+        assert_eq!(files, [("metadata.sqlite".to_string(), b"after".to_vec())]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn session_and_feedback_are_encrypted_with_the_index() {
+        // they are stored next to the metadata, so they end up in an unlocked index
+        let index_dir = paths::catalog_file().parent().unwrap().to_path_buf();
+        for state in [paths::session_file(), paths::feedback_file()] {
+            assert_eq!(state.parent(), Some(index_dir.as_path()));
+            let name = state.file_name().unwrap().to_str().unwrap();
+            assert!(INDEX_FILES.contains(&name), "{name} isn't encrypted");
+        }
+
+        let (root, file) = encrypted_index("state");
+        let dir = root.join("unlocked");
+        let unlocked = unlock_at(&file, dir.clone(), "secret").unwrap();
+        fs::write(dir.join("session.json"), "{\"query\": \"passport\"}").unwrap();
+        fs::write(dir.join("feedback.json"), "{}").unwrap();
+        drop(unlocked);
+
+        assert!(!dir.exists());
+        let (_, mut files) = read_encrypted(&file, "secret").unwrap();
+        files.sort();
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["feedback.json", "metadata.sqlite", "session.json"]);
+        assert!(
+            !String::from_utf8_lossy(&fs::read(&file).unwrap()).contains("passport"),
+            "the session is stored in plaintext"
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,354 @@
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+    collections::{HashMap, HashSet, VecDeque, hash_map::DefaultHasher},
+    fs,
+    hash::{Hash, Hasher},
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    catalog::Catalog,
+    index::{HEIGHT_FIELD, TAGS_FIELD, WIDTH_FIELD, cosine_similarity},
+    query::Filter,
+    remote,
+    store::VectorStore,
+};
+
+/// Number of best candidates sent to the re-ranking service
+pub const RERANK_CANDIDATES: usize = 50;
+/// Damping constant of reciprocal rank fusion, keeps the first ranks from dominating the sum
+const RRF_K: f32 = 60.0;
+/// Most rankings the score cache keeps, rankings of the whole library can be large
+const CACHED_RANKINGS: usize = 16;
+
+/// How the similarity between a query and an image embedding is measured.
+/// Some models expect dot products on unnormalized vectors instead of cosine similarity.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Similarity {
+    #[default]
+    Cosine,
+    Dot,
+    /// Euclidean distance, turned into a score between 0 and 1 as `1 / (1 + distance)`
+    Euclidean,
+}
+
+impl Similarity {
+    /// Scores an image embedding against a query embedding, higher is more similar
+    pub fn score(self, query: &[f32], vector: &[f32]) -> f32 {
+        match self {
+            Similarity::Cosine => cosine_similarity(query, vector),
+            Similarity::Dot => query.iter().zip(vector).map(|(a, b)| a * b).sum(),
+            Similarity::Euclidean => {
+                let distance = query
+                    .iter()
+                    .zip(vector)
+                    .map(|(a, b)| (a - b).powi(2))
+                    .sum::<f32>()
+                    .sqrt();
+                1.0 / (1.0 + distance)
+            }
+        }
+    }
+}
+
+/// Where the image of a hit comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HitSource {
+    /// A file in the library directory
+    Local,
+    /// An image on the web, see `remote::Remotes`. Only its thumbnail is stored.
+    Remote,
+}
+
+/// A search result with the metadata shown of it, what every search mode hands to the
+/// interface, the JSON output and the exporters so they don't each look it up again
+#[derive(Debug, Clone, Serialize)]
+pub struct Hit {
+    pub path: String,
+    /// Similarity to the query, higher is better
+    pub score: f32,
+    /// Width and height in pixels, as measured while indexing
+    pub dims: Option<(u32, u32)>,
+    /// Modification time of the file in seconds since the Unix epoch, `None` for remote items
+    pub mtime: Option<u64>,
+    pub tags: Vec<String>,
+    pub source: HitSource,
+}
+
+impl Hit {
+    /// Describes a ranked image with its metadata from the catalog
+    pub fn new(path: String, score: f32, fields: &HashMap<String, Value>) -> Self {
+        let dimension = |field: &str| fields.get(field)?.as_u64().map(|value| value as u32);
+        let source = if remote::is_remote(&path) {
+            HitSource::Remote
+        } else {
+            HitSource::Local
+        };
+        let mtime = match source {
+            HitSource::Local => fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|age| age.as_secs()),
+            HitSource::Remote => None,
+        };
+        Self {
+            dims: dimension(WIDTH_FIELD).zip(dimension(HEIGHT_FIELD)),
+            mtime,
+            tags: fields
+                .get(TAGS_FIELD)
+                .and_then(|tags| serde_json::from_value(tags.clone()).ok())
+                .unwrap_or_default(),
+            source,
+            path,
+            score,
+        }
+    }
+}
+
+/// Describes ranked images as hits, in the same order
+pub fn hits(ranked: Vec<(String, f32)>, catalog: &Catalog) -> Vec<Hit> {
+    ranked
+        .into_iter()
+        .map(|(path, score)| {
+            let fields = catalog.fields(&path);
+            Hit::new(path, score, &fields)
+        })
+        .collect()
+}
+
+/// A constraint on the images that is checked before any of them is scored, so a
+/// constrained query over a big library only scores the few images that can match
+pub trait PreFilter {
+    /// Leaves out the candidates that don't pass
+    fn narrow(&self, catalog: &Catalog, candidates: &mut Vec<String>);
+}
+
+/// Filters of the search bar, answered by the catalog in one query
+impl PreFilter for Vec<Filter> {
+    fn narrow(&self, catalog: &Catalog, candidates: &mut Vec<String>) {
+        let matching = catalog.matching(self);
+        candidates.retain(|path| matching.contains(path));
+    }
+}
+
+/// A set of images, e.g. the pool a refinement ranks
+impl PreFilter for HashSet<String> {
+    fn narrow(&self, _: &Catalog, candidates: &mut Vec<String>) {
+        candidates.retain(|path| self.contains(path));
+    }
+}
+
+/// Pre-filters run one after the other on the library, the cheapest should come first
+#[derive(Default)]
+pub struct Pipeline<'a> {
+    stages: Vec<&'a dyn PreFilter>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn push(&mut self, stage: &'a dyn PreFilter) {
+        self.stages.push(stage);
+    }
+
+    /// The images of `paths` passing every stage, all of them if there are no stages
+    pub fn candidates<'p>(&self, catalog: &Catalog, paths: &'p [String]) -> Candidates<'p> {
+        if self.stages.is_empty() {
+            return Candidates::All(paths);
+        }
+        let mut candidates = paths.to_vec();
+        for stage in &self.stages {
+            if candidates.is_empty() {
+                break;
+            }
+            stage.narrow(catalog, &mut candidates);
+        }
+        Candidates::Narrowed(candidates)
+    }
+}
+
+/// The images a query is scored against
+pub enum Candidates<'a> {
+    /// The whole library
+    All(&'a [String]),
+    /// What the pre-filters let through
+    Narrowed(Vec<String>),
+}
+
+impl Candidates<'_> {
+    pub fn paths(&self) -> &[String] {
+        match self {
+            Candidates::All(paths) => paths,
+            Candidates::Narrowed(paths) => paths,
+        }
+    }
+}
+
+/// Ranks the candidates by similarity to `query`, from best match to worst.
+///
+/// Cosine similarity over the whole library uses the vector store's own search, otherwise
+/// only the candidates are scored.
+pub fn rank(
+    database: &dyn VectorStore,
+    candidates: &Candidates,
+    query: &[f32],
+    similarity: Similarity,
+    top_k: usize,
+) -> Vec<(String, f32)> {
+    if similarity == Similarity::Cosine && matches!(candidates, Candidates::All(_)) {
+        return database.query(query, top_k);
+    }
+
+    let mut ranked: Vec<(String, f32)> = database
+        .get(candidates.paths())
+        .into_iter()
+        .map(|data| (data.id.clone(), similarity.score(query, &data.vector)))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked.truncate(top_k);
+    ranked
+}
+
+/// Rankings of the session's earlier queries, so searching again or going back from a
+/// refinement doesn't score the index again. Only the scores of the index are kept, everything
+/// that reorders them afterwards runs again. Has to be cleared whenever the index changes.
+#[derive(Debug, Default)]
+pub struct ScoreCache {
+    rankings: HashMap<u64, Vec<(String, f32)>>,
+    /// Keys from the oldest ranking to the newest, the oldest is dropped first
+    order: VecDeque<u64>,
+}
+
+impl ScoreCache {
+    /// Identifies what `rank` is called with: a hash of the query embedding, the similarity,
+    /// the candidates and how many of them are kept
+    pub fn key(
+        candidates: &Candidates,
+        query: &[f32],
+        similarity: Similarity,
+        top_k: usize,
+    ) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for value in query {
+            value.to_bits().hash(&mut hasher);
+        }
+        similarity.hash(&mut hasher);
+        top_k.hash(&mut hasher);
+        match candidates {
+            Candidates::All(_) => None,
+            Candidates::Narrowed(paths) => Some(paths),
+        }
+        .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn get(&self, key: u64) -> Option<Vec<(String, f32)>> {
+        self.rankings.get(&key).cloned()
+    }
+
+    pub fn insert(&mut self, key: u64, ranking: Vec<(String, f32)>) {
+        if self.rankings.insert(key, ranking).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > CACHED_RANKINGS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.rankings.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.rankings.clear();
+        self.order.clear();
+    }
+}
+
+/// Fuses several rankings of the same images into one with reciprocal rank fusion.
+///
+/// Every image scores `1 / (RRF_K + rank)` per ranking it appears in, so images ranked well
+/// by several models rise to the top even though the models' scores are not comparable.
+pub fn reciprocal_rank_fusion(rankings: &[Vec<(String, f32)>]) -> Vec<(String, f32)> {
+    let mut scores: HashMap<&str, f32> = HashMap::new();
+    for ranking in rankings {
+        for (rank, (path, _)) in ranking.iter().enumerate() {
+            *scores.entry(path).or_default() += 1.0 / (RRF_K + rank as f32 + 1.0);
+        }
+    }
+
+    let mut fused: Vec<(String, f32)> = scores
+        .into_iter()
+        .map(|(path, score)| (path.to_string(), score))
+        .collect();
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    fused
+}
+
+/// Gives the images of a fused ranking their similarity to the query as their score and keeps
+/// the fused order. The scores of reciprocal rank fusion only order the images, shown as the
+/// confidence they would mean nothing. Images without an embedding score 0.
+pub fn rescore(
+    database: &dyn VectorStore,
+    fused: Vec<(String, f32)>,
+    query: &[f32],
+    similarity: Similarity,
+) -> Vec<(String, f32)> {
+    let paths: Vec<String> = fused.iter().map(|(path, _)| path.clone()).collect();
+    let scores: HashMap<String, f32> = database
+        .get(&paths)
+        .into_iter()
+        .map(|data| (data.id.clone(), similarity.score(query, &data.vector)))
+        .collect();
+    paths
+        .into_iter()
+        .map(|path| {
+            let score = scores.get(&path).copied().unwrap_or_default();
+            (path, score)
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct RerankResponse {
+    scores: Vec<f32>,
+}
+
+/// Re-orders the best `RERANK_CANDIDATES` results with a remote re-ranking service,
+/// e.g. a cross-encoder that looks at the query and each image together.
+///
+/// The service receives `{"query": "...", "paths": [...]}` and answers with
+/// `{"scores": [...]}`, one score per path. Results after the candidates keep their order.
+pub fn rerank(
+    client: &Client,
+    endpoint: &str,
+    query: &str,
+    mut ranked: Vec<(String, f32)>,
+) -> Result<Vec<(String, f32)>, Box<dyn std::error::Error>> {
+    let count = ranked.len().min(RERANK_CANDIDATES);
+    let paths: Vec<&String> = ranked[..count].iter().map(|(path, _)| path).collect();
+
+    let response = client
+        .post(endpoint)
+        .header("Content-Type", "application/json")
+        .body(json!({ "query": query, "paths": paths }).to_string())
+        .send()?
+        .error_for_status()?
+        .text()?;
+    let response: RerankResponse = serde_json::from_str(&response)?;
+
+    if response.scores.len() != count {
+        return Err(format!(
+            "Re-ranker returned {} scores for {} candidates",
+            response.scores.len(),
+            count
+        )
+        .into());
+    }
+
+    for ((_, score), new_score) in ranked.iter_mut().zip(response.scores) {
+        *score = new_score;
+    }
+    ranked[..count].sort_by(|a, b| b.1.total_cmp(&a.1));
+    Ok(ranked)
+}
@@ -0,0 +1,141 @@
+use cliprs::ClipModel;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use crate::{
+    config::Config,
+    curate::Library,
+    engine::{self, Pipeline},
+    expansion, query,
+};
+
+/// Number of results the metrics look at without `--k`
+pub const DEFAULT_K: usize = 10;
+
+/// Retrieval quality averaged over the queries of a dataset
+#[derive(Debug, Default)]
+pub struct Report {
+    pub queries: usize,
+    pub k: usize,
+    /// Share of the relevant images found in the first `k` results
+    pub recall: f64,
+    /// Mean reciprocal rank of the first relevant image in the whole ranking
+    pub mrr: f64,
+    /// Normalized discounted cumulative gain of the first `k` results
+    pub ndcg: f64,
+    /// Queries without a relevant image in the first `k` results
+    pub misses: Vec<String>,
+}
+
+/// Reads a dataset of `query,image` lines, one per relevant image of a query. The header
+/// line is optional and images are named by their file name, so the same labels work for
+/// a library on another machine. The query may contain commas, the image name may not.
+pub fn read_dataset(file: &Path) -> Result<Vec<(String, HashSet<String>)>, String> {
+    let text = fs::read_to_string(file).map_err(|e| e.to_string())?;
+
+    let mut queries: Vec<(String, HashSet<String>)> = Vec::new();
+    let mut positions = HashMap::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (number == 0 && line == "query,image") {
+            continue;
+        }
+        let (query, image) = line
+            .rsplit_once(',')
+            .ok_or_else(|| format!("Line {} is not a query and an image", number + 1))?;
+        let query = query.trim().trim_matches('"').to_string();
+        let image = file_name(image.trim().trim_matches('"'));
+
+        // the lines of a query don't have to be next to each other
+        let position = *positions.entry(query.clone()).or_insert_with(|| {
+            queries.push((query, HashSet::new()));
+            queries.len() - 1
+        });
+        queries[position].1.insert(image);
+    }
+
+    if queries.is_empty() {
+        return Err("The dataset has no queries".into());
+    }
+    Ok(queries)
+}
+
+/// Runs every query of the dataset against the library like the search bar does, with
+/// filters and query expansion, and measures how well the relevant images are ranked.
+pub fn evaluate(
+    model: &ClipModel,
+    library: &Library,
+    config: &Config,
+    similarity: engine::Similarity,
+    dataset: &[(String, HashSet<String>)],
+    k: usize,
+) -> Result<Report, String> {
+    let mut report = Report {
+        queries: dataset.len(),
+        k,
+        ..Default::default()
+    };
+
+    for (text, relevant) in dataset {
+        let query = query::parse(text);
+        let embedding = expansion::embed_query(model, &query.text, config)?;
+        let mut pipeline = Pipeline::default();
+        if !query.filters.is_empty() {
+            pipeline.push(&query.filters);
+        }
+        let candidates = pipeline.candidates(library.catalog, library.image_paths);
+        let ranked = engine::rank(
+            library.database,
+            &candidates,
+            &embedding,
+            similarity,
+            usize::MAX,
+        );
+        let hits: Vec<bool> = ranked
+            .iter()
+            .map(|(path, _)| relevant.contains(&file_name(path)))
+            .collect();
+
+        let found = hits.iter().take(k).filter(|hit| **hit).count();
+        report.recall += found as f64 / relevant.len() as f64;
+        report.mrr += hits
+            .iter()
+            .position(|hit| *hit)
+            .map_or(0.0, |rank| 1.0 / (rank + 1) as f64);
+        report.ndcg += ndcg(&hits, relevant.len(), k);
+        if found == 0 {
+            report.misses.push(text.clone());
+        }
+    }
+
+    let queries = report.queries.max(1) as f64;
+    report.recall /= queries;
+    report.mrr /= queries;
+    report.ndcg /= queries;
+    Ok(report)
+}
+
+/// nDCG with binary relevance: the gain of the first `k` results divided by the gain
+/// of a ranking that puts all relevant images first
+fn ndcg(hits: &[bool], relevant: usize, k: usize) -> f64 {
+    let gain = |rank: usize| 1.0 / (rank as f64 + 2.0).log2();
+    let dcg: f64 = hits
+        .iter()
+        .take(k)
+        .enumerate()
+        .filter(|(_, hit)| **hit)
+        .map(|(rank, _)| gain(rank))
+        .sum();
+    let ideal: f64 = (0..relevant.min(k)).map(gain).sum();
+    if ideal > 0.0 { dcg / ideal } else { 0.0 }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
@@ -0,0 +1,174 @@
+use cliprs::ClipModel;
+use crossterm::event::{self, Event};
+use std::{
+    io,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{cleanup::Group, encode::Encoded, search::SearchOutcome};
+
+/// Everything the main loop reacts to, multiplexed over one channel.
+pub enum AppEvent {
+    /// Terminal input: keys, mouse and resizes
+    Input(Event),
+    /// Time to draw the next frame
+    Tick,
+    /// A search running on a worker thread has finished
+    Search(Box<SearchOutcome>),
+    /// Ctrl+Z was pressed or the process got SIGTSTP
+    Suspend,
+    /// The process got SIGCONT after being stopped
+    Resume,
+    /// The process got SIGINT or SIGTERM and should quit
+    Shutdown,
+    /// Open the metadata of an image in the external editor
+    EditMetadata(String),
+    /// Run the search in the search bar again
+    StartSearch,
+    /// Undo the last delete
+    Undo,
+    /// What the background loading is doing right now
+    Loading(String),
+    /// The model of a slot finished loading
+    ModelLoaded(usize, Arc<ClipModel>),
+    /// The model of a slot could not be loaded
+    ModelFailed(usize),
+    /// Images that could not be read, with the reason, to be listed in the stats
+    Unreadable(Vec<(String, String)>),
+    /// Indexing a slot finished, with the library directory and the paths of all images in it
+    Indexed(usize, PathBuf, Vec<String>),
+    /// The voice input command finished, with the spoken query or why it failed
+    Transcribed(Result<String, String>),
+    /// What the library clean-up found to decide on, see `cleanup::find_groups`
+    CleanupFound(Vec<Group>),
+    /// The collage of the results is made, with what happened to it or why it failed
+    CollageMade(Result<String, String>),
+    /// A result image is encoded for the terminal, see `encode::Encoder`
+    Encoded(Box<Encoded>),
+}
+
+/// Source of `AppEvent`s. Input and ticks come from their own threads,
+/// workers send their results through `sender`.
+pub struct Events {
+    sender: Sender<AppEvent>,
+    receiver: Receiver<AppEvent>,
+    /// Set while a tick is waiting in the channel, so slow frames don't pile up ticks
+    tick_pending: Arc<AtomicBool>,
+    /// Set while another program uses the terminal, input is left to it
+    input_paused: Arc<AtomicBool>,
+}
+
+/// How long the input thread waits for input before checking whether it got paused
+const INPUT_POLL: Duration = Duration::from_millis(50);
+
+impl Events {
+    /// Starts the input and tick threads.
+    ///
+    /// # Arguments
+    ///
+    /// - tick_rate: Time between two ticks, i.e. between two frames.
+    pub fn new(tick_rate: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        let input = sender.clone();
+        let input_paused = Arc::new(AtomicBool::new(false));
+        let paused = Arc::clone(&input_paused);
+        thread::spawn(move || {
+            loop {
+                if paused.load(Ordering::Acquire) {
+                    thread::sleep(INPUT_POLL);
+                    continue;
+                }
+                match event::poll(INPUT_POLL) {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(_) => break,
+                }
+                let Ok(event) = event::read() else {
+                    break;
+                };
+                if input.send(AppEvent::Input(event)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        #[cfg(unix)]
+        {
+            use signal_hook::{
+                consts::{SIGCONT, SIGINT, SIGTERM, SIGTSTP},
+                iterator::Signals,
+            };
+
+            let signal = sender.clone();
+            if let Ok(mut signals) = Signals::new([SIGTSTP, SIGCONT, SIGINT, SIGTERM]) {
+                thread::spawn(move || {
+                    for received in signals.forever() {
+                        let event = match received {
+                            SIGTSTP => AppEvent::Suspend,
+                            SIGCONT => AppEvent::Resume,
+                            _ => AppEvent::Shutdown,
+                        };
+                        if signal.send(event).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        }
+
+        let tick = sender.clone();
+        let tick_pending = Arc::new(AtomicBool::new(false));
+        let pending = Arc::clone(&tick_pending);
+        thread::spawn(move || {
+            loop {
+                if !pending.swap(true, Ordering::AcqRel) && tick.send(AppEvent::Tick).is_err() {
+                    break;
+                }
+                thread::sleep(tick_rate);
+            }
+        });
+
+        Self {
+            sender,
+            receiver,
+            tick_pending,
+            input_paused,
+        }
+    }
+
+    /// A sender for worker threads to report back with
+    pub fn sender(&self) -> Sender<AppEvent> {
+        self.sender.clone()
+    }
+
+    /// Stops reading input so another program can use the terminal
+    pub fn pause_input(&self) {
+        self.input_paused.store(true, Ordering::Release);
+        // let a poll that is already running finish
+        thread::sleep(INPUT_POLL * 2);
+    }
+
+    pub fn resume_input(&self) {
+        self.input_paused.store(false, Ordering::Release);
+    }
+
+    /// Waits for the next event
+    pub fn next(&self) -> io::Result<AppEvent> {
+        let event = self
+            .receiver
+            .recv()
+            .map_err(|_| io::Error::other("Event channel closed"))?;
+        if matches!(event, AppEvent::Tick) {
+            self.tick_pending.store(false, Ordering::Release);
+        }
+        Ok(event)
+    }
+}
@@ -0,0 +1,107 @@
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+
+use crate::remote;
+
+/// Tags of IFD1 locating the thumbnail, a JPEG stored in the EXIF data
+const THUMBNAIL_OFFSET: u16 = 0x0201;
+const THUMBNAIL_LENGTH: u16 = 0x0202;
+
+/// The EXIF data of an image as a TIFF structure, `None` if it has none. Only the header
+/// of the file is read.
+pub fn read(path: &str) -> Option<Vec<u8>> {
+    let mut decoder = ImageReader::open(remote::local_file(path))
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .into_decoder()
+        .ok()?;
+    let exif = decoder.exif_metadata().ok()??;
+    Some(match exif.strip_prefix(b"Exif\0\0") {
+        Some(tiff) => tiff.to_vec(),
+        None => exif,
+    })
+}
+
+/// The thumbnail cameras and phones embed in the EXIF data, decoded. It is much smaller
+/// than the photo, usually 160x120 pixels, so it takes a fraction of the time to decode.
+pub fn thumbnail(path: &str) -> Option<DynamicImage> {
+    let exif = read(path)?;
+    let tiff = Tiff::new(&exif)?;
+    let ifd1 = tiff.next_ifd(tiff.ifd0()?)?;
+    let offset = tiff.u32(tiff.entry(ifd1, THUMBNAIL_OFFSET)? + 8)? as usize;
+    let length = tiff.u32(tiff.entry(ifd1, THUMBNAIL_LENGTH)? + 8)? as usize;
+    let jpeg = exif.get(offset..offset.checked_add(length)?)?;
+    image::load_from_memory_with_format(jpeg, ImageFormat::Jpeg).ok()
+}
+
+/// Just enough of a TIFF reader to look up entries in the EXIF directories
+pub struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        Some(Self {
+            data,
+            little_endian,
+        })
+    }
+
+    pub fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    pub fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    /// Offset of the first directory, IFD0 with the tags of the image
+    pub fn ifd0(&self) -> Option<usize> {
+        Some(self.u32(4)? as usize)
+    }
+
+    /// Offset of the directory after the one at `ifd`, `None` if it is the last one
+    pub fn next_ifd(&self, ifd: usize) -> Option<usize> {
+        let count = self.u16(ifd)? as usize;
+        let next = self.u32(ifd + 2 + count * 12)? as usize;
+        (next != 0).then_some(next)
+    }
+
+    /// Offset of the 12 byte entry with the given tag in the directory at `ifd`
+    pub fn entry(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let count = self.u16(ifd)? as usize;
+        (0..count)
+            .map(|i| ifd + 2 + i * 12)
+            .find(|entry| self.u16(*entry) == Some(tag))
+    }
+
+    /// Reads the ASCII value of an entry
+    pub fn ascii(&self, entry: usize) -> Option<String> {
+        let count = self.u32(entry + 4)? as usize;
+        // values of up to 4 bytes are stored in the entry itself
+        let offset = if count <= 4 {
+            entry + 8
+        } else {
+            self.u32(entry + 8)? as usize
+        };
+        let bytes = self.data.get(offset..offset + count)?;
+        let text = bytes.split(|b| *b == 0).next()?;
+        Some(String::from_utf8_lossy(text).to_string())
+    }
+}
@@ -0,0 +1,138 @@
+use cliprs::ClipModel;
+
+use crate::config::Config;
+
+/// Prompt templates used as paraphrases of English queries
+const TEMPLATES: [&str; 4] = [
+    "a photo of {}",
+    "a picture of {}",
+    "an image showing {}",
+    "a close-up photo of {}",
+];
+
+/// Prompt templates that describe text written on something in the image
+const TEXT_ON_IMAGE_TEMPLATES: [&str; 4] = [
+    "a photo containing the text \"{}\"",
+    "a sign that says \"{}\"",
+    "the words \"{}\" written on it",
+    "a picture with the caption \"{}\"",
+];
+
+/// Words that mean the same thing in photos. Extended by `synonyms` in the config.
+const SYNONYMS: [&[&str]; 10] = [
+    &["couch", "sofa"],
+    &["car", "automobile"],
+    &["kid", "child"],
+    &["puppy", "dog"],
+    &["kitten", "cat"],
+    &["sea", "ocean"],
+    &["mountain", "hill"],
+    &["road", "street"],
+    &["picture", "photo"],
+    &["sunset", "dusk"],
+];
+
+/// Rewordings of `text` that get embedded together with it, at most
+/// `query_expansions` of them. Word swaps from the synonym lists come first,
+/// followed by prompt templates.
+///
+/// The built-in synonyms and templates are English, so with a multilingual model
+/// only the synonyms from the config are used and the query is embedded as written.
+pub fn paraphrases(text: &str, config: &Config) -> Vec<String> {
+    let text = text.trim();
+    let mut paraphrases = Vec::new();
+
+    for (index, word) in text.split_whitespace().enumerate() {
+        for synonym in synonyms(word, config) {
+            let reworded: Vec<&str> = text
+                .split_whitespace()
+                .enumerate()
+                .map(|(i, w)| if i == index { synonym.as_str() } else { w })
+                .collect();
+            paraphrases.push(reworded.join(" "));
+        }
+    }
+
+    if !config.multilingual {
+        paraphrases.extend(
+            TEMPLATES
+                .iter()
+                .map(|template| template.replace("{}", text)),
+        );
+    }
+
+    let mut unique: Vec<String> = Vec::new();
+    for paraphrase in paraphrases {
+        if paraphrase != text && !unique.contains(&paraphrase) {
+            unique.push(paraphrase);
+        }
+    }
+    unique.truncate(config.query_expansions);
+    unique
+}
+
+fn synonyms(word: &str, config: &Config) -> Vec<String> {
+    let word = word.to_lowercase();
+
+    let mut synonyms: Vec<String> = config.synonyms.get(&word).cloned().unwrap_or_default();
+    if !config.multilingual {
+        for group in SYNONYMS
+            .iter()
+            .filter(|group| group.contains(&word.as_str()))
+        {
+            synonyms.extend(
+                group
+                    .iter()
+                    .filter(|synonym| **synonym != word)
+                    .map(|synonym| synonym.to_string()),
+            );
+        }
+    }
+    synonyms
+}
+
+/// Embeds a text query together with its paraphrases and averages the embeddings.
+/// Without expansions configured this is the same as embedding the text.
+pub fn embed_query(model: &ClipModel, text: &str, config: &Config) -> Result<Vec<f32>, String> {
+    let mut embedding = normalized(model.embed_text(text)?);
+
+    let paraphrases = paraphrases(text, config);
+    if paraphrases.is_empty() {
+        return Ok(embedding);
+    }
+
+    for paraphrase in &paraphrases {
+        let vector = normalized(model.embed_text(paraphrase)?);
+        for (sum, value) in embedding.iter_mut().zip(vector) {
+            *sum += value;
+        }
+    }
+
+    Ok(normalized(embedding))
+}
+
+/// Embeds text that should be visible in the image by averaging the embeddings of the
+/// text wrapped in each of the text-on-image templates.
+pub fn embed_text_on_image(model: &ClipModel, text: &str) -> Result<Vec<f32>, String> {
+    let text = text.trim();
+    let mut embedding = vec![];
+    for template in TEXT_ON_IMAGE_TEMPLATES {
+        let vector = normalized(model.embed_text(template.replace("{}", text))?);
+        if embedding.is_empty() {
+            embedding = vector;
+        } else {
+            for (sum, value) in embedding.iter_mut().zip(vector) {
+                *sum += value;
+            }
+        }
+    }
+    Ok(normalized(embedding))
+}
+
+pub fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
@@ -0,0 +1,124 @@
+use serde_json::json;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{config::Config, index, models, parquet, store::VectorStore};
+
+/// File formats the embeddings can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorFormat {
+    /// A NumPy array of shape `(images, dimensions)`, with the paths in a text file next to it
+    Npy,
+    /// A Parquet table with a `path` and a `vector` column
+    Parquet,
+    /// One JSON object with `path` and `vector` per line
+    Jsonl,
+}
+
+impl VectorFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "npy" => Some(VectorFormat::Npy),
+            "parquet" => Some(VectorFormat::Parquet),
+            "jsonl" => Some(VectorFormat::Jsonl),
+            _ => None,
+        }
+    }
+
+    pub const fn extension(self) -> &'static str {
+        match self {
+            VectorFormat::Npy => "npy",
+            VectorFormat::Parquet => "parquet",
+            VectorFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+/// Exports the embeddings of the library from the index of the main model, or of the
+/// additional model with the given name. Returns the number of exported images.
+pub fn export_index(
+    config: &Config,
+    main_model: Option<&Path>,
+    model: Option<&str>,
+    format: VectorFormat,
+    output: &Path,
+) -> Result<usize, String> {
+    let info = models::find_index(config, main_model, model)?;
+    let database = index::open_database(config, &info);
+    let image_paths = index::library_items(&config.library_dir());
+
+    export_vectors(&*database, &image_paths, format, output).map_err(|e| e.to_string())
+}
+
+/// Writes the embeddings of the given images to `output`. Images that are not indexed
+/// or whose embedding has an unexpected length are left out.
+pub fn export_vectors(
+    database: &dyn VectorStore,
+    image_paths: &[String],
+    format: VectorFormat,
+    output: &Path,
+) -> io::Result<usize> {
+    let data = database.get(image_paths);
+    let dimensions = data
+        .iter()
+        .map(|data| data.vector.len())
+        .find(|length| *length > 0)
+        .unwrap_or_default();
+    let rows: Vec<(&str, &[f32])> = data
+        .iter()
+        .filter(|data| data.vector.len() == dimensions && dimensions > 0)
+        .map(|data| (data.id.as_str(), data.vector.as_slice()))
+        .collect();
+
+    let mut file = BufWriter::new(File::create(output)?);
+    match format {
+        VectorFormat::Npy => {
+            write_npy(&mut file, &rows, dimensions)?;
+            let mut paths = BufWriter::new(File::create(npy_paths_file(output))?);
+            for (path, _) in &rows {
+                writeln!(paths, "{}", path)?;
+            }
+            paths.flush()?;
+        }
+        VectorFormat::Parquet => parquet::write_vectors(&mut file, &rows, dimensions)?,
+        VectorFormat::Jsonl => {
+            for (path, vector) in &rows {
+                writeln!(file, "{}", json!({ "path": path, "vector": vector }))?;
+            }
+        }
+    }
+    file.flush()?;
+    Ok(rows.len())
+}
+
+/// Text file listing the path of every row of an exported NumPy array, one per line
+pub fn npy_paths_file(output: &Path) -> PathBuf {
+    output.with_extension("paths.txt")
+}
+
+/// Writes a little-endian float32 array in the NumPy format version 1.0
+fn write_npy(out: &mut impl Write, rows: &[(&str, &[f32])], dimensions: usize) -> io::Result<()> {
+    const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        rows.len(),
+        dimensions
+    );
+    // the data has to start at a multiple of 64 bytes, the header ends with a newline
+    let unpadded = MAGIC.len() + 2 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+    header.push('\n');
+
+    out.write_all(MAGIC)?;
+    out.write_all(&(header.len() as u16).to_le_bytes())?;
+    out.write_all(header.as_bytes())?;
+    for (_, vector) in rows {
+        for value in *vector {
+            out.write_all(&value.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, io};
+
+use crate::{atomic, index::cosine_similarity, paths};
+
+/// CLIP scores are small, they are scaled up like CLIP's own logits before training
+const LOGIT_SCALE: f32 = 100.0;
+const LEARNING_RATE: f32 = 0.01;
+const EPOCHS: usize = 50;
+/// L2 penalty keeping the learned weights close to plain CLIP ranking
+const REGULARIZATION: f32 = 0.001;
+
+/// A result marked as relevant or not for a query.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Judgement {
+    /// Embedding of the query the result was shown for
+    pub query: Vec<f32>,
+    pub path: String,
+    pub liked: bool,
+}
+
+/// Relevance feedback together with a small linear re-ranker trained on it.
+///
+/// The re-ranker adds a learned weighting of the query and image embedding dimensions to the
+/// CLIP score: `score = cosine(q, v) + w · (q ⊙ v)`. Liking a few photos of your own dog for
+/// "dog" queries shifts the weights towards the dimensions that set it apart.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Feedback {
+    judgements: Vec<Judgement>,
+    weights: Vec<f32>,
+}
+
+impl Feedback {
+    /// Reads the feedback file. A missing or broken file results in an untrained re-ranker.
+    pub fn load() -> Self {
+        fs::read_to_string(paths::feedback_file())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Stores a judgement, replacing an earlier one for the same query and image,
+    /// and retrains the re-ranker.
+    ///
+    /// # Arguments
+    ///
+    /// - vector: Looks up the embedding of an image, judgements of removed images are skipped.
+    pub fn record(
+        &mut self,
+        judgement: Judgement,
+        vector: impl Fn(&str) -> Option<Vec<f32>>,
+    ) -> io::Result<()> {
+        self.judgements
+            .retain(|old| old.path != judgement.path || old.query != judgement.query);
+        self.judgements.push(judgement);
+        self.train(vector);
+        self.save()
+    }
+
+    pub fn len(&self) -> usize {
+        self.judgements.len()
+    }
+
+    /// Re-scores and sorts ranked results. Does nothing until there is feedback.
+    ///
+    /// The scores have to be cosine similarities to `query`: the weights are trained to adjust
+    /// those, so added to other scores, like fused ranks, they would outweigh them.
+    pub fn rerank(
+        &self,
+        query: &[f32],
+        ranked: &mut [(String, f32)],
+        vector: impl Fn(&str) -> Option<Vec<f32>>,
+    ) {
+        if self.weights.is_empty() {
+            return;
+        }
+        for (path, score) in ranked.iter_mut() {
+            if let Some(vector) = vector(path) {
+                *score += self.adjustment(query, &vector);
+            }
+        }
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    }
+
+    fn adjustment(&self, query: &[f32], vector: &[f32]) -> f32 {
+        self.weights
+            .iter()
+            .zip(query.iter().zip(vector))
+            .map(|(w, (q, v))| w * q * v)
+            .sum()
+    }
+
+    /// Logistic regression on the judgements, starting from scratch each time
+    fn train(&mut self, vector: impl Fn(&str) -> Option<Vec<f32>>) {
+        let samples: Vec<(&Judgement, Vec<f32>)> = self
+            .judgements
+            .iter()
+            .filter_map(|judgement| Some((judgement, vector(&judgement.path)?)))
+            .collect();
+        let Some((first, _)) = samples.first() else {
+            self.weights.clear();
+            return;
+        };
+
+        let mut weights = vec![0.0; first.query.len()];
+        let mut bias = 0.0;
+
+        for _ in 0..EPOCHS {
+            for (judgement, vector) in &samples {
+                let query = &judgement.query;
+                let adjustment: f32 = weights
+                    .iter()
+                    .zip(query.iter().zip(vector))
+                    .map(|(w, (q, v))| w * q * v)
+                    .sum();
+                let logit = bias + LOGIT_SCALE * (cosine_similarity(query, vector) + adjustment);
+                let probability = 1.0 / (1.0 + (-logit).exp());
+                let error = probability - if judgement.liked { 1.0 } else { 0.0 };
+
+                for (w, (q, v)) in weights.iter_mut().zip(query.iter().zip(vector)) {
+                    *w -= LEARNING_RATE * (error * LOGIT_SCALE * q * v + REGULARIZATION * *w);
+                }
+                bias -= LEARNING_RATE * error;
+            }
+        }
+
+        self.weights = weights;
+    }
+
+    fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(paths::data_dir())?;
+        atomic::write(&paths::feedback_file(), serde_json::to_string(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const QUERY: [f32; 4] = [0.5, 0.5, 0.5, 0.5];
+
+    fn vectors() -> HashMap<&'static str, Vec<f32>> {
+        HashMap::from([
+            ("liked.jpg", vec![0.9, 0.1, 0.3, 0.3]),
+            ("disliked.jpg", vec![0.2, 0.9, 0.3, 0.3]),
+        ])
+    }
+
+    fn ranking(vectors: &HashMap<&'static str, Vec<f32>>) -> Vec<(String, f32)> {
+        let mut ranking: Vec<(String, f32)> = vectors
+            .iter()
+            .map(|(path, vector)| (path.to_string(), cosine_similarity(&QUERY, vector)))
+            .collect();
+        ranking.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranking
+    }
+
+    fn order(ranking: &[(String, f32)]) -> Vec<&str> {
+        ranking.iter().map(|(path, _)| path.as_str()).collect()
+    }
+
+    #[test]
+    fn liked_images_rise_and_disliked_ones_fall() {
+        let vectors = vectors();
+        let mut feedback = Feedback {
+            judgements: [("liked.jpg", true), ("disliked.jpg", false)]
+                .map(|(path, liked)| Judgement {
+                    query: QUERY.to_vec(),
+                    path: path.to_string(),
+                    liked,
+                })
+                .to_vec(),
+            weights: Vec::new(),
+        };
+        feedback.train(|path| vectors.get(path).cloned());
+
+        let before = ranking(&vectors);
+        assert_eq!(order(&before), ["disliked.jpg", "liked.jpg"]);
+        let mut after = before.clone();
+        feedback.rerank(&QUERY, &mut after, |path| vectors.get(path).cloned());
+
+        assert_eq!(order(&after), ["liked.jpg", "disliked.jpg"]);
+        let score = |ranking: &[(String, f32)], path: &str| {
+            ranking.iter().find(|(p, _)| p == path).unwrap().1
+        };
+        assert!(score(&after, "disliked.jpg") < score(&before, "disliked.jpg"));
+        assert!(
+            score(&after, "liked.jpg") - score(&before, "liked.jpg")
+                > score(&after, "disliked.jpg") - score(&before, "disliked.jpg")
+        );
+    }
+
+    #[test]
+    fn without_feedback_the_ranking_is_left_as_it_is() {
+        let vectors = vectors();
+        let mut feedback = Feedback::default();
+        feedback.train(|path| vectors.get(path).cloned());
+
+        let before = ranking(&vectors);
+        let mut after = before.clone();
+        feedback.rerank(&QUERY, &mut after, |path| vectors.get(path).cloned());
+        assert_eq!(after, before);
+    }
+}
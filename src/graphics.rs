@@ -0,0 +1,134 @@
+use ratatui_image::{
+    FontSize,
+    picker::{Picker, ProtocolType},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    atomic,
+    config::{Config, ImageProtocol},
+    paths, terminal,
+};
+
+/// Age in seconds after which a cached detection is checked again
+const REFRESH_INTERVAL: u64 = 24 * 60 * 60;
+
+/// What the terminal reported when it was queried
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+struct Detection {
+    protocol: ImageProtocol,
+    font_size: FontSize,
+    /// Seconds since the Unix epoch
+    checked: u64,
+}
+
+/// Creates the picker images are drawn with. Returns it together with the protocol the
+/// terminal supports, which is used when the configured protocol is set back to auto.
+///
+/// Querying the terminal is slow on some terminals, so the result is cached per terminal
+/// and only queried on the first launch in a terminal. `protocol` and `font_size` in the
+/// config override the detection, with both set the terminal isn't queried at all.
+pub fn picker(config: &Config) -> (Picker, ProtocolType) {
+    let detection = if let (Some(protocol), Some(font_size)) =
+        (config.protocol.protocol_type(), config.font_size)
+    {
+        Detection {
+            protocol: ImageProtocol::from(protocol),
+            font_size,
+            checked: 0,
+        }
+    } else {
+        match load().remove(&terminal_id()) {
+            Some(detection) => detection,
+            None => query(),
+        }
+    };
+
+    let detected = detection
+        .protocol
+        .protocol_type()
+        .unwrap_or(ProtocolType::Halfblocks);
+    // the only constructor taking a font size, the others query the terminal again
+    #[allow(deprecated)]
+    let mut picker = Picker::from_fontsize(config.font_size.unwrap_or(detection.font_size));
+    picker.set_protocol_type(config.protocol.protocol_type().unwrap_or(detected));
+    (picker, detected)
+}
+
+/// Queries the terminal again if its cached detection is old, e.g. because its font size
+/// changed. Querying blocks and the answers arrive as input, so this runs on exit once
+/// input isn't read anymore instead of delaying the next start.
+pub fn refresh(config: &Config) {
+    if config.protocol.protocol_type().is_some() && config.font_size.is_some() {
+        return;
+    }
+    let stale = load()
+        .get(&terminal_id())
+        .is_none_or(|detection| now().saturating_sub(detection.checked) >= REFRESH_INTERVAL);
+    if stale {
+        query();
+    }
+}
+
+/// Asks the terminal which protocol and font size it supports and caches the answer.
+/// The old Windows console would print the query, it only gets half blocks.
+fn query() -> Detection {
+    let picker = if terminal::is_legacy_console() {
+        Picker::halfblocks()
+    } else {
+        Picker::from_query_stdio().unwrap_or(Picker::halfblocks())
+    };
+    let detection = Detection {
+        protocol: ImageProtocol::from(picker.protocol_type()),
+        font_size: picker.font_size(),
+        checked: now(),
+    };
+
+    let mut detections = load();
+    detections.insert(terminal_id(), detection);
+    if let Err(e) = save(&detections) {
+        eprintln!("Failed to cache the terminal capabilities: {}", e);
+    }
+    detection
+}
+
+/// Identifies the terminal program, so switching between terminals doesn't use the
+/// capabilities of another one
+fn terminal_id() -> String {
+    let var = |name| env::var(name).unwrap_or_default();
+    let mut program = var("TERM_PROGRAM");
+    // Windows Terminal sets none of the usual variables, only an id that differs per tab
+    if program.is_empty() && env::var_os("WT_SESSION").is_some() {
+        program = "WindowsTerminal".into();
+    }
+    format!(
+        "{}/{}/{}",
+        var("TERM"),
+        program,
+        var("TERM_PROGRAM_VERSION")
+    )
+}
+
+fn load() -> HashMap<String, Detection> {
+    fs::read_to_string(paths::terminals_file())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save(detections: &HashMap<String, Detection>) -> io::Result<()> {
+    fs::create_dir_all(paths::cache_dir())?;
+    let content = serde_json::to_string_pretty(detections)?;
+    atomic::write(&paths::terminals_file(), content)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
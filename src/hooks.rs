@@ -0,0 +1,71 @@
+use cliprs::log_warning;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+};
+
+/// Shell commands run when something happens in findimg, see `Config::hooks`.
+///
+/// Every command gets a JSON object describing the event on stdin. Its output is discarded
+/// and findimg doesn't wait for it, a failure is shown as a warning.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Hooks {
+    /// Run when a model finished indexing the library
+    pub on_index_complete: Option<String>,
+    /// Run when a search finished, with the query and the ranked results
+    pub on_search: Option<String>,
+    /// Run when a result is opened in the preview or the browser
+    pub on_result_open: Option<String>,
+}
+
+/// Runs a hook command on a background thread, writing `context` to its stdin.
+/// Does nothing if the hook isn't configured.
+pub fn run(command: Option<&str>, context: Value) {
+    let Some(command) = command.filter(|command| !command.trim().is_empty()) else {
+        return;
+    };
+    let command = command.to_string();
+    thread::spawn(move || {
+        if let Err(e) = execute(&command, &context) {
+            log_warning(format!("Hook {} failed: {}", command, e));
+        }
+    });
+}
+
+fn execute(command: &str, context: &Value) -> Result<(), String> {
+    let mut child = shell(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // a hook that doesn't read its input closes the pipe early, which is fine
+        let _ = writeln!(stdin, "{}", context);
+    }
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(status.to_string())
+    }
+}
+
+/// A command run by the shell of the platform
+#[cfg(windows)]
+pub fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
+
+#[cfg(not(windows))]
+pub fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
@@ -0,0 +1,83 @@
+use reqwest::Url;
+use std::{
+    fs, io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Cookies from a `cookies.txt` in the Netscape format that browser extensions and
+/// `yt-dlp --cookies-from-browser` export, sent along to fetch albums that need a login
+#[derive(Debug, Default, Clone)]
+pub struct Cookies {
+    cookies: Vec<Cookie>,
+}
+
+#[derive(Debug, Clone)]
+struct Cookie {
+    /// Without a leading dot
+    domain: String,
+    /// Also sent to the subdomains of `domain`
+    subdomains: bool,
+    path: String,
+    /// Only sent over HTTPS
+    secure: bool,
+    name: String,
+    value: String,
+}
+
+impl Cookies {
+    /// Reads a cookies file. Expired cookies are left out.
+    pub fn load(file: &Path) -> io::Result<Self> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let content = fs::read_to_string(file)?;
+        let mut cookies = Vec::new();
+        for line in content.lines() {
+            // curl marks cookies only sent over HTTP this way, they are cookies all the same
+            let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [domain, subdomains, path, secure, expires, name, value] = fields[..] else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("not a line of a Netscape cookies file: {}", line),
+                ));
+            };
+            // 0 marks a session cookie
+            let expires: u64 = expires.parse().unwrap_or_default();
+            if expires != 0 && expires < now {
+                continue;
+            }
+            cookies.push(Cookie {
+                subdomains: subdomains.eq_ignore_ascii_case("TRUE") || domain.starts_with('.'),
+                domain: domain.trim_start_matches('.').to_lowercase(),
+                path: path.to_string(),
+                secure: secure.eq_ignore_ascii_case("TRUE"),
+                name: name.to_string(),
+                value: value.to_string(),
+            });
+        }
+        Ok(Self { cookies })
+    }
+
+    /// The `Cookie` header for a request to `url`, `None` if no cookie is for it
+    pub fn header(&self, url: &Url) -> Option<String> {
+        let host = url.host_str()?.to_lowercase();
+        let pairs: Vec<String> = self
+            .cookies
+            .iter()
+            .filter(|cookie| {
+                let domain = host == cookie.domain
+                    || (cookie.subdomains && host.ends_with(&format!(".{}", cookie.domain)));
+                domain
+                    && url.path().starts_with(&cookie.path)
+                    && (!cookie.secure || url.scheme() == "https")
+            })
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        (!pairs.is_empty()).then(|| pairs.join("; "))
+    }
+}
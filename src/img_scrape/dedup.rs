@@ -0,0 +1,95 @@
+use cliprs::ClipModel;
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    cache::content_hash,
+    catalog::Catalog,
+    config::Config,
+    index::{self, cosine_similarity},
+    models, paths,
+    store::Store,
+};
+
+/// Finds the image a downloaded one duplicates: the same file by its content hash, or the
+/// same photo in another size or encoding by the similarity of their embeddings
+pub struct Dedup {
+    /// `None` before anything was indexed
+    catalog: Option<Catalog>,
+    near: Option<NearDuplicates>,
+    /// Hashes of the images downloaded so far, so the same image twice in an album is found
+    downloaded: HashMap<String, String>,
+}
+
+struct NearDuplicates {
+    model: ClipModel,
+    database: Store,
+    /// See `ScraperConfig::duplicate_similarity`
+    threshold: f32,
+}
+
+impl Dedup {
+    /// Compares downloads with the library in the index of the main model. The model is
+    /// only loaded if near-duplicates are looked for.
+    pub fn open(config: &Config, model_path: &Path) -> Self {
+        let indexed = paths::catalog_file().is_file();
+        let catalog = indexed
+            .then(|| Catalog::open(&paths::catalog_file()).ok())
+            .flatten();
+        let near = config
+            .scraper
+            .duplicate_similarity
+            .filter(|_| indexed && paths::database_file().is_file())
+            .map(|threshold| NearDuplicates {
+                model: ClipModel::new(model_path.to_string_lossy()),
+                database: index::open_database(
+                    config,
+                    &models::main_index(config, Some(model_path)),
+                ),
+                threshold,
+            });
+        Self {
+            catalog,
+            near,
+            downloaded: HashMap::new(),
+        }
+    }
+
+    /// The image `file` duplicates, `None` if it's new
+    pub fn duplicate_of(&mut self, file: &Path) -> Option<String> {
+        let path = file.display().to_string();
+        let hash = content_hash(&path).ok()?;
+        if let Some(original) = self.downloaded.get(&hash) {
+            return Some(original.clone());
+        }
+
+        let identical = self.catalog.as_ref().and_then(|catalog| {
+            catalog
+                .paths_with_hash(&hash)
+                .into_iter()
+                .find(|other| *other != path && Path::new(other).is_file())
+        });
+        let duplicate = identical.or_else(|| self.near.as_ref()?.find(&path));
+        if duplicate.is_none() {
+            self.downloaded.insert(hash, path);
+        }
+        duplicate
+    }
+}
+
+impl NearDuplicates {
+    /// The most similar indexed image, if it's similar enough to be the same photo
+    fn find(&self, path: &str) -> Option<String> {
+        let vector = self.model.embed_image(path).ok()?;
+        let (best, _) = self.database.query(&vector, 1).into_iter().next()?;
+        let other = self
+            .database
+            .get(std::slice::from_ref(&best))
+            .first()?
+            .vector
+            .clone();
+        (best != path
+            && Path::new(&best).is_file()
+            && cosine_similarity(&vector, &other) >= self.threshold)
+            .then_some(best)
+    }
+}
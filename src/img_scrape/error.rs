@@ -0,0 +1,44 @@
+use std::{error::Error, fmt, io};
+
+/// Why scraping, or downloading one of the images, failed
+#[derive(Debug)]
+pub enum ScrapeError {
+    /// The scraper couldn't be set up, e.g. the cookies file couldn't be read
+    Setup(String),
+    /// The album page couldn't be fetched or isn't a link
+    Page(String),
+    /// An image couldn't be downloaded, the others are downloaded all the same
+    Download { url: String, reason: String },
+    /// The library or the record of scraped images couldn't be written
+    Io(io::Error),
+    /// The text of an image couldn't be stored in the metadata database
+    Catalog(rusqlite::Error),
+}
+
+impl fmt::Display for ScrapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScrapeError::Setup(reason) => write!(f, "{}", reason),
+            ScrapeError::Page(reason) => write!(f, "Failed to fetch the album page: {}", reason),
+            ScrapeError::Download { url, reason } => {
+                write!(f, "Failed to download {}: {}", url, reason)
+            }
+            ScrapeError::Io(e) => write!(f, "{}", e),
+            ScrapeError::Catalog(e) => write!(f, "Failed to store the text of an image: {}", e),
+        }
+    }
+}
+
+impl Error for ScrapeError {}
+
+impl From<io::Error> for ScrapeError {
+    fn from(e: io::Error) -> Self {
+        ScrapeError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for ScrapeError {
+    fn from(e: rusqlite::Error) -> Self {
+        ScrapeError::Catalog(e)
+    }
+}
@@ -0,0 +1,435 @@
+use reqwest::{
+    StatusCode, Url,
+    blocking::{Client, Response},
+    header::{COOKIE, RANGE, RETRY_AFTER},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::img_scrape::cookies::Cookies;
+
+/// Name findimg looks for in the `User-agent` lines of robots.txt
+const ROBOTS_NAME: &str = "findimg";
+/// Longest wait before a retry, even if the site asks for more with `Retry-After`
+const MAX_WAIT: Duration = Duration::from_secs(300);
+
+/// How findimg treats the sites it downloads images from, see `Config::scraper`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ScraperConfig {
+    /// Sent with every request
+    pub user_agent: String,
+    /// Most requests per second to the same site
+    pub requests_per_second: f64,
+    /// How often a request is retried after 429 Too Many Requests, a server error or a timeout
+    pub max_retries: u32,
+    /// Wait before the first retry in milliseconds, doubled for every further one
+    pub backoff_ms: u64,
+    /// Leave out URLs the robots.txt of their site disallows
+    pub respect_robots: bool,
+    /// Netscape `cookies.txt` whose cookies are sent along, for albums that need a login
+    pub cookies_file: Option<PathBuf>,
+    /// Cosine similarity to an indexed image above which a downloaded image is the same
+    /// photo and not kept, `None` only skips identical files
+    pub duplicate_similarity: Option<f32>,
+}
+
+impl Default for ScraperConfig {
+    fn default() -> Self {
+        Self {
+            // sites serve different pages to clients that don't look like a browser
+            user_agent: format!(
+                "Mozilla/5.0 (compatible; findimg/{}; +https://github.com/simon0302010/findimg)",
+                env!("CARGO_PKG_VERSION")
+            ),
+            requests_per_second: 2.0,
+            max_retries: 5,
+            backoff_ms: 1000,
+            respect_robots: true,
+            cookies_file: None,
+            duplicate_similarity: Some(0.97),
+        }
+    }
+}
+
+/// An HTTP client that keeps to the limits of `ScraperConfig`: it spaces out requests to the
+/// same site, backs off and retries when the site is overloaded and follows robots.txt.
+pub struct Fetcher {
+    client: Client,
+    config: ScraperConfig,
+    cookies: Cookies,
+    /// When the next request to each site may be sent
+    next_request: Mutex<HashMap<String, Instant>>,
+    /// The robots.txt of each site, fetched with the first request to it
+    robots: Mutex<HashMap<String, Robots>>,
+}
+
+impl Fetcher {
+    pub fn new(config: &ScraperConfig) -> Result<Self, String> {
+        let cookies = match &config.cookies_file {
+            Some(file) => Cookies::load(file)
+                .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?,
+            None => Cookies::default(),
+        };
+        Ok(Self {
+            client: Client::builder()
+                .user_agent(&config.user_agent)
+                .build()
+                .map_err(|e| e.to_string())?,
+            config: config.clone(),
+            cookies,
+            next_request: Mutex::default(),
+            robots: Mutex::default(),
+        })
+    }
+
+    /// Requests a URL, an error status is returned as an error
+    pub fn get(&self, url: &str) -> Result<Response, String> {
+        self.send(url, None)?
+            .error_for_status()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Downloads a URL to a file unless the file exists. The data is written next to it with
+    /// a `.part` extension first, so a download that was cut off continues where it stopped
+    /// if the site supports it. Returns whether anything was downloaded.
+    pub fn download(&self, url: &str, path: &Path) -> Result<bool, String> {
+        if path.is_file() {
+            return Ok(false);
+        }
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        let have = fs::metadata(&partial).map_or(0, |metadata| metadata.len());
+
+        let response = self.send(url, (have > 0).then_some(have))?;
+        // the part was complete, it only didn't get its final name
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return fs::rename(&partial, path)
+                .map(|_| true)
+                .map_err(|e| e.to_string());
+        }
+        let mut response = response.error_for_status().map_err(|e| e.to_string())?;
+        let append = response.status() == StatusCode::PARTIAL_CONTENT;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(&partial)
+            .map_err(|e| e.to_string())?;
+        response.copy_to(&mut file).map_err(|e| e.to_string())?;
+        fs::rename(&partial, path).map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    /// Sends a request once it's the site's turn, from byte `from` on if given, and retries
+    /// it with exponential backoff while the site is overloaded
+    fn send(&self, url: &str, from: Option<u64>) -> Result<Response, String> {
+        let parsed = Url::parse(url).map_err(|e| e.to_string())?;
+        let site = parsed.origin().ascii_serialization();
+        let crawl_delay = self.check_robots(&site, &parsed)?;
+        let mut backoff = Duration::from_millis(self.config.backoff_ms);
+
+        let mut attempt = 0;
+        loop {
+            self.wait_turn(&site, crawl_delay);
+            let mut request = self.client.get(url);
+            if let Some(from) = from {
+                request = request.header(RANGE, format!("bytes={}-", from));
+            }
+            if let Some(cookies) = self.cookies.header(&parsed) {
+                request = request.header(COOKIE, cookies);
+            }
+            let response = request.send();
+            let retry = match &response {
+                Ok(response)
+                    if response.status() == StatusCode::TOO_MANY_REQUESTS
+                        || response.status().is_server_error() =>
+                {
+                    Some(retry_after(response).unwrap_or(backoff))
+                }
+                Err(e) if e.is_timeout() || e.is_connect() => Some(backoff),
+                _ => None,
+            };
+            match retry {
+                Some(wait) if attempt < self.config.max_retries => {
+                    thread::sleep(wait.min(MAX_WAIT));
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                _ => return response.map_err(|e| e.to_string()),
+            }
+        }
+    }
+
+    /// Waits until the next request to a site may be sent and reserves the slot after it
+    fn wait_turn(&self, site: &str, crawl_delay: Option<Duration>) {
+        let interval = if self.config.requests_per_second > 0.0 {
+            Duration::from_secs_f64(1.0 / self.config.requests_per_second)
+        } else {
+            Duration::ZERO
+        }
+        .max(crawl_delay.unwrap_or_default());
+
+        let now = Instant::now();
+        let turn = {
+            let mut next_request = self
+                .next_request
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let turn = next_request.get(site).map_or(now, |next| (*next).max(now));
+            next_request.insert(site.to_string(), turn + interval);
+            turn
+        };
+        thread::sleep(turn - now);
+    }
+
+    /// Fails if robots.txt disallows the URL. Returns the crawl delay the site asks for.
+    fn check_robots(&self, site: &str, url: &Url) -> Result<Option<Duration>, String> {
+        if !self.config.respect_robots {
+            return Ok(None);
+        }
+        let known = self
+            .robots
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(site)
+            .cloned();
+        let robots = match known {
+            Some(robots) => robots,
+            None => {
+                let robots = self.fetch_robots(site);
+                self.robots
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(site.to_string(), robots.clone());
+                robots
+            }
+        };
+
+        let mut path = url.path().to_string();
+        if let Some(query) = url.query() {
+            path = format!("{}?{}", path, query);
+        }
+        if robots.allows(&path) {
+            Ok(robots.crawl_delay)
+        } else {
+            Err(format!("robots.txt of {} disallows {}", site, url))
+        }
+    }
+
+    /// The robots.txt of a site. A site without one, or one that can't be read, allows everything.
+    fn fetch_robots(&self, site: &str) -> Robots {
+        self.wait_turn(site, None);
+        self.client
+            .get(format!("{}/robots.txt", site))
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text())
+            .map(|text| Robots::parse(&text))
+            .unwrap_or_default()
+    }
+}
+
+/// The wait a response asks for in its `Retry-After` header, in seconds
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    seconds.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// The rules of a robots.txt that apply to findimg: the group naming it, or the one for
+/// every crawler if there is none
+#[derive(Debug, Default, Clone)]
+struct Robots {
+    /// Path patterns and whether they are allowed
+    rules: Vec<(String, bool)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    fn parse(text: &str) -> Self {
+        let mut own = Robots::default();
+        let mut general = Robots::default();
+        let mut named = false;
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (key, value) = (key.trim().to_lowercase(), value.trim());
+            if key == "user-agent" {
+                // a user agent line after rules starts a new group
+                if in_rules {
+                    agents.clear();
+                    in_rules = false;
+                }
+                agents.push(value.to_lowercase());
+                continue;
+            }
+            in_rules = true;
+            for agent in &agents {
+                let robots = match agent.as_str() {
+                    "*" => &mut general,
+                    ROBOTS_NAME => {
+                        named = true;
+                        &mut own
+                    }
+                    _ => continue,
+                };
+                match key.as_str() {
+                    // an empty disallow allows everything
+                    "allow" | "disallow" if !value.is_empty() => {
+                        robots.rules.push((value.to_string(), key == "allow"));
+                    }
+                    "crawl-delay" => {
+                        robots.crawl_delay = value
+                            .parse()
+                            .ok()
+                            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if named { own } else { general }
+    }
+
+    /// Whether a path is allowed: the longest matching pattern decides, allow wins a tie
+    fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(pattern, _)| matches(pattern, path))
+            .max_by_key(|(pattern, allowed)| (pattern.len(), *allowed))
+            .is_none_or(|(_, allowed)| *allowed)
+    }
+}
+
+/// Matches a robots.txt path pattern, where `*` stands for anything and a trailing `$`
+/// anchors it at the end of the path
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (index, part) in parts.iter().enumerate() {
+        if anchored && index + 1 == parts.len() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcards_and_anchors() {
+        let cases = [
+            ("/private", "/private/a.jpg", true),
+            ("/private", "/public/private", false),
+            ("/*.jpg", "/albums/a.jpg", true),
+            ("/*.jpg", "/albums/a.jpg?size=2", true),
+            ("/*.jpg$", "/albums/a.jpg?size=2", false),
+            ("/*.jpg$", "/albums/a.jpg", true),
+            ("/a*b*c", "/axxbyyc/", true),
+            ("/a*b*c", "/axxcyyb", false),
+            ("/ab*b$", "/ab", false),
+            ("/ab*b$", "/abb", true),
+            ("/exact$", "/exact", true),
+            ("/exact$", "/exact/", false),
+            ("/", "/anything", true),
+        ];
+        for (pattern, path, expected) in cases {
+            assert_eq!(matches(pattern, path), expected, "{pattern} on {path}");
+        }
+    }
+
+    #[test]
+    fn the_longest_pattern_decides_and_allow_wins_a_tie() {
+        let robots = Robots::parse(
+            "User-agent: *\n\
+             Disallow: /photos/\n\
+             Allow: /photos/public/\n\
+             Disallow: /photos/public/raw/\n\
+             Allow: /same\n\
+             Disallow: /same\n",
+        );
+        let cases = [
+            ("/index.html", true),
+            ("/photos/a.jpg", false),
+            ("/photos/public/a.jpg", true),
+            ("/photos/public/raw/a.cr2", false),
+            ("/same/a.jpg", true),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(robots.allows(path), expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn the_group_naming_findimg_replaces_the_general_one() {
+        let text = "# comments are ignored\n\
+                    User-agent: *\n\
+                    Disallow: /\n\
+                    Crawl-delay: 10\n\
+                    \n\
+                    User-agent: otherbot\n\
+                    User-agent: FindImg # the name is matched in any case\n\
+                    Disallow: /private\n\
+                    Crawl-delay: 0.5\n\
+                    \n\
+                    User-agent: otherbot\n\
+                    Disallow: /photos\n";
+        let robots = Robots::parse(text);
+
+        assert!(robots.allows("/photos/a.jpg"));
+        assert!(!robots.allows("/private/a.jpg"));
+        assert_eq!(robots.crawl_delay, Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn the_general_group_applies_without_a_named_one() {
+        let robots = Robots::parse(
+            "User-agent: otherbot\n\
+             Disallow: /\n\
+             \n\
+             User-agent: *\n\
+             Disallow: /private\n\
+             Crawl-delay: soon\n",
+        );
+
+        assert!(robots.allows("/photos/a.jpg"));
+        assert!(!robots.allows("/private/a.jpg"));
+        assert_eq!(robots.crawl_delay, None);
+    }
+
+    #[test]
+    fn an_empty_disallow_allows_everything() {
+        let robots = Robots::parse("User-agent: *\nDisallow:\n");
+
+        assert!(robots.rules.is_empty());
+        assert!(robots.allows("/"));
+        assert!(robots.allows("/private/a.jpg"));
+        assert!(Robots::parse("").allows("/private/a.jpg"));
+    }
+}
@@ -0,0 +1,144 @@
+use regex::Regex;
+use reqwest::Url;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::{catalog::Catalog, index::WEB_TEXT_FIELD};
+
+/// An image on a web page
+#[derive(Debug, Clone)]
+pub struct PageImage {
+    pub url: String,
+    /// Its caption, alt text and title, `None` if the page gives it none
+    pub text: Option<String>,
+}
+
+/// The images of a web gallery in the order they appear on the page, with the text the page
+/// describes them with: the `<figcaption>` of their `<figure>`, their `alt` and their `title`.
+/// Lazily loaded images are found by their `data-src`.
+pub fn page_images(html: &str, page_url: &Url) -> Vec<PageImage> {
+    let figure_re = Regex::new(r"(?is)<figure\b.*?</figure>").unwrap();
+    let figcaption_re = Regex::new(r"(?is)<figcaption\b[^>]*>(.*?)</figcaption>").unwrap();
+    let img_re = Regex::new(r"(?is)<img\b[^>]*>").unwrap();
+
+    let figures: Vec<(usize, usize, Option<String>)> = figure_re
+        .find_iter(html)
+        .map(|figure| {
+            let caption = figcaption_re
+                .captures(figure.as_str())
+                .map(|caption| clean_text(&caption[1]));
+            (figure.start(), figure.end(), caption)
+        })
+        .collect();
+
+    let mut images: Vec<PageImage> = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for tag in img_re.find_iter(html) {
+        let attributes = attributes(tag.as_str());
+        let Some(url) = attributes
+            .get("data-src")
+            .or(attributes.get("src"))
+            .and_then(|src| page_url.join(src.trim()).ok())
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+        else {
+            continue;
+        };
+
+        let caption = figures
+            .iter()
+            .find(|(start, end, _)| (*start..*end).contains(&tag.start()))
+            .and_then(|(_, _, caption)| caption.clone());
+        let mut parts: Vec<String> = Vec::new();
+        for part in [
+            caption,
+            attributes.get("alt").map(|alt| collapse_whitespace(alt)),
+            attributes
+                .get("title")
+                .map(|title| collapse_whitespace(title)),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if !part.is_empty() && !parts.contains(&part) {
+                parts.push(part);
+            }
+        }
+        let text = (!parts.is_empty()).then(|| parts.join(". "));
+
+        // thumbnails often link the same image twice, the one with a description wins
+        let url = url.to_string();
+        match seen.get(&url) {
+            Some(&index) => {
+                if images[index].text.is_none() {
+                    images[index].text = text;
+                }
+            }
+            None => {
+                seen.insert(url.clone(), images.len());
+                images.push(PageImage { url, text });
+            }
+        }
+    }
+    images
+}
+
+/// Stores the text an image had on the web, so searching for its words finds it. Text the
+/// image has already is kept.
+pub fn remember_text(catalog: &Catalog, path: &str, text: &str) -> rusqlite::Result<()> {
+    let mut fields = catalog.fields(path);
+    if fields.contains_key(WEB_TEXT_FIELD) {
+        return Ok(());
+    }
+    fields.insert(WEB_TEXT_FIELD.into(), Value::from(text));
+    catalog.set_fields(path, &fields)
+}
+
+/// The attributes of an HTML tag by their lowercase name, with entities decoded
+fn attributes(tag: &str) -> HashMap<String, String> {
+    let attribute_re =
+        Regex::new(r#"(?is)\s([a-z][a-z0-9_:-]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#)
+            .unwrap();
+    attribute_re
+        .captures_iter(tag)
+        .filter_map(|attribute| {
+            let value = attribute.get(2).or(attribute.get(3)).or(attribute.get(4))?;
+            Some((attribute[1].to_lowercase(), decode_entities(value.as_str())))
+        })
+        .collect()
+}
+
+/// Text without its tags, entities and runs of whitespace
+fn clean_text(html: &str) -> String {
+    let tag_re = Regex::new(r"(?s)<[^>]*>").unwrap();
+    collapse_whitespace(&decode_entities(&tag_re.replace_all(html, " ")))
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Decodes the entities pages use in captions and attributes
+fn decode_entities(text: &str) -> String {
+    let entity_re = Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").unwrap();
+    entity_re
+        .replace_all(text, |entity: &regex::Captures| {
+            let name = &entity[1];
+            let decoded = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => name
+                    .strip_prefix("#x")
+                    .or(name.strip_prefix("#X"))
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or(name.strip_prefix('#').map(str::parse))
+                    .and_then(Result::ok)
+                    .and_then(char::from_u32),
+            };
+            decoded.map_or_else(|| entity[0].to_string(), String::from)
+        })
+        .into_owned()
+}
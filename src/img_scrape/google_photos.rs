@@ -1,31 +1,245 @@
 use regex::Regex;
-use reqwest::blocking::Client;
-use std::path::PathBuf;
-use std::{collections::HashSet, fs::File, io::Write, path::Path};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::Path,
+};
 
-pub fn scrape(path: PathBuf, album_url: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let client = Client::builder().user_agent("Mozilla/5.0").build()?;
+use crate::{
+    atomic,
+    catalog::Catalog,
+    config::Config,
+    img_scrape::{
+        dedup::Dedup,
+        error::ScrapeError,
+        fetcher::Fetcher,
+        gallery::{self, PageImage},
+    },
+    paths,
+};
 
-    println!("Fetching album page...");
-    let html = client.get(album_url).send()?.text()?;
+/// Album images downloaded before by their URL
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct Scraped {
+    /// Images stored in the library, with their path
+    downloaded: HashMap<String, String>,
+    /// Images that weren't kept, with the library image they duplicate
+    duplicates: HashMap<String, String>,
+    /// Images whose download failed, with the reason. They are only tried again with
+    /// `--retry-failed`, so a link that is gone doesn't slow down every scrape.
+    failed: HashMap<String, String>,
+}
+
+impl Scraped {
+    fn load() -> Self {
+        fs::read_to_string(paths::scraped_file())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(paths::data_dir())?;
+        let content = serde_json::to_string(self)?;
+        atomic::write(&paths::scraped_file(), content)
+    }
 
-    let image_urls = extract_image_urls(&html);
+    /// Whether the image of a URL is still in the library
+    fn has(&self, url: &str) -> bool {
+        self.downloaded
+            .get(url)
+            .or(self.duplicates.get(url))
+            .is_some_and(|path| Path::new(path).is_file())
+    }
+}
+
+/// What a scrape did
+#[derive(Debug, Default)]
+pub struct ScrapeReport {
+    /// URLs of the album's images in page order
+    pub urls: Vec<String>,
+    pub downloaded: usize,
+    /// Images the library had already, downloaded before or as a duplicate
+    pub skipped: usize,
+    /// Images left out because their download failed before, see `--retry-failed`
+    pub failed_before: usize,
+    pub failed: Vec<ScrapeError>,
+}
+
+impl ScrapeReport {
+    pub fn print_summary(&self) {
+        for e in &self.failed {
+            eprintln!("{}", e);
+        }
+        println!(
+            "Downloaded {}, skipped {}, failed {}",
+            self.downloaded,
+            self.skipped,
+            self.failed.len()
+        );
+        if self.failed_before > 0 {
+            println!(
+                "Left out {} images that failed before, --retry-failed tries them again",
+                self.failed_before
+            );
+        }
+    }
+}
+
+/// Downloads the images of a shared album or web gallery into the library. Images downloaded
+/// by an earlier run are kept, so an interrupted scrape picks up where it stopped. Images the
+/// library holds already, as the same file or the same photo in another size, are not kept
+/// twice. The caption or alt text a gallery gives an image is stored with it, with the image
+/// it duplicates otherwise.
+///
+/// A failed download doesn't stop the scrape, it ends up in the report and is only tried again
+/// with `retry_failed`.
+pub fn scrape(
+    config: &Config,
+    model_path: &Path,
+    album_url: &str,
+    retry_failed: bool,
+) -> Result<ScrapeReport, ScrapeError> {
+    let fetcher = Fetcher::new(&config.scraper).map_err(ScrapeError::Setup)?;
+    let images = album_images(&fetcher, album_url)?;
+    let library_dir = config.library_dir();
+    let mut scraped = Scraped::load();
+    let mut dedup = Dedup::open(config, model_path);
+    let catalog = images
+        .iter()
+        .any(|image| image.text.is_some())
+        .then(|| Catalog::open(&paths::catalog_file()))
+        .transpose()?;
+
+    let mut report = ScrapeReport::default();
+    for image in &images {
+        let url = &image.url;
+        if scraped.has(url) {
+            report.skipped += 1;
+            continue;
+        }
+        if !retry_failed && scraped.failed.contains_key(url) {
+            report.failed_before += 1;
+            continue;
+        }
+        let filename = library_dir.join(file_name(url));
+        match fetcher.download(url, &filename) {
+            Ok(true) => {
+                scraped.failed.remove(url);
+            }
+            Ok(false) => {
+                report.skipped += 1;
+                continue;
+            }
+            Err(reason) => {
+                scraped.failed.insert(url.clone(), reason.clone());
+                scraped.save()?;
+                report.failed.push(ScrapeError::Download {
+                    url: url.clone(),
+                    reason,
+                });
+                continue;
+            }
+        }
+        let path = match dedup.duplicate_of(&filename) {
+            Some(original) => {
+                fs::remove_file(&filename)?;
+                println!("Skipped {}, the library has it as {}", url, original);
+                scraped.duplicates.insert(url.clone(), original.clone());
+                report.skipped += 1;
+                original
+            }
+            None => {
+                println!("Downloaded {}", filename.display());
+                let path = filename.display().to_string();
+                scraped.downloaded.insert(url.clone(), path.clone());
+                report.downloaded += 1;
+                path
+            }
+        };
+        if let (Some(catalog), Some(text)) = (&catalog, &image.text) {
+            gallery::remember_text(catalog, &path, text)?;
+        }
+        scraped.save()?;
+    }
 
-    println!("Found {} images", image_urls.len());
+    report.urls = images.into_iter().map(|image| image.url).collect();
+    Ok(report)
+}
 
-    for (i, url) in image_urls.iter().enumerate() {
-        let filename = format!("{}/img_{:04}.jpg", path.display(), i);
-        download_image(&client, url, &filename)?;
-        println!("Downloaded {}", filename);
+/// Deletes the downloaded images of album URLs from the library and forgets about them.
+/// Returns the paths of the deleted images.
+pub fn forget(urls: &[String]) -> io::Result<Vec<String>> {
+    let mut scraped = Scraped::load();
+    let mut deleted = Vec::new();
+    for url in urls {
+        scraped.duplicates.remove(url);
+        scraped.failed.remove(url);
+        let Some(path) = scraped.downloaded.remove(url) else {
+            continue;
+        };
+        match fs::remove_file(&path) {
+            Ok(()) => deleted.push(path),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
     }
+    scraped.save()?;
+    Ok(deleted)
+}
 
-    Ok(())
+/// Name of the downloaded image of a URL, named by its hash so the images of different
+/// albums don't collide and a second run finds the same file
+fn file_name(url: &str) -> String {
+    let hash: String = Sha256::digest(url.as_bytes())[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("img_{}.jpg", hash)
 }
 
+/// Finds the images of a shared album without downloading them. A page that isn't a Google
+/// Photos album is read as a web gallery, whose images come with their captions.
+pub fn album_images(fetcher: &Fetcher, album_url: &str) -> Result<Vec<PageImage>, ScrapeError> {
+    println!("Fetching album page...");
+    let page_url = Url::parse(album_url).map_err(|e| ScrapeError::Page(e.to_string()))?;
+    let html = fetcher
+        .get(album_url)
+        .and_then(|response| response.text().map_err(|e| e.to_string()))
+        .map_err(ScrapeError::Page)?;
+
+    let images = if is_google_photos(&page_url) {
+        extract_image_urls(&html)
+            .into_iter()
+            .map(|url| PageImage { url, text: None })
+            .collect()
+    } else {
+        gallery::page_images(&html, &page_url)
+    };
+
+    println!("Found {} images", images.len());
+
+    Ok(images)
+}
+
+/// Whether a link is a shared Google Photos album, full or shortened
+fn is_google_photos(url: &Url) -> bool {
+    matches!(
+        url.host_str(),
+        Some("photos.google.com" | "photos.app.goo.gl" | "goo.gl")
+    )
+}
+
+/// The image URLs in the order they appear on the page
 fn extract_image_urls(html: &str) -> Vec<String> {
     let re = Regex::new(r"https://lh3\.googleusercontent\.com/[a-zA-Z0-9_\-=/]+").unwrap();
 
-    let mut urls = HashSet::new();
+    let mut seen = HashSet::new();
+    let mut urls = Vec::new();
 
     for m in re.find_iter(html) {
         let mut url = m.as_str().to_string();
@@ -35,22 +249,10 @@ fn extract_image_urls(html: &str) -> Vec<String> {
             url.truncate(idx);
         }
 
-        urls.insert(url);
+        if seen.insert(url.clone()) {
+            urls.push(url);
+        }
     }
 
-    urls.into_iter().collect()
-}
-
-fn download_image(
-    client: &Client,
-    url: &str,
-    path: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let resp = client.get(url).send()?;
-    let bytes = resp.bytes()?;
-
-    let mut file = File::create(Path::new(path))?;
-    file.write_all(&bytes)?;
-
-    Ok(())
+    urls
 }
@@ -1 +1,7 @@
+pub mod cookies;
+pub mod dedup;
+pub mod error;
+pub mod fetcher;
+pub mod gallery;
 pub mod google_photos;
+pub mod sync;
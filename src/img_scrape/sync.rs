@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{atomic, compact, config::Config, img_scrape::google_photos, paths};
+
+const HOUR: u64 = 60 * 60;
+
+/// Shared albums the library is kept in step with: they are scraped again on `sync run`
+/// and on startup once `Config::album_sync_hours` passed, new images are downloaded and
+/// images removed from an album are deleted from the library.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Albums {
+    albums: Vec<Album>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Album {
+    url: String,
+    /// Image URLs the album had at the last sync
+    images: Vec<String>,
+    /// Seconds since the Unix epoch, 0 before the first sync
+    synced: u64,
+}
+
+impl Albums {
+    pub fn load() -> Self {
+        fs::read_to_string(paths::albums_file())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(paths::data_dir())?;
+        let content = serde_json::to_string(self)?;
+        atomic::write(&paths::albums_file(), content)
+    }
+
+    /// Starts syncing an album. Returns `false` if it's synced already.
+    pub fn add(&mut self, url: &str) -> bool {
+        if self.albums.iter().any(|album| album.url == url) {
+            return false;
+        }
+        self.albums.push(Album {
+            url: url.to_string(),
+            images: Vec::new(),
+            synced: 0,
+        });
+        true
+    }
+
+    /// Stops syncing an album, its images stay in the library. Returns `false` if it
+    /// wasn't synced.
+    pub fn remove(&mut self, url: &str) -> bool {
+        let count = self.albums.len();
+        self.albums.retain(|album| album.url != url);
+        self.albums.len() < count
+    }
+
+    /// Whether any album wasn't synced for `hours`. Never with 0 hours, which only syncs on
+    /// `sync run`.
+    pub fn due(&self, hours: u64) -> bool {
+        hours > 0
+            && self
+                .albums
+                .iter()
+                .any(|album| now().saturating_sub(album.synced) >= hours * HOUR)
+    }
+}
+
+/// What a sync changed
+#[derive(Debug, Default)]
+pub struct Synced {
+    pub albums: usize,
+    /// Images deleted because they were removed from their album
+    pub removed: usize,
+}
+
+/// Scrapes the synced albums, every one with `all` and otherwise those not synced for
+/// `Config::album_sync_hours`. Images no longer in their album are deleted from the
+/// library and the indexes, unless another synced album still has them. Downloads that failed
+/// before are tried again with `retry_failed`.
+pub fn sync(
+    config: &Config,
+    model_path: &Path,
+    all: bool,
+    retry_failed: bool,
+) -> Result<Synced, Box<dyn std::error::Error>> {
+    let mut albums = Albums::load();
+    let mut synced = Synced::default();
+    for index in 0..albums.albums.len() {
+        let album = &albums.albums[index];
+        if !all && now().saturating_sub(album.synced) < config.album_sync_hours * HOUR {
+            continue;
+        }
+        println!("Syncing {}", album.url);
+        let report = google_photos::scrape(config, model_path, &album.url, retry_failed)?;
+        report.print_summary();
+        let images = report.urls;
+        // a page that changed its layout looks like an empty album, which must not empty the library
+        if images.is_empty() {
+            return Err(format!("Found no images in {}, nothing was removed", album.url).into());
+        }
+
+        let current: HashSet<&String> = images.iter().collect();
+        let elsewhere: HashSet<&String> = albums
+            .albums
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .flat_map(|(_, album)| &album.images)
+            .collect();
+        let gone: Vec<String> = albums.albums[index]
+            .images
+            .iter()
+            .filter(|url| !current.contains(url) && !elsewhere.contains(url))
+            .cloned()
+            .collect();
+        let deleted = google_photos::forget(&gone)?;
+        if !deleted.is_empty() {
+            compact::remove_images(config, Some(model_path), &deleted)?;
+            synced.removed += deleted.len();
+        }
+
+        let album = &mut albums.albums[index];
+        album.images = images;
+        album.synced = now();
+        albums.save()?;
+        synced.albums += 1;
+    }
+    Ok(synced)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
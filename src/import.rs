@@ -0,0 +1,137 @@
+use nano_vectordb_rs::Data;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use crate::{
+    config::Config,
+    engine::Similarity,
+    img_scrape::fetcher::Fetcher,
+    index,
+    models::{self, IndexInfo},
+    remote::{self, Remotes},
+};
+
+/// How far the length of an imported vector may be from 1
+const NORM_TOLERANCE: f32 = 1e-3;
+
+/// One line of an embeddings file, the same shape `export-vectors --format jsonl` writes
+#[derive(Deserialize)]
+struct Line {
+    path: String,
+    vector: Vec<f32>,
+}
+
+/// Result of an import
+#[derive(Debug, Default)]
+pub struct Imported {
+    /// Images whose embedding was added or replaced
+    pub images: usize,
+    /// Lines for images that aren't in the library, or remote items that failed to download
+    pub skipped: usize,
+}
+
+/// Merges embeddings computed elsewhere into the index of the model with the given name.
+///
+/// The whole file is checked before anything is merged: every vector needs the dimensions
+/// of the model, and a length of 1 if the model is ranked by cosine similarity. Paths are
+/// looked up in the library by their file name, so they may come from another machine.
+/// URLs are added as remote items, their thumbnail is downloaded once the file checked out.
+/// Metadata of images that are indexed already is kept.
+pub fn import_index(
+    config: &Config,
+    main_model: Option<&Path>,
+    model_id: &str,
+    input: &Path,
+) -> Result<Imported, String> {
+    let info = models::find_index(config, main_model, Some(model_id))?;
+    let library_dir = config.library_dir();
+
+    let file = File::open(input).map_err(|e| e.to_string())?;
+    let mut imported = Imported::default();
+    let mut vectors = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line: Line = serde_json::from_str(&line)
+            .map_err(|e| format!("Line {} is not a path and a vector: {}", number + 1, e))?;
+        validate(&line.vector, &info).map_err(|e| format!("Line {}: {}", number + 1, e))?;
+
+        if remote::is_remote(&line.path) {
+            vectors.push((line.path, line.vector));
+            continue;
+        }
+        match library_path(&library_dir, &line.path) {
+            Some(path) => vectors.push((path, line.vector)),
+            None => imported.skipped += 1,
+        }
+    }
+
+    let fetcher = Fetcher::new(&config.scraper)?;
+    let mut remotes = Remotes::load();
+    vectors.retain(|(path, _)| {
+        let added = !remote::is_remote(path) || remotes.add(&fetcher, path).is_ok();
+        imported.skipped += usize::from(!added);
+        added
+    });
+    remotes.save().map_err(|e| e.to_string())?;
+
+    let mut database = index::open_database(config, &info);
+    let paths: Vec<String> = vectors.iter().map(|(path, _)| path.clone()).collect();
+    let mut fields: HashMap<String, _> = database
+        .get(&paths)
+        .into_iter()
+        .map(|data| (data.id.clone(), data.fields.clone()))
+        .collect();
+    let data: Vec<Data> = vectors
+        .into_iter()
+        .map(|(path, vector)| Data {
+            fields: fields.remove(&path).unwrap_or_default(),
+            id: path,
+            vector,
+        })
+        .collect();
+
+    imported.images = data.len();
+    if !data.is_empty() {
+        database.upsert(data).map_err(|e| e.to_string())?;
+        database.save().map_err(|e| e.to_string())?;
+    }
+    Ok(imported)
+}
+
+fn validate(vector: &[f32], info: &IndexInfo) -> Result<(), String> {
+    if vector.len() != info.dimensions {
+        return Err(format!(
+            "the vector has {} dimensions but the model has {}",
+            vector.len(),
+            info.dimensions
+        ));
+    }
+    if vector.iter().any(|value| !value.is_finite()) {
+        return Err("the vector contains a value that is not a number".into());
+    }
+    // dot products and distances depend on the length, so only vectors
+    // of models ranked by cosine similarity are known to be normalized
+    let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if info.similarity == Similarity::Cosine && (norm - 1.0).abs() > NORM_TOLERANCE {
+        return Err(format!(
+            "the vector has a length of {} instead of being normalized",
+            norm
+        ));
+    }
+    Ok(())
+}
+
+/// Path of an image in the library under the id the index uses for it. The library is
+/// flat, so only the file name of the imported path matters.
+fn library_path(library_dir: &Path, path: &str) -> Option<String> {
+    let path = library_dir.join(Path::new(path).file_name()?);
+    path.is_file().then(|| path.display().to_string())
+}
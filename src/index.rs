@@ -0,0 +1,493 @@
+use cliprs::{ClipModel, log_warning};
+use nano_vectordb_rs::Data;
+use reqwest::blocking::Client;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs,
+    path::Path,
+    sync::{PoisonError, RwLock},
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    aesthetic::AestheticModel,
+    atomic,
+    cache::{VectorCache, content_hash},
+    caption::caption_image,
+    catalog::Catalog,
+    colors,
+    config::Config,
+    dates,
+    metadata::Editable,
+    models::{self, IndexInfo},
+    quality,
+    remote::{self, Remotes},
+    shutdown::Shutdown,
+    sidecar,
+    store::{Entry, Store, StoreConfig, VectorStore},
+};
+
+pub const SUPPORTED_IMAGE_FORMATS: [&str; 10] = [
+    "jpg", "jpeg", "png", "tga", "bmp", "psd", "gif", "hdr", "pic", "ppm",
+];
+
+/// Length of the embeddings of the main model
+pub const DIMENSIONS: usize = 768;
+
+/// Number of changed images after which the database is written to disk
+const SAVE_INTERVAL: usize = 25;
+
+/// Metadata field holding the SHA-256 of an image's content
+pub const HASH_FIELD: &str = "hash";
+/// Metadata field holding the generated caption of an image
+pub const CAPTION_FIELD: &str = "caption";
+/// Metadata field holding the caption and alt text of a scraped image on its web page
+pub const WEB_TEXT_FIELD: &str = "web_text";
+/// Metadata field holding the tags added by hand
+pub const TAGS_FIELD: &str = "tags";
+/// Metadata field holding a free-text note added by hand
+pub const NOTE_FIELD: &str = "note";
+/// Metadata field holding the star rating from 1 to 5, unrated images don't have it
+pub const RATING_FIELD: &str = "rating";
+/// Metadata field holding when the sidecars of an image were last read, see `sidecar::modified`
+pub const SIDECAR_FIELD: &str = "sidecar_time";
+/// Metadata field holding the dominant colors of an image
+pub const COLORS_FIELD: &str = "colors";
+/// Metadata fields holding the size of an image in pixels
+pub const WIDTH_FIELD: &str = "width";
+pub const HEIGHT_FIELD: &str = "height";
+/// Metadata field holding the capture date of an image as `YYYY-MM-DD`
+pub const DATE_FIELD: &str = "date";
+/// Metadata field set to `true` for images classified as not safe for work
+pub const NSFW_FIELD: &str = "nsfw";
+/// Metadata field holding how sharp an image is, see `quality::Quality`
+pub const SHARPNESS_FIELD: &str = "sharpness";
+/// Metadata field holding the technical quality of an image from 0 to 1
+pub const QUALITY_FIELD: &str = "quality";
+/// Metadata field holding the rating of the aesthetic predictor from 1 to 10
+pub const AESTHETIC_FIELD: &str = "aesthetic";
+
+/// Prompts compared against each image for the NSFW classification
+const NSFW_PROMPT: &str = "an explicit nsfw photo containing nudity";
+const SFW_PROMPT: &str = "a safe for work photo";
+/// How much closer an image has to be to the NSFW prompt than to the safe one to be flagged
+const NSFW_MARGIN: f32 = 0.02;
+
+/// Everything needed to add metadata to images, created once per indexing run
+struct Enrichment<'a> {
+    config: &'a Config,
+    client: Client,
+    /// Embeddings of the NSFW and safe prompts, if classification is enabled
+    nsfw_prompts: Option<(Vec<f32>, Vec<f32>)>,
+    /// The aesthetic predictor, if `Config::aesthetic_model` is set and could be loaded
+    aesthetic: Option<AestheticModel>,
+}
+
+/// Returns the images of the library in `dir` followed by the URLs of the remote items,
+/// which are indexed and searched the same way
+pub fn library_items(dir: &Path) -> Vec<String> {
+    let mut items = find_images(dir);
+    items.extend_from_slice(Remotes::load().urls());
+    items
+}
+
+/// Returns the paths of all supported images directly inside `dir`
+pub fn find_images(dir: &Path) -> Vec<String> {
+    let paths = fs::create_dir_all(dir)
+        .and_then(|_| fs::read_dir(dir))
+        .expect("Failed to create or read images directory");
+
+    let mut images_paths: Vec<String> = vec![];
+    for entry in paths.flatten() {
+        let img_path = entry.path().display().to_string();
+        if SUPPORTED_IMAGE_FORMATS
+            .iter()
+            .any(|suffix| img_path.ends_with(suffix))
+        {
+            images_paths.push(img_path);
+        }
+    }
+    images_paths
+}
+
+/// Opens the index in the vector store from the config, restoring a local one if a save
+/// was interrupted
+pub fn open_database(config: &Config, info: &IndexInfo) -> Store {
+    let file = &info.database_file;
+    if config.vector_store == StoreConfig::Local {
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir).expect("Failed to create data directory");
+        }
+        match atomic::recover_database(file) {
+            Ok(true) => println!("Restored the index from before an interrupted save"),
+            Ok(false) => {}
+            Err(e) => eprintln!("Failed to restore the index backup: {}", e),
+        }
+    }
+    config
+        .vector_store
+        .open(file, info.dimensions, info.similarity)
+        .expect("Failed to initialize database")
+}
+
+/// Indexing from the command line stopped by SIGINT or SIGTERM, see `open_index`
+#[derive(Debug)]
+pub struct Interrupted {
+    /// Images indexed before it stopped
+    pub done: usize,
+    pub total: usize,
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Stopped indexing after {} of {} images, they are saved. \
+             Run the command again to continue from there.",
+            self.done, self.total
+        )
+    }
+}
+
+impl Error for Interrupted {}
+
+/// Opens the database and indexes the library, printing the progress.
+/// Returns the database together with the paths of all images in the library.
+///
+/// Ctrl+C or SIGTERM stops indexing after the image being embedded and saves what was
+/// indexed. The command then has to end with how far it got, `Interrupted` says that.
+pub fn open_index(
+    model: &ClipModel,
+    model_path: &str,
+    catalog: &Catalog,
+    config: &Config,
+    read_only: bool,
+) -> Result<(Store, Vec<String>), Interrupted> {
+    let images_paths = library_items(&config.library_dir());
+    let info = models::main_index(config, Some(Path::new(model_path)));
+    let image_embeddings = RwLock::new(open_database(config, &info));
+    let vector_cache =
+        read_only.then(|| VectorCache::open(&models::main_name(Path::new(model_path))));
+
+    let shutdown = Shutdown::catch();
+    let mut done = 0;
+    let unreadable = index_images(
+        model,
+        &image_embeddings,
+        catalog,
+        &images_paths,
+        config,
+        vector_cache.as_ref(),
+        |index, total, image| {
+            done = index;
+            if shutdown.requested() {
+                return false;
+            }
+            println!("Embedding {}/{} {}", index, total, image);
+            true
+        },
+    );
+    for (path, reason) in unreadable {
+        eprintln!("Skipped {}, it can not be read: {}", path, reason);
+    }
+    if shutdown.requested() {
+        return Err(Interrupted {
+            done,
+            total: images_paths.len(),
+        });
+    }
+    Ok((
+        image_embeddings
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner),
+        images_paths,
+    ))
+}
+
+/// Embeds every image that is not in the database yet and fills in missing metadata
+/// of the ones that are.
+///
+/// With a vector cache, which is used in read-only mode, embeddings are additionally looked up
+/// in and written to it by content hash, so a share mounted at another path doesn't get
+/// embedded again.
+///
+/// The database is only locked while an image is looked up or stored, so it stays usable
+/// while indexing runs in the background. `progress` is called with the index of every image,
+/// the number of images and its path, and returns whether to go on. Stopping early keeps what
+/// was indexed so far.
+///
+/// Images that can not be read are skipped and returned with the reason. The catalog remembers
+/// them, and later runs skip them without a word until their file changes.
+pub fn index_images(
+    model: &ClipModel,
+    image_embeddings: &RwLock<Store>,
+    catalog: &Catalog,
+    images_paths: &[String],
+    config: &Config,
+    vector_cache: Option<&VectorCache>,
+    mut progress: impl FnMut(usize, usize, &str) -> bool,
+) -> Vec<(String, String)> {
+    let read = || {
+        image_embeddings
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    };
+
+    let enrichment = Enrichment {
+        config,
+        client: Client::new(),
+        nsfw_prompts: config
+            .nsfw_filter
+            .then(|| {
+                Some((
+                    model.embed_text(NSFW_PROMPT).ok()?,
+                    model.embed_text(SFW_PROMPT).ok()?,
+                ))
+            })
+            .flatten(),
+        aesthetic: config.aesthetic_model.as_ref().and_then(|file| {
+            AestheticModel::load(file)
+                .inspect_err(|e| {
+                    log_warning(format!(
+                        "Failed to load the aesthetic model {}: {}",
+                        file.display(),
+                        e
+                    ))
+                })
+                .ok()
+        }),
+    };
+
+    let mut unsaved = 0;
+    let mut unreadable = Vec::new();
+    let failures: HashMap<String, Option<u64>> = catalog
+        .failures()
+        .into_iter()
+        .map(|failure| (failure.path, failure.modified))
+        .collect();
+
+    for (index, image) in images_paths.iter().enumerate() {
+        if !progress(index, images_paths.len(), image) {
+            break;
+        }
+        // remote items are read from their thumbnail, but indexed under their URL
+        let file = remote::local_file(image);
+
+        let existing = read()
+            .get(std::slice::from_ref(image))
+            .into_iter()
+            .next()
+            .map(Entry::into_owned);
+        let embedded = existing.is_none();
+
+        let data = match existing {
+            Some(data) => data,
+            None => {
+                let modified = modified(&file);
+                // it failed before and is still the same file
+                if failures.get(image) == Some(&modified) {
+                    continue;
+                }
+                match embed(model, &file, vector_cache) {
+                    Ok(vector) => {
+                        if failures.contains_key(image) {
+                            let _ = catalog.forget_failures(Some(std::slice::from_ref(image)));
+                        }
+                        Data {
+                            id: image.clone(),
+                            vector,
+                            fields: HashMap::new(),
+                        }
+                    }
+                    Err(e) => {
+                        if let Err(error) = catalog.record_failure(image, &e, modified) {
+                            log_warning(format!(
+                                "Failed to remember that {} can not be read: {}",
+                                image, error
+                            ));
+                        }
+                        unreadable.push((image.clone(), e));
+                        continue;
+                    }
+                }
+            }
+        };
+
+        // indexes from before the catalog kept the metadata next to the embedding
+        let mut fields = catalog.fields(image);
+        let mut changed = fields.is_empty() && !data.fields.is_empty();
+        if changed {
+            fields = data.fields.clone();
+        }
+        changed |= enrichment.enrich(&file, &data.vector, &mut fields);
+        if changed && let Err(e) = catalog.set_fields(image, &fields) {
+            log_warning(format!("Failed to save the metadata of {}: {}", image, e));
+        }
+
+        if !embedded {
+            continue;
+        }
+
+        let _ = image_embeddings
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .upsert(vec![data]);
+
+        // saving rewrites the whole database, so it only happens every few images.
+        // An interrupted run continues after the last save.
+        unsaved += 1;
+        if unsaved >= SAVE_INTERVAL {
+            save(&**read());
+            unsaved = 0;
+        }
+    }
+
+    if unsaved > 0 {
+        save(&**read());
+    }
+    unreadable
+}
+
+fn save(image_embeddings: &dyn VectorStore) {
+    image_embeddings.save().unwrap_or_else(|_| {
+        log_warning("Failed to save database to disk".into());
+    })
+}
+
+/// Modification time of a file in seconds since the epoch
+fn modified(file: &str) -> Option<u64> {
+    let modified = fs::metadata(file).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+/// Embeds an image, going through the vector cache if there is one
+fn embed(
+    model: &ClipModel,
+    image: &str,
+    vector_cache: Option<&VectorCache>,
+) -> Result<Vec<f32>, String> {
+    let hash = vector_cache.and_then(|_| content_hash(image).ok());
+    if let (Some(cache), Some(hash)) = (vector_cache, &hash)
+        && let Some(vector) = cache.get(hash)
+    {
+        return Ok(vector);
+    }
+
+    let vector = model.embed_image(image)?;
+
+    if let (Some(cache), Some(hash)) = (vector_cache, &hash)
+        && cache.put(hash, &vector).is_err()
+    {
+        log_warning(format!("Failed to cache the embedding of {}", image));
+    }
+    Ok(vector)
+}
+
+impl Enrichment<'_> {
+    /// Adds the metadata enabled in the config to an image. Returns whether anything was added.
+    fn enrich(&self, path: &str, vector: &[f32], fields: &mut HashMap<String, Value>) -> bool {
+        let mut changed = false;
+
+        if !fields.contains_key(HASH_FIELD)
+            && let Ok(hash) = content_hash(path)
+        {
+            fields.insert(HASH_FIELD.into(), hash.into());
+            changed = true;
+        }
+
+        if let Some(endpoint) = &self.config.caption_endpoint
+            && !fields.contains_key(CAPTION_FIELD)
+        {
+            match caption_image(&self.client, endpoint, path) {
+                Ok(caption) => {
+                    fields.insert(CAPTION_FIELD.into(), caption.into());
+                    changed = true;
+                }
+                Err(e) => log_warning(format!("Failed to caption {}: {}", path, e)),
+            }
+        }
+
+        if let Some((nsfw, sfw)) = &self.nsfw_prompts
+            && !fields.contains_key(NSFW_FIELD)
+        {
+            let flagged =
+                cosine_similarity(vector, nsfw) > cosine_similarity(vector, sfw) + NSFW_MARGIN;
+            fields.insert(NSFW_FIELD.into(), flagged.into());
+            changed = true;
+        }
+
+        // the predictor only fits the embeddings of one model, the others are skipped
+        if let Some(aesthetic) = &self.aesthetic
+            && !fields.contains_key(AESTHETIC_FIELD)
+            && let Some(score) = aesthetic.score(vector)
+        {
+            fields.insert(AESTHETIC_FIELD.into(), score.into());
+            changed = true;
+        }
+
+        if !fields.contains_key(WIDTH_FIELD)
+            && let Ok((width, height)) = image::image_dimensions(path)
+        {
+            fields.insert(WIDTH_FIELD.into(), width.into());
+            fields.insert(HEIGHT_FIELD.into(), height.into());
+            changed = true;
+        }
+
+        if !fields.contains_key(DATE_FIELD)
+            && let Some(date) = dates::capture_date(path)
+        {
+            fields.insert(DATE_FIELD.into(), date.into());
+            changed = true;
+        }
+
+        let modified = sidecar::modified(path);
+        if fields.get(SIDECAR_FIELD).and_then(Value::as_u64) != Some(modified) {
+            if let Some(sidecar) = sidecar::read(path) {
+                let mut editable = Editable::from_fields(fields);
+                for tag in sidecar.tags {
+                    if !editable.tags.contains(&tag) {
+                        editable.tags.push(tag);
+                    }
+                }
+                if sidecar.rating > 0 {
+                    editable.rating = sidecar.rating;
+                }
+                editable.apply(fields);
+            }
+            fields.insert(SIDECAR_FIELD.into(), modified.into());
+            changed = true;
+        }
+
+        // both need the decoded image, so it is only decoded when one of them is missing
+        if (!fields.contains_key(COLORS_FIELD) || !fields.contains_key(QUALITY_FIELD))
+            && let Ok(image) = image::open(path)
+        {
+            if !fields.contains_key(COLORS_FIELD) {
+                let dominant = colors::dominant_colors(&image);
+                fields.insert(COLORS_FIELD.into(), colors::to_value(&dominant));
+            }
+            if !fields.contains_key(QUALITY_FIELD) {
+                let quality = quality::measure(&image);
+                fields.insert(SHARPNESS_FIELD.into(), quality.sharpness.into());
+                fields.insert(QUALITY_FIELD.into(), quality.score().into());
+            }
+            changed = true;
+        }
+
+        changed
+    }
+}
+
+/// Cosine similarity of two vectors, 0 if either of them is all zeros
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm_a = a.iter().map(|a| a * a).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|b| b * b).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::{
+    Arc, Condvar, Mutex, PoisonError,
+    atomic::{AtomicUsize, Ordering},
+};
+
+/// What the interface asked a background job to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+/// A job running on a background thread, shared between the thread and the interface.
+/// The thread reports its progress here and checks in with `proceed` between two steps,
+/// which is where it waits while paused and stops once cancelled.
+#[derive(Debug)]
+pub struct Job {
+    /// What the job does, shown in the jobs popup
+    pub name: String,
+    /// Whether the thread checks in with `proceed`, the others can't be paused or cancelled
+    pub controllable: bool,
+    done: AtomicUsize,
+    /// Number of steps, 0 while it isn't known
+    total: AtomicUsize,
+    state: Mutex<JobState>,
+    resumed: Condvar,
+}
+
+impl Job {
+    pub fn set_progress(&self, done: usize, total: usize) {
+        self.done.store(done, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    /// Steps done and the number of steps, `None` while the number isn't known
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        let total = self.total.load(Ordering::Relaxed);
+        (total > 0).then(|| (self.done.load(Ordering::Relaxed), total))
+    }
+
+    pub fn state(&self) -> JobState {
+        *self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Pauses a running job or resumes a paused one
+    pub fn toggle_pause(&self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        *state = match *state {
+            JobState::Running => JobState::Paused,
+            JobState::Paused => JobState::Running,
+            JobState::Cancelled => JobState::Cancelled,
+        };
+        self.resumed.notify_all();
+    }
+
+    pub fn cancel(&self) {
+        *self.state.lock().unwrap_or_else(PoisonError::into_inner) = JobState::Cancelled;
+        self.resumed.notify_all();
+    }
+
+    /// Called by the thread between two steps: blocks while the job is paused. Returns
+    /// whether to go on, `false` once the job is cancelled.
+    pub fn proceed(&self) -> bool {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = self
+            .resumed
+            .wait_while(state, |state| *state == JobState::Paused)
+            .unwrap_or_else(PoisonError::into_inner);
+        *state == JobState::Running
+    }
+}
+
+/// The jobs the interface started on background threads
+#[derive(Debug, Default)]
+pub struct Jobs {
+    jobs: Vec<Arc<Job>>,
+}
+
+impl Jobs {
+    /// Adds a job, the returned handle goes to its thread. The job counts as running until
+    /// the thread drops the handle.
+    pub fn start(&mut self, name: impl Into<String>, controllable: bool) -> Arc<Job> {
+        let job = Arc::new(Job {
+            name: name.into(),
+            controllable,
+            done: AtomicUsize::new(0),
+            total: AtomicUsize::new(0),
+            state: Mutex::new(JobState::Running),
+            resumed: Condvar::new(),
+        });
+        self.jobs.push(Arc::clone(&job));
+        job
+    }
+
+    /// The jobs whose thread is still running, oldest first
+    pub fn running(&mut self) -> &[Arc<Job>] {
+        self.jobs.retain(|job| Arc::strong_count(job) > 1);
+        &self.jobs
+    }
+}
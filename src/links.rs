@@ -0,0 +1,39 @@
+use std::path::Path;
+
+use crate::remote;
+
+/// Markdown image link to an image, e.g. `![beach day](Photos/beach%20day.jpg)`. Paths inside
+/// `vault_root` are written relative to it, so the link works in the notes of that vault,
+/// others stay absolute. Remote items link to their URL.
+pub fn markdown_image(path: &str, vault_root: Option<&Path>) -> String {
+    if remote::is_remote(path) {
+        return format!("![]({})", path);
+    }
+    let file = Path::new(path);
+    let name = file
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let target = vault_root
+        .and_then(|root| file.strip_prefix(root).ok())
+        .unwrap_or(file);
+    // Markdown wants slashes on every platform
+    let target = target.to_string_lossy().replace('\\', "/");
+    format!("![{}]({})", name.replace(['[', ']'], ""), encode(&target))
+}
+
+/// Escapes the characters that would end the link target early
+fn encode(target: &str) -> String {
+    let mut encoded = String::with_capacity(target.len());
+    for c in target.chars() {
+        match c {
+            ' ' => encoded.push_str("%20"),
+            '(' => encoded.push_str("%28"),
+            ')' => encoded.push_str("%29"),
+            '<' => encoded.push_str("%3C"),
+            '>' => encoded.push_str("%3E"),
+            c => encoded.push(c),
+        }
+    }
+    encoded
+}
@@ -0,0 +1,96 @@
+use cliprs::ClipModel;
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, RwLock, mpsc::Sender},
+    thread,
+};
+
+use crate::{
+    cache::VectorCache, catalog::Catalog, config::Config, events::AppEvent, index, jobs::Job,
+    store::Store, throttle::Throttle,
+};
+
+/// Loads the model of a slot on a background thread. Sends `AppEvent::ModelLoaded` once it
+/// is ready, or `AppEvent::ModelFailed` if loading panicked. `job` is held until then.
+pub fn load_model(
+    slot: usize,
+    name: String,
+    model_path: String,
+    job: Arc<Job>,
+    sender: Sender<AppEvent>,
+) {
+    thread::spawn(move || {
+        let _running = job;
+        let _ = sender.send(AppEvent::Loading(format!("Loading the model {}", name)));
+        let event = match panic::catch_unwind(|| ClipModel::new(&model_path)) {
+            Ok(model) => AppEvent::ModelLoaded(slot, Arc::new(model)),
+            Err(_) => AppEvent::ModelFailed(slot),
+        };
+        let _ = sender.send(event);
+    });
+}
+
+/// Everything needed to index the library with the model of one slot
+pub struct IndexJob {
+    pub slot: usize,
+    /// Pauses and cancels indexing from the jobs popup
+    pub job: Arc<Job>,
+    /// Name of the model, shown in the progress
+    pub name: String,
+    pub model: Arc<ClipModel>,
+    pub database: Arc<RwLock<Store>>,
+    pub catalog: Arc<Catalog>,
+    pub config: Config,
+    pub read_only: bool,
+}
+
+impl IndexJob {
+    /// Indexes the library on a background thread, reporting the progress as `AppEvent::Loading`.
+    /// Sends `AppEvent::Indexed` with the directory and the paths of all its images when it is
+    /// done or cancelled, a cancelled index is still searchable.
+    pub fn spawn(self, sender: Sender<AppEvent>) {
+        thread::spawn(move || {
+            let library_dir = self.config.library_dir();
+            let image_paths =
+                panic::catch_unwind(|| index::library_items(&library_dir)).unwrap_or_default();
+            // the model is identified by its name in the selector, like for `import-vectors`
+            let vector_cache = self.read_only.then(|| VectorCache::open(&self.name));
+            let mut throttle = Throttle::new(self.config.index_throttle.clone());
+            // a panic while embedding leaves the library partly indexed, which is still searchable
+            let unreadable = panic::catch_unwind(AssertUnwindSafe(|| {
+                index::index_images(
+                    &self.model,
+                    &self.database,
+                    &self.catalog,
+                    &image_paths,
+                    &self.config,
+                    vector_cache.as_ref(),
+                    |index, total, _| {
+                        self.job.set_progress(index, total);
+                        // the previous image is done, the throttle holds back before the next one
+                        if index > 0 {
+                            throttle.wait(|reason| {
+                                let _ = sender.send(AppEvent::Loading(reason.to_string()));
+                            });
+                        }
+                        if !self.job.proceed() {
+                            return false;
+                        }
+                        let _ = sender.send(AppEvent::Loading(format!(
+                            "Indexing image {} of {} with {}",
+                            index + 1,
+                            total,
+                            self.name
+                        )));
+                        true
+                    },
+                )
+            }))
+            .unwrap_or_default();
+            if !unreadable.is_empty() {
+                let _ = sender.send(AppEvent::Unreadable(unreadable));
+            }
+            let _ = sender.send(AppEvent::Indexed(self.slot, library_dir, image_paths));
+        });
+    }
+}
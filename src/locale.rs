@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+
+use crate::config::Config;
+
+/// Units file sizes are written in
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SizeUnits {
+    /// Powers of 1024: KiB, MiB, GiB
+    #[default]
+    Binary,
+    /// Powers of 1000: kB, MB, GB
+    Decimal,
+}
+
+/// Order and separator of the day, month and year in a date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateOrder {
+    /// `2023-03-14`
+    Iso,
+    /// `03/14/2023`
+    MonthDayYear,
+    /// `14/03/2023` with the separator
+    DayMonthYear(char),
+    /// `2023/03/14`
+    YearMonthDay,
+}
+
+/// How numbers, percentages, sizes and dates are written in the interface and in exports,
+/// following `Config::locale` or the locale of the environment
+#[derive(Debug, Clone, Copy)]
+pub struct Locale {
+    decimal: char,
+    /// Between groups of three digits, `None` to not group them
+    grouping: Option<char>,
+    /// Whether a space goes between a number and `%`
+    percent_space: bool,
+    date: DateOrder,
+    units: SizeUnits,
+}
+
+impl Default for Locale {
+    /// The POSIX locale: `1234.5`, `85%` and `2023-03-14`
+    fn default() -> Self {
+        Self {
+            decimal: '.',
+            grouping: None,
+            percent_space: false,
+            date: DateOrder::Iso,
+            units: SizeUnits::Binary,
+        }
+    }
+}
+
+impl Locale {
+    pub fn new(config: &Config) -> Self {
+        let name = config.locale.clone().or_else(|| {
+            ["LC_ALL", "LC_NUMERIC", "LANG"]
+                .iter()
+                .filter_map(|variable| env::var(variable).ok())
+                .find(|value| !value.is_empty())
+        });
+        Self {
+            units: config.size_units,
+            ..name.as_deref().map_or_else(Self::default, Self::named)
+        }
+    }
+
+    /// The conventions of a locale name like `de_DE.UTF-8` or `en-GB`. Only the common
+    /// languages are known, the others are written like the POSIX locale.
+    fn named(name: &str) -> Self {
+        // `de_DE.UTF-8` and `de_DE@euro` are both German in Germany
+        let name = name.split(['.', '@']).next().unwrap_or_default();
+        let (language, region) = name.split_once(['_', '-']).unwrap_or((name, ""));
+        let language = language.to_lowercase();
+        let region = region.to_uppercase();
+
+        let (decimal, grouping) = match language.as_str() {
+            "en" | "ja" | "zh" | "ko" | "he" | "th" => ('.', Some(',')),
+            "de" if region == "CH" => ('.', Some('\'')),
+            "de" | "nl" | "it" | "es" | "pt" | "da" | "tr" | "id" | "el" => (',', Some('.')),
+            // a no-break space, so numbers aren't wrapped in the middle
+            "fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "nb" | "nn" | "no" | "fi" | "uk" | "hu" => {
+                (',', Some('\u{a0}'))
+            }
+            _ => return Self::default(),
+        };
+        let percent_space = matches!(
+            language.as_str(),
+            "fr" | "de" | "sv" | "nb" | "nn" | "no" | "fi" | "cs" | "sk" | "da" | "ru"
+        );
+        let date = match language.as_str() {
+            "en" if region == "US" || region.is_empty() => DateOrder::MonthDayYear,
+            "en" | "fr" | "es" | "it" | "pt" | "el" | "id" => DateOrder::DayMonthYear('/'),
+            "de" | "ru" | "pl" | "cs" | "sk" | "nb" | "nn" | "no" | "fi" | "da" | "tr" | "uk" => {
+                DateOrder::DayMonthYear('.')
+            }
+            "nl" => DateOrder::DayMonthYear('-'),
+            "ja" | "zh" | "ko" => DateOrder::YearMonthDay,
+            _ => DateOrder::Iso,
+        };
+        Self {
+            decimal,
+            grouping,
+            percent_space,
+            date,
+            units: SizeUnits::default(),
+        }
+    }
+
+    /// A whole number with its digits grouped, e.g. `12,345`
+    pub fn count(&self, number: usize) -> String {
+        let digits = number.to_string();
+        let Some(separator) = self.grouping else {
+            return digits;
+        };
+        let mut grouped = String::new();
+        for (index, digit) in digits.chars().enumerate() {
+            if index > 0 && (digits.len() - index).is_multiple_of(3) {
+                grouped.push(separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+
+    /// A number with `decimals` digits after the decimal separator, negative numbers aren't
+    /// grouped
+    pub fn decimal(&self, number: f64, decimals: usize) -> String {
+        let text = format!("{:.*}", decimals, number);
+        let (whole, fraction) = text.split_once('.').unwrap_or((&text, ""));
+        let mut written = whole
+            .parse()
+            .map_or_else(|_| whole.to_string(), |whole| self.count(whole));
+        if !fraction.is_empty() {
+            written.push(self.decimal);
+            written.push_str(fraction);
+        }
+        written
+    }
+
+    /// A share from 0 to 1 as a whole percentage, e.g. `85%` or `85 %`
+    pub fn percent(&self, share: f64) -> String {
+        let percent = (share * 100.0) as u64;
+        if self.percent_space {
+            format!("{}\u{a0}%", percent)
+        } else {
+            format!("{}%", percent)
+        }
+    }
+
+    /// A file size with one decimal in the largest unit it reaches, e.g. `1.5 MiB`
+    pub fn size(&self, bytes: u64) -> String {
+        let (base, units) = match self.units {
+            SizeUnits::Binary => (1024.0, ["B", "KiB", "MiB", "GiB", "TiB"]),
+            SizeUnits::Decimal => (1000.0, ["B", "kB", "MB", "GB", "TB"]),
+        };
+        let mut value = bytes as f64;
+        let mut unit = 0;
+        while value >= base && unit < units.len() - 1 {
+            value /= base;
+            unit += 1;
+        }
+        if unit == 0 {
+            format!("{} {}", self.count(bytes as usize), units[0])
+        } else {
+            format!("{} {}", self.decimal(value, 1), units[unit])
+        }
+    }
+
+    /// A `YYYY-MM-DD` date as the locale writes it, other text is kept as it is
+    pub fn date(&self, date: &str) -> String {
+        let parts = (date.get(..4), date.get(5..7), date.get(8..10));
+        let (Some(year), Some(month), Some(day)) = parts else {
+            return date.to_string();
+        };
+        if date.len() != 10 {
+            return date.to_string();
+        }
+        match self.date {
+            DateOrder::Iso => date.to_string(),
+            DateOrder::MonthDayYear => format!("{}/{}/{}", month, day, year),
+            DateOrder::DayMonthYear(separator) => {
+                format!("{}{}{}{}{}", day, separator, month, separator, year)
+            }
+            DateOrder::YearMonthDay => format!("{}/{}/{}", year, month, day),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{self, Write},
+};
+
+use crate::{
+    encryption::{self, Unlocked},
+    paths,
+};
+
+pub enum LockError {
+    /// Another instance holds the lock, contains the PID it wrote into the lock file
+    Held(String),
+    Io(io::Error),
+}
+
+impl From<io::Error> for LockError {
+    fn from(e: io::Error) -> Self {
+        LockError::Io(e)
+    }
+}
+
+/// Exclusive advisory lock on the index, held for as long as this value lives.
+/// Only one findimg instance can write to the index at a time.
+pub struct IndexLock {
+    _file: File,
+}
+
+impl IndexLock {
+    /// Locks the index without waiting for another instance to release it.
+    pub fn acquire() -> Result<Self, LockError> {
+        fs::create_dir_all(paths::data_dir())?;
+        let path = paths::data_dir().join("index.lock");
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let pid = fs::read_to_string(&path).unwrap_or_default();
+                return Err(LockError::Held(pid.trim().to_string()));
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        file.flush()?;
+
+        Ok(Self { _file: file })
+    }
+}
+
+/// The index for this run: locked against other instances, and decrypted if it is stored
+/// encrypted. Dropping it encrypts the index again and then releases the lock, so the
+/// process has to end by returning from `main`, `process::exit` skips the destructors and
+/// would leave the index decrypted.
+pub struct IndexGuard {
+    // fields are dropped in order, the index is encrypted again while it is still locked
+    unlocked: Option<Unlocked>,
+    lock: Option<IndexLock>,
+    /// What the index was decrypted with, to decrypt it again in `reacquire`
+    passphrase: Option<String>,
+}
+
+impl IndexGuard {
+    pub fn new(
+        lock: Option<IndexLock>,
+        unlocked: Option<Unlocked>,
+        passphrase: Option<String>,
+    ) -> Self {
+        Self {
+            unlocked,
+            lock,
+            passphrase,
+        }
+    }
+
+    /// Encrypts the index again and releases the lock, so other instances can use it while
+    /// a long running command waits
+    pub fn release(&mut self) {
+        self.unlocked = None;
+        self.lock = None;
+    }
+
+    /// Locks the index again after `release` and decrypts it if it was decrypted before
+    pub fn reacquire(&mut self) -> Result<(), String> {
+        let lock = IndexLock::acquire().map_err(|e| match e {
+            LockError::Held(pid) => {
+                format!("Another findimg instance (PID {}) is using the index", pid)
+            }
+            LockError::Io(e) => format!("Failed to lock the index: {}", e),
+        })?;
+        if let Some(passphrase) = &self.passphrase {
+            self.unlocked = Some(encryption::unlock(passphrase)?);
+        }
+        self.lock = Some(lock);
+        Ok(())
+    }
+
+    /// Runs `run` while the index is held and returns what it returned once the index is
+    /// encrypted again and unlocked
+    pub fn run<T>(mut self, run: impl FnOnce(&mut Self) -> T) -> T {
+        let result = run(&mut self);
+        drop(self);
+        result
+    }
+}
@@ -1,67 +1,331 @@
-use std::{io::Read, result, sync::mpsc, time::Duration, usize};
+use std::time::{Duration, Instant};
 mod ui;
-use cliprs::{ClipModel, log_warning, poll_warnings};
-use nano_vectordb_rs::{Data, NanoVectorDB, constants};
+use cliprs::{ClipModel, poll_warnings};
+use nano_vectordb_rs::Data;
 use ratatui_image::{
-    ResizeEncodeRender, StatefulImage, picker::Picker, protocol::StatefulProtocol,
+    StatefulImage,
+    picker::{Picker, ProtocolType},
+    protocol::StatefulProtocol,
 };
-use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 use std::{
-    fs::{self, File},
-    io::{self, Write},
-    path::PathBuf,
-    process::exit,
+    fs,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
 };
 
+mod actions;
+mod aesthetic;
+mod analyze;
+mod atomic;
+mod autotag;
+mod bundle;
+mod bursts;
+mod cache;
+mod caption;
+mod catalog;
+mod cleanup;
+mod cli;
+mod clipboard;
+mod collage;
+mod colors;
+mod compact;
+mod config;
+mod corpus;
+mod curate;
+mod dates;
+mod encode;
+mod encryption;
+mod engine;
+mod eval;
+mod events;
+mod exif;
+mod expansion;
+mod export;
+mod feedback;
+mod graphics;
+mod hooks;
 mod img_scrape;
+mod import;
+mod index;
+mod jobs;
+mod links;
+mod loader;
+mod locale;
+mod lock;
+mod map;
+mod memory;
+mod metadata;
+mod migrate;
+mod models;
+mod outliers;
+mod parquet;
+mod paths;
+mod pins;
+mod pipe;
+mod plugins;
+mod privacy;
+mod provider;
+mod qdrant;
+mod quality;
+mod query;
+mod remote;
+mod search;
+mod serve;
+mod session;
+mod setup;
+mod shutdown;
+mod sidecar;
+mod sketch;
+mod store;
+mod terminal;
+mod throttle;
+mod translate;
+mod trash;
+mod voice;
+mod wallpaper;
 
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::{
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
+    execute,
+};
 use ratatui::{
     DefaultTerminal, Frame,
     layout::{Constraint, HorizontalAlignment, Layout, Position, Rect},
     style::{Color, Modifier, Style, Stylize},
+    symbols::Marker,
     text::{Line, Text},
-    widgets::{Block, Clear, List, ListItem, Paragraph},
+    widgets::{
+        Block, BorderType, Clear, List, ListItem, Paragraph, Wrap,
+        canvas::{Canvas, Line as CanvasLine, Points},
+    },
 };
 
-use img_scrape::google_photos::scrape;
+use img_scrape::{
+    fetcher::{Fetcher, ScraperConfig},
+    gallery,
+    google_photos::{self, scrape},
+    sync::Albums,
+};
 
+use crate::actions::Action;
+use crate::cache::VectorCache;
+use crate::catalog::Catalog;
+use crate::cleanup::Group;
+use crate::cli::{Args, Command};
+use crate::config::Config;
+use crate::encode::{Encoded, Encoder, Source, graphics_bytes, pixel_side};
+use crate::engine::ScoreCache;
+use crate::events::{AppEvent, Events};
+use crate::feedback::{Feedback, Judgement};
+use crate::index::{
+    CAPTION_FIELD, DATE_FIELD, Interrupted, NOTE_FIELD, NSFW_FIELD, QUALITY_FIELD, open_index,
+};
+use crate::jobs::{Job, JobState, Jobs};
+use crate::loader::IndexJob;
+use crate::locale::Locale;
+use crate::lock::{IndexGuard, IndexLock, LockError};
+use crate::map::EmbeddingMap;
+use crate::memory::ImageCache;
+use crate::metadata::Editable;
+use crate::models::ModelSlot;
+use crate::pins::Pins;
+use crate::plugins::{PluginAction, Plugins};
+use crate::remote::Remotes;
+use crate::search::{EnsembleMember, LoadedResult, SearchJob, SearchOutcome, Timings};
+use crate::serve::Server;
+use crate::session::Session;
+use crate::store::Store;
+use crate::trash::Trash;
 use crate::ui::{
-    button::{BLUE, Button, ButtonState},
+    button::{Button, ButtonState, RED, Theme},
+    canvas::SketchCanvas,
+    cleanup::{CleanupSession, Decision},
+    grid, hud,
+    jobs::JobsView,
     list::{OptionList, OptionStatus, SearchEnum, alternate_colors},
     message::{Message, MessageSeverity, Messages},
+    modal::{Modal, Modals},
+    palette::{CommandPalette, Entry},
+    settings::{Setting, SettingsPopup},
+    stats::StatsView,
+    throbber::Throbber,
+    timeline::{self, Timeline},
 };
 
-use std::collections::HashMap;
-
-const SUPPORTED_IMAGE_FORMATS: [&str; 10] = [
-    "jpg", "jpeg", "png", "tga", "bmp", "psd", " gif", "hdr", "pic", "ppm",
-];
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 pub struct App {
-    model: ClipModel,
+    /// The main model followed by the additional ones from the config, each with its own index
+    models: Vec<ModelSlot>,
+    /// Metadata of the images, shared by all models
+    catalog: Arc<Catalog>,
+    /// Slot of the model used for searching
+    active_model: usize,
+    /// Whether searches fuse the rankings of all models, `active_model` is the main one then
+    ensemble: bool,
+    /// When the background loading started and what it is doing, `None` once the model
+    /// is loaded and the library indexed
+    loading: Option<(Instant, String)>,
     search: String,
     input_mode: InputMode,
     char_index: usize,
     exit: bool,
     current_element: CurrentElement,
     button_pressed: bool,
-    modesel_open: bool,
     modesel_list: OptionList,
     mode: SearchEnum,
     search_results: Vec<SearchResult>,
-    images_embeddings: NanoVectorDB,
+    events: Events,
     picker: Picker,
+    /// Protocol the terminal reported, used when the config doesn't pick one
+    detected_protocol: ProtocolType,
+    /// The open popups, see `Modals`
+    modals: Modals,
     search_area: Rect,
+    /// Where the mode button was drawn, for mouse clicks
+    mode_area: Rect,
+    /// Where the results are drawn, the cells are laid out in it
+    results_block: Rect,
+    /// Where the buttons of the action bar were drawn, in the order of `ToolbarButton::ALL`
+    toolbar_areas: Vec<Rect>,
     clear_terminal: bool,
     notifications: Messages,
+    selected_result: usize,
+    result_areas: Vec<Rect>,
+    /// How far the timeline is scrolled down
+    timeline_scroll: u16,
+    /// When the running search started, shown as a throbber
+    searching: Option<Instant>,
+    /// Query of the running search and whether it refines the results
+    running_query: Option<(String, bool)>,
+    /// Whether a search submitted while another one ran refines the results, it starts when
+    /// the running one is done
+    queued_search: Option<bool>,
+    /// Images a refinement ranks, the best matches of the last search
+    candidates: Vec<String>,
+    /// Queries of the last search and its refinements, shown above the results
+    refinements: Vec<String>,
+    /// Results pinned to the tray below the results
+    pins: Pins,
+    preview_open: bool,
+    /// Whether the next key rates the selected result, after `*` was pressed
+    rating: bool,
+    config: Config,
+    trash: Trash,
+    feedback: Feedback,
+    image_paths: Vec<String>,
+    last_query_embedding: Option<Vec<f32>>,
+    map: Option<EmbeddingMap>,
+    map_open: bool,
+    /// The sketch canvas, `None` while it is closed
+    sketch: Option<SketchCanvas>,
+    map_cursor: (f64, f64),
+    read_only: bool,
+    /// Started with `--picker`, quitting on a result picks it
+    pick_mode: bool,
+    /// Path of the result picked in `pick_mode`, printed on exit
+    picked: Option<String>,
+    /// Search once the model has loaded, for the query of `--picker`
+    search_on_load: bool,
+    /// Whether the performance HUD is shown, toggled with F2
+    hud_open: bool,
+    /// How numbers and dates are written, see `Config::locale`
+    locale: Locale,
+    /// The indexing job cancelled by SIGINT or SIGTERM, the interface quits once it is saved
+    stopping: Option<Arc<Job>>,
+    /// How many images of the library were indexed when SIGINT or SIGTERM stopped indexing
+    interrupted: Option<(usize, usize)>,
+    /// Files that failed to index or to decode this session, with the reason
+    unreadable: BTreeMap<String, String>,
+    /// Scripts adjusting the ranking, shared with the searches
+    plugins: Arc<Plugins>,
+    /// Result actions of the plugins, listed in the command palette
+    plugin_actions: Vec<PluginAction>,
+    /// Serves the selected result to a browser, started the first time it's used
+    server: Option<Server>,
+    /// Command recording a spoken query, `--mic-command` or `Config::mic_command`
+    mic_command: Option<String>,
+    /// Set while the voice input command runs
+    listening: bool,
+    /// The library clean-up, `None` while there is none going on
+    cleanup: Option<CleanupSession>,
+    /// Set while the clean-up looks for what to decide on
+    finding_cleanup: bool,
+    /// Model loading, indexing and the clean-up running in the background
+    jobs: Jobs,
+    /// Decoded images shared with the search threads, see `memory::ImageCache`
+    images: Arc<Mutex<ImageCache>>,
+    /// Threads encoding the results for the terminal
+    encoder: Encoder,
+    /// Rankings of the session's searches, shared with the search threads
+    scores: Arc<Mutex<ScoreCache>>,
+    /// Set while the results are the ones restored from the last session, until they are
+    /// checked against the indexed library
+    restored: bool,
+    /// Number of frames drawn so far
+    frame_count: u64,
+    /// Where the time of the last search went, encoding the results for the terminal
+    /// includes re-encoding them after the layout changed
+    timings: Option<Timings>,
 }
 
-#[derive(Debug, PartialEq)]
+/// What has the focus. Keys that aren't bound everywhere go to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum CurrentElement {
     Search,
+    /// The mode button
     Filter,
-    Modesel,
+    /// A button of the action bar right of the mode button
+    Toolbar(ToolbarButton),
+    Images,
+}
+
+impl CurrentElement {
+    /// The focus ring: the elements `Tab` moves the focus through, in order, the top bar from
+    /// left to right and then the results. Popups take the focus while they are open and give
+    /// it back when they are closed, so they aren't part of it.
+    const RING: [CurrentElement; 7] = [
+        CurrentElement::Search,
+        CurrentElement::Filter,
+        CurrentElement::Toolbar(ToolbarButton::Search),
+        CurrentElement::Toolbar(ToolbarButton::Sources),
+        CurrentElement::Toolbar(ToolbarButton::Filters),
+        CurrentElement::Toolbar(ToolbarButton::Settings),
+        CurrentElement::Images,
+    ];
+}
+
+/// Buttons of the action bar, in the order `Left` and `Right` move the focus through them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ToolbarButton {
+    Search,
+    Sources,
+    Filters,
+    Settings,
+}
+
+impl ToolbarButton {
+    const ALL: [ToolbarButton; 4] = [
+        ToolbarButton::Search,
+        ToolbarButton::Sources,
+        ToolbarButton::Filters,
+        ToolbarButton::Settings,
+    ];
+
+    const fn label(self) -> &'static str {
+        match self {
+            ToolbarButton::Search => "Search",
+            ToolbarButton::Sources => "Sources",
+            ToolbarButton::Filters => "Filters",
+            ToolbarButton::Settings => "Settings",
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -71,447 +335,3379 @@ enum InputMode {
 }
 
 struct SearchResult {
-    image: StatefulProtocol,
+    /// Terminal graphics of the image, `None` after they were dropped to stay within
+    /// `Config::memory_budget`
+    image: Option<StatefulProtocol>,
+    /// Estimated memory of `image` in bytes
+    graphics_bytes: usize,
+    /// Longest side of the EXIF thumbnail `image` was made from, `None` if it was made from
+    /// the image itself. The image is read in full once it is shown larger.
+    reduced: Option<u32>,
+    /// Why the image could not be read, a placeholder is drawn instead
+    failed: Option<String>,
+    /// Id of the encoding of the image running on the encoder threads, which have the
+    /// graphics meanwhile
+    encoding: Option<u64>,
+    /// Frame the result was last drawn in, the graphics shown least recently are dropped first
+    last_shown: u64,
     confidence: f64,
     file_path: String,
+    caption: Option<String>,
+    /// Note added by hand
+    note: Option<String>,
+    /// Stars from 1 to 5, 0 for unrated
+    rating: u8,
+    /// Capture date as `YYYY-MM-DD`
+    date: Option<String>,
+    /// Width and height in pixels, the grid gives the image a cell of about its shape
+    dims: Option<(u32, u32)>,
+    /// Technical quality from 0 to 1, see `quality::Quality`
+    quality: Option<f32>,
+    /// Position in the ranking, used to restore the order after the timeline
+    rank: usize,
+    /// Near-identical shots hidden behind this result
+    burst: Vec<(String, f32)>,
+    /// In one of `Config::private_dirs`, the image is pixelated unless `revealed`
+    private: bool,
+    revealed: bool,
     last_area: Option<ratatui::layout::Rect>,
 }
 
-const SEARCH_RESULTS: usize = 20;
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = std::env::args().collect::<Vec<String>>();
-
-    if args.len() < 2 {
-        println!(
-            "Usage: {} <model_path> [--photos <google photos link>]",
-            args[0]
-        );
-        exit(1);
+impl SearchResult {
+    /// Title of the timeline section the result belongs to
+    fn section(&self) -> String {
+        self.date
+            .as_deref()
+            .map(dates::month_title)
+            .unwrap_or_else(|| "Unknown date".into())
     }
 
-    if !fs::exists(PathBuf::from(&args[1])).unwrap_or(false) {
-        eprintln!("ERROR: Model file does not exist");
-        exit(1);
+    /// Whether the image is drawn pixelated
+    fn pixelated(&self) -> bool {
+        self.private && !self.revealed
     }
 
-    if args.len() > 3
-        && args[2] == "--photos"
-        && let Err(e) = scrape(PathBuf::from("images/"), args[3].as_str())
-    {
-        println!("Failed to download images: {}", e);
-        exit(1);
+    /// Prepares the image to be drawn in `area` in frame `frame`: unless its graphics are
+    /// encoded at the size of the area already, hands them to the encoder threads, which
+    /// load them again first if they were dropped. Handing them over takes one of the
+    /// `encodes` left in the frame, without one left the image waits for the next frame.
+    /// Returns whether the image can be drawn.
+    fn show(
+        &mut self,
+        area: Rect,
+        frame: u64,
+        encodes: &mut usize,
+        picker: &Picker,
+        encoder: &mut Encoder,
+    ) -> bool {
+        self.last_shown = frame;
+        // e.g. the preview of a result shown from its thumbnail
+        if self
+            .reduced
+            .is_some_and(|side| side < pixel_side(area, picker))
+            && self.encoding.is_none()
+        {
+            self.image = None;
+            self.reduced = None;
+        }
+        if self.failed.is_some() || (self.image.is_some() && self.last_area == Some(area)) {
+            return true;
+        }
+        // encoded for another area, the next frame starts over once it's back
+        if self.encoding.is_some() || *encodes == 0 {
+            return false;
+        }
+        *encodes -= 1;
+        let source = match self.image.take() {
+            Some(image) => Source::Loaded(Box::new(image)),
+            None => Source::Reload {
+                picker: picker.clone(),
+                pixelate: self.pixelated(),
+            },
+        };
+        self.encoding = Some(encoder.submit(self.file_path.clone(), area, source));
+        self.last_area = None;
+        false
     }
 
-    ratatui::run(|terminal| App::default().run(terminal))?;
-    Ok(())
-}
-
-impl App {
-    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
-        let _ = terminal.clear();
-        while !self.exit {
-            if self.clear_terminal {
-                let _ = terminal.clear();
-                self.clear_terminal = false;
-            }
-            terminal.draw(|frame| self.draw(frame))?;
-            self.handle_events()?;
+    /// Draws the image, or why it could not be read. `shown` is what `show` returned, a
+    /// placeholder is drawn while the image is encoded.
+    fn render(&mut self, frame: &mut Frame, area: Rect, shown: bool) {
+        if !shown {
+            frame.render_widget(
+                Paragraph::new("loading…")
+                    .alignment(HorizontalAlignment::Center)
+                    .fg(Color::DarkGray),
+                area.centered_vertically(Constraint::Length(1)),
+            );
+        } else if let Some(image) = &mut self.image {
+            frame.render_stateful_widget(StatefulImage::default(), area, image);
+        } else if let Some(reason) = &self.failed {
+            frame.render_widget(
+                Paragraph::new(format!("Failed to load {}\n{}", self.file_path, reason))
+                    .wrap(Wrap { trim: true })
+                    .alignment(HorizontalAlignment::Center)
+                    .fg(RED.highlight),
+                area,
+            );
         }
-        Ok(())
     }
 
-    fn draw(&mut self, frame: &mut Frame) {
-        let vertical = Layout::vertical([
-            Constraint::Length(1),
-            Constraint::Length(3),
-            Constraint::Min(1),
-        ]);
-        let [help_area, input_area, img_area] = vertical.areas(frame.area());
-
-        let interactive_bar =
-            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]);
-        let [search_area, mode_area] = interactive_bar.areas(input_area);
-
-        self.search_area = search_area;
-
-        let (msg, style) = match self.input_mode {
-            InputMode::Normal => {
-                if self.current_element == CurrentElement::Search {
-                    (
-                        vec![
-                            "Press ".into(),
-                            "q".bold(),
-                            " to exit, ".into(),
-                            "c".bold(),
-                            " to clear the search, ".into(),
-                            "Enter".bold(),
-                            " to start editing".into(),
-                            "    ".into(),
-                            match self.mode {
-                                SearchEnum::Search => "The images will be the most similar to the prompt",
-                                SearchEnum::NegativePrompt => "The images will be the least similar to the prompt",
-                                SearchEnum::Image2Image => "A absolute path that will be matched to similar images",
-                                SearchEnum::Ranking => "Two criteria a \"high-low\" this is a trait followed by the inverse",
-                            }.into()
-                        ],
-                        Style::default().add_modifier(Modifier::RAPID_BLINK),
-                    )
-                } else if self.current_element == CurrentElement::Filter {
-                    (
-                        vec![
-                            "Press ".into(),
-                            "q".bold(),
-                            " to exit, ".into(),
-                            "Enter".bold(),
-                            " to select mode".into(),
-                        ],
-                        Style::default(),
-                    )
-                } else {
-                    (vec![], Style::default())
-                }
-            }
-            InputMode::Editing => (
-                vec![
-                    "Press ".into(),
-                    "Esc".bold(),
-                    " to stop editing, ".into(),
-                    "Enter".bold(),
-                    " to search for images".into(),
-                ],
-                Style::default(),
-            ),
+    /// Text shown below the image: the caption and the note if there are any, followed by the path
+    fn footer(&self) -> String {
+        let texts: Vec<&str> = [&self.caption, &self.note]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        let source = if self.pixelated() {
+            format!("🔒 {}", self.file_path)
+        } else if remote::is_remote(&self.file_path) {
+            format!("🌐 {}", self.file_path)
+        } else {
+            self.file_path.clone()
         };
-        let text = Text::from(Line::from(msg)).patch_style(style);
-        let help_message = Paragraph::new(text);
-        frame.render_widget(help_message, help_area);
-
-        // images block
-        let block = Block::bordered()
-            .title("Images")
-            .title_alignment(HorizontalAlignment::Center)
-            .style(Style::default().fg(Color::Rgb(70, 130, 180)));
-
-        let img_block = block.inner(img_area);
-        frame.render_widget(Clear, img_area);
-        frame.render_widget(block, img_area);
-
-        let results_count = self.search_results.len().min(10);
-        if results_count > 0 {
-            let mut areas = Vec::with_capacity(results_count);
-
-            if results_count == 1 {
-                areas.push(img_block);
-            } else {
-                let main_split =
-                    Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
-                        .split(img_block);
-
-                areas.push(main_split[0]);
+        if texts.is_empty() {
+            format!("[{}]", source)
+        } else {
+            format!("{} [{}]", texts.join(" | "), source)
+        }
+    }
+}
 
-                let right_area = main_split[1];
-                let remaining = results_count - 1;
+const DISPLAYED_RESULTS: usize = 10;
+/// Time between two frames, 25 frames per second
+const TICK_RATE: Duration = Duration::from_millis(40);
+/// Folder in the working directory the pinned images are copied to
+const PINNED_DIR: &str = "pinned";
+/// Where the collage of the results is saved, next to `PINNED_DIR`
+const COLLAGE_FILE: &str = "collage.png";
+/// Time between two wallpapers with `wallpaper --daily`
+const WALLPAPER_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Number of images `similar` prints
+const SIMILAR_RESULTS: usize = 20;
 
-                let r_constraints = if remaining <= 2 {
-                    vec![Constraint::Percentage(100)]
-                } else if remaining <= 5 {
-                    vec![Constraint::Percentage(50), Constraint::Percentage(50)]
-                } else {
-                    vec![
-                        Constraint::Percentage(33),
-                        Constraint::Percentage(33),
-                        Constraint::Percentage(34),
-                    ]
-                };
+fn main() -> ExitCode {
+    let program = std::env::args().next().unwrap_or("findimg".into());
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            println!("{}\n{}", e, cli::usage(&program));
+            return ExitCode::FAILURE;
+        }
+    };
 
-                let r_rows = Layout::vertical(r_constraints).split(right_area);
+    // everything but the picked path goes to the terminal, so it can be piped
+    let picked_output = match &args.picker {
+        Some(_) => match picker_output() {
+            Ok(output) => Some(output),
+            Err(e) => {
+                eprintln!("ERROR: --picker needs a terminal: {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
 
-                let mut r_idx = 0;
+    let lock = match IndexLock::acquire() {
+        Ok(lock) => lock,
+        Err(LockError::Held(pid)) => {
+            eprintln!(
+                "ERROR: Another findimg instance (PID {}) is using the index, close it first",
+                pid
+            );
+            return ExitCode::FAILURE;
+        }
+        Err(LockError::Io(e)) => {
+            eprintln!("ERROR: Failed to lock the index: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
 
-                if remaining > 0 {
-                    let n = if remaining <= 2 { remaining } else { 2 };
-                    let row_areas = Layout::horizontal(vec![Constraint::Ratio(1, n as u32); n])
-                        .split(r_rows[0]);
-                    areas.extend_from_slice(&row_areas);
-                    r_idx += n;
+    // the index is decrypted for this run and encrypted again once the guard holding it is
+    // dropped, so nothing after this may end the process with `exit`
+    let unlocked = match &args.command {
+        Some(Command::EncryptIndex) => {
+            match encryption::ask_passphrase(true)
+                .and_then(|passphrase| encryption::encrypt_index(&passphrase))
+            {
+                Ok(count) => println!("Encrypted {} index files", count),
+                Err(e) => {
+                    eprintln!("ERROR: Failed to encrypt the index: {}", e);
+                    return ExitCode::FAILURE;
                 }
-
-                if remaining > r_idx {
-                    let rem = remaining - r_idx;
-                    let limit = if remaining <= 5 { rem } else { 3 };
-                    let n = rem.min(limit);
-
-                    let row_areas = Layout::horizontal(vec![Constraint::Ratio(1, n as u32); n])
-                        .split(r_rows[1]);
-                    areas.extend_from_slice(&row_areas);
-                    r_idx += n;
+            }
+            return ExitCode::SUCCESS;
+        }
+        Some(Command::DecryptIndex) => {
+            match encryption::ask_passphrase(false)
+                .and_then(|passphrase| encryption::decrypt_index(&passphrase))
+            {
+                Ok(count) => println!("Decrypted {} index files", count),
+                Err(e) => {
+                    eprintln!("ERROR: Failed to decrypt the index: {}", e);
+                    return ExitCode::FAILURE;
                 }
-
-                if remaining > r_idx {
-                    let rem = remaining - r_idx;
-                    let row_areas = Layout::horizontal(vec![Constraint::Ratio(1, rem as u32); rem])
-                        .split(r_rows[2]);
-                    areas.extend_from_slice(&row_areas);
+            }
+            return ExitCode::SUCCESS;
+        }
+        // a bundle brings its own index
+        Some(Command::OpenBundle { .. }) => None,
+        _ if encryption::is_encrypted() => {
+            match encryption::ask_passphrase(false).and_then(|passphrase| {
+                encryption::unlock(&passphrase).map(|unlocked| (unlocked, passphrase))
+            }) {
+                Ok(unlocked) => Some(unlocked),
+                Err(e) => {
+                    eprintln!("ERROR: Failed to decrypt the index: {}", e);
+                    return ExitCode::FAILURE;
                 }
             }
+        }
+        _ => None,
+    };
+    let (unlocked, passphrase) = unlocked.unzip();
 
-            for (i, area) in areas.into_iter().enumerate() {
-                if let Some(result) = self.search_results.get_mut(i) {
-                    let confidence_text =
-                        format!("Confidence: {}%", (result.confidence * 100.0) as u64);
-                    let title = if i == 0 {
-                        format!("Highest {}", confidence_text)
-                    } else {
-                        confidence_text
-                    };
-
-                    let cell_block = Block::bordered()
-                        .title(title)
-                        .title_alignment(HorizontalAlignment::Center)
-                        .title_bottom(format!("[{}]", result.file_path))
-                        .style(Style::default().fg(Color::Rgb(70, 130, 180)));
+    let status = IndexGuard::new(Some(lock), unlocked, passphrase)
+        .run(|guard| run(args, &program, picked_output, guard));
+    match status {
+        Ok(status) => status,
+        Err(e) => match e.downcast::<Interrupted>() {
+            Ok(interrupted) => {
+                eprintln!("{}", interrupted);
+                ExitCode::from(130)
+            }
+            Err(e) => {
+                eprintln!("ERROR: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
 
-                    let inner_area = cell_block.inner(area);
-                    frame.render_widget(cell_block, area);
+/// Everything after the index is locked and decrypted, the status the process ends with
+fn run(
+    mut args: Args,
+    program: &str,
+    picked_output: Option<fs::File>,
+    guard: &mut IndexGuard,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    if let Err(e) = migrate::migrate_legacy_layout() {
+        eprintln!(
+            "ERROR: Failed to migrate data to the new directories: {}",
+            e
+        );
+        return Ok(ExitCode::FAILURE);
+    }
 
-                    if result.last_area != Some(inner_area) {
-                        result
-                            .image
-                            .resize_encode(&ratatui_image::Resize::Fit(None), inner_area);
-                        result.last_area = Some(inner_area);
-                    }
-                    frame.render_stateful_widget(
-                        StatefulImage::default(),
-                        inner_area,
-                        &mut result.image,
+    // a bundle replaces the own library for this run, the rest works as usual
+    if let Some(Command::OpenBundle { input }) = &args.command {
+        let main_model = args
+            .model_path
+            .clone()
+            .map(PathBuf::from)
+            .or(Config::load().model_path);
+        match bundle::open(input, &Config::load()) {
+            Ok(model) => {
+                if main_model.is_some_and(|path| models::main_name(&path) != model) {
+                    eprintln!(
+                        "WARNING: The bundle was made with the model {}, results with another model are poor",
+                        model
                     );
                 }
             }
+            Err(e) => {
+                eprintln!("ERROR: Failed to open the bundle: {}", e);
+                return Ok(ExitCode::FAILURE);
+            }
         }
+        args.command = None;
+        args.read_only = true;
+    }
 
-        // input area
-        let input = Paragraph::new(self.search.as_str())
-            .style(match self.input_mode {
-                InputMode::Normal => {
-                    if self.current_element == CurrentElement::Search {
-                        Style::default().fg(BLUE.highlight)
-                    } else {
-                        Style::default().fg(BLUE.background)
+    // moving embeddings in and out of the index doesn't need a model
+    let config = Config::load();
+    let main_model = args
+        .model_path
+        .as_deref()
+        .map(Path::new)
+        .or(config.model_path.as_deref());
+    match &args.command {
+        Some(Command::ExportVectors {
+            format,
+            output,
+            model,
+        }) => {
+            match export::export_index(&config, main_model, model.as_deref(), *format, output) {
+                Ok(count) => println!("Exported {} embeddings to {}", count, output.display()),
+                Err(e) => {
+                    eprintln!("ERROR: Failed to export the embeddings: {}", e);
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Pairwise {
+            top,
+            format,
+            output,
+            model,
+        }) => {
+            match analyze::export_pairwise(
+                &config,
+                main_model,
+                model.as_deref(),
+                *top,
+                *format,
+                output,
+            ) {
+                Ok(edges) => println!("Wrote {} edges to {}", edges, output.display()),
+                Err(e) => {
+                    eprintln!("ERROR: Failed to compare the images: {}", e);
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::ImportVectors { input, model_id }) => {
+            match import::import_index(&config, main_model, model_id, input) {
+                Ok(imported) => {
+                    println!("Imported {} embeddings", imported.images);
+                    if imported.skipped > 0 {
+                        println!(
+                            "Skipped {} images that aren't in the library",
+                            imported.skipped
+                        );
                     }
                 }
-                InputMode::Editing => Style::default().fg(Color::LightCyan),
-            })
-            .block(Block::bordered().title("Search"));
-        frame.render_widget(input, search_area);
-        match self.input_mode {
-            InputMode::Normal => {}
-            InputMode::Editing => frame.set_cursor_position(Position::new(
-                search_area.x + self.char_index as u16 + 1,
-                search_area.y + 1,
-            )),
+                Err(e) => {
+                    eprintln!("ERROR: Failed to import the embeddings: {}", e);
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+            return Ok(ExitCode::SUCCESS);
         }
-
-        let button_state = if self.current_element == CurrentElement::Filter {
-            if self.button_pressed {
-                ButtonState::Active
+        Some(Command::Compact) => {
+            match compact::compact(&config, main_model) {
+                Ok(compacted) => {
+                    println!(
+                        "Removed {} images that left the library and {} unused or duplicate thumbnails",
+                        compacted.removed, compacted.thumbnails
+                    );
+                    let locale = Locale::new(&config);
+                    println!(
+                        "Reclaimed {} ({} before, {} after)",
+                        locale.size(compacted.reclaimed()),
+                        locale.size(compacted.before),
+                        locale.size(compacted.after)
+                    );
+                }
+                Err(e) => {
+                    eprintln!("ERROR: Failed to compact the index: {}", e);
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::SyncAdd { url }) => {
+            let mut albums = Albums::load();
+            if albums.add(url) {
+                albums.save()?;
+                println!("Syncing {}, run sync run to download it now", url);
             } else {
-                ButtonState::Selected
+                println!("{} is synced already", url);
             }
-        } else {
-            ButtonState::Normal
-        };
-        self.button_pressed = false;
-        let mode_selector = Button::new("Choose Mode").state(button_state).theme(BLUE);
-        frame.render_widget(mode_selector, mode_area);
-
-        if self.modesel_open {
-            let popup_vertical = Layout::vertical([
-                Constraint::Percentage(20),
-                Constraint::Percentage(60),
-                Constraint::Percentage(20),
-            ]);
-
-            let [_, middle_vertical, _] = popup_vertical.areas(frame.area());
-
-            let popup_horizontal = Layout::horizontal([
-                Constraint::Percentage(20),
-                Constraint::Percentage(60),
-                Constraint::Percentage(20),
-            ]);
-
-            let [_, middle, _] = popup_horizontal.areas(middle_vertical);
-
-            let popup_block = Block::bordered()
-                .title("Select Mode")
-                .title_alignment(HorizontalAlignment::Center)
-                .title_bottom("Move with the arrow keys, submit by pressing Enter")
-                .fg(BLUE.background);
-
-            /*let block_area = popup_block.inner(middle);
-
-            frame.render_widget(popup_block, middle);*/
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::SyncRemove { url }) => {
+            let mut albums = Albums::load();
+            if albums.remove(url) {
+                albums.save()?;
+                println!("Stopped syncing {}, its images stay in the library", url);
+            } else {
+                eprintln!("ERROR: {} is not synced", url);
+                return Ok(ExitCode::FAILURE);
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::CreateBundle { output }) => {
+            match bundle::create(&config, main_model, output) {
+                Ok(count) => println!("Bundled {} images into {}", count, output.display()),
+                Err(e) => {
+                    eprintln!("ERROR: Failed to create the bundle: {}", e);
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Corpus { output }) => {
+            match corpus::generate(output) {
+                Ok(images) => println!(
+                    "Wrote {} images and their labels to {}",
+                    images.len(),
+                    output.display()
+                ),
+                Err(e) => {
+                    eprintln!("ERROR: Failed to write the corpus: {}", e);
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::ConvertCache { model_id }) => {
+            let model_id = model_id
+                .clone()
+                .unwrap_or_else(|| main_model.map_or("main".into(), models::main_name));
+            match VectorCache::open(&model_id).convert_legacy() {
+                Ok(count) => println!("Converted {} cached embeddings", count),
+                Err(e) => {
+                    eprintln!("ERROR: Failed to convert the cached embeddings: {}", e);
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => {}
+    }
 
-            let items: Vec<ListItem> = self
-                .modesel_list
-                .items
-                .iter()
-                .enumerate()
-                .map(|(i, todo_item)| {
-                    let color = alternate_colors(i);
-                    ListItem::from(todo_item).bg(color)
-                })
-                .collect();
+    let known_model = args
+        .model_path
+        .take()
+        .map(PathBuf::from)
+        .or(Config::load().model_path)
+        .filter(|_| !args.setup);
+    let model_path = match known_model {
+        Some(path) => path,
+        // first launch, or the user asked to set findimg up again
+        None if io::stdin().is_terminal() => match setup::run_wizard() {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("ERROR: Setup failed: {}", e);
+                return Ok(ExitCode::FAILURE);
+            }
+        },
+        None => {
+            println!("Missing model path\n{}", cli::usage(program));
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let model_path = model_path.display().to_string();
 
-            frame.render_widget(Clear, middle);
+    if !fs::exists(&model_path).unwrap_or(false) {
+        eprintln!("ERROR: Model file does not exist, run with --setup to pick another one");
+        return Ok(ExitCode::FAILURE);
+    }
 
-            let list = List::new(items)
-                .block(popup_block)
-                .highlight_style(Style::new().bg(BLUE.highlight).add_modifier(Modifier::BOLD))
-                .highlight_symbol(">")
-                .highlight_spacing(ratatui::widgets::HighlightSpacing::Always);
+    if let Some(album) = &args.photos {
+        if args.read_only && !args.remote {
+            eprintln!("ERROR: --photos can not be used together with --read-only");
+            return Ok(ExitCode::FAILURE);
+        }
+        let mut config = Config::load();
+        if let Some(cookies) = &args.cookies {
+            config.scraper.cookies_file = Some(cookies.clone());
+        }
+        let added = if args.remote {
+            link_album(album, &config.scraper)
+        } else {
+            scrape(&config, Path::new(&model_path), album, args.retry_failed)
+                .map(|report| report.print_summary())
+                .map_err(Into::into)
+        };
+        if let Err(e) = added {
+            println!("Failed to download images: {}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+    }
 
-            frame.render_stateful_widget(list, middle, &mut self.modesel_list.state);
+    // synced albums are scraped on `sync run`, and before the interface once they are due
+    let config = Config::load();
+    let sync_now = match &args.command {
+        Some(Command::SyncRun) => true,
+        None => !args.read_only && Albums::load().due(config.album_sync_hours),
+        _ => false,
+    };
+    if sync_now {
+        let all = args.command.is_some();
+        match img_scrape::sync::sync(&config, Path::new(&model_path), all, args.retry_failed) {
+            Ok(synced) => println!(
+                "Synced {} albums, removed {} images that left them",
+                synced.albums, synced.removed
+            ),
+            Err(e) if all => {
+                eprintln!("ERROR: Failed to sync the albums: {}", e);
+                return Ok(ExitCode::FAILURE);
+            }
+            Err(e) => eprintln!("WARNING: Failed to sync the albums: {}", e),
         }
+        if all {
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
 
-        let warnings = poll_warnings();
-        for warning in warnings {
-            self.notifications.add(Message::new(
-                warning,
-                MessageSeverity::Warning,
-                Duration::from_secs(3),
-            ));
+    // the search of the interface needs an absolute path to the image
+    if let Some(Command::Similar { image, .. }) = &mut args.command {
+        match paths::canonicalize(image) {
+            Ok(absolute) => *image = absolute,
+            Err(e) => {
+                eprintln!("ERROR: Can't open {}: {}", image.display(), e);
+                return Ok(ExitCode::FAILURE);
+            }
         }
+    }
 
-        self.notifications.draw(frame);
+    match &args.command {
+        Some(Command::Similar {
+            open_grid: true, ..
+        }) => {}
+        Some(command) => {
+            return run_command(&args, &model_path, command, guard).map(|()| ExitCode::SUCCESS);
+        }
+        None => {}
     }
 
-    fn handle_events(&mut self) -> io::Result<()> {
-        if let Ok(true) = event::poll(std::time::Duration::from_millis(500))
-            && let Event::Key(mut key) = event::read()?
+    let app = ratatui::run(|terminal| {
+        let mut app = App::new(&args, &model_path);
+        // the picker and `similar` start with their own search
+        if args.picker.as_deref().is_none_or(str::is_empty)
+            && args.command.is_none()
+            && let Some(session) = Session::load()
         {
-            if let KeyCode::Char(c) = key.code {
-                key.code = KeyCode::Char(c.to_lowercase().collect::<Vec<char>>()[0])
-            }
+            app.restore_session(session);
+        }
+        app.run(terminal).map(|_| app)
+    })?;
 
-            match self.input_mode {
-                InputMode::Normal => {
-                    if key.code == KeyCode::Char('r') {
-                        self.clear_terminal = true;
-                    }
+    // the terminal is restored by now, so the error can be read
+    if let Err(e) = app.save_session() {
+        eprintln!("Failed to save session: {}", e);
+    }
+    let (picked, interrupted) = (app.picked, app.interrupted);
+    if let Some((done, total)) = interrupted {
+        eprintln!(
+            "Stopped indexing after {} of {} images, they are saved. \
+             Start findimg again to continue from there.",
+            done, total
+        );
+        return Ok(ExitCode::from(130));
+    }
 
-                    if key.code == KeyCode::Char(' ') {
-                        self.notifications.add(Message::new(
-                            "User pressed space",
-                            MessageSeverity::Warning,
-                            Duration::from_secs(5),
-                        ));
-                    }
+    if let Some(mut output) = picked_output {
+        match picked {
+            Some(path) => writeln!(output, "{}", path)?,
+            // nothing to substitute, like fzf when it is cancelled
+            None => return Ok(ExitCode::FAILURE),
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
 
-                    match self.current_element {
-                        CurrentElement::Search => match key.code {
-                            KeyCode::Char('c') => self.clear_search(),
-                            KeyCode::Char('q') => self.exit(),
-                            KeyCode::Right => self.current_element = CurrentElement::Filter,
-                            KeyCode::Left => self.current_element = CurrentElement::Search,
-                            KeyCode::Enter => self.input_mode = InputMode::Editing,
-                            _ => {}
-                        },
-                        CurrentElement::Filter => match key.code {
-                            KeyCode::Char('q') => self.exit(),
-                            KeyCode::Right => self.current_element = CurrentElement::Filter,
-                            KeyCode::Left => self.current_element = CurrentElement::Search,
-                            KeyCode::Enter => {
-                                self.button_pressed = true;
-                                self.modesel_open = !self.modesel_open;
-                                self.current_element = CurrentElement::Modesel;
-                                self.modesel_list.state.select(Some(0));
-                            }
-                            _ => {}
-                        },
-                        CurrentElement::Modesel => match key.code {
-                            KeyCode::Char('q') | KeyCode::Esc => {
-                                self.modesel_open = false;
-                                self.current_element = CurrentElement::Filter;
-                                self.invalidate_image_cache();
-                            }
-                            KeyCode::Enter => {
-                                self.toggle_status();
-                                self.modesel_open = false;
-                                self.current_element = CurrentElement::Filter;
-                                self.invalidate_image_cache();
-                            }
-                            KeyCode::Char(' ') => self.toggle_status(),
-                            KeyCode::Down => self.select_next(),
-                            KeyCode::Up => self.select_previous(),
-                            _ => {}
-                        },
+/// Adds the images of a shared album as remote items instead of downloading them
+fn link_album(album: &str, config: &ScraperConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let fetcher = Fetcher::new(config)?;
+    let mut remotes = Remotes::load();
+    let images = google_photos::album_images(&fetcher, album)?;
+    let catalog = images
+        .iter()
+        .any(|image| image.text.is_some())
+        .then(|| Catalog::open(&paths::catalog_file()))
+        .transpose()?;
+    for image in images {
+        match remotes.add(&fetcher, &image.url) {
+            Ok(()) => println!("Added {}", image.url),
+            Err(e) => {
+                eprintln!("Failed to add {}: {}", image.url, e);
+                continue;
+            }
+        }
+        if let (Some(catalog), Some(text)) = (&catalog, &image.text) {
+            gallery::remember_text(catalog, &image.url, text)?;
+        }
+    }
+    remotes.save()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn picker_output() -> io::Result<fs::File> {
+    terminal::redirect_stdout()
+}
+
+#[cfg(not(unix))]
+fn picker_output() -> io::Result<fs::File> {
+    Err(io::Error::other("only supported on Unix"))
+}
+
+/// Indexes the library and sets one of the best matches of the prompt as the wallpaper.
+/// Returns its path.
+fn change_wallpaper(
+    model: &ClipModel,
+    model_path: &str,
+    config: &Config,
+    read_only: bool,
+    prompt: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let catalog = Catalog::open(&paths::catalog_file())?;
+    let (image_embeddings, image_paths) =
+        open_index(model, model_path, &catalog, config, read_only)?;
+    let path = wallpaper::pick(
+        model,
+        &curate::Library {
+            database: &*image_embeddings,
+            catalog: &catalog,
+            image_paths: &image_paths,
+        },
+        config,
+        config.similarity_for(model_path),
+        prompt,
+    )?;
+    wallpaper::set(Path::new(&path))?;
+    Ok(path)
+}
+
+/// Runs a command from the command line without starting the interface. `guard` holds the
+/// index, a command running for long releases it while it waits.
+fn run_command(
+    args: &Args,
+    model_path: &str,
+    command: &Command,
+    guard: &mut IndexGuard,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let model = ClipModel::new(model_path);
+    let config = Config::load();
+
+    match command {
+        Command::Curate {
+            prompt,
+            count,
+            output,
+            html,
+        } => {
+            let catalog = Catalog::open(&paths::catalog_file())?;
+            let (image_embeddings, image_paths) =
+                open_index(&model, model_path, &catalog, &config, args.read_only)?;
+            let photos = curate::curate(
+                &model,
+                &curate::Library {
+                    database: &*image_embeddings,
+                    catalog: &catalog,
+                    image_paths: &image_paths,
+                },
+                &config,
+                config.similarity_for(model_path),
+                prompt,
+                *count,
+            )?;
+            let copies = curate::export(&photos, output, *html)?;
+            println!("Copied {} photos to {}", copies.len(), output.display());
+        }
+        Command::Wallpaper { prompt, daily } => loop {
+            // indexed every time, so images added since the last wallpaper can be picked
+            let picked = change_wallpaper(&model, model_path, &config, args.read_only, prompt);
+            match picked {
+                Ok(path) => println!("Set {} as the wallpaper", path),
+                // a daily wallpaper tries again tomorrow, unless a signal stopped it
+                Err(e) if *daily && !e.is::<Interrupted>() => {
+                    eprintln!("Failed to change the wallpaper: {}", e)
+                }
+                Err(e) => return Err(e),
+            }
+            if !*daily {
+                break;
+            }
+            // other instances can use the index until the next wallpaper, an encrypted
+            // index is encrypted again in the meantime
+            guard.release();
+            std::thread::sleep(WALLPAPER_INTERVAL);
+            while let Err(e) = guard.reacquire() {
+                eprintln!("Failed to change the wallpaper: {}", e);
+                std::thread::sleep(WALLPAPER_INTERVAL);
+            }
+        },
+        Command::Golden => {
+            let outcomes = corpus::golden(&model, config.similarity_for(model_path))?;
+            for outcome in &outcomes {
+                if outcome.passed() {
+                    println!("ok      {}", outcome.query);
+                } else {
+                    println!(
+                        "FAILED  {}: expected {}, ranked {} first",
+                        outcome.query,
+                        outcome.expected,
+                        outcome.ranked_first.as_deref().unwrap_or("nothing")
+                    );
+                }
+            }
+            let failed = outcomes.iter().filter(|outcome| !outcome.passed()).count();
+            if failed > 0 {
+                return Err(format!("{} of {} queries failed", failed, outcomes.len()).into());
+            }
+            println!("All {} queries passed", outcomes.len());
+        }
+        Command::Eval { dataset, k } => {
+            let dataset = eval::read_dataset(dataset)?;
+            let catalog = Catalog::open(&paths::catalog_file())?;
+            let (image_embeddings, image_paths) =
+                open_index(&model, model_path, &catalog, &config, args.read_only)?;
+            let report = eval::evaluate(
+                &model,
+                &curate::Library {
+                    database: &*image_embeddings,
+                    catalog: &catalog,
+                    image_paths: &image_paths,
+                },
+                &config,
+                config.similarity_for(model_path),
+                &dataset,
+                *k,
+            )?;
+            println!("Queries    {}", report.queries);
+            println!("Recall@{:<3} {:.3}", report.k, report.recall);
+            println!("MRR        {:.3}", report.mrr);
+            println!("nDCG@{:<5} {:.3}", report.k, report.ndcg);
+            if !report.misses.is_empty() {
+                println!("\nNo relevant image in the first {}:", report.k);
+                for query in &report.misses {
+                    println!("  {}", query);
+                }
+            }
+        }
+        Command::Autotag {
+            labels,
+            threshold,
+            xmp,
+        } => {
+            let labels = autotag::read_labels(labels)?;
+            let catalog = Catalog::open(&paths::catalog_file())?;
+            let (image_embeddings, image_paths) =
+                open_index(&model, model_path, &catalog, &config, args.read_only)?;
+            let report = autotag::autotag(
+                &model,
+                &curate::Library {
+                    database: &*image_embeddings,
+                    catalog: &catalog,
+                    image_paths: &image_paths,
+                },
+                &labels,
+                *threshold,
+                *xmp || (config.write_sidecars && !args.read_only),
+            )?;
+            for (path, reason) in &report.failed_sidecars {
+                eprintln!("Failed to write the XMP sidecar of {}: {}", path, reason);
+            }
+            println!(
+                "Added {} tags to {} of {} images",
+                report.tags, report.tagged, report.images
+            );
+        }
+        Command::RankStdin { query } => {
+            let stdin = io::stdin();
+            if stdin.is_terminal() {
+                return Err(
+                    "--stdin needs image paths piped in, e.g. fd -e png | findimg --stdin <query>"
+                        .into(),
+                );
+            }
+            let paths = pipe::read_paths(stdin.lock())?;
+            // the library isn't indexed, only looked up, so piping a few files stays fast
+            let database = index::open_database(
+                &config,
+                &models::main_index(&config, Some(Path::new(model_path))),
+            );
+            let ranked = pipe::rank(
+                &model,
+                &*database,
+                &VectorCache::open(&models::main_name(Path::new(model_path))),
+                &config,
+                config.similarity_for(model_path),
+                query,
+                &paths,
+            )?;
+
+            let mut stdout = io::stdout().lock();
+            for (path, _) in ranked {
+                match writeln!(stdout, "{}", path) {
+                    Ok(()) => {}
+                    // the reader is done, e.g. `head`
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => break,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+        Command::Similar { image, .. } => {
+            let catalog = Catalog::open(&paths::catalog_file())?;
+            // indexing would print its progress into the JSON, the index is used as it is
+            let database = index::open_database(
+                &config,
+                &models::main_index(&config, Some(Path::new(model_path))),
+            );
+            let image_paths = index::library_items(&config.library_dir());
+            let similar = curate::similar_images(
+                &model,
+                &curate::Library {
+                    database: &*database,
+                    catalog: &catalog,
+                    image_paths: &image_paths,
+                },
+                &config,
+                config.similarity_for(model_path),
+                image,
+                SIMILAR_RESULTS,
+            )?;
+            println!("{}", serde_json::to_string_pretty(&similar)?);
+        }
+        Command::Provider { format, query } => {
+            let query = match query {
+                Some(query) => query.clone(),
+                None => {
+                    let mut line = String::new();
+                    io::stdin().read_line(&mut line)?;
+                    line.trim().to_string()
+                }
+            };
+            let catalog = Catalog::open(&paths::catalog_file())?;
+            // launchers ask on every key press, so the library is only looked up, not indexed
+            let database = index::open_database(
+                &config,
+                &models::main_index(&config, Some(Path::new(model_path))),
+            );
+            let image_paths = index::library_items(&config.library_dir());
+            let matches = curate::best_matches(
+                &model,
+                &curate::Library {
+                    database: &*database,
+                    catalog: &catalog,
+                    image_paths: &image_paths,
+                },
+                &config,
+                config.similarity_for(model_path),
+                &query::parse(&query),
+                provider::RESULTS,
+            )?;
+            println!(
+                "{}",
+                provider::render(*format, &matches, Locale::new(&config))
+            );
+        }
+        // handled in `main` without loading the model
+        Command::ExportVectors { .. }
+        | Command::Pairwise { .. }
+        | Command::ImportVectors { .. }
+        | Command::ConvertCache { .. }
+        | Command::Compact
+        | Command::EncryptIndex
+        | Command::DecryptIndex
+        | Command::CreateBundle { .. }
+        | Command::SyncAdd { .. }
+        | Command::SyncRemove { .. }
+        | Command::SyncRun
+        | Command::OpenBundle { .. }
+        | Command::Corpus { .. } => {}
+    }
+    Ok(())
+}
+
+fn read(database: &RwLock<Store>) -> RwLockReadGuard<'_, Store> {
+    database.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write(database: &RwLock<Store>) -> RwLockWriteGuard<'_, Store> {
+    database.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Draws the mode selector in the middle of the screen
+fn draw_mode_selector(frame: &mut Frame, modes: &mut OptionList, theme: Theme) {
+    let popup_vertical = Layout::vertical([
+        Constraint::Percentage(20),
+        Constraint::Percentage(60),
+        Constraint::Percentage(20),
+    ]);
+
+    let [_, middle_vertical, _] = popup_vertical.areas(frame.area());
+
+    let popup_horizontal = Layout::horizontal([
+        Constraint::Percentage(20),
+        Constraint::Percentage(60),
+        Constraint::Percentage(20),
+    ]);
+
+    let [_, middle, _] = popup_horizontal.areas(middle_vertical);
+
+    let popup_block = Block::bordered()
+        .title("Select Mode")
+        .title_alignment(HorizontalAlignment::Center)
+        .title_bottom("Move with the arrow keys, submit by pressing Enter")
+        .fg(theme.background);
+
+    /*let block_area = popup_block.inner(middle);
+
+    frame.render_widget(popup_block, middle);*/
+
+    let items: Vec<ListItem> = modes
+        .items
+        .iter()
+        .enumerate()
+        .map(|(i, todo_item)| {
+            let color = alternate_colors(i);
+            ListItem::from(todo_item).bg(color)
+        })
+        .collect();
+
+    frame.render_widget(Clear, middle);
+
+    let list = List::new(items)
+        .block(popup_block)
+        .highlight_style(
+            Style::new()
+                .bg(theme.highlight)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">")
+        .highlight_spacing(ratatui::widgets::HighlightSpacing::Always);
+
+    frame.render_stateful_widget(list, middle, &mut modes.state);
+}
+
+/// Draws the question whether to delete `count` files
+fn draw_confirm_delete(frame: &mut Frame, count: usize) {
+    let text = format!("Delete {} files? (y/n)", count);
+    let width = (text.len() as u16 + 4).min(frame.area().width);
+    let area = Rect::new(
+        frame.area().width.saturating_sub(width) / 2,
+        frame.area().height.saturating_sub(3) / 2,
+        width,
+        3.min(frame.area().height),
+    );
+
+    let confirm_block = Block::bordered()
+        .title("Confirm")
+        .title_alignment(HorizontalAlignment::Center)
+        .border_type(BorderType::Thick)
+        .fg(RED.highlight);
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(text)
+            .alignment(HorizontalAlignment::Center)
+            .block(confirm_block),
+        area,
+    );
+}
+
+impl App {
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let _ = terminal.clear();
+        execute!(io::stdout(), EnableMouseCapture)?;
+        while !self.exit {
+            match self.events.next()? {
+                AppEvent::Tick => {
+                    if self.clear_terminal {
+                        let _ = terminal.clear();
+                        self.clear_terminal = false;
                     }
+                    terminal.draw(|frame| self.draw(frame))?;
                 }
-                InputMode::Editing if key.kind == KeyEventKind::Press => match key.code {
-                    KeyCode::Enter => {
-                        if let Some(results) = self.search() {
-                            self.search_results = results;
-                        }
+                AppEvent::Input(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                    self.handle_key(key)
+                }
+                AppEvent::Input(Event::Mouse(mouse)) => self.handle_mouse(mouse),
+                // resizes are picked up by the next frame
+                AppEvent::Input(_) => {}
+                AppEvent::Search(outcome) => self.finish_search(*outcome),
+                AppEvent::Suspend => self.suspend(terminal)?,
+                AppEvent::Resume => {
+                    // after being stopped from outside, the shell may have reset the terminal
+                    terminal::enter(terminal)?;
+                    self.clear_terminal = true;
+                    self.invalidate_image_cache();
+                }
+                AppEvent::Shutdown => self.shut_down(),
+                AppEvent::EditMetadata(path) => self.edit_metadata(terminal, &path),
+                AppEvent::StartSearch => self.start_search(),
+                AppEvent::Undo => self.undo_delete(),
+                AppEvent::Loading(status) => {
+                    let started = self
+                        .loading
+                        .take()
+                        .map_or_else(Instant::now, |(started, _)| started);
+                    self.loading = Some((started, status));
+                }
+                AppEvent::ModelLoaded(slot, model) => {
+                    self.models[slot].model = Some(model);
+                    if slot == self.active_model && std::mem::take(&mut self.search_on_load) {
+                        self.start_search();
                     }
-                    KeyCode::Char(to_insert) => {
-                        let char_to_insert = if key.modifiers.contains(event::KeyModifiers::SHIFT) {
-                            to_insert.to_uppercase().next().unwrap_or(to_insert)
-                        } else {
-                            to_insert
-                        };
-                        self.enter_char(char_to_insert);
+                    self.start_indexing(slot);
+                }
+                AppEvent::ModelFailed(slot) => {
+                    let hint = if slot == 0 {
+                        "run findimg with --setup to pick another one"
+                    } else {
+                        "check its path in the config"
+                    };
+                    self.notifications.add(Message::new(
+                        format!(
+                            "Failed to load the model {}, {}",
+                            self.models[slot].path, hint
+                        ),
+                        MessageSeverity::Error,
+                        Duration::from_secs(30),
+                    ));
+                    self.continue_loading(slot + 1);
+                }
+                AppEvent::Unreadable(files) => self.unreadable.extend(files),
+                AppEvent::Indexed(slot, library_dir, image_paths) => {
+                    match self.stopping.take() {
+                        // what was indexed is saved, so it is safe to quit now
+                        Some(job) => {
+                            self.interrupted = job.progress();
+                            self.exit = true;
+                        }
+                        None => self.finish_indexing(slot, &library_dir, image_paths),
                     }
-                    KeyCode::Backspace => self.delete_char(),
-                    KeyCode::Delete => self.delete_right(),
-                    KeyCode::Left => self.move_cursor_left(),
-                    KeyCode::Right => self.move_cursor_right(),
-                    KeyCode::Esc => self.input_mode = InputMode::Normal,
-                    _ => {}
-                },
-                InputMode::Editing => {}
+                }
+                AppEvent::Transcribed(spoken) => self.finish_voice_search(spoken),
+                AppEvent::CleanupFound(groups) => self.start_cleanup(groups),
+                AppEvent::CollageMade(made) => {
+                    let (text, severity) = match made {
+                        Ok(text) => (text, MessageSeverity::Info),
+                        Err(e) => (
+                            format!("Failed to make the collage: {}", e),
+                            MessageSeverity::Error,
+                        ),
+                    };
+                    self.notifications
+                        .add(Message::new(text, severity, Duration::from_secs(5)));
+                }
+                AppEvent::Encoded(encoded) => self.finish_encoding(*encoded),
             }
         }
 
+        // the answers of the terminal would end up as key presses while input is read
+        self.events.pause_input();
+        graphics::refresh(&self.config);
+        execute!(io::stdout(), DisableMouseCapture)
+    }
+
+    /// Leaves the terminal to the shell until the process is continued
+    fn suspend(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            self.events.pause_input();
+            let suspended = terminal::suspend(terminal);
+            self.events.resume_input();
+            suspended?;
+        }
+        #[cfg(not(unix))]
+        let _ = terminal;
+
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
         Ok(())
     }
 
-    fn exit(&mut self) {
-        self.exit = true;
+    /// Opens the caption, tags and note of an image in `$EDITOR` and stores the edited values
+    fn edit_metadata(&mut self, terminal: &mut DefaultTerminal, path: &str) {
+        let editable = Editable::from_fields(&self.fields(path));
+        let edited = metadata::prepare(&editable).and_then(|file| {
+            self.events.pause_input();
+            let status = terminal::run_external(terminal, &mut metadata::editor_command(&file));
+            self.events.resume_input();
+
+            match status? {
+                status if status.success() => metadata::collect(&file),
+                status => Err(io::Error::other(format!("Editor exited with {}", status))),
+            }
+        });
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
+
+        let edited = match edited {
+            Ok(edited) => edited,
+            Err(e) => {
+                self.notifications.add(
+                    Message::new(
+                        format!("Failed to edit metadata: {}", e),
+                        MessageSeverity::Error,
+                        Duration::from_secs(5),
+                    )
+                    .action(
+                        'r',
+                        "retry",
+                        AppEvent::EditMetadata(path.to_string()),
+                    ),
+                );
+                return;
+            }
+        };
+
+        self.update_metadata(path, |fields| edited.clone().apply(fields));
+        self.notifications.add(Message::new(
+            "Saved metadata",
+            MessageSeverity::Info,
+            Duration::from_secs(2),
+        ));
+    }
+
+    /// Changes the metadata of an image in the catalog and shows the new values in the results
+    fn update_metadata(&mut self, path: &str, change: impl Fn(&mut HashMap<String, Value>)) {
+        let mut fields = self.catalog.fields(path);
+        change(&mut fields);
+        if let Err(e) = self.catalog.set_fields(path, &fields) {
+            self.notifications.add(Message::new(
+                format!("Failed to save the metadata: {}", e),
+                MessageSeverity::Error,
+                Duration::from_secs(5),
+            ));
+            return;
+        }
+
+        // remote items have no file to put a sidecar next to
+        if self.config.write_sidecars && !self.read_only && !remote::is_remote(path) {
+            let editable = Editable::from_fields(&fields);
+            if let Err(e) = sidecar::write(path, &editable.tags, editable.rating) {
+                self.notifications.add(Message::new(
+                    format!("Failed to write the XMP sidecar: {}", e),
+                    MessageSeverity::Warning,
+                    Duration::from_secs(3),
+                ));
+            }
+        }
+
+        let text = |field: &str| {
+            fields
+                .get(field)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        for result in &mut self.search_results {
+            if result.file_path == path {
+                result.caption = text(CAPTION_FIELD);
+                result.note = text(NOTE_FIELD);
+                result.rating = metadata::rating(&fields);
+            }
+        }
+    }
+
+    /// Gives the selected result a star rating, 0 removes it
+    fn rate_selected(&mut self, rating: u8) {
+        let Some(path) = self
+            .search_results
+            .get(self.selected_result)
+            .map(|result| result.file_path.clone())
+        else {
+            return;
+        };
+        self.update_metadata(&path, |fields| metadata::set_rating(fields, rating));
+        let text = match rating {
+            0 => "Removed the rating".to_string(),
+            rating => format!("Rated {}", "★".repeat(rating as usize)),
+        };
+        self.notifications.add(Message::new(
+            text,
+            MessageSeverity::Info,
+            Duration::from_secs(2),
+        ));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        self.frame_count += 1;
+        let theme = self.theme();
+        let vertical = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(3),
+            Constraint::Min(1),
+            Constraint::Length(if self.pins.paths().is_empty() { 0 } else { 3 }),
+        ]);
+        let [help_area, input_area, img_area, tray_area] = vertical.areas(frame.area());
+        self.draw_tray(frame, tray_area);
+
+        let interactive_bar = Layout::horizontal([
+            Constraint::Percentage(self.config.search_bar_width),
+            Constraint::Fill(1),
+        ]);
+        let [search_area, buttons_area] = interactive_bar.areas(input_area);
+        // the mode button takes what the action bar leaves
+        let toolbar = ToolbarButton::ALL.map(|button| self.toolbar_button(button));
+        let button_areas = Layout::horizontal(
+            std::iter::once(Constraint::Fill(1)).chain(
+                toolbar
+                    .iter()
+                    .map(|button| Constraint::Length(button.width())),
+            ),
+        )
+        .split(buttons_area);
+        let mode_area = button_areas[0];
+
+        self.search_area = search_area;
+        self.mode_area = mode_area;
+        self.toolbar_areas = button_areas[1..].to_vec();
+
+        let (msg, style) = match self.input_mode {
+            InputMode::Normal => {
+                if self.current_element == CurrentElement::Search {
+                    (
+                        vec![
+                            "Press ".into(),
+                            "q".bold(),
+                            " to exit, ".into(),
+                            "c".bold(),
+                            " to clear the search, ".into(),
+                            "m".bold(),
+                            " to open the map, ".into(),
+                            "s".bold(),
+                            " for settings, ".into(),
+                            "Tab".bold(),
+                            " to move the focus, ".into(),
+                            "Ctrl+P".bold(),
+                            " for all commands, ".into(),
+                            "Enter".bold(),
+                            " to start editing".into(),
+                            "    ".into(),
+                            match self.mode {
+                                SearchEnum::Search => "The images will be the most similar to the prompt",
+                                SearchEnum::NegativePrompt => "The images will be the least similar to the prompt",
+                                SearchEnum::Image2Image => "A absolute path that will be matched to similar images",
+                                SearchEnum::Ranking => "Two criteria a \"high-low\" this is a trait followed by the inverse",
+                                SearchEnum::Color => "The images will mostly consist of the color name or hex code",
+                                SearchEnum::TextOnImage => "The images will show the written text, like a sign or a label",
+                                SearchEnum::Sketch => "A path to a sketch, \"clipboard\" for a copied image, or press p to draw one",
+                                SearchEnum::Outliers => "The images will be the least like the rest of the library, filters narrow them down",
+                            }.into()
+                        ],
+                        if self.config.reduced_motion {
+                            Style::default()
+                        } else {
+                            Style::default().add_modifier(Modifier::RAPID_BLINK)
+                        },
+                    )
+                } else if self.current_element == CurrentElement::Filter {
+                    (
+                        vec![
+                            "Press ".into(),
+                            "q".bold(),
+                            " to exit, ".into(),
+                            "Enter".bold(),
+                            " to select mode".into(),
+                        ],
+                        Style::default(),
+                    )
+                } else if let CurrentElement::Toolbar(button) = self.current_element {
+                    (
+                        vec![
+                            "Press ".into(),
+                            "q".bold(),
+                            " to exit, ".into(),
+                            "Tab".bold(),
+                            " to move, ".into(),
+                            "<".bold(),
+                            "/".into(),
+                            ">".bold(),
+                            " to resize the search bar, ".into(),
+                            "Enter".bold(),
+                            format!(" for {}", button.label().to_lowercase()).into(),
+                        ],
+                        Style::default(),
+                    )
+                } else if self.current_element == CurrentElement::Images && self.rating {
+                    (
+                        vec![
+                            "Rating: press ".into(),
+                            "1-5".bold(),
+                            " for the stars, ".into(),
+                            "0".bold(),
+                            " to remove the rating, any other key to cancel".into(),
+                        ],
+                        Style::default(),
+                    )
+                } else if self.current_element == CurrentElement::Images {
+                    (
+                        vec![
+                            "Press ".into(),
+                            "q".bold(),
+                            " to exit, ".into(),
+                            "Arrows/hjkl".bold(),
+                            " to move, ".into(),
+                            "1-9".bold(),
+                            " to jump, ".into(),
+                            "*".bold(),
+                            " to rate, ".into(),
+                            "Enter".bold(),
+                            " to preview, ".into(),
+                            "v".bold(),
+                            " to change the layout, ".into(),
+                            "t".bold(),
+                            " for the timeline, ".into(),
+                            "e".bold(),
+                            " to expand a burst, ".into(),
+                            "i".bold(),
+                            " to edit caption/tags/note, ".into(),
+                            "+".bold(),
+                            "/".into(),
+                            "-".bold(),
+                            " to like/dislike, ".into(),
+                            "d".bold(),
+                            "/".into(),
+                            "x".bold(),
+                            " to delete one/all, ".into(),
+                            "u".bold(),
+                            " to undo, ".into(),
+                            "Esc".bold(),
+                            " to go back".into(),
+                        ],
+                        Style::default(),
+                    )
+                } else {
+                    (vec![], Style::default())
+                }
+            }
+            InputMode::Editing => (
+                vec![
+                    "Press ".into(),
+                    "Esc".bold(),
+                    " to stop editing, ".into(),
+                    "Enter".bold(),
+                    " to search for images, ".into(),
+                    "Alt+Enter".bold(),
+                    " to search within the results".into(),
+                ],
+                Style::default(),
+            ),
+        };
+        let text = Text::from(Line::from(msg)).patch_style(style);
+        let help_message = Paragraph::new(text);
+        frame.render_widget(help_message, help_area);
+        if let Some(cleanup) = &self.cleanup {
+            cleanup.draw(frame, help_area, theme);
+        }
+
+        // images block
+        let refinements = if self.refinements.len() > 1 {
+            format!(": {}", self.refinements.join(" › "))
+        } else {
+            String::new()
+        };
+        let block = Block::bordered()
+            .title(format!(
+                "Images ({}){}",
+                if self.config.timeline {
+                    "Timeline"
+                } else {
+                    self.config.layout.name()
+                },
+                refinements
+            ))
+            .title_alignment(HorizontalAlignment::Center)
+            .style(Style::default().fg(Color::Rgb(70, 130, 180)));
+
+        let img_block = block.inner(img_area);
+        self.results_block = img_block;
+        frame.render_widget(Clear, img_area);
+        frame.render_widget(block, img_area);
+
+        if let Some((started, status)) = &self.loading {
+            // in the middle while there is nothing else to show, else on the bottom border
+            let (x, y) = if self.search_results.is_empty() {
+                (
+                    img_block.x + img_block.width.saturating_sub(status.len() as u16 + 2) / 2,
+                    img_block.y + img_block.height / 2,
+                )
+            } else {
+                (img_area.x + 2, img_area.bottom().saturating_sub(1))
+            };
+            let status_area =
+                Rect::new(x, y, status.len() as u16 + 2, 1).intersection(frame.area());
+            let [throbber_area, text_area] =
+                Layout::horizontal([Constraint::Length(2), Constraint::Fill(1)]).areas(status_area);
+            frame.render_widget(
+                Throbber::new(*started)
+                    .style(Style::default().fg(theme.highlight))
+                    .still(self.config.reduced_motion),
+                throbber_area,
+            );
+            frame.render_widget(Paragraph::new(status.as_str()), text_area);
+        }
+
+        let results_count = if self.config.timeline {
+            self.search_results.len()
+        } else {
+            self.search_results.len().min(DISPLAYED_RESULTS)
+        };
+        if results_count > 0 && !self.map_open && self.sketch.is_none() {
+            let areas = if self.config.timeline {
+                let sections: Vec<String> = self
+                    .search_results
+                    .iter()
+                    .map(SearchResult::section)
+                    .collect();
+                let timeline = Timeline::new(img_block, &sections);
+                // the timeline is positioned as if the area was endless, navigation works on
+                // those positions and only the visible part is drawn
+                self.result_areas = timeline.cells.clone();
+
+                let scroll = timeline.scroll(img_block, self.selected_result);
+                if scroll != self.timeline_scroll {
+                    self.timeline_scroll = scroll;
+                    self.clear_terminal = true;
+                }
+                for (area, title) in &timeline.headers {
+                    if let Some(area) = timeline::visible(img_block, *area, scroll) {
+                        frame.render_widget(Paragraph::new(title.clone().bold()), area);
+                    }
+                }
+                timeline
+                    .cells
+                    .iter()
+                    .map(|cell| timeline::visible(img_block, *cell, scroll).unwrap_or_default())
+                    .collect()
+            } else {
+                let aspects: Vec<f64> = self
+                    .search_results
+                    .iter()
+                    .take(results_count)
+                    .map(|result| grid::aspect(result.dims, self.picker.font_size()))
+                    .collect();
+                let areas = self.config.layout.areas(
+                    img_block,
+                    results_count,
+                    &aspects,
+                    self.config.grid_columns,
+                    self.selected_result,
+                );
+                self.result_areas = areas.clone();
+                areas
+            };
+
+            // the images past the budget are encoded in the next frames, best results first
+            let mut encodes = match self.config.encodes_per_frame {
+                0 => usize::MAX,
+                encodes => encodes,
+            };
+            for (i, area) in areas.into_iter().enumerate() {
+                if area.is_empty() {
+                    continue;
+                }
+                let selected =
+                    self.current_element == CurrentElement::Images && i == self.selected_result;
+                if let Some(result) = self.search_results.get_mut(i) {
+                    let confidence_text =
+                        format!("Confidence: {}", self.locale.percent(result.confidence));
+                    let decision = self
+                        .cleanup
+                        .as_ref()
+                        .and_then(|cleanup| cleanup.decision(&result.file_path));
+                    let title = match decision {
+                        Some(Decision::Keep) => "✓ Keep".to_string(),
+                        Some(Decision::Delete) => "✗ Delete".to_string(),
+                        None if i == 0 => format!("Highest {}", confidence_text),
+                        None => confidence_text,
+                    };
+                    let title = if result.burst.is_empty() {
+                        title
+                    } else {
+                        format!("{} (+{} similar)", title, result.burst.len())
+                    };
+                    let title = if result.rating > 0 {
+                        format!("{} {}", title, "★".repeat(result.rating as usize))
+                    } else {
+                        title
+                    };
+                    let title = if self.pins.contains(&result.file_path) {
+                        format!("📌 {}", title)
+                    } else {
+                        title
+                    };
+
+                    let cell_block = Block::bordered()
+                        .title(title)
+                        .title_alignment(HorizontalAlignment::Center)
+                        .title_bottom(result.footer());
+                    let cell_block = if selected {
+                        cell_block.border_type(BorderType::Thick).style(
+                            Style::default()
+                                .fg(theme.highlight)
+                                .add_modifier(Modifier::BOLD),
+                        )
+                    } else {
+                        cell_block.style(Style::default().fg(Color::Rgb(70, 130, 180)))
+                    };
+
+                    let inner_area = cell_block.inner(area);
+                    frame.render_widget(cell_block, area);
+
+                    let shown = result.show(
+                        inner_area,
+                        self.frame_count,
+                        &mut encodes,
+                        &self.picker,
+                        &mut self.encoder,
+                    );
+                    result.render(frame, inner_area, shown);
+                }
+            }
+        } else {
+            self.result_areas.clear();
+        }
+
+        if self.preview_open
+            && let Some(result) = self.search_results.get_mut(self.selected_result)
+        {
+            let quality = result
+                .quality
+                .map(|quality| format!(" - Quality: {}", self.locale.percent(quality as f64)))
+                .unwrap_or_default();
+            let date = result
+                .date
+                .as_deref()
+                .map(|date| format!(" - Taken: {}", self.locale.date(date)))
+                .unwrap_or_default();
+            let preview_block = Block::bordered()
+                .title(format!(
+                    "Preview - Confidence: {}{}{}",
+                    self.locale.percent(result.confidence),
+                    quality,
+                    date
+                ))
+                .title_alignment(HorizontalAlignment::Center)
+                .title_bottom(result.footer())
+                .border_type(BorderType::Thick)
+                .style(Style::default().fg(theme.highlight));
+
+            let inner_area = preview_block.inner(img_area);
+            frame.render_widget(Clear, img_area);
+            frame.render_widget(preview_block, img_area);
+
+            // the preview is a single image and what the user looks at, it doesn't wait
+            let shown = result.show(
+                inner_area,
+                self.frame_count,
+                &mut 1,
+                &self.picker,
+                &mut self.encoder,
+            );
+            result.render(frame, inner_area, shown);
+        }
+
+        if self.map_open
+            && let Some(map) = &self.map
+        {
+            let hovered = map.nearest(self.map_cursor.0, self.map_cursor.1);
+            let query = self
+                .last_query_embedding
+                .as_ref()
+                .map(|vector| map.project(vector));
+            let cursor = self.map_cursor;
+            let coords: Vec<(f64, f64)> = map.points.iter().map(|(_, x, y)| (*x, *y)).collect();
+
+            let map_block = Block::bordered()
+                .title("Embedding Map")
+                .title_alignment(HorizontalAlignment::Center)
+                .title_bottom(
+                    hovered
+                        .map(|i| format!("[{}]", map.points[i].0))
+                        .unwrap_or_default(),
+                )
+                .fg(Color::Rgb(70, 130, 180));
+
+            let canvas = Canvas::default()
+                .block(map_block)
+                .marker(Marker::Braille)
+                .x_bounds(map.x_bounds)
+                .y_bounds(map.y_bounds)
+                .paint(|ctx| {
+                    ctx.draw(&Points {
+                        coords: &coords,
+                        color: Color::Rgb(70, 130, 180),
+                    });
+                    if let Some((x, y)) = query {
+                        ctx.draw(&CanvasLine::new(
+                            map.x_bounds[0],
+                            y,
+                            map.x_bounds[1],
+                            y,
+                            Color::Yellow,
+                        ));
+                        ctx.draw(&CanvasLine::new(
+                            x,
+                            map.y_bounds[0],
+                            x,
+                            map.y_bounds[1],
+                            Color::Yellow,
+                        ));
+                    }
+                    ctx.layer();
+                    if let Some(i) = hovered {
+                        let (_, x, y) = &map.points[i];
+                        ctx.print(*x, *y, "●".fg(theme.highlight).bold());
+                    }
+                    ctx.print(cursor.0, cursor.1, "+".fg(Color::White).bold());
+                });
+
+            frame.render_widget(Clear, img_area);
+            frame.render_widget(canvas, img_area);
+        }
+
+        // input area
+        let input = Paragraph::new(self.search.as_str())
+            .style(match self.input_mode {
+                InputMode::Normal => {
+                    if self.current_element == CurrentElement::Search {
+                        Style::default().fg(theme.highlight)
+                    } else {
+                        Style::default().fg(theme.background)
+                    }
+                }
+                InputMode::Editing => Style::default().fg(Color::LightCyan),
+            })
+            .block(Block::bordered().title(format!(
+                "{}{}",
+                match self.models.as_slice() {
+                    [_] => "Search".to_string(),
+                    _ if self.ensemble =>
+                        "Search (ensemble of all models, ] to switch)".to_string(),
+                    models => format!("Search ({}, ] to switch)", models[self.active_model].name),
+                },
+                // the still throbber alone doesn't tell that a search is running
+                if self.searching.is_some() && self.config.reduced_motion {
+                    " - searching…"
+                } else {
+                    ""
+                }
+            )));
+        frame.render_widget(input, search_area);
+        if let Some(started) = self.searching {
+            let throbber_area = Rect::new(
+                search_area.right().saturating_sub(2),
+                search_area.y + 1,
+                1,
+                1,
+            );
+            frame.render_widget(
+                Throbber::new(started)
+                    .style(Style::default().fg(Color::LightCyan))
+                    .still(self.config.reduced_motion),
+                throbber_area,
+            );
+        }
+        match self.input_mode {
+            InputMode::Normal => {}
+            InputMode::Editing => frame.set_cursor_position(Position::new(
+                search_area.x + self.char_index as u16 + 1,
+                search_area.y + 1,
+            )),
+        }
+
+        let mode_selector = self.mode_button();
+        self.button_pressed = false;
+        frame.render_widget(mode_selector, mode_area);
+        for (button, area) in toolbar.into_iter().zip(button_areas[1..].iter()) {
+            frame.render_widget(button, *area);
+        }
+
+        if let Some(sketch) = &mut self.sketch {
+            sketch.draw(frame, theme);
+        }
+
+        if self.hud_open {
+            hud::draw(frame, self.timings.as_ref(), self.locale, theme);
+        }
+
+        for modal in self.modals.iter_mut() {
+            match modal {
+                Modal::ModeSelector => draw_mode_selector(frame, &mut self.modesel_list, theme),
+                Modal::Settings(settings) => settings.draw(frame, &self.config, theme),
+                Modal::Stats(stats) => stats.draw(frame, theme),
+                Modal::Jobs(jobs_view) => jobs_view.draw(frame, self.jobs.running(), theme),
+                Modal::Palette(palette) => palette.draw(frame, theme),
+                Modal::ConfirmDelete(paths) => draw_confirm_delete(frame, paths.len()),
+            }
+        }
+
+        let warnings = poll_warnings();
+        for warning in warnings {
+            self.notifications.add(Message::new(
+                warning,
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+        }
+
+        self.notifications.draw(frame);
+        self.evict_graphics();
+    }
+
+    /// Drops the graphics of the results shown least recently while they take more than their
+    /// share of `Config::memory_budget`. They are loaded again once the results are shown.
+    fn evict_graphics(&mut self) {
+        let (_, budget) = memory::split_budget(self.config.memory_budget);
+        let mut used: usize = self
+            .search_results
+            .iter()
+            .filter(|result| result.image.is_some())
+            .map(|result| result.graphics_bytes)
+            .sum();
+        while used > budget {
+            // the results on screen stay, even if they alone are over the budget
+            let Some(oldest) = self
+                .search_results
+                .iter_mut()
+                .filter(|result| result.image.is_some() && result.last_shown < self.frame_count)
+                .min_by_key(|result| result.last_shown)
+            else {
+                break;
+            };
+            oldest.image = None;
+            oldest.last_area = None;
+            used -= oldest.graphics_bytes;
+        }
+    }
+
+    fn handle_key(&mut self, mut key: KeyEvent) {
+        #[cfg(unix)]
+        if key.code == KeyCode::Char('z') && key.modifiers.contains(event::KeyModifiers::CONTROL) {
+            let _ = self.events.sender().send(AppEvent::Suspend);
+            return;
+        }
+
+        // raw mode turns Ctrl+C into a key, it quits like SIGINT does in a normal shell program
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(event::KeyModifiers::CONTROL) {
+            self.shut_down();
+            return;
+        }
+
+        if key.code == KeyCode::Char('p') && key.modifiers.contains(event::KeyModifiers::CONTROL) {
+            self.modals.open(Modal::Palette(CommandPalette::new(
+                self.plugin_actions.clone(),
+            )));
+            self.input_mode = InputMode::Normal;
+            return;
+        }
+
+        // the HUD is for watching the other views, so it toggles from anywhere
+        if key.code == KeyCode::F(2) {
+            self.perform(Action::TogglePerformanceHud);
+            return;
+        }
+
+        // the popup on top gets the keys, the palette and the settings before they are
+        // lowercased since they can be typed in
+        if let Some(modal) = self.modals.top() {
+            match modal {
+                Modal::ModeSelector => self.handle_mode_selector_key(key.code),
+                Modal::Settings(_) => self.handle_settings_key(key.code),
+                Modal::Stats(_) => self.handle_stats_key(key.code),
+                Modal::Jobs(_) => self.handle_jobs_key(key.code),
+                Modal::Palette(_) => self.handle_palette_key(key.code),
+                Modal::ConfirmDelete(_) => self.handle_confirm_delete_key(key.code),
+            }
+            return;
+        }
+
+        if let KeyCode::Char(c) = key.code {
+            key.code = KeyCode::Char(c.to_lowercase().collect::<Vec<char>>()[0])
+        }
+
+        // keys of notification actions take precedence while the notification is shown
+        if matches!(self.input_mode, InputMode::Normal)
+            && let KeyCode::Char(c) = key.code
+            && let Some(event) = self.notifications.take_action(c)
+        {
+            let _ = self.events.sender().send(event);
+            return;
+        }
+
+        if self.map_open && matches!(self.input_mode, InputMode::Normal) {
+            self.handle_map_key(key.code);
+            return;
+        }
+
+        if self.sketch.is_some() && matches!(self.input_mode, InputMode::Normal) {
+            self.handle_sketch_key(key.code);
+            return;
+        }
+
+        if self.cleanup.is_some() && matches!(self.input_mode, InputMode::Normal) {
+            self.handle_cleanup_key(key.code);
+            return;
+        }
+
+        match self.input_mode {
+            InputMode::Normal => {
+                if key.code == KeyCode::Char('r') {
+                    self.clear_terminal = true;
+                }
+
+                if let Some(action) = Action::for_key(key.code) {
+                    self.perform(action);
+                }
+
+                // the preview keeps the focus until it is closed
+                if matches!(key.code, KeyCode::Tab | KeyCode::BackTab) && !self.preview_open {
+                    self.move_focus(if key.code == KeyCode::Tab { 1 } else { -1 }, true);
+                    return;
+                }
+
+                if key.code == KeyCode::Char(' ') {
+                    self.notifications.add(Message::new(
+                        "User pressed space",
+                        MessageSeverity::Warning,
+                        Duration::from_secs(5),
+                    ));
+                }
+
+                match self.current_element {
+                    // the search bar and the buttons next to it
+                    element @ (CurrentElement::Search
+                    | CurrentElement::Filter
+                    | CurrentElement::Toolbar(_)) => match key.code {
+                        KeyCode::Char('c') if element == CurrentElement::Search => {
+                            self.clear_search()
+                        }
+                        KeyCode::Char('p') => self.perform(Action::DrawSketch),
+                        KeyCode::Char('q') => self.exit(),
+                        KeyCode::Char('<') => self.adjust_search_bar(-1),
+                        KeyCode::Char('>') => self.adjust_search_bar(1),
+                        KeyCode::Right => self.move_focus(1, false),
+                        KeyCode::Left => self.move_focus(-1, false),
+                        KeyCode::Down => self.focus_images(),
+                        KeyCode::Enter => match element {
+                            CurrentElement::Filter => self.open_mode_selector(),
+                            CurrentElement::Toolbar(button) => self.press_toolbar_button(button),
+                            _ => self.input_mode = InputMode::Editing,
+                        },
+                        _ => {}
+                    },
+                    CurrentElement::Images if self.preview_open => match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc | KeyCode::Enter => {
+                            self.preview_open = false;
+                            self.clear_terminal = true;
+                            self.invalidate_image_cache();
+                        }
+                        KeyCode::Char('i') => {
+                            if let Some(result) = self.search_results.get(self.selected_result) {
+                                let path = result.file_path.clone();
+                                let _ = self.events.sender().send(AppEvent::EditMetadata(path));
+                            }
+                        }
+                        KeyCode::Char('p') => self.toggle_pin(),
+                        KeyCode::Char('w') => self.perform(Action::SetWallpaper),
+                        KeyCode::Char('b') => self.perform(Action::RevealPrivate),
+                        KeyCode::Char('g') => self.perform(Action::ServeResult),
+                        _ => {}
+                    },
+                    // a digit after `*` rates the result, any other key leaves it unrated
+                    CurrentElement::Images if self.rating => {
+                        self.rating = false;
+                        if let KeyCode::Char(c @ '0'..='5') = key.code {
+                            self.rate_selected(c as u8 - b'0');
+                        }
+                    }
+                    CurrentElement::Images => match key.code {
+                        KeyCode::Char('q') => self.exit(),
+                        KeyCode::Enter if self.pick_mode => self.exit(),
+                        KeyCode::Esc => self.current_element = CurrentElement::Search,
+                        KeyCode::Left | KeyCode::Char('h') => self.move_selection(-1, 0),
+                        KeyCode::Right | KeyCode::Char('l') => self.move_selection(1, 0),
+                        KeyCode::Up | KeyCode::Char('k') => self.move_selection(0, -1),
+                        KeyCode::Down | KeyCode::Char('j') => self.move_selection(0, 1),
+                        KeyCode::Char(c @ '1'..='9') => {
+                            self.jump_to_result(c as usize - '1' as usize)
+                        }
+                        KeyCode::Char('*') => self.rating = true,
+                        KeyCode::Char('e') => self.expand_burst(),
+                        KeyCode::Char('p') => self.toggle_pin(),
+                        KeyCode::Char('w') => self.perform(Action::SetWallpaper),
+                        KeyCode::Char('y') => self.perform(Action::CopyMarkdownLink),
+                        KeyCode::Char('b') => self.perform(Action::RevealPrivate),
+                        KeyCode::Char('g') => self.perform(Action::ServeResult),
+                        KeyCode::Char('i') => {
+                            if let Some(result) = self.search_results.get(self.selected_result) {
+                                let path = result.file_path.clone();
+                                let _ = self.events.sender().send(AppEvent::EditMetadata(path));
+                            }
+                        }
+                        KeyCode::Char('+') => self.give_feedback(true),
+                        KeyCode::Char('-') => self.give_feedback(false),
+                        KeyCode::Char('d') => {
+                            if let Some(result) = self.search_results.get(self.selected_result) {
+                                self.delete_files(vec![result.file_path.clone()]);
+                            }
+                        }
+                        KeyCode::Char('x') => {
+                            let paths = self
+                                .search_results
+                                .iter()
+                                .take(self.displayed_results())
+                                .map(|result| result.file_path.clone())
+                                .collect::<Vec<String>>();
+                            if !paths.is_empty() && !self.refuse_read_only() {
+                                self.modals.open(Modal::ConfirmDelete(paths));
+                                self.clear_terminal = true;
+                            }
+                        }
+                        KeyCode::Enter if self.selected_result < self.search_results.len() => {
+                            let result = &self.search_results[self.selected_result];
+                            hooks::run(
+                                self.config.hooks.on_result_open.as_deref(),
+                                json!({
+                                    "event": "result_open",
+                                    "path": result.file_path,
+                                    "rank": result.rank,
+                                    "confidence": result.confidence,
+                                    "query": self.search,
+                                }),
+                            );
+                            let path = &result.file_path;
+                            if remote::is_remote(path) {
+                                self.open_remote(path.clone());
+                            } else {
+                                self.preview_open = true;
+                                self.clear_terminal = true;
+                            }
+                        }
+                        _ => {}
+                    },
+                }
+            }
+            InputMode::Editing if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter if key.modifiers.contains(event::KeyModifiers::ALT) => {
+                    self.refine_search();
+                }
+                KeyCode::Enter => {
+                    self.start_search();
+                }
+                KeyCode::Char(to_insert) => {
+                    let char_to_insert = if key.modifiers.contains(event::KeyModifiers::SHIFT) {
+                        to_insert.to_uppercase().next().unwrap_or(to_insert)
+                    } else {
+                        to_insert
+                    };
+                    self.enter_char(char_to_insert);
+                }
+                KeyCode::Backspace => self.delete_char(),
+                KeyCode::Delete => self.delete_right(),
+                KeyCode::Tab | KeyCode::BackTab => {
+                    self.input_mode = InputMode::Normal;
+                    self.move_focus(if key.code == KeyCode::Tab { 1 } else { -1 }, true);
+                }
+                KeyCode::Left => self.move_cursor_left(),
+                KeyCode::Right => self.move_cursor_right(),
+                KeyCode::Esc => self.input_mode = InputMode::Normal,
+                _ => {}
+            },
+            InputMode::Editing => {}
+        }
+    }
+
+    /// Quits on SIGINT or SIGTERM. Indexing is cancelled and saves the images it embedded
+    /// before the interface quits, a second signal quits without waiting for it.
+    fn shut_down(&mut self) {
+        if self.stopping.is_some() {
+            self.exit = true;
+            return;
+        }
+        let indexing = self
+            .jobs
+            .running()
+            .iter()
+            .find(|job| job.controllable)
+            .cloned();
+        match indexing {
+            Some(job) => {
+                job.cancel();
+                self.stopping = Some(job);
+                self.loading = Some((Instant::now(), "Saving the index before quitting".into()));
+            }
+            None => self.exit = true,
+        }
+    }
+
+    fn exit(&mut self) {
+        if self.pick_mode && self.current_element == CurrentElement::Images {
+            self.picked = self
+                .search_results
+                .get(self.selected_result)
+                .map(|result| result.file_path.clone());
+        }
+        self.exit = true;
+    }
+
+    fn move_cursor_left(&mut self) {
+        let cursor_moved_left = self.char_index.saturating_sub(1);
+        self.char_index = self.clamp_cursor(cursor_moved_left);
+    }
+
+    fn move_cursor_right(&mut self) {
+        let cursor_moved_right = self.char_index.saturating_add(1);
+        self.char_index = self.clamp_cursor(cursor_moved_right);
+    }
+
+    fn clamp_cursor(&self, new_cursor_pos: usize) -> usize {
+        new_cursor_pos.clamp(0, self.search.chars().count())
+    }
+
+    fn enter_char(&mut self, new_char: char) {
+        let index = self.byte_index();
+        self.search.insert(index, new_char);
+        self.move_cursor_right();
+    }
+
+    fn byte_index(&self) -> usize {
+        self.search
+            .char_indices()
+            .map(|(i, _)| i)
+            .nth(self.char_index)
+            .unwrap_or(self.search.len())
+    }
+
+    /// The button opening the mode selector. It is disabled while a search is running,
+    /// since the search already picked its mode.
+    fn mode_button(&self) -> Button<'static> {
+        let state = if self.searching.is_some() {
+            ButtonState::Disabled
+        } else if self.current_element == CurrentElement::Filter {
+            if self.button_pressed {
+                ButtonState::Active
+            } else {
+                ButtonState::Selected
+            }
+        } else {
+            ButtonState::Normal
+        };
+        Button::new("Choose Mode")
+            .icon("⚙")
+            .min_width(16)
+            .state(state)
+            .theme(self.theme())
+    }
+
+    /// A button of the action bar. Search is disabled while a search is running, like the
+    /// mode button.
+    fn toolbar_button(&self, button: ToolbarButton) -> Button<'static> {
+        let state = if button == ToolbarButton::Search && self.searching.is_some() {
+            ButtonState::Disabled
+        } else if self.current_element == CurrentElement::Toolbar(button) {
+            ButtonState::Selected
+        } else {
+            ButtonState::Normal
+        };
+        Button::new(button.label())
+            .padding((0, 0, 0, 1))
+            .min_width(10)
+            .state(state)
+            .theme(self.theme())
+    }
+
+    fn press_toolbar_button(&mut self, button: ToolbarButton) {
+        if self.toolbar_button(button).is_disabled() {
+            return;
+        }
+        match button {
+            ToolbarButton::Search => self.start_search(),
+            // where the images come from: the library, remote items and what is indexed
+            ToolbarButton::Sources => self.open_stats(),
+            // the filters that aren't typed into the query can be toggled from the palette
+            ToolbarButton::Filters => {
+                let mut palette = CommandPalette::new(self.plugin_actions.clone());
+                palette.query = "toggle".into();
+                self.modals.open(Modal::Palette(palette));
+            }
+            ToolbarButton::Settings => self.perform(Action::OpenSettings),
+        }
+    }
+
+    fn open_mode_selector(&mut self) {
+        if self.mode_button().is_disabled() {
+            return;
+        }
+        self.button_pressed = true;
+        self.current_element = CurrentElement::Filter;
+        self.modals.open(Modal::ModeSelector);
+        self.modesel_list.select_first();
+    }
+
+    fn handle_mode_selector_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.close_modal(),
+            KeyCode::Enter => {
+                self.toggle_status();
+                self.close_modal();
+            }
+            KeyCode::Char(' ') => self.toggle_status(),
+            KeyCode::Down => self.select_next(),
+            KeyCode::Up => self.select_previous(),
+            _ => {}
+        }
+    }
+
+    /// Deletes the files once the confirmation is answered with `y`, any other key declines
+    fn handle_confirm_delete_key(&mut self, key: KeyCode) {
+        let Some(Modal::ConfirmDelete(paths)) = self.modals.close() else {
+            return;
+        };
+        if matches!(key, KeyCode::Char('y') | KeyCode::Char('Y')) {
+            self.delete_files(paths);
+            // a declined confirmation goes back to the clean-up
+            self.cleanup = None;
+        }
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
+    }
+
+    /// Closes the popup on top, the one below gets the keys again
+    fn close_modal(&mut self) {
+        self.modals.close();
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
+    }
+
+    /// Handles left clicks on the mode button and the search bar
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if let Some(sketch) = &mut self.sketch {
+            let position = Position::new(mouse.column, mouse.row);
+            match mouse.kind {
+                MouseEventKind::Down(MouseButton::Left) => sketch.mouse(position, false),
+                MouseEventKind::Drag(MouseButton::Left) => sketch.mouse(position, true),
+                MouseEventKind::Up(MouseButton::Left) => sketch.release(),
+                _ => {}
+            }
+            return;
+        }
+        if mouse.kind != MouseEventKind::Down(MouseButton::Left) || self.modals.is_open() {
+            return;
+        }
+        let position = Position::new(mouse.column, mouse.row);
+
+        let clicked = ToolbarButton::ALL
+            .into_iter()
+            .zip(self.toolbar_areas.clone())
+            .find(|(button, area)| self.toolbar_button(*button).contains(*area, position));
+        if let Some((button, _)) = clicked {
+            self.current_element = CurrentElement::Toolbar(button);
+            self.press_toolbar_button(button);
+        } else if self.mode_button().contains(self.mode_area, position) {
+            self.current_element = CurrentElement::Filter;
+            self.open_mode_selector();
+        } else if self.search_area.contains(position) {
+            self.current_element = CurrentElement::Search;
+            self.input_mode = InputMode::Editing;
+        }
+    }
+
+    /// Takes back the graphics of a result from the encoder threads. Encodings of results
+    /// that were replaced or of images that were dropped since are thrown away.
+    fn finish_encoding(&mut self, encoded: Encoded) {
+        if let Some(timings) = &mut self.timings {
+            timings.encoding += encoded.took;
+        }
+        let Some(result) = self
+            .search_results
+            .iter_mut()
+            .find(|result| result.file_path == encoded.path && result.encoding == Some(encoded.id))
+        else {
+            return;
+        };
+        result.encoding = None;
+        match encoded.graphics {
+            Ok(image) => {
+                result.image = Some(image);
+                result.last_area = Some(encoded.area);
+                if let Some(reloaded) = encoded.reloaded {
+                    result.graphics_bytes = reloaded.bytes;
+                    result.reduced = reloaded.reduced;
+                }
+            }
+            Err(e) => result.failed = Some(e),
+        }
+    }
+
+    /// Removes the cached area from each image
+    fn invalidate_image_cache(&mut self) {
+        for result in &mut self.search_results {
+            result.last_area = None;
+        }
+    }
+
+    /// Moves the focus `step` elements along the focus ring, skipping the results while there
+    /// are none. `Tab` wraps around at the ends, `Left` and `Right` stay in the top bar.
+    fn move_focus(&mut self, step: isize, wrap: bool) {
+        let ring: Vec<CurrentElement> = CurrentElement::RING
+            .into_iter()
+            .filter(|element| {
+                *element != CurrentElement::Images || (wrap && !self.search_results.is_empty())
+            })
+            .collect();
+        let index = ring
+            .iter()
+            .position(|element| *element == self.current_element)
+            .unwrap_or_default() as isize;
+        let next = if wrap {
+            (index + step).rem_euclid(ring.len() as isize)
+        } else {
+            (index + step).clamp(0, ring.len() as isize - 1)
+        };
+        match ring[next as usize] {
+            CurrentElement::Images => self.focus_images(),
+            element => self.current_element = element,
+        }
+    }
+
+    /// Moves the focus to the result grid if there is anything to select
+    fn focus_images(&mut self) {
+        if !self.search_results.is_empty() {
+            self.current_element = CurrentElement::Images;
+            self.selected_result = self
+                .selected_result
+                .min(self.displayed_results().saturating_sub(1));
+        }
+    }
+
+    fn displayed_results(&self) -> usize {
+        let shown = if self.config.timeline {
+            self.search_results.len()
+        } else {
+            self.search_results.len().min(DISPLAYED_RESULTS)
+        };
+        self.result_areas.len().min(shown)
+    }
+
+    /// Longest side in pixels the result cells show their images at, so the searches read
+    /// no more of the images than that, see `memory::load`
+    fn display_side(&self) -> u32 {
+        if self.results_block.is_empty() {
+            return memory::MAX_SIDE;
+        }
+        self.config
+            .layout
+            .areas(
+                self.results_block,
+                self.config.result_count.min(DISPLAYED_RESULTS),
+                &[],
+                self.config.grid_columns,
+                0,
+            )
+            .into_iter()
+            .map(|area| pixel_side(area, &self.picker))
+            .max()
+            .unwrap_or(memory::MAX_SIDE)
+    }
+
+    /// Accent colors of the interface
+    fn theme(&self) -> Theme {
+        self.config.theme.theme()
+    }
+
+    fn handle_settings_key(&mut self, key: KeyCode) {
+        let Some(Modal::Settings(settings)) = self.modals.top_mut() else {
+            return;
+        };
+
+        if let Some(text) = &mut settings.editing {
+            match key {
+                KeyCode::Enter => {
+                    let dir = PathBuf::from(text.trim());
+                    settings.editing = None;
+                    self.change_library(dir);
+                }
+                KeyCode::Esc => settings.editing = None,
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Char(c) => text.push(c),
+                _ => {}
+            }
+            return;
+        }
+
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('s') => self.close_settings(),
+            KeyCode::Down => settings.state.select_next(),
+            KeyCode::Up => settings.state.select_previous(),
+            KeyCode::Left => self.adjust_setting(-1),
+            KeyCode::Right => self.adjust_setting(1),
+            KeyCode::Enter if settings.selected() == Setting::LibraryDir => {
+                settings.editing = Some(self.config.library_dir().display().to_string());
+            }
+            KeyCode::Enter => self.adjust_setting(1),
+            _ => {}
+        }
+    }
+
+    fn handle_palette_key(&mut self, key: KeyCode) {
+        let Some(Modal::Palette(palette)) = self.modals.top_mut() else {
+            return;
+        };
+        match key {
+            KeyCode::Esc => self.close_modal(),
+            KeyCode::Down => palette.state.select_next(),
+            KeyCode::Up => palette.state.select_previous(),
+            KeyCode::Backspace => palette.pop(),
+            KeyCode::Char(c) => palette.push(c),
+            KeyCode::Enter => {
+                let entry = palette.selected();
+                self.close_modal();
+                match entry {
+                    Some(Entry::Action(action)) => self.perform(action),
+                    Some(Entry::Plugin(action)) => self.run_plugin_action(&action),
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs the action of a plugin on the selected result
+    fn run_plugin_action(&mut self, action: &PluginAction) {
+        let Some(result) = self.search_results.get(self.selected_result) else {
+            self.notifications.add(Message::new(
+                format!("{} needs a selected result", action.name),
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+            return;
+        };
+        let message = match self.plugins.run_action(action, &result.file_path) {
+            Ok(Some(text)) => Message::new(text, MessageSeverity::Info, Duration::from_secs(3)),
+            Ok(None) => return,
+            Err(e) => Message::new(e, MessageSeverity::Error, Duration::from_secs(5)),
+        };
+        self.notifications.add(message);
+    }
+
+    /// Runs an action picked by key or from the command palette
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::EditSearch => {
+                self.current_element = CurrentElement::Search;
+                self.input_mode = InputMode::Editing;
+            }
+            Action::RefineSearch => self.refine_search(),
+            Action::ClearSearch => self.clear_search(),
+            Action::ChangeMode => {
+                self.current_element = CurrentElement::Filter;
+                self.open_mode_selector();
+            }
+            Action::CycleModel => self.cycle_model(),
+            Action::CycleLayout => self.cycle_layout(),
+            Action::ToggleTimeline => self.toggle_timeline(),
+            Action::ToggleSafeSearch => self.toggle_safe_search(),
+            Action::ToggleHideBlurry => self.toggle_hide_blurry(),
+            Action::ToggleTranslation => self.toggle_translation(),
+            Action::UndoDelete => self.undo_delete(),
+            Action::OpenMap => self.open_map(),
+            Action::DrawSketch => {
+                self.sketch = Some(SketchCanvas::default());
+                self.clear_terminal = true;
+            }
+            Action::OpenPinned => self.open_pinned(),
+            Action::ExportPinned => self.export_pinned(),
+            Action::ClearPinned => {
+                self.pins.clear();
+                self.save_pins();
+            }
+            Action::SetWallpaper => self.set_wallpaper(),
+            Action::CopyMarkdownLink => self.copy_markdown_link(),
+            Action::CopyCollage => self.copy_collage(),
+            Action::RevealPrivate => self.toggle_reveal(),
+            Action::ServeResult => self.serve_result(),
+            Action::VoiceSearch => self.voice_search(),
+            Action::OpenSettings => self
+                .modals
+                .open(Modal::Settings(Box::new(SettingsPopup::new(&self.config)))),
+            Action::CycleTheme => {
+                Setting::Theme.adjust(&mut self.config, 1);
+                self.save_config();
+            }
+            Action::RescanLibrary => self.rescan_library(),
+            Action::ShowStats => self.open_stats(),
+            Action::ShowJobs => self.modals.open(Modal::Jobs(JobsView::default())),
+            Action::CleanUpLibrary => self.find_cleanup(),
+            Action::Redraw => self.clear_terminal = true,
+            Action::TogglePerformanceHud => self.hud_open = !self.hud_open,
+            Action::Quit => self.exit(),
+        }
+    }
+
+    fn handle_sketch_key(&mut self, key: KeyCode) {
+        let Some(sketch) = &mut self.sketch else {
+            return;
+        };
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') => self.close_sketch(),
+            KeyCode::Enter => self.search_sketch(),
+            KeyCode::Char(' ') => sketch.toggle_pen(),
+            KeyCode::Char('c') | KeyCode::Backspace => sketch.clear(),
+            KeyCode::Left | KeyCode::Char('h') => sketch.move_cursor(-1, 0),
+            KeyCode::Right | KeyCode::Char('l') => sketch.move_cursor(1, 0),
+            KeyCode::Up | KeyCode::Char('k') => sketch.move_cursor(0, -1),
+            KeyCode::Down | KeyCode::Char('j') => sketch.move_cursor(0, 1),
+            _ => {}
+        }
+    }
+
+    fn close_sketch(&mut self) {
+        self.sketch = None;
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
+    }
+
+    /// Saves the drawn sketch and searches for images with the same composition
+    fn search_sketch(&mut self) {
+        let Some(sketch) = &self.sketch else {
+            return;
+        };
+        if sketch.is_empty() {
+            self.notifications.add(Message::new(
+                "Draw something first",
+                MessageSeverity::Warning,
+                Duration::from_secs(2),
+            ));
+            return;
+        }
+
+        let path = paths::sketch_file();
+        if let Err(e) = sketch.save(&path) {
+            self.notifications.add(Message::new(
+                format!("Failed to save the sketch: {}", e),
+                MessageSeverity::Error,
+                Duration::from_secs(3),
+            ));
+            return;
+        }
+        self.close_sketch();
+        self.set_mode(SearchEnum::Sketch);
+        self.search = path.display().to_string();
+        self.reset_cursor();
+        let _ = self.events.sender().send(AppEvent::StartSearch);
+    }
+
+    /// Steps the highlighted setting and applies it right away
+    fn adjust_setting(&mut self, step: isize) {
+        let Some(Modal::Settings(settings)) = self.modals.top() else {
+            return;
+        };
+        let setting = settings.selected();
+        if !setting.adjust(&mut self.config, step) {
+            return;
+        }
+
+        match setting {
+            Setting::Protocol => self.apply_protocol(),
+            Setting::DefaultMode => self.set_mode(self.config.default_mode),
+            // the result count is applied when the settings are closed,
+            // so stepping through values doesn't start a search each time
+            Setting::ResultCount
+            | Setting::Theme
+            | Setting::SearchBarWidth
+            | Setting::LibraryDir => {}
+        }
+        self.save_config();
+    }
+
+    /// Widens (`step` > 0) or narrows the search bar, from the keyboard with `>` and `<`
+    fn adjust_search_bar(&mut self, step: isize) {
+        Setting::SearchBarWidth.adjust(&mut self.config, step);
+        self.save_config();
+    }
+
+    /// Remembers the results that could not be read for the stats
+    fn record_unreadable(&mut self) {
+        for result in &self.search_results {
+            if let Some(reason) = &result.failed {
+                self.unreadable
+                    .insert(result.file_path.clone(), reason.clone());
+            }
+        }
+    }
+
+    fn open_stats(&mut self) {
+        self.record_unreadable();
+        // files that failed in earlier sessions are skipped by indexing without a word,
+        // the ones that left the library since don't matter
+        let library: HashSet<&String> = self.image_paths.iter().collect();
+        for failure in self.catalog.failures() {
+            if library.contains(&failure.path) {
+                self.unreadable
+                    .entry(failure.path)
+                    .or_insert(failure.reason);
+            }
+        }
+        let slot = &self.models[self.active_model];
+        let remote = self
+            .image_paths
+            .iter()
+            .filter(|path| remote::is_remote(path))
+            .count();
+        self.modals.open(Modal::Stats(StatsView {
+            locale: self.locale,
+            images: self.image_paths.len() - remote,
+            remote,
+            indexed: read(&slot.database).get(&self.image_paths).len(),
+            pinned: self.pins.paths().len(),
+            unreadable: self
+                .unreadable
+                .iter()
+                .map(|(path, reason)| (path.clone(), reason.clone()))
+                .collect(),
+            state: Default::default(),
+        }));
+    }
+
+    fn handle_stats_key(&mut self, key: KeyCode) {
+        let Some(Modal::Stats(stats)) = self.modals.top_mut() else {
+            return;
+        };
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') => self.close_modal(),
+            KeyCode::Down => stats.state.select_next(),
+            KeyCode::Up => stats.state.select_previous(),
+            KeyCode::Char('x') => self.prune_unreadable(),
+            KeyCode::Char('r') => self.retry_unreadable(),
+            _ => {}
+        }
+    }
+
+    /// Forgets which files failed to embed and indexes the library again, so they are tried
+    /// once more
+    fn retry_unreadable(&mut self) {
+        let failures = self.catalog.failures();
+        if failures.is_empty() {
+            return;
+        }
+        if let Err(e) = self.catalog.forget_failures(None) {
+            self.notifications.add(Message::new(
+                format!("Failed to retry the unreadable files: {}", e),
+                MessageSeverity::Error,
+                Duration::from_secs(3),
+            ));
+            return;
+        }
+        for failure in &failures {
+            self.unreadable.remove(&failure.path);
+        }
+        let text = if self.loading.is_none() {
+            self.continue_loading(0);
+            format!("Trying {} unreadable files again", failures.len())
+        } else {
+            format!(
+                "Trying {} unreadable files again the next time the library is indexed",
+                failures.len()
+            )
+        };
+        self.notifications.add(Message::new(
+            text,
+            MessageSeverity::Info,
+            Duration::from_secs(3),
+        ));
+        self.open_stats();
+    }
+
+    fn handle_jobs_key(&mut self, key: KeyCode) {
+        let Some(Modal::Jobs(jobs_view)) = self.modals.top_mut() else {
+            return;
+        };
+        let jobs = self.jobs.running();
+        let job = jobs.get(jobs_view.selected);
+        match key {
+            KeyCode::Esc | KeyCode::Char('q') => self.close_modal(),
+            KeyCode::Down | KeyCode::Char('j') => {
+                jobs_view.selected = (jobs_view.selected + 1).min(jobs.len().saturating_sub(1))
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                jobs_view.selected = jobs_view.selected.saturating_sub(1)
+            }
+            KeyCode::Char(' ') | KeyCode::Char('p') => {
+                let Some(job) = job.filter(|job| job.controllable) else {
+                    return;
+                };
+                job.toggle_pause();
+                // the job is waiting now, so it won't report anything until it is resumed
+                if job.state() == JobState::Paused
+                    && let Some((_, status)) = &mut self.loading
+                {
+                    *status = format!("{} is paused", job.name);
+                }
+            }
+            KeyCode::Char('x') => {
+                if let Some(job) = job.filter(|job| job.controllable) {
+                    job.cancel();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Removes the files that could not be read from the index of every model and from
+    /// the results. The files themselves are left alone.
+    fn prune_unreadable(&mut self) {
+        if self.unreadable.is_empty() || self.refuse_read_only() {
+            return;
+        }
+        let paths: Vec<String> = std::mem::take(&mut self.unreadable).into_keys().collect();
+        for slot in &self.models {
+            let _ = write(&slot.database).delete(&paths);
+        }
+        self.forget_scores();
+        self.save_index();
+        self.map = None;
+        self.image_paths.retain(|path| !paths.contains(path));
+        self.candidates.retain(|path| !paths.contains(path));
+        self.search_results
+            .retain(|result| !paths.contains(&result.file_path));
+        self.selected_result = self
+            .selected_result
+            .min(self.search_results.len().saturating_sub(1));
+        self.notifications.add(Message::new(
+            format!("Removed {} unreadable files from the index", paths.len()),
+            MessageSeverity::Info,
+            Duration::from_secs(3),
+        ));
+        self.open_stats();
+    }
+
+    /// Closes the settings and searches again if the number of results changed
+    fn close_settings(&mut self) {
+        let Some(Modal::Settings(settings)) = self.modals.close() else {
+            return;
+        };
+        if settings.initial.result_count != self.config.result_count && !self.search.is_empty() {
+            let _ = self.events.sender().send(AppEvent::StartSearch);
+        }
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
+    }
+
+    /// Switches the picker to the configured protocol and draws the results with it
+    fn apply_protocol(&mut self) {
+        let protocol = self
+            .config
+            .protocol
+            .protocol_type()
+            .unwrap_or(self.detected_protocol);
+        self.picker.set_protocol_type(protocol);
+
+        // the results are encoded for the old protocol, so they are decoded again
+        let mut results: Vec<&SearchResult> = self.search_results.iter().collect();
+        results.sort_by_key(|result| result.rank);
+        let ranked: Vec<(String, f32)> = results
+            .iter()
+            .map(|result| (result.file_path.clone(), result.confidence as f32))
+            .collect();
+        let bursts: HashMap<String, Vec<(String, f32)>> = results
+            .into_iter()
+            .map(|result| (result.file_path.clone(), result.burst.clone()))
+            .collect();
+
+        let mut reloaded = self.load_results(&ranked);
+        for result in &mut reloaded {
+            result.burst = bursts.get(&result.file_path).cloned().unwrap_or_default();
+        }
+        self.search_results = reloaded;
+        self.arrange_results();
+        self.selected_result = self
+            .selected_result
+            .min(self.search_results.len().saturating_sub(1));
+        self.clear_terminal = true;
+    }
+
+    /// Sets a new library directory and indexes it
+    fn change_library(&mut self, dir: PathBuf) {
+        if dir == self.config.library_dir() {
+            return;
+        }
+        if !dir.is_dir() {
+            self.notifications.add(Message::new(
+                format!("{} is not a directory", dir.display()),
+                MessageSeverity::Error,
+                Duration::from_secs(5),
+            ));
+            return;
+        }
+
+        self.config.library_dir = Some(dir);
+        self.save_config();
+        // an indexing run that is still going picks the new directory up when it finishes
+        if self.loading.is_none() {
+            self.continue_loading(0);
+        }
+    }
+
+    /// Draws the pinned images as a row of file names below the results
+    fn draw_tray(&self, frame: &mut Frame, area: Rect) {
+        if area.is_empty() {
+            return;
+        }
+        let names: Vec<String> = self
+            .pins
+            .paths()
+            .iter()
+            .map(|path| {
+                Path::new(path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.clone())
+            })
+            .collect();
+        let block = Block::bordered()
+            .title(format!("Pinned ({})", names.len()))
+            .title_bottom("p to unpin a result, o to open all, Ctrl+P to copy them")
+            .border_type(BorderType::Rounded)
+            .fg(self.theme().background);
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(names.join("  ")).block(block), area);
+    }
+
+    /// Pins the selected result to the tray or unpins it
+    fn toggle_pin(&mut self) {
+        let Some(result) = self.search_results.get(self.selected_result) else {
+            return;
+        };
+        let path = result.file_path.clone();
+        self.pins.toggle(&path);
+        self.save_pins();
+        // the tray appearing or disappearing moves the results
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
+    }
+
+    fn save_pins(&mut self) {
+        if let Err(e) = self.pins.save() {
+            self.notifications.add(Message::new(
+                format!("Failed to save the pinned images: {}", e),
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+        }
+    }
+
+    fn open_pinned(&mut self) {
+        if self.pins.paths().is_empty() {
+            return;
+        }
+        if let Err(e) = pins::open(self.pins.paths()) {
+            self.notifications.add(Message::new(
+                format!("Failed to open the pinned images: {}", e),
+                MessageSeverity::Error,
+                Duration::from_secs(3),
+            ));
+        }
+    }
+
+    /// Opens a remote item in the browser, its full image only exists there
+    fn open_remote(&mut self, url: String) {
+        if let Err(e) = pins::open(&[url]) {
+            self.notifications.add(Message::new(
+                format!("Failed to open the browser: {}", e),
+                MessageSeverity::Error,
+                Duration::from_secs(3),
+            ));
+        }
+    }
+
+    fn set_wallpaper(&mut self) {
+        let Some(result) = self.search_results.get(self.selected_result) else {
+            return;
+        };
+        let (text, severity) = match wallpaper::set(Path::new(&result.file_path)) {
+            Ok(()) => ("Set as the wallpaper".to_string(), MessageSeverity::Info),
+            Err(e) => (
+                format!("Failed to set the wallpaper: {}", e),
+                MessageSeverity::Error,
+            ),
+        };
+        self.notifications
+            .add(Message::new(text, severity, Duration::from_secs(3)));
+    }
+
+    fn copy_markdown_link(&mut self) {
+        let Some(result) = self.search_results.get(self.selected_result) else {
+            return;
+        };
+        let link = links::markdown_image(&result.file_path, self.config.vault_root.as_deref());
+        let (text, severity) = match clipboard::copy(&link) {
+            Ok(()) => (format!("Copied {}", link), MessageSeverity::Info),
+            Err(e) => (
+                format!("Failed to copy the link: {}", e),
+                MessageSeverity::Error,
+            ),
+        };
+        self.notifications
+            .add(Message::new(text, severity, Duration::from_secs(3)));
+    }
+
+    /// Stitches the best results into a collage with their rank and confidence on a background
+    /// thread, saves it to `COLLAGE_FILE` and copies it
+    fn copy_collage(&mut self) {
+        let mut results: Vec<&SearchResult> = self.search_results.iter().collect();
+        if results.is_empty() {
+            return;
+        }
+        results.sort_by_key(|result| result.rank);
+        let results: Vec<(String, f32)> = results
+            .into_iter()
+            .take(DISPLAYED_RESULTS)
+            .map(|result| (result.file_path.clone(), result.confidence as f32))
+            .collect();
+        let sender = self.events.sender();
+        let job = self.jobs.start("Making a collage of the results", false);
+        let locale = self.locale;
+        std::thread::spawn(move || {
+            let _running = job;
+            let made = collage::make(&results, locale).and_then(|png| {
+                let file = Path::new(COLLAGE_FILE);
+                fs::write(file, png).map_err(|e| e.to_string())?;
+                Ok(match clipboard::copy_image(file) {
+                    Ok(()) => format!(
+                        "Copied a collage of {} results, also saved to {}",
+                        results.len(),
+                        COLLAGE_FILE
+                    ),
+                    Err(e) => format!("Saved the collage to {}, {}", COLLAGE_FILE, e),
+                })
+            });
+            let _ = sender.send(AppEvent::CollageMade(made));
+        });
+        self.notifications.add(Message::new(
+            "Making a collage of the results...",
+            MessageSeverity::Info,
+            Duration::from_secs(3),
+        ));
+    }
+
+    /// Starts recording a spoken query with the voice input command
+    fn voice_search(&mut self) {
+        let Some(command) = &self.mic_command else {
+            self.notifications.add(Message::new(
+                "Set mic_command in the config or pass --mic-command to search by voice",
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+            return;
+        };
+        if self.listening {
+            return;
+        }
+        self.listening = true;
+        voice::listen(command, self.events.sender());
+        self.notifications.add(Message::new(
+            "Listening...",
+            MessageSeverity::Info,
+            Duration::from_secs(3),
+        ));
+    }
+
+    /// Puts the spoken query into the search bar and searches for it
+    fn finish_voice_search(&mut self, spoken: Result<String, String>) {
+        self.listening = false;
+        match spoken {
+            Ok(text) if !text.is_empty() => {
+                self.char_index = text.chars().count();
+                self.search = text;
+                self.current_element = CurrentElement::Search;
+                self.start_search();
+            }
+            Ok(_) => self.notifications.add(Message::new(
+                "Heard nothing, press a to try again",
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            )),
+            Err(e) => self.notifications.add(Message::new(
+                format!("Voice input failed: {}", e),
+                MessageSeverity::Error,
+                Duration::from_secs(5),
+            )),
+        }
+    }
+
+    /// Looks for duplicates, bursts and blurry photos on a background thread, measuring the
+    /// sharpness of new images takes a while
+    fn find_cleanup(&mut self) {
+        if self.finding_cleanup || self.refuse_read_only() {
+            return;
+        }
+        self.finding_cleanup = true;
+        let catalog = Arc::clone(&self.catalog);
+        let database = Arc::clone(&self.models[self.active_model].database);
+        let image_paths = self.image_paths.clone();
+        let burst_similarity = self.config.burst_similarity;
+        let blur_threshold = self.config.blur_threshold;
+        let sender = self.events.sender();
+        let job = self
+            .jobs
+            .start("Looking for duplicates, bursts and blurry photos", false);
+        std::thread::spawn(move || {
+            let _running = job;
+            let groups = cleanup::find_groups(
+                &catalog,
+                &**read(&database),
+                &image_paths,
+                burst_similarity,
+                blur_threshold,
+            );
+            let _ = sender.send(AppEvent::CleanupFound(groups));
+        });
+        self.notifications.add(Message::new(
+            "Looking for duplicates, bursts and blurry photos...",
+            MessageSeverity::Info,
+            Duration::from_secs(3),
+        ));
+    }
+
+    fn start_cleanup(&mut self, groups: Vec<Group>) {
+        self.finding_cleanup = false;
+        if groups.is_empty() {
+            self.notifications.add(Message::new(
+                "Nothing to clean up, the library has no duplicates, bursts or blurry photos",
+                MessageSeverity::Info,
+                Duration::from_secs(3),
+            ));
+            return;
+        }
+        self.notifications.add(Message::new(
+            format!("Found {} groups to clean up", groups.len()),
+            MessageSeverity::Info,
+            Duration::from_secs(3),
+        ));
+        self.cleanup = Some(CleanupSession::new(groups));
+        self.show_cleanup_group();
+    }
+
+    /// Shows the images of the current clean-up group as the results
+    fn show_cleanup_group(&mut self) {
+        let Some(cleanup) = &self.cleanup else {
+            return;
+        };
+        let ranked: Vec<(String, f32)> = cleanup
+            .group()
+            .paths
+            .iter()
+            .map(|path| (path.clone(), 1.0))
+            .collect();
+        self.search_results = self.load_results(&ranked);
+        self.arrange_results();
+        self.selected_result = 0;
+        self.current_element = CurrentElement::Images;
+        self.preview_open = false;
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
+    }
+
+    fn handle_cleanup_key(&mut self, key: KeyCode) {
+        let Some(cleanup) = &mut self.cleanup else {
+            return;
+        };
+        if self.preview_open {
+            if matches!(key, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q')) {
+                self.preview_open = false;
+                self.clear_terminal = true;
+                self.invalidate_image_cache();
+            }
+            return;
+        }
+        match key {
+            KeyCode::Left | KeyCode::Char('h') => self.move_selection(-1, 0),
+            KeyCode::Right | KeyCode::Char('l') => self.move_selection(1, 0),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(0, -1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(0, 1),
+            KeyCode::Enter if self.selected_result < self.search_results.len() => {
+                self.preview_open = true;
+                self.clear_terminal = true;
+            }
+            KeyCode::Char(' ') => {
+                if let Some(result) = self.search_results.get(self.selected_result) {
+                    cleanup.toggle(&result.file_path);
+                }
+            }
+            KeyCode::Char(step @ ('n' | 'b')) => {
+                if cleanup.step(if step == 'n' { 1 } else { -1 }) {
+                    self.show_cleanup_group();
+                } else {
+                    let text = if step == 'n' {
+                        "This is the last group, press c to finish"
+                    } else {
+                        "This is the first group"
+                    };
+                    self.notifications.add(Message::new(
+                        text,
+                        MessageSeverity::Info,
+                        Duration::from_secs(3),
+                    ));
+                }
+            }
+            KeyCode::Char('u') => {
+                let group = cleanup.current;
+                if !cleanup.undo() {
+                    self.notifications.add(Message::new(
+                        "Nothing to undo",
+                        MessageSeverity::Info,
+                        Duration::from_secs(2),
+                    ));
+                } else if cleanup.current != group {
+                    self.show_cleanup_group();
+                }
+            }
+            KeyCode::Char('c') => {
+                let marked = cleanup.marked();
+                if marked.is_empty() {
+                    self.cleanup = None;
+                    self.notifications.add(Message::new(
+                        "Nothing marked for deletion, the clean-up is done",
+                        MessageSeverity::Info,
+                        Duration::from_secs(3),
+                    ));
+                } else {
+                    self.modals.open(Modal::ConfirmDelete(marked));
+                    self.clear_terminal = true;
+                }
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.cleanup = None;
+                self.search_results.clear();
+                self.clear_terminal = true;
+                self.invalidate_image_cache();
+            }
+            _ => {}
+        }
+    }
+
+    /// Serves the selected result on localhost and copies its address, to open it in the
+    /// browser of the machine an SSH session comes from
+    fn serve_result(&mut self) {
+        let Some(result) = self.search_results.get(self.selected_result) else {
+            return;
+        };
+        if result.pixelated() {
+            self.notifications.add(Message::new(
+                "Reveal the private result with b before serving it",
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+            return;
+        }
+        let path = result.file_path.clone();
+        if self.server.is_none() {
+            match Server::start(self.config.serve_port) {
+                Ok(server) => self.server = Some(server),
+                Err(e) => {
+                    self.notifications.add(Message::new(
+                        format!("Failed to serve on port {}: {}", self.config.serve_port, e),
+                        MessageSeverity::Error,
+                        Duration::from_secs(3),
+                    ));
+                    return;
+                }
+            }
+        }
+        let Some(server) = &self.server else {
+            return;
+        };
+        let url = server.serve(&path);
+        let copied = if clipboard::copy(&url).is_ok() {
+            ", copied"
+        } else {
+            ""
+        };
+        self.notifications.add(Message::new(
+            format!("Serving at {}{}", url, copied),
+            MessageSeverity::Info,
+            Duration::from_secs(5),
+        ));
+    }
+
+    /// Shows the selected private result as it is, or pixelates it again
+    fn toggle_reveal(&mut self) {
+        let Some(result) = self.search_results.get_mut(self.selected_result) else {
+            return;
+        };
+        if !result.private {
+            self.notifications.add(Message::new(
+                "The result is not in a private directory",
+                MessageSeverity::Info,
+                Duration::from_secs(3),
+            ));
+            return;
+        }
+        result.revealed = !result.revealed;
+        // loaded again by `SearchResult::show`, pixelated or not
+        result.image = None;
+        result.encoding = None;
+        result.last_area = None;
+        self.clear_terminal = true;
+    }
+
+    /// Copies the pinned images into `pinned/` in the working directory, like `curate` does
+    fn export_pinned(&mut self) {
+        // pinned by hand, they have no score
+        let photos = engine::hits(
+            self.pins
+                .paths()
+                .iter()
+                .map(|path| (path.clone(), 0.0))
+                .collect(),
+            &self.catalog,
+        );
+        let output = Path::new(PINNED_DIR);
+        let (text, severity) = match curate::export(&photos, output, false) {
+            Ok(copies) => (
+                format!("Copied {} images to {}", copies.len(), output.display()),
+                MessageSeverity::Info,
+            ),
+            Err(e) => (
+                format!("Failed to copy the pinned images: {}", e),
+                MessageSeverity::Error,
+            ),
+        };
+        self.notifications
+            .add(Message::new(text, severity, Duration::from_secs(3)));
+    }
+
+    /// Indexes the library again to pick up images added since the start
+    fn rescan_library(&mut self) {
+        if self.loading.is_some() {
+            self.notifications.add(Message::new(
+                "The library is being indexed already",
+                MessageSeverity::Info,
+                Duration::from_secs(2),
+            ));
+            return;
+        }
+        self.continue_loading(0);
+    }
+
+    /// Loads or indexes the models one after another, starting with `slot`.
+    /// Models are loaded one at a time, so the loading status always shows a single task.
+    fn continue_loading(&mut self, slot: usize) {
+        match self.models.get(slot) {
+            Some(next) if next.model.is_none() => {
+                self.loading = Some((Instant::now(), format!("Loading the model {}", next.name)));
+                let job = self
+                    .jobs
+                    .start(format!("Loading the model {}", next.name), false);
+                loader::load_model(
+                    slot,
+                    next.name.clone(),
+                    next.path.clone(),
+                    job,
+                    self.events.sender(),
+                );
+            }
+            Some(_) => self.start_indexing(slot),
+            None => {
+                self.loading = None;
+                self.notifications.add(Message::new(
+                    format!(
+                        "Indexed {} images in {}",
+                        self.image_paths.len(),
+                        self.config.library_dir().display()
+                    ),
+                    MessageSeverity::Info,
+                    Duration::from_secs(3),
+                ));
+            }
+        }
+    }
+
+    /// Indexes the library directory with the model of a slot on a background thread
+    fn start_indexing(&mut self, slot: usize) {
+        let Some(model) = &self.models[slot].model else {
+            return;
+        };
+        self.loading = Some((Instant::now(), "Indexing the library".into()));
+        IndexJob {
+            slot,
+            job: self.jobs.start(
+                format!("Indexing the library with {}", self.models[slot].name),
+                true,
+            ),
+            name: self.models[slot].name.clone(),
+            model: Arc::clone(model),
+            database: Arc::clone(&self.models[slot].database),
+            catalog: Arc::clone(&self.catalog),
+            config: self.config.clone(),
+            read_only: self.read_only,
+        }
+        .spawn(self.events.sender());
+    }
+
+    fn finish_indexing(&mut self, slot: usize, library_dir: &Path, image_paths: Vec<String>) {
+        if library_dir != self.config.library_dir() {
+            // the directory changed in the settings while indexing
+            self.start_indexing(slot);
+            return;
+        }
+
+        hooks::run(
+            self.config.hooks.on_index_complete.as_deref(),
+            json!({
+                "event": "index_complete",
+                "model": self.models[slot].name,
+                "library": library_dir,
+                "images": image_paths.len(),
+            }),
+        );
+        self.image_paths = image_paths;
+        self.forget_scores();
+        if slot == self.active_model {
+            // the map only shows the images that were indexed when it was built
+            self.map = None;
+        }
+        if self.restored {
+            self.drop_stale_results();
+        }
+        self.continue_loading(slot + 1);
+    }
+
+    /// Drops the results restored from the last session whose images left the library
+    /// since, now that it is indexed
+    fn drop_stale_results(&mut self) {
+        self.restored = false;
+        let library: HashSet<&String> = self.image_paths.iter().collect();
+        let before = self.search_results.len();
+        let selected = self
+            .search_results
+            .get(self.selected_result)
+            .map(|result| result.file_path.clone());
+        self.search_results
+            .retain(|result| library.contains(&result.file_path));
+        let dropped = before - self.search_results.len();
+        if dropped == 0 {
+            return;
+        }
+        self.selected_result = selected
+            .and_then(|path| {
+                self.search_results
+                    .iter()
+                    .position(|result| result.file_path == path)
+            })
+            .unwrap_or_default();
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
+        self.notifications.add(Message::new(
+            format!(
+                "Dropped {} results of the last session that are gone",
+                dropped
+            ),
+            MessageSeverity::Info,
+            Duration::from_secs(3),
+        ));
+    }
+
+    /// Picks the next model for the following searches. After the last model comes the
+    /// ensemble of all models, then the main model again.
+    fn cycle_model(&mut self) {
+        if self.models.len() < 2 {
+            return;
+        }
+        if self.ensemble {
+            self.ensemble = false;
+            self.active_model = 0;
+        } else if self.active_model + 1 == self.models.len() {
+            self.ensemble = true;
+            self.active_model = 0;
+        } else {
+            self.active_model += 1;
+        }
+        // the map and the query position are made with the embeddings of the previous model
+        self.map = None;
+        self.last_query_embedding = None;
+
+        let slot = &self.models[self.active_model];
+        let text = if self.ensemble {
+            "Searching with all models combined".to_string()
+        } else if slot.model.is_some() {
+            format!("Searching with {}", slot.name)
+        } else {
+            format!("Searching with {} once it has loaded", slot.name)
+        };
+        self.notifications.add(Message::new(
+            text,
+            MessageSeverity::Info,
+            Duration::from_secs(2),
+        ));
+    }
+
+    fn save_config(&mut self) {
+        if let Err(e) = self.config.save() {
+            self.notifications.add(Message::new(
+                format!("Failed to save config: {}", e),
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+        }
     }
 
-    fn move_cursor_left(&mut self) {
-        let cursor_moved_left = self.char_index.saturating_sub(1);
-        self.char_index = self.clamp_cursor(cursor_moved_left);
+    /// Switches between the ranked results and the timeline grouped by month
+    fn toggle_timeline(&mut self) {
+        self.config.timeline = !self.config.timeline;
+        self.arrange_results();
+        self.selected_result = 0;
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
+        self.save_config();
     }
 
-    fn move_cursor_right(&mut self) {
-        let cursor_moved_right = self.char_index.saturating_add(1);
-        self.char_index = self.clamp_cursor(cursor_moved_right);
+    /// Orders the results by date for the timeline, oldest first, or else by rank
+    fn arrange_results(&mut self) {
+        if self.config.timeline {
+            // undated results go last
+            self.search_results
+                .sort_by(|a, b| match (&a.date, &b.date) {
+                    (Some(a), Some(b)) => a.cmp(b),
+                    (a, b) => b.is_some().cmp(&a.is_some()),
+                });
+        } else {
+            self.search_results.sort_by_key(|result| result.rank);
+        }
     }
 
-    fn clamp_cursor(&self, new_cursor_pos: usize) -> usize {
-        new_cursor_pos.clamp(0, self.search.chars().count())
+    /// Switches to the next result layout and remembers it in the config
+    fn cycle_layout(&mut self) {
+        self.config.layout = self.config.layout.next();
+        self.selected_result = 0;
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
+        self.save_config();
     }
 
-    fn enter_char(&mut self, new_char: char) {
-        let index = self.byte_index();
-        self.search.insert(index, new_char);
-        self.move_cursor_right();
+    /// Selects the cell with the given index if it is currently displayed
+    fn jump_to_result(&mut self, index: usize) {
+        if index < self.displayed_results() {
+            self.selected_result = index;
+        }
     }
 
-    fn byte_index(&self) -> usize {
-        self.search
-            .char_indices()
-            .map(|(i, _)| i)
-            .nth(self.char_index)
-            .unwrap_or(self.search.len())
-    }
+    /// Moves the selection to the closest cell in the given direction.
+    /// Moving up from the top row gives the focus back to the search bar.
+    fn move_selection(&mut self, dx: i32, dy: i32) {
+        let Some(current) = self.result_areas.get(self.selected_result) else {
+            return;
+        };
+        let center = |area: &Rect| {
+            (
+                area.x as i32 * 2 + area.width as i32,
+                area.y as i32 * 2 + area.height as i32,
+            )
+        };
+        let (cx, cy) = center(current);
 
-    /// Removes the cached area from each image
-    fn invalidate_image_cache(&mut self) {
-        for result in &mut self.search_results {
-            result.last_area = None;
+        let next = self
+            .result_areas
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != self.selected_result)
+            .filter_map(|(i, area)| {
+                let (x, y) = center(area);
+                let along = (x - cx) * dx + (y - cy) * dy;
+                let across = ((x - cx) * dy).abs() + ((y - cy) * dx).abs();
+                (along > 0).then_some((i, along + across * 2))
+            })
+            .min_by_key(|(_, distance)| *distance)
+            .map(|(i, _)| i);
+
+        match next {
+            Some(i) => self.selected_result = i,
+            None if dy < 0 => self.current_element = CurrentElement::Search,
+            None => {}
         }
     }
 
@@ -537,244 +3733,646 @@ impl App {
         self.char_index = 0;
     }
 
-    // gets called whenever enter is pressed.
-    // is supposed to return an array of all matching image paths from best match to worst.
-    // returns as many results as SEARCH_RESULTS specifies.
-    fn search(&mut self) -> Option<Vec<SearchResult>> {
-        let rightmost_x = self.search_area.right() - 1;
-        let rightmost_y = self.search_area.y + 2;
+    /// Starts the search on a worker thread, its results arrive as an `AppEvent::Search`
+    fn start_search(&mut self) {
+        self.run_search(false);
+    }
+
+    /// Searches only the best matches of the last search, so e.g. `beach`, then `sunset`,
+    /// then `people` in the negative prompt mode narrows the results down step by step
+    fn refine_search(&mut self) {
+        if self.candidates.is_empty() {
+            self.notifications.add(Message::new(
+                "Search first, then refine the results",
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+            return;
+        }
+        self.run_search(true);
+    }
+
+    fn run_search(&mut self, refine: bool) {
+        if self.searching.is_some() {
+            self.queued_search = Some(refine);
+            return;
+        }
+        let slot = &self.models[self.active_model];
+        let Some(model) = &slot.model else {
+            self.notifications.add(Message::new(
+                "The model is still loading, search again in a moment",
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+            return;
+        };
+        let mut config = self.config.clone();
+        // the personal ranking is trained on embeddings of the main model, and sorting by its
+        // scores would undo the fused order of an ensemble
+        config.personal_ranking &= self.active_model == 0 && !self.ensemble;
 
-        let (send_kill, recv_kill) = mpsc::channel();
+        // models that are still loading are left out of the ensemble
+        let ensemble = if self.ensemble {
+            self.models[1..]
+                .iter()
+                .filter_map(|member| {
+                    Some(EnsembleMember {
+                        model: Arc::clone(member.model.as_ref()?),
+                        database: Arc::clone(&member.database),
+                        similarity: member.similarity,
+                    })
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
 
+        let job = SearchJob {
+            input: self.search.clone(),
+            mode: self.mode,
+            model: Arc::clone(model),
+            database: Arc::clone(&slot.database),
+            catalog: Arc::clone(&self.catalog),
+            images: Arc::clone(&self.images),
+            display_side: self.display_side(),
+            image_paths: if refine {
+                self.candidates.clone()
+            } else {
+                self.image_paths.clone()
+            },
+            config,
+            feedback: self.feedback.clone(),
+            similarity: slot.similarity,
+            ensemble,
+            pool: refine.then(|| self.candidates.iter().cloned().collect()),
+            plugins: Arc::clone(&self.plugins),
+            // the index changes with every image while indexing
+            scores: self.loading.is_none().then(|| Arc::clone(&self.scores)),
+        };
+        self.searching = Some(Instant::now());
+        self.running_query = Some((self.search.clone(), refine));
+        let sender = self.events.sender();
         std::thread::spawn(move || {
-            let animation = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
-            let mut running = true;
-            while running {
-                for frame in &animation {
-                    if recv_kill.try_recv().is_ok() {
-                        running = false;
-                    }
-                    print!("\x1B[{};{}H", rightmost_y, rightmost_x);
-                    print!("{}", frame);
-                    std::io::Write::flush(&mut std::io::stdout()).ok();
-                    std::thread::sleep(Duration::from_millis(50));
+            let _ = sender.send(AppEvent::Search(Box::new(job.run())));
+        });
+    }
+
+    fn finish_search(&mut self, outcome: SearchOutcome) {
+        self.searching = None;
+        for message in outcome.messages {
+            self.notifications.add(message);
+        }
+        if outcome.query_embedding.is_some() {
+            self.last_query_embedding = outcome.query_embedding;
+        }
+
+        let running_query = self.running_query.take();
+        if let Some(results) = outcome.results {
+            hooks::run(
+                self.config.hooks.on_search.as_deref(),
+                json!({
+                    "event": "search",
+                    "query": running_query.as_ref().map(|(query, _)| query),
+                    "results": results.iter().map(|result| &result.hit).collect::<Vec<_>>(),
+                }),
+            );
+            if let Some((query, refine)) = running_query {
+                if !refine {
+                    self.refinements.clear();
                 }
+                self.refinements.push(query);
+            }
+            self.candidates = outcome.candidates;
+            let started = Instant::now();
+            self.search_results = self.to_results(results);
+            self.restored = false;
+            self.record_unreadable();
+            for path in outcome.missing {
+                self.unreadable
+                    .insert(path, "deleted since it was indexed".into());
             }
+            self.timings = Some(Timings {
+                encoding: started.elapsed(),
+                ..outcome.timings
+            });
+            self.arrange_results();
+            self.selected_result = 0;
+            self.clear_terminal = true;
+        }
 
-            print!("\x1B[{};{}H", rightmost_y, rightmost_x);
-            print!(" ");
-            std::io::Write::flush(&mut std::io::stdout()).ok();
-        });
+        if let Some(refine) = self.queued_search.take() {
+            self.run_search(refine);
+        }
+    }
 
-        let mut embed_rank: Vec<(String, f32)> = vec![];
+    /// Decodes the images of the best ranked paths, see `search::decode`
+    fn load_results(&self, ranked: &[(String, f32)]) -> Vec<SearchResult> {
+        let loaded = search::decode(
+            &self.catalog,
+            &self.images,
+            ranked,
+            self.config.result_count,
+            self.display_side(),
+        );
+        self.to_results(loaded)
+    }
 
-        if self
-            .modesel_list
-            .items
-            .iter()
-            .find(|e| e.option == "Search")
-            .is_some_and(|e| e.status == OptionStatus::Checked)
-        {
-            let text_embedding = match self.model.embed_text(&self.search) {
-                Ok(embed) => embed,
-                Err(e) => {
-                    let _ = send_kill.send(());
-                    self.notifications.add(Message::new(
-                        e,
-                        MessageSeverity::Error,
-                        Duration::from_secs(3),
-                    ));
-                    return None;
+    fn to_results(&self, loaded: Vec<LoadedResult>) -> Vec<SearchResult> {
+        loaded
+            .into_iter()
+            .enumerate()
+            .map(|(rank, result)| {
+                let text = |field: &str| {
+                    result
+                        .fields
+                        .get(field)
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                };
+                let private = privacy::is_private(&self.config.private_dirs, &result.hit.path);
+                // an image that isn't read yet is read by `SearchResult::show`
+                let (image, failed) = match result.image {
+                    Some(Ok(image)) if private => (Some(privacy::pixelate(&image)), None),
+                    Some(Ok(image)) => (Some(image), None),
+                    Some(Err(e)) => (None, Some(e)),
+                    None => (None, None),
+                };
+                let reduced = match &image {
+                    Some(image) if result.reduced => Some(image.width().max(image.height())),
+                    _ => None,
+                };
+                // the size of an EXIF thumbnail is only its shape
+                let dims = result
+                    .hit
+                    .dims
+                    .or_else(|| image.as_ref().map(|image| (image.width(), image.height())));
+                SearchResult {
+                    caption: text(CAPTION_FIELD),
+                    note: text(NOTE_FIELD),
+                    rating: metadata::rating(&result.fields),
+                    date: text(DATE_FIELD),
+                    dims,
+                    quality: result
+                        .fields
+                        .get(QUALITY_FIELD)
+                        .and_then(Value::as_f64)
+                        .map(|quality| quality as f32),
+                    rank,
+                    graphics_bytes: image.as_ref().map_or(0, graphics_bytes),
+                    reduced,
+                    last_shown: 0,
+                    image: image.map(|image| self.picker.new_resize_protocol(image)),
+                    failed,
+                    encoding: None,
+                    file_path: result.hit.path,
+                    burst: result.burst,
+                    private,
+                    revealed: false,
+                    confidence: result.hit.score as f64,
+                    last_area: None,
                 }
-            };
+            })
+            .collect()
+    }
 
-            let results =
-                self.images_embeddings
-                    .query(&text_embedding, SEARCH_RESULTS, None, None);
-
-            for result in results {
-                embed_rank.push((
-                    result[constants::F_ID]
-                        .as_str()
-                        .unwrap_or_default()
-                        .to_string(),
-                    result[constants::F_METRICS].as_f64().unwrap_or_default() as f32,
-                ));
-            }
+    /// Replaces the results with the selected result and the shots of its burst
+    fn expand_burst(&mut self) {
+        let Some(result) = self.search_results.get(self.selected_result) else {
+            return;
+        };
+        if result.burst.is_empty() {
+            return;
+        }
 
-            /*embed_rank.sort_by(|a, b| {
-                b.1.partial_cmp(&a.1)
-                    .expect("Failed to sort search results")
-            });*/
-        } else if self
-            .modesel_list
-            .items
-            .iter()
-            .find(|e| e.option == "Negative Prompt")
-            .is_some_and(|e| e.status == OptionStatus::Checked)
-        {
-            let text_embedding = match self.model.embed_text(&self.search) {
-                Ok(embed) => embed,
-                Err(e) => {
-                    let _ = send_kill.send(());
-                    self.notifications.add(Message::new(
-                        e,
-                        MessageSeverity::Error,
-                        Duration::from_secs(3),
-                    ));
-                    return None;
-                }
-            };
+        let mut shots = vec![(result.file_path.clone(), result.confidence as f32)];
+        shots.extend(result.burst.iter().cloned());
 
-            let results =
-                self.images_embeddings
-                    .query(&text_embedding, usize::MAX, None, None);
-
-            for result in results {
-                embed_rank.push((
-                    result[constants::F_ID]
-                        .as_str()
-                        .unwrap_or_default()
-                        .to_string(),
-                    result[constants::F_METRICS].as_f64().unwrap_or_default() as f32,
-                ));
+        self.search_results = self.load_results(&shots);
+        self.arrange_results();
+        self.selected_result = 0;
+        self.clear_terminal = true;
+        self.notifications.add(Message::new(
+            format!("Showing {} similar shots", shots.len()),
+            MessageSeverity::Info,
+            Duration::from_secs(3),
+        ));
+    }
+
+    /// Opens the embedding map, building it first if the index changed since it was last shown
+    fn open_map(&mut self) {
+        if self.map.is_none() {
+            let data = self
+                .database()
+                .get(&self.image_paths)
+                .into_iter()
+                .map(|data| (data.id.clone(), data.vector.clone()))
+                .collect();
+            self.map = EmbeddingMap::build(data);
+        }
+
+        let Some(map) = &self.map else {
+            self.notifications.add(Message::new(
+                "Not enough images for a map",
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+            return;
+        };
+
+        self.map_cursor = self
+            .last_query_embedding
+            .as_ref()
+            .map(|vector| map.project(vector))
+            .unwrap_or((
+                (map.x_bounds[0] + map.x_bounds[1]) / 2.0,
+                (map.y_bounds[0] + map.y_bounds[1]) / 2.0,
+            ));
+        self.map_open = true;
+        self.clear_terminal = true;
+    }
+
+    fn handle_map_key(&mut self, code: KeyCode) {
+        let Some(map) = &self.map else {
+            self.map_open = false;
+            return;
+        };
+        let step_x = (map.x_bounds[1] - map.x_bounds[0]) / 40.0;
+        let step_y = (map.y_bounds[1] - map.y_bounds[0]) / 20.0;
+
+        match code {
+            KeyCode::Char('q') | KeyCode::Char('m') | KeyCode::Esc => {
+                self.map_open = false;
+                self.clear_terminal = true;
+                self.invalidate_image_cache();
+            }
+            KeyCode::Left | KeyCode::Char('h') => self.map_cursor.0 -= step_x,
+            KeyCode::Right | KeyCode::Char('l') => self.map_cursor.0 += step_x,
+            KeyCode::Up | KeyCode::Char('k') => self.map_cursor.1 += step_y,
+            KeyCode::Down | KeyCode::Char('j') => self.map_cursor.1 -= step_y,
+            KeyCode::Enter => {
+                let Some(i) = map.nearest(self.map_cursor.0, self.map_cursor.1) else {
+                    return;
+                };
+                let path = map.points[i].0.clone();
+                let confidence = self
+                    .last_query_embedding
+                    .as_ref()
+                    .and_then(|query| {
+                        self.vector(&path).map(|vector| {
+                            query.iter().zip(&vector).map(|(a, b)| a * b).sum::<f32>()
+                        })
+                    })
+                    .unwrap_or_default();
+
+                self.search_results = self.load_results(&[(path, confidence)]);
+                self.selected_result = 0;
+                self.map_open = false;
+                self.current_element = CurrentElement::Images;
+                self.preview_open = !self.search_results.is_empty();
+                self.clear_terminal = true;
             }
+            _ => {}
+        }
+    }
+
+    /// Shows a warning and returns true if files must not be modified
+    fn refuse_read_only(&mut self) -> bool {
+        if self.read_only {
+            self.notifications.add(Message::new(
+                "Read-only mode, files are not modified",
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+        }
+        self.read_only
+    }
 
-            embed_rank.reverse();
+    /// Moves the given files to the trash and removes them from the index and the results
+    fn delete_files(&mut self, mut paths: Vec<String>) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let local = paths.len();
+        paths.retain(|path| !remote::is_remote(path));
+        if paths.len() < local {
+            self.notifications.add(Message::new(
+                "Remote items are not deleted, only local files",
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+        }
+        if paths.is_empty() {
+            return;
+        }
 
-            /*embed_rank.sort_by(|a, b| {
-                a.1.partial_cmp(&b.1)
-                    .expect("Failed to sort search results")
-            });*/
-        } else if self
-            .modesel_list
-            .items
+        let files = paths
             .iter()
-            .find(|e| e.option == "Ranking")
-            .is_some_and(|e| e.status == OptionStatus::Checked)
-        {
-            let search_clone = self.search.clone();
-            let search_split: Vec<&str> = search_clone.split("-").collect();
-            if search_split.len() != 2 {
-                let _ = send_kill.send(());
-                return None;
-            }
-            let positive_embedding = self.model.embed_text(search_split[0]);
-            let negative_embedding = self.model.embed_text(search_split[1]);
+            .map(|path| {
+                let vector = self.vector(path).unwrap_or_default();
+                (path.clone(), vector)
+            })
+            .collect();
+
+        let result = self.trash.delete(files);
+
+        // the trash journal knows which files got moved, drop only those from the index
+        let deleted: Vec<String> = paths
+            .into_iter()
+            .filter(|path| !fs::exists(path).unwrap_or(true))
+            .collect();
+        for slot in &self.models {
+            let _ = write(&slot.database).delete(&deleted);
+        }
+        self.forget_scores();
+        self.save_index();
+        self.map = None;
+        self.search_results
+            .retain(|result| !deleted.contains(&result.file_path));
+        self.selected_result = self
+            .selected_result
+            .min(self.search_results.len().saturating_sub(1));
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
 
-            if positive_embedding.is_err() && negative_embedding.is_err() {
-                let _ = send_kill.send(());
+        match result {
+            Ok(()) => self.notifications.add(
+                Message::new(
+                    format!("Deleted {} files", deleted.len()),
+                    MessageSeverity::Info,
+                    Duration::from_secs(5),
+                )
+                .action('u', "undo", AppEvent::Undo),
+            ),
+            Err(e) => self.notifications.add(Message::new(
+                format!("Failed to delete files: {}", e),
+                MessageSeverity::Error,
+                Duration::from_secs(3),
+            )),
+        }
+    }
+
+    /// Restores the files of the last delete operation
+    fn undo_delete(&mut self) {
+        if self.refuse_read_only() {
+            return;
+        }
+
+        match self.trash.undo() {
+            Ok(Some(operation)) => {
+                let count = operation.files.len();
+                let data = operation
+                    .files
+                    .into_iter()
+                    .filter(|file| !file.vector.is_empty())
+                    .map(|file| Data {
+                        id: file.original,
+                        vector: file.vector,
+                        fields: HashMap::new(),
+                    })
+                    .collect();
+                // the trash keeps the embeddings of the main model,
+                // the other models embed the restored images when the library is indexed again
+                let _ = write(&self.models[0].database).upsert(data);
+                self.forget_scores();
+                self.save_index();
+                if self.models.len() > 1 && self.loading.is_none() {
+                    self.continue_loading(1);
+                }
+                self.map = None;
                 self.notifications.add(Message::new(
-                    "Failed to embed text",
-                    MessageSeverity::Error,
+                    format!("Restored {} files", count),
+                    MessageSeverity::Info,
                     Duration::from_secs(3),
                 ));
-                return None;
             }
+            Ok(None) => self.notifications.add(Message::new(
+                "Nothing to undo",
+                MessageSeverity::Info,
+                Duration::from_secs(3),
+            )),
+            Err(e) => self.notifications.add(Message::new(
+                format!("Failed to undo: {}", e),
+                MessageSeverity::Error,
+                Duration::from_secs(3),
+            )),
+        }
+    }
 
-            let positive_embedding = positive_embedding.unwrap();
-            let negative_embedding = negative_embedding.unwrap();
+    /// Drops the cached rankings, after the index changed
+    fn forget_scores(&self) {
+        self.scores
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
 
-            let results =
-                self.images_embeddings
-                    .query(&positive_embedding, SEARCH_RESULTS, None, None);
+    /// Saves the index of every model
+    fn save_index(&mut self) {
+        let failed = self
+            .models
+            .iter()
+            .any(|slot| read(&slot.database).save().is_err());
+        if failed {
+            self.notifications.add(Message::new(
+                "Failed to save database to disk",
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+        }
+    }
 
-            for result in results {
-                let file_id = result[constants::F_ID]
-                    .as_str()
-                    .unwrap_or_default()
-                    .to_string();
-                let positive_score =
-                    result[constants::F_METRICS].as_f64().unwrap_or_default() as f32;
+    fn is_nsfw(&self, path: &str) -> bool {
+        self.fields(path)
+            .get(NSFW_FIELD)
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+    }
 
-                let negative_results =
-                    self.images_embeddings
-                        .query(&negative_embedding, 1, None, None);
+    /// Toggles hiding images flagged as not safe for work and remembers the choice in the config
+    fn toggle_safe_search(&mut self) {
+        self.config.hide_nsfw = !self.config.hide_nsfw;
 
-                let negative_score = negative_results
-                    .first()
-                    .and_then(|result| result[constants::F_METRICS].as_f64())
-                    .unwrap_or_default() as f32;
+        let text = if self.config.hide_nsfw {
+            let flagged: Vec<String> = self
+                .search_results
+                .iter()
+                .map(|result| result.file_path.clone())
+                .filter(|path| self.is_nsfw(path))
+                .collect();
+            self.search_results
+                .retain(|result| !flagged.contains(&result.file_path));
+            "Safe search on"
+        } else {
+            "Safe search off, search again to show hidden images"
+        };
 
-                let combined_score = positive_score - negative_score;
+        if !self.config.nsfw_filter {
+            self.notifications.add(Message::new(
+                "Enable nsfw_filter in the config to classify images",
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+        }
+        self.notifications.add(Message::new(
+            text,
+            MessageSeverity::Info,
+            Duration::from_secs(3),
+        ));
+        self.selected_result = 0;
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
+        self.save_config();
+    }
 
-                embed_rank.push((file_id, combined_score));
-            }
+    /// Toggles hiding blurry photos and remembers the choice in the config
+    fn toggle_hide_blurry(&mut self) {
+        self.config.hide_blurry = !self.config.hide_blurry;
 
-            embed_rank.sort_by(|a, b| {
-                b.1.partial_cmp(&a.1)
-                    .expect("Failed to sort search results")
-            });
+        let text = if self.config.hide_blurry {
+            let blurry = self.catalog.blurry(self.config.blur_threshold);
+            self.search_results
+                .retain(|result| !blurry.contains(&result.file_path));
+            "Blurry photos hidden"
+        } else {
+            "Blurry photos shown, search again to show hidden images"
+        };
+        self.notifications.add(Message::new(
+            text,
+            MessageSeverity::Info,
+            Duration::from_secs(3),
+        ));
+        self.selected_result = 0;
+        self.clear_terminal = true;
+        self.invalidate_image_cache();
+        self.save_config();
+    }
 
-            /*embed_rank.sort_by(|a, b| {
-                b.1.partial_cmp(&a.1)
-                    .expect("Failed to sort search results")
-            });*/
-        } else if self
-            .modesel_list
-            .items
-            .iter()
-            .find(|e| e.option == "Image 2 Image")
-            .is_some_and(|e| e.status == OptionStatus::Checked)
-        {
-            let image_embedding = self.model.embed_image(&self.search);
+    /// Toggles translating queries and remembers the choice in the config
+    fn toggle_translation(&mut self) {
+        self.config.translate_queries = !self.config.translate_queries;
+        let text = match (
+            &self.config.translation_endpoint,
+            self.config.translate_queries,
+        ) {
+            (None, _) => "Set translation_endpoint in the config to translate queries",
+            (Some(_), true) => "Queries are translated into English",
+            (Some(_), false) => "Queries are searched as written",
+        };
+        self.notifications.add(Message::new(
+            text,
+            MessageSeverity::Info,
+            Duration::from_secs(3),
+        ));
+        self.save_config();
+    }
 
-            let real_image_embedding = match image_embedding {
-                Ok(embed) => embed,
-                Err(e) => {
-                    let _ = send_kill.send(());
-                    self.notifications.add(Message::new(
-                        e,
-                        MessageSeverity::Error,
-                        Duration::from_secs(3),
-                    ));
-                    return None;
-                }
-            };
+    /// The index of the model used for searching, shared with search workers
+    fn database(&self) -> RwLockReadGuard<'_, Store> {
+        read(&self.models[self.active_model].database)
+    }
 
-            let results =
-                self.images_embeddings
-                    .query(&real_image_embedding, SEARCH_RESULTS, None, None);
-
-            for result in results {
-                embed_rank.push((
-                    result[constants::F_ID]
-                        .as_str()
-                        .unwrap_or_default()
-                        .to_string(),
-                    result[constants::F_METRICS].as_f64().unwrap_or_default() as f32,
-                ));
-            }
+    /// The embedding of an image made with the main model
+    fn vector(&self, path: &str) -> Option<Vec<f32>> {
+        search::vector(&**read(&self.models[0].database), path)
+    }
+
+    /// Marks the selected result as relevant or not for the last query and retrains the personal re-ranker
+    fn give_feedback(&mut self, liked: bool) {
+        let (Some(result), Some(query)) = (
+            self.search_results.get(self.selected_result),
+            self.last_query_embedding.clone(),
+        ) else {
+            return;
+        };
 
-            /*embed_rank.sort_by(|a, b| {
-                b.1.partial_cmp(&a.1)
-                    .expect("Failed to sort search results")
-            });*/
+        let judgement = Judgement {
+            query,
+            path: result.file_path.clone(),
+            liked,
+        };
+        if self.active_model != 0 {
+            self.notifications.add(Message::new(
+                "The personal ranking only learns from searches with the main model",
+                MessageSeverity::Warning,
+                Duration::from_secs(3),
+            ));
+            return;
         }
 
-        let mut results: Vec<SearchResult> = Vec::new();
-        for (path, confidence) in embed_rank.iter().take(SEARCH_RESULTS) {
-            let dyn_img = match image::ImageReader::open(path) {
-                Ok(reader) => reader,
-                Err(_) => continue,
-            };
-            let dyn_img = match dyn_img.decode() {
-                Ok(img) => img,
-                Err(_) => continue,
-            };
+        let database = read(&self.models[0].database);
+        let recorded = self
+            .feedback
+            .record(judgement, |path| search::vector(&**database, path));
+        drop(database);
 
-            let image = self.picker.new_resize_protocol(dyn_img);
-            results.push(SearchResult {
-                image,
-                file_path: path.clone(),
-                confidence: *confidence as f64,
-                last_area: None,
-            });
-        }
+        let message = match recorded {
+            Ok(()) => Message::new(
+                format!(
+                    "Marked as {}, learned from {} results",
+                    if liked { "relevant" } else { "not relevant" },
+                    self.feedback.len()
+                ),
+                MessageSeverity::Info,
+                Duration::from_secs(2),
+            ),
+            Err(e) => Message::new(
+                format!("Failed to save feedback: {}", e),
+                MessageSeverity::Error,
+                Duration::from_secs(3),
+            ),
+        };
+        self.notifications.add(message);
+    }
 
-        let _ = send_kill.send(());
+    /// Returns the metadata stored with an image in the index
+    fn fields(&self, path: &str) -> HashMap<String, Value> {
+        self.catalog.fields(path)
+    }
+
+    /// Restores the query, mode and results of the last session. The results are shown right
+    /// away, their images are read in the background as they are drawn, and the ones that
+    /// are gone from the library are dropped once it is indexed.
+    fn restore_session(&mut self, session: Session) {
+        self.search = session.query;
+        self.char_index = self.search.chars().count();
+        self.set_mode(session.mode);
+        let loaded = search::undecoded(&self.catalog, &session.results, self.config.result_count);
+        self.search_results = self.to_results(loaded);
+        self.restored = true;
+        self.arrange_results();
+        // the session stores the rank of the selected result
+        self.selected_result = self
+            .search_results
+            .iter()
+            .position(|result| result.rank == session.selected_result)
+            .unwrap_or_default();
+    }
+
+    /// Remembers the query, mode and results for the next launch
+    fn save_session(&self) -> io::Result<()> {
+        let mut results: Vec<&SearchResult> = self.search_results.iter().collect();
+        results.sort_by_key(|result| result.rank);
+
+        Session {
+            query: self.search.clone(),
+            mode: self.mode,
+            results: results
+                .into_iter()
+                .map(|result| (result.file_path.clone(), result.confidence as f32))
+                .collect(),
+            selected_result: self
+                .search_results
+                .get(self.selected_result)
+                .map(|result| result.rank)
+                .unwrap_or_default(),
+        }
+        .save()
+    }
 
-        Some(results)
+    /// Selects `mode` and checks its entry in the mode list
+    fn set_mode(&mut self, mode: SearchEnum) {
+        self.mode = mode;
+        self.modesel_list.set_checked(mode);
     }
 
     fn clear_search(&mut self) {
@@ -783,115 +4381,143 @@ impl App {
     }
 
     fn select_next(&mut self) {
-        self.modesel_list.state.select_next();
+        self.modesel_list.select_next();
     }
 
     fn select_previous(&mut self) {
-        self.modesel_list.state.select_previous();
+        self.modesel_list.select_previous();
     }
 
     fn toggle_status(&mut self) {
-        for item in &mut self.modesel_list.items {
-            item.status = OptionStatus::Unchecked;
-        }
-        if let Some(i) = self.modesel_list.state.selected() {
-            self.modesel_list.items[i].status = match self.modesel_list.items[i].status {
-                OptionStatus::Checked => OptionStatus::Unchecked,
-                OptionStatus::Unchecked => {
-                    self.mode = match self.modesel_list.items[i].option.as_str() {
-                        "Search" => SearchEnum::Search,
-                        "Negative Prompt" => SearchEnum::NegativePrompt,
-                        "Ranking" => SearchEnum::Ranking,
-                        "Image 2 Image" => SearchEnum::Image2Image,
-                        &_ => SearchEnum::Search,
-                    };
-
-                    OptionStatus::Checked
-                }
-            }
+        if self.modesel_list.toggle_selected().is_some() {
+            // unchecking the current mode keeps it, there always is one
+            let mode = self.modesel_list.checked_value().unwrap_or(self.mode);
+            self.set_mode(mode);
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Embedding {
-    path: String,
-    vector: Vec<f32>,
-}
-
-impl Default for App {
-    fn default() -> Self {
-        let clip_model = ClipModel::new(&std::env::args().collect::<Vec<String>>()[1]);
-
-        let paths = fs::create_dir_all("images/")
-            .and_then(|_| fs::read_dir("images/"))
-            .expect("Failed to create or read images directory");
-
-        let mut images_paths: Vec<String> = vec![];
-        let mut image_embeddings =
-            NanoVectorDB::new(768, "images/embeddings.db").expect("Failed to initialize database");
-
-        for entry in paths.flatten() {
-            let img_path = entry.path().display().to_string();
-            if SUPPORTED_IMAGE_FORMATS
-                .iter()
-                .any(|suffix| img_path.ends_with(suffix))
-            {
-                images_paths.push(img_path);
-            }
-        }
-
-        for (index, image) in images_paths.iter().enumerate() {
-            println!("Embedding {}/{} {}", index, images_paths.len(), image);
-            if !image_embeddings.get(&[image.clone()]).is_empty() {
-                continue;
-            }
-
-            let embedding = clip_model
-                .embed_image(image.clone())
-                .expect("Failed to embed image");
-
-            image_embeddings.upsert(vec![Data {
-                id: image.clone(),
-                vector: embedding,
-                fields: HashMap::new(),
-            }]);
+impl App {
+    /// Creates the app and starts loading the model in the background
+    fn new(args: &Args, model_path: &str) -> Self {
+        let config = Config::load();
+        // the library is indexed once the models have loaded in the background,
+        // until then the embeddings from earlier runs can be browsed
+        let models = models::open_slots(&config, model_path);
+        let catalog =
+            Catalog::open(&paths::catalog_file()).expect("Failed to open the metadata database");
+        let images_paths = index::library_items(&config.library_dir());
 
-            image_embeddings.save().unwrap_or_else(|_| {
-                log_warning("Failed to save database to disk".into());
-            })
-        }
+        let (picker, detected_protocol) = graphics::picker(&config);
+        let (image_budget, _) = memory::split_budget(config.memory_budget);
+        let images = Arc::new(Mutex::new(ImageCache::new(
+            image_budget,
+            config.private_dirs.clone(),
+        )));
+        let (plugins, plugin_errors) = Plugins::load();
+        let plugin_actions = plugins.actions();
+        let events = Events::new(TICK_RATE);
+        let encoder = Encoder::new(Arc::clone(&images), events.sender());
 
-        Self {
-            model: clip_model,
+        let mut app = Self {
+            models,
+            catalog: Arc::new(catalog),
+            active_model: 0,
+            ensemble: false,
+            loading: None,
             search: String::new(),
             exit: false,
             input_mode: InputMode::Normal,
             char_index: 0,
             current_element: CurrentElement::Search,
             button_pressed: false,
-            modesel_open: false,
-            mode: SearchEnum::Search,
-            modesel_list: OptionList::from_iter([
-                (OptionStatus::Checked, "Search", SearchEnum::Search),
-                (
-                    OptionStatus::Unchecked,
-                    "Negative Prompt",
-                    SearchEnum::NegativePrompt,
-                ),
-                (OptionStatus::Unchecked, "Ranking", SearchEnum::Ranking),
-                (
-                    OptionStatus::Unchecked,
-                    "Image 2 Image",
-                    SearchEnum::Image2Image,
-                ),
-            ]),
+            mode: config.default_mode,
+            modesel_list: SearchEnum::ALL
+                .into_iter()
+                .map(|mode| {
+                    let status = if mode == config.default_mode {
+                        OptionStatus::Checked
+                    } else {
+                        OptionStatus::Unchecked
+                    };
+                    (status, mode.name(), mode)
+                })
+                .collect(),
             search_results: Vec::new(),
-            images_embeddings: image_embeddings,
-            picker: Picker::from_query_stdio().unwrap_or(Picker::halfblocks()),
+            events,
+            picker,
+            detected_protocol,
+            modals: Modals::default(),
             search_area: Rect::default(),
+            mode_area: Rect::default(),
+            toolbar_areas: Vec::new(),
+            results_block: Rect::default(),
             clear_terminal: false,
-            notifications: Messages::default(),
+            notifications: Messages::with_theme(config.notification_colors.clone())
+                .reduced_motion(config.reduced_motion),
+            selected_result: 0,
+            result_areas: Vec::new(),
+            timeline_scroll: 0,
+            searching: None,
+            running_query: None,
+            queued_search: None,
+            candidates: Vec::new(),
+            refinements: Vec::new(),
+            pins: Pins::load(),
+            preview_open: false,
+            rating: false,
+            trash: Trash::open(),
+            feedback: Feedback::load(),
+            image_paths: images_paths,
+            last_query_embedding: None,
+            map: None,
+            map_open: false,
+            sketch: None,
+            map_cursor: (0.0, 0.0),
+            read_only: args.read_only,
+            pick_mode: args.picker.is_some(),
+            picked: None,
+            search_on_load: false,
+            hud_open: false,
+            locale: Locale::new(&config),
+            stopping: None,
+            interrupted: None,
+            timings: None,
+            images,
+            encoder,
+            scores: Arc::default(),
+            restored: false,
+            frame_count: 0,
+            unreadable: BTreeMap::new(),
+            plugins: Arc::new(plugins),
+            plugin_actions,
+            server: None,
+            mic_command: args.mic_command.clone().or(config.mic_command.clone()),
+            listening: false,
+            cleanup: None,
+            finding_cleanup: false,
+            jobs: Jobs::default(),
+            config,
+        };
+        for error in plugin_errors {
+            app.notifications.add(Message::new(
+                error,
+                MessageSeverity::Error,
+                Duration::from_secs(10),
+            ));
+        }
+        if let Some(query) = args.picker.as_ref().filter(|query| !query.is_empty()) {
+            app.search = query.clone();
+            app.char_index = app.search.chars().count();
+            app.search_on_load = true;
+        }
+        if let Some(Command::Similar { image, .. }) = &args.command {
+            app.set_mode(SearchEnum::Image2Image);
+            app.search = image.display().to_string();
+            app.char_index = app.search.chars().count();
+            app.search_on_load = true;
         }
+        app.continue_loading(0);
+        app
     }
 }
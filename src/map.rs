@@ -0,0 +1,198 @@
+/// Number of power iterations used to find each principal component
+const PCA_ITERATIONS: usize = 50;
+
+/// A 2D projection of image embeddings, computed with PCA.
+pub struct EmbeddingMap {
+    /// Image paths with their projected coordinates
+    pub points: Vec<(String, f64, f64)>,
+    mean: Vec<f32>,
+    components: [Vec<f32>; 2],
+    /// Smallest and largest projected x coordinate
+    pub x_bounds: [f64; 2],
+    /// Smallest and largest projected y coordinate
+    pub y_bounds: [f64; 2],
+}
+
+impl EmbeddingMap {
+    /// Projects the given embeddings onto their first two principal components.
+    /// Returns `None` if there are less than two embeddings.
+    pub fn build(data: Vec<(String, Vec<f32>)>) -> Option<Self> {
+        if data.len() < 2 {
+            return None;
+        }
+        let dim = data[0].1.len();
+
+        let mut mean = vec![0.0f32; dim];
+        for (_, vector) in &data {
+            for (m, v) in mean.iter_mut().zip(vector) {
+                *m += v / data.len() as f32;
+            }
+        }
+
+        let centered: Vec<Vec<f32>> = data
+            .iter()
+            .map(|(_, vector)| vector.iter().zip(&mean).map(|(v, m)| v - m).collect())
+            .collect();
+
+        let first = principal_component(&centered, None);
+        let second = principal_component(&centered, Some(&first));
+
+        let mut map = Self {
+            points: Vec::with_capacity(data.len()),
+            mean,
+            components: [first, second],
+            x_bounds: [f64::MAX, f64::MIN],
+            y_bounds: [f64::MAX, f64::MIN],
+        };
+
+        for (path, vector) in data {
+            let (x, y) = map.project(&vector);
+            map.x_bounds = [map.x_bounds[0].min(x), map.x_bounds[1].max(x)];
+            map.y_bounds = [map.y_bounds[0].min(y), map.y_bounds[1].max(y)];
+            map.points.push((path, x, y));
+        }
+
+        Some(map)
+    }
+
+    /// Projects an embedding into the 2D space of the map
+    pub fn project(&self, vector: &[f32]) -> (f64, f64) {
+        let centered: Vec<f32> = vector.iter().zip(&self.mean).map(|(v, m)| v - m).collect();
+        (
+            dot(&centered, &self.components[0]) as f64,
+            dot(&centered, &self.components[1]) as f64,
+        )
+    }
+
+    /// Returns the index of the point closest to (x, y)
+    pub fn nearest(&self, x: f64, y: f64) -> Option<usize> {
+        self.points
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                let distance_a = (a.1 - x).powi(2) + (a.2 - y).powi(2);
+                let distance_b = (b.1 - x).powi(2) + (b.2 - y).powi(2);
+                distance_a.total_cmp(&distance_b)
+            })
+            .map(|(i, _)| i)
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm = dot(vector, vector).sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+/// Finds the direction of largest variance with power iteration.
+/// If `orthogonal_to` is given, that direction is removed in every step.
+fn principal_component(centered: &[Vec<f32>], orthogonal_to: Option<&[f32]>) -> Vec<f32> {
+    let dim = centered[0].len();
+    // deterministic start vector that is unlikely to be orthogonal to the solution
+    let mut component: Vec<f32> = (0..dim).map(|i| 1.0 + (i % 7) as f32 * 0.1).collect();
+    normalize(&mut component);
+
+    for _ in 0..PCA_ITERATIONS {
+        let mut next = vec![0.0f32; dim];
+        for row in centered {
+            let weight = dot(row, &component);
+            for (n, r) in next.iter_mut().zip(row) {
+                *n += weight * r;
+            }
+        }
+
+        if let Some(other) = orthogonal_to {
+            let overlap = dot(&next, other);
+            for (n, o) in next.iter_mut().zip(other) {
+                *n -= overlap * o;
+            }
+        }
+
+        normalize(&mut next);
+        component = next;
+    }
+
+    component
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points spread widely along `(1, 1, 0)`, a little and uncorrelated along `(0, 0, 1)` and
+    /// not at all along `(1, -1, 0)`, around `(1, 2, 3)`
+    fn data() -> Vec<(String, Vec<f32>)> {
+        [
+            (-2.0, 0.1),
+            (-1.0, -0.2),
+            (0.0, 0.2),
+            (1.0, -0.2),
+            (2.0, 0.1),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, (wide, narrow))| {
+            let vector = vec![1.0 + wide, 2.0 + wide, 3.0 + narrow];
+            (format!("{}.jpg", i), vector)
+        })
+        .collect()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{a} isn't {b}");
+    }
+
+    #[test]
+    fn components_are_the_directions_of_largest_variance() {
+        let map = EmbeddingMap::build(data()).unwrap();
+        let [first, second] = &map.components;
+
+        let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(first[0].abs(), diagonal);
+        assert_close(first[1].abs(), diagonal);
+        assert_close(first[2], 0.0);
+        assert_close(second[2].abs(), 1.0);
+        assert_close(dot(first, second), 0.0);
+        assert_close(dot(first, first), 1.0);
+    }
+
+    #[test]
+    fn points_are_projected_around_the_mean() {
+        let map = EmbeddingMap::build(data()).unwrap();
+
+        assert_eq!(map.points.len(), 5);
+        let (x, y) = map.project(&map.mean);
+        assert_close(x as f32, 0.0);
+        assert_close(y as f32, 0.0);
+
+        // the wide spread ends up on the x axis, in order
+        let xs: Vec<f64> = map.points.iter().map(|(_, x, _)| *x).collect();
+        let increasing = xs.windows(2).all(|pair| pair[0] < pair[1]);
+        let decreasing = xs.windows(2).all(|pair| pair[0] > pair[1]);
+        assert!(increasing || decreasing, "{xs:?}");
+        assert_close(
+            (map.x_bounds[1] - map.x_bounds[0]) as f32,
+            4.0 * 2.0f32.sqrt(),
+        );
+        assert_close((map.y_bounds[1] - map.y_bounds[0]) as f32, 0.4);
+    }
+
+    #[test]
+    fn nearest_finds_the_closest_point() {
+        let map = EmbeddingMap::build(data()).unwrap();
+
+        for (i, (_, x, y)) in map.points.iter().enumerate() {
+            assert_eq!(map.nearest(*x + 0.01, *y - 0.01), Some(i));
+        }
+    }
+
+    #[test]
+    fn a_single_embedding_has_no_map() {
+        assert!(EmbeddingMap::build(data()[..1].to_vec()).is_none());
+    }
+}
@@ -0,0 +1,147 @@
+use image::DynamicImage;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+};
+
+use crate::{exif, privacy, remote};
+
+/// Longest side decoded images are scaled down to, more than a terminal cell grid shows
+pub const MAX_SIDE: u32 = 2048;
+const MIB: usize = 1024 * 1024;
+
+/// Splits `Config::memory_budget`, given in MiB, between the decoded images and the
+/// terminal graphics of the results. Returns the bytes for each.
+pub fn split_budget(memory_budget: usize) -> (usize, usize) {
+    let bytes = memory_budget.saturating_mul(MIB);
+    (bytes / 2, bytes - bytes / 2)
+}
+
+/// Decoded images by path, the least recently used ones are dropped once they take more
+/// than the budget. Searches often return the same images again, which then don't have to
+/// be read and decoded a second time. Images in private directories are never kept.
+#[derive(Debug, Default)]
+pub struct ImageCache {
+    entries: HashMap<String, Entry>,
+    /// Bytes the cache may hold
+    budget: usize,
+    /// Bytes held right now
+    used: usize,
+    /// Counts up with every access, orders the entries by when they were last used
+    clock: u64,
+    /// See `Config::private_dirs`
+    private_dirs: Vec<PathBuf>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    image: Decoded,
+    last_used: u64,
+}
+
+/// An image read by `load`
+#[derive(Debug, Clone)]
+pub struct Decoded {
+    pub image: DynamicImage,
+    /// Whether it is the thumbnail embedded in the file rather than the image itself
+    pub reduced: bool,
+}
+
+impl Decoded {
+    /// Whether the image has enough pixels to be shown at `side`
+    fn covers(&self, side: u32) -> bool {
+        !self.reduced || self.image.width().max(self.image.height()) >= side
+    }
+}
+
+impl ImageCache {
+    pub fn new(budget: usize, private_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            budget,
+            private_dirs,
+            ..Default::default()
+        }
+    }
+
+    fn get(&mut self, path: &str) -> Option<Decoded> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(path)?;
+        entry.last_used = self.clock;
+        Some(entry.image.clone())
+    }
+
+    fn insert(&mut self, path: &str, image: Decoded) {
+        let bytes = image.image.as_bytes().len();
+        // an image bigger than the whole budget would only push everything else out
+        if bytes > self.budget {
+            return;
+        }
+        if privacy::is_private(&self.private_dirs, path) {
+            return;
+        }
+        self.clock += 1;
+        let entry = Entry {
+            image,
+            last_used: self.clock,
+        };
+        if let Some(old) = self.entries.insert(path.to_string(), entry) {
+            self.used -= old.image.image.as_bytes().len();
+        }
+        self.used += bytes;
+
+        while self.used > self.budget {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.used -= entry.image.image.as_bytes().len();
+            }
+        }
+    }
+}
+
+/// The decoded image of `path` from the cache, read and decoded on a miss. `side` is the
+/// longest side in pixels it is shown at: the thumbnail embedded in the EXIF data is taken
+/// instead of decoding the whole photo when it is at least that large, and a cached
+/// thumbnail smaller than that counts as a miss. The cache is only locked for the lookup and
+/// the insert, so decoding doesn't block other threads.
+/// Returns why if the image can not be read.
+pub fn load(cache: &Mutex<ImageCache>, path: &str, side: u32) -> Result<Decoded, String> {
+    let lock = || cache.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(image) = lock().get(path).filter(|image| image.covers(side)) {
+        return Ok(image);
+    }
+
+    if let Some(thumbnail) = exif::thumbnail(path)
+        && thumbnail.width().max(thumbnail.height()) >= side
+    {
+        let image = Decoded {
+            image: thumbnail,
+            reduced: true,
+        };
+        lock().insert(path, image.clone());
+        return Ok(image);
+    }
+
+    let image = image::ImageReader::open(remote::local_file(path))
+        .map_err(|e| e.to_string())?
+        .decode()
+        .map_err(|e| e.to_string())?;
+    let image = if image.width().max(image.height()) > MAX_SIDE {
+        image.thumbnail(MAX_SIDE, MAX_SIDE)
+    } else {
+        image
+    };
+    let image = Decoded {
+        image,
+        reduced: false,
+    };
+    lock().insert(path, image.clone());
+    Ok(image)
+}
@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    index::{CAPTION_FIELD, NOTE_FIELD, RATING_FIELD, TAGS_FIELD},
+    paths,
+};
+
+/// Editor used when neither `$VISUAL` nor `$EDITOR` is set
+const DEFAULT_EDITOR: &str = if cfg!(windows) { "notepad" } else { "vi" };
+
+/// The part of an image's metadata that can be edited by hand.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Editable {
+    #[serde(default)]
+    pub caption: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub note: String,
+    /// Stars from 1 to 5, 0 for unrated
+    #[serde(default)]
+    pub rating: u8,
+}
+
+/// Highest star rating
+pub const MAX_RATING: u8 = 5;
+
+/// The star rating of an image, 0 if it is unrated
+pub fn rating(fields: &HashMap<String, Value>) -> u8 {
+    fields
+        .get(RATING_FIELD)
+        .and_then(Value::as_u64)
+        .map_or(0, |rating| rating.min(MAX_RATING as u64) as u8)
+}
+
+/// Sets the star rating of an image, 0 removes it
+pub fn set_rating(fields: &mut HashMap<String, Value>, rating: u8) {
+    if rating == 0 {
+        fields.remove(RATING_FIELD);
+    } else {
+        fields.insert(RATING_FIELD.into(), rating.min(MAX_RATING).into());
+    }
+}
+
+impl Editable {
+    pub fn from_fields(fields: &HashMap<String, Value>) -> Self {
+        Self {
+            caption: fields
+                .get(CAPTION_FIELD)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            tags: fields
+                .get(TAGS_FIELD)
+                .and_then(Value::as_array)
+                .map(|tags| {
+                    tags.iter()
+                        .filter_map(|tag| tag.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            note: fields
+                .get(NOTE_FIELD)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            rating: rating(fields),
+        }
+    }
+
+    /// Writes the values back into the metadata. An empty caption or note removes it,
+    /// as does a rating of 0.
+    pub fn apply(self, fields: &mut HashMap<String, Value>) {
+        let caption = self.caption.trim();
+        if caption.is_empty() {
+            fields.remove(CAPTION_FIELD);
+        } else {
+            fields.insert(CAPTION_FIELD.into(), caption.into());
+        }
+
+        let tags: Vec<Value> = self
+            .tags
+            .iter()
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty())
+            .map(Value::from)
+            .collect();
+        if tags.is_empty() {
+            fields.remove(TAGS_FIELD);
+        } else {
+            fields.insert(TAGS_FIELD.into(), tags.into());
+        }
+
+        let note = self.note.trim();
+        if note.is_empty() {
+            fields.remove(NOTE_FIELD);
+        } else {
+            fields.insert(NOTE_FIELD.into(), note.into());
+        }
+
+        set_rating(fields, self.rating);
+    }
+}
+
+/// File the metadata is written to while it is being edited
+pub fn edit_file() -> PathBuf {
+    paths::cache_dir().join("metadata.json")
+}
+
+/// The editor from `$VISUAL` or `$EDITOR`, `vi` or Notepad on Windows if neither is set.
+/// The variable may contain arguments, e.g. `code --wait`.
+pub fn editor_command(file: &Path) -> Command {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.into());
+
+    let mut parts = editor.split_whitespace();
+    let mut command = Command::new(parts.next().unwrap_or(DEFAULT_EDITOR));
+    command.args(parts).arg(file);
+    command
+}
+
+/// Writes the metadata to the edit file
+pub fn prepare(editable: &Editable) -> io::Result<PathBuf> {
+    let file = edit_file();
+    fs::create_dir_all(paths::cache_dir())?;
+    fs::write(&file, serde_json::to_string_pretty(editable)?)?;
+    Ok(file)
+}
+
+/// Reads the edited metadata back and removes the edit file
+pub fn collect(file: &Path) -> io::Result<Editable> {
+    let content = fs::read_to_string(file)?;
+    let _ = fs::remove_file(file);
+    serde_json::from_str(&content).map_err(io::Error::other)
+}
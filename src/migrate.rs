@@ -0,0 +1,120 @@
+use nano_vectordb_rs::{Data, NanoVectorDB};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    atomic,
+    config::Config,
+    index::find_images,
+    paths::{self, LEGACY_DIR},
+    session::Session,
+    trash::{JOURNAL_FILE, Operation},
+};
+
+/// Moves the database, settings and trash of the old `images/` layout into the XDG directories.
+///
+/// The images themselves stay where they are, the old directory becomes the configured library.
+/// Paths stored in the index, session and trash journal are made absolute so they keep working
+/// no matter where findimg is started from. Does nothing if there is no old layout or the
+/// new database already exists.
+pub fn migrate_legacy_layout() -> io::Result<()> {
+    let legacy_dir = Path::new(LEGACY_DIR);
+    let legacy_database = legacy_dir.join("embeddings.db");
+    if !legacy_database.exists() || paths::database_file().exists() {
+        return Ok(());
+    }
+
+    println!(
+        "Migrating {} to {} and {}",
+        legacy_dir.display(),
+        paths::data_dir().display(),
+        paths::config_dir().display()
+    );
+    fs::create_dir_all(paths::data_dir())?;
+    fs::create_dir_all(paths::config_dir())?;
+
+    let legacy_config = legacy_dir.join("config.json");
+    if legacy_config.exists() && !paths::config_file().exists() {
+        fs::rename(&legacy_config, paths::config_file())?;
+    }
+    let mut config = Config::load();
+    if config.library_dir.is_none() {
+        config.library_dir = Some(std::path::absolute(legacy_dir)?);
+        config.save()?;
+    }
+
+    migrate_database(legacy_dir, &legacy_database)?;
+    migrate_trash(&legacy_dir.join(".trash"))?;
+
+    let legacy_session = legacy_dir.join("session.json");
+    if legacy_session.exists() {
+        fs::rename(&legacy_session, paths::session_file())?;
+        if let Some(mut session) = Session::load() {
+            for (path, _) in &mut session.results {
+                *path = absolute(path);
+            }
+            session.save()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies every embedding into the new database under its absolute path.
+/// The old database is kept with a `.migrated` suffix.
+fn migrate_database(legacy_dir: &Path, legacy_database: &Path) -> io::Result<()> {
+    let legacy =
+        NanoVectorDB::new(768, &legacy_database.to_string_lossy()).map_err(io::Error::other)?;
+    let mut database = NanoVectorDB::new(768, &paths::database_file().to_string_lossy())
+        .map_err(io::Error::other)?;
+
+    let data: Vec<Data> = legacy
+        .get(&find_images(legacy_dir))
+        .into_iter()
+        .map(|data| Data {
+            id: absolute(&data.id),
+            vector: data.vector.clone(),
+            fields: data.fields.clone(),
+        })
+        .collect();
+    println!("Moving {} embeddings", data.len());
+
+    database.upsert(data).map_err(io::Error::other)?;
+    atomic::save_database(&database, &paths::database_file())?;
+
+    let mut backup = legacy_database.as_os_str().to_owned();
+    backup.push(".migrated");
+    fs::rename(legacy_database, PathBuf::from(backup))
+}
+
+/// Moves the trash and rewrites its journal to point at the new locations
+fn migrate_trash(legacy_trash: &Path) -> io::Result<()> {
+    if !legacy_trash.exists() || paths::trash_dir().exists() {
+        return Ok(());
+    }
+    fs::rename(legacy_trash, paths::trash_dir())?;
+
+    let journal_file = paths::trash_dir().join(JOURNAL_FILE);
+    let Ok(content) = fs::read_to_string(&journal_file) else {
+        return Ok(());
+    };
+    let mut journal: Vec<Operation> = serde_json::from_str(&content).unwrap_or_default();
+    for file in journal
+        .iter_mut()
+        .flat_map(|operation| &mut operation.files)
+    {
+        file.original = absolute(&file.original);
+        if let Some(name) = Path::new(&file.staged).file_name() {
+            file.staged = paths::trash_dir().join(name).display().to_string();
+        }
+    }
+    atomic::write(&journal_file, serde_json::to_string(&journal)?)
+}
+
+fn absolute(path: &str) -> String {
+    std::path::absolute(path)
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| path.to_string())
+}
@@ -0,0 +1,113 @@
+use cliprs::ClipModel;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    config::{Config, ModelConfig},
+    engine::Similarity,
+    index, paths,
+    store::Store,
+};
+
+/// A model that can be picked for a query, together with its own index
+pub struct ModelSlot {
+    /// Name shown in the model selector
+    pub name: String,
+    /// Path of the model file
+    pub path: String,
+    /// The model, `None` while it is loaded in the background
+    pub model: Option<Arc<ClipModel>>,
+    /// Embeddings of the library made with this model
+    pub database: Arc<RwLock<Store>>,
+    /// Similarity function used to rank with this model
+    pub similarity: Similarity,
+}
+
+impl ModelSlot {
+    fn open(name: String, path: String, info: IndexInfo, config: &Config) -> Self {
+        let database = index::open_database(config, &info);
+        Self {
+            similarity: info.similarity,
+            name,
+            path,
+            model: None,
+            database: Arc::new(RwLock::new(database)),
+        }
+    }
+}
+
+/// Name of the main model in the model selector, also identifies its cached embeddings
+pub fn main_name(model_path: &Path) -> String {
+    model_path
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "main".into())
+}
+
+/// Opens the indexes of the main model and of every additional model in the config.
+/// The main model comes first and keeps the index of the single model setup.
+pub fn open_slots(config: &Config, model_path: &str) -> Vec<ModelSlot> {
+    let mut slots = vec![ModelSlot::open(
+        main_name(Path::new(model_path)),
+        model_path.to_string(),
+        main_index(config, Some(Path::new(model_path))),
+        config,
+    )];
+
+    for model in &config.models {
+        slots.push(ModelSlot::open(
+            model.name.clone(),
+            model.path.display().to_string(),
+            model_index(config, model),
+            config,
+        ));
+    }
+    slots
+}
+
+/// Where the index of a model is stored and how its embeddings look
+pub struct IndexInfo {
+    pub database_file: PathBuf,
+    pub dimensions: usize,
+    pub similarity: Similarity,
+}
+
+/// The index of the main model, which keeps the index of the single model setup
+pub fn main_index(config: &Config, main_model: Option<&Path>) -> IndexInfo {
+    IndexInfo {
+        database_file: paths::database_file(),
+        dimensions: index::DIMENSIONS,
+        similarity: main_model.map_or(config.similarity, |path| {
+            config.similarity_for(&path.to_string_lossy())
+        }),
+    }
+}
+
+fn model_index(config: &Config, model: &ModelConfig) -> IndexInfo {
+    IndexInfo {
+        database_file: paths::model_database_file(&model.name),
+        dimensions: model.dimensions,
+        similarity: config.similarity_for(&model.path.to_string_lossy()),
+    }
+}
+
+/// Finds the index of the model with the given name, or of the main model if there is none.
+/// The main model can also be named like in the model selector.
+pub fn find_index(
+    config: &Config,
+    main_model: Option<&Path>,
+    name: Option<&str>,
+) -> Result<IndexInfo, String> {
+    let Some(name) = name else {
+        return Ok(main_index(config, main_model));
+    };
+    if let Some(model) = config.models.iter().find(|model| model.name == name) {
+        return Ok(model_index(config, model));
+    }
+    if main_model.map_or("main".into(), main_name) == name {
+        return Ok(main_index(config, main_model));
+    }
+    Err(format!("There is no model named {} in the config", name))
+}
@@ -0,0 +1,87 @@
+use crate::{expansion::normalized, store::VectorStore};
+
+/// Most groups the library is clustered into
+const MAX_CLUSTERS: usize = 16;
+/// Rounds of k-means, enough for the centers to settle on a library
+const ITERATIONS: usize = 10;
+/// Share of the library a cluster needs to count as a kind of image the library has, smaller
+/// ones are likely a handful of junk that found each other
+const MIN_CLUSTER_SHARE: f32 = 0.01;
+
+/// Ranks the images by how unlike everything else in the library they are, most unusual
+/// first: corrupted files, stray screenshots and other junk to clean out.
+///
+/// The library is clustered with spherical k-means and every image scores one minus its cosine
+/// similarity to the nearest cluster center, so a library of e.g. pets and documents doesn't
+/// flag the smaller of the two. Clusters too small to be a kind of image are left out.
+pub fn rank(database: &dyn VectorStore, paths: &[String]) -> Vec<(String, f32)> {
+    let data = database.get(paths);
+    let dimensions = data
+        .iter()
+        .map(|data| data.vector.len())
+        .find(|length| *length > 0)
+        .unwrap_or_default();
+    let rows: Vec<(&str, Vec<f32>)> = data
+        .iter()
+        .filter(|data| data.vector.len() == dimensions && dimensions > 0)
+        .map(|data| (data.id.as_str(), normalized(data.vector.clone())))
+        .collect();
+    if rows.is_empty() {
+        return Vec::new();
+    }
+
+    let clusters = ((rows.len() as f32 / 2.0).sqrt() as usize).clamp(1, MAX_CLUSTERS);
+    // spread over the library, so the start doesn't depend on chance
+    let mut centers: Vec<Vec<f32>> = (0..clusters)
+        .map(|cluster| rows[cluster * rows.len() / clusters].1.clone())
+        .collect();
+    let mut assigned = vec![0; rows.len()];
+    for _ in 0..ITERATIONS {
+        for ((_, vector), cluster) in rows.iter().zip(&mut assigned) {
+            *cluster = nearest(&centers, vector).0;
+        }
+        for (index, center) in centers.iter_mut().enumerate() {
+            let mut sum = vec![0.0; dimensions];
+            for ((_, vector), _) in rows
+                .iter()
+                .zip(&assigned)
+                .filter(|(_, cluster)| **cluster == index)
+            {
+                for (total, value) in sum.iter_mut().zip(vector) {
+                    *total += value;
+                }
+            }
+            // an empty cluster keeps its center
+            if sum.iter().any(|value| *value != 0.0) {
+                *center = normalized(sum);
+            }
+        }
+    }
+
+    let min_size = (rows.len() as f32 * MIN_CLUSTER_SHARE).ceil() as usize;
+    let kept: Vec<Vec<f32>> = centers
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| {
+            assigned.iter().filter(|cluster| *cluster == index).count() >= min_size
+        })
+        .map(|(_, center)| center)
+        .collect();
+
+    let mut ranked: Vec<(String, f32)> = rows
+        .iter()
+        .map(|(path, vector)| (path.to_string(), 1.0 - nearest(&kept, vector).1))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked
+}
+
+/// The index of the center most similar to a normalized vector, with their cosine similarity
+fn nearest(centers: &[Vec<f32>], vector: &[f32]) -> (usize, f32) {
+    centers
+        .iter()
+        .map(|center| center.iter().zip(vector).map(|(a, b)| a * b).sum::<f32>())
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((0, 0.0))
+}
@@ -0,0 +1,264 @@
+//! A minimal Parquet writer for a table of image paths and their embeddings.
+//!
+//! The file holds one row group with two uncompressed columns: `path`, a UTF-8 string, and
+//! `vector`, a repeated float that readers like pyarrow and pandas load as a list column.
+//! The metadata is encoded with Thrift's compact protocol as the format requires.
+
+use std::io::{self, Write};
+
+const MAGIC: &[u8] = b"PAR1";
+const CREATED_BY: &str = "findimg";
+
+/// Parquet physical types
+const TYPE_FLOAT: i32 = 4;
+const TYPE_BYTE_ARRAY: i32 = 6;
+/// Parquet repetition types
+const REQUIRED: i32 = 0;
+const REPEATED: i32 = 2;
+/// Converted type marking byte arrays as UTF-8 strings
+const CONVERTED_UTF8: i32 = 0;
+/// Encodings of values and levels
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const CODEC_UNCOMPRESSED: i32 = 0;
+const PAGE_DATA: i32 = 0;
+
+/// Thrift compact protocol types
+const COMPACT_I32: u8 = 5;
+const COMPACT_I64: u8 = 6;
+const COMPACT_BINARY: u8 = 8;
+const COMPACT_LIST: u8 = 9;
+const COMPACT_STRUCT: u8 = 12;
+
+/// Writes the rows as a Parquet file. Every vector must have `dimensions` values.
+pub fn write_vectors(
+    out: &mut impl Write,
+    rows: &[(&str, &[f32])],
+    dimensions: usize,
+) -> io::Result<()> {
+    let mut file = MAGIC.to_vec();
+
+    // the paths are required, so their page holds only the values
+    let mut paths = Vec::new();
+    for (path, _) in rows {
+        paths.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        paths.extend_from_slice(path.as_bytes());
+    }
+    let path_chunk = write_page(&mut file, rows.len(), &paths);
+
+    // the vectors are repeated: a repetition level of 0 starts a new row,
+    // and every value is defined since no vector is empty
+    let mut repetition = Vec::new();
+    for _ in rows {
+        rle_run(&mut repetition, 1, 0);
+        rle_run(&mut repetition, dimensions.saturating_sub(1), 1);
+    }
+    let mut definition = Vec::new();
+    rle_run(&mut definition, rows.len() * dimensions, 1);
+
+    let mut vectors = Vec::new();
+    for levels in [repetition, definition] {
+        vectors.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+        vectors.extend_from_slice(&levels);
+    }
+    for (_, vector) in rows {
+        for value in *vector {
+            vectors.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    let vector_chunk = write_page(&mut file, rows.len() * dimensions, &vectors);
+
+    let metadata = file_metadata(
+        rows.len(),
+        &[
+            ("path", TYPE_BYTE_ARRAY, path_chunk),
+            ("vector", TYPE_FLOAT, vector_chunk),
+        ],
+    );
+    file.extend_from_slice(&metadata);
+    file.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    file.extend_from_slice(MAGIC);
+    out.write_all(&file)
+}
+
+/// Where a column chunk was written, as needed for the file metadata
+struct Chunk {
+    offset: usize,
+    size: usize,
+    values: usize,
+}
+
+/// Appends a data page with its header and returns where it is
+fn write_page(file: &mut Vec<u8>, values: usize, data: &[u8]) -> Chunk {
+    let mut header = Compact::default();
+    header.i32(1, PAGE_DATA);
+    header.i32(2, data.len() as i32);
+    header.i32(3, data.len() as i32);
+    header.begin_struct(5);
+    header.i32(1, values as i32);
+    header.i32(2, ENCODING_PLAIN);
+    header.i32(3, ENCODING_RLE);
+    header.i32(4, ENCODING_RLE);
+    header.end_struct();
+    header.end_struct();
+
+    let offset = file.len();
+    file.extend_from_slice(&header.buffer);
+    file.extend_from_slice(data);
+    Chunk {
+        offset,
+        size: header.buffer.len() + data.len(),
+        values,
+    }
+}
+
+/// Appends an RLE run of a level to a level buffer with a bit width of 1
+fn rle_run(buffer: &mut Vec<u8>, count: usize, level: u8) {
+    if count > 0 {
+        varint(buffer, (count as u64) << 1);
+        buffer.push(level);
+    }
+}
+
+fn file_metadata(rows: usize, columns: &[(&str, i32, Chunk)]) -> Vec<u8> {
+    let mut metadata = Compact::default();
+    metadata.i32(1, 1);
+
+    metadata.begin_list(2, COMPACT_STRUCT, columns.len() + 1);
+    metadata.begin_element();
+    metadata.string(4, "schema");
+    metadata.i32(5, columns.len() as i32);
+    metadata.end_struct();
+    for (name, physical, _) in columns {
+        metadata.begin_element();
+        metadata.i32(1, *physical);
+        if *physical == TYPE_BYTE_ARRAY {
+            metadata.i32(3, REQUIRED);
+            metadata.string(4, name);
+            metadata.i32(6, CONVERTED_UTF8);
+        } else {
+            metadata.i32(3, REPEATED);
+            metadata.string(4, name);
+        }
+        metadata.end_struct();
+    }
+
+    metadata.i64(3, rows as i64);
+
+    metadata.begin_list(4, COMPACT_STRUCT, 1);
+    metadata.begin_element();
+    metadata.begin_list(1, COMPACT_STRUCT, columns.len());
+    for (name, physical, chunk) in columns {
+        metadata.begin_element();
+        metadata.i64(2, chunk.offset as i64);
+        metadata.begin_struct(3);
+        metadata.i32(1, *physical);
+        metadata.begin_list(2, COMPACT_I32, 2);
+        metadata.list_i32(ENCODING_PLAIN);
+        metadata.list_i32(ENCODING_RLE);
+        metadata.begin_list(3, COMPACT_BINARY, 1);
+        metadata.list_string(name);
+        metadata.i32(4, CODEC_UNCOMPRESSED);
+        metadata.i64(5, chunk.values as i64);
+        metadata.i64(6, chunk.size as i64);
+        metadata.i64(7, chunk.size as i64);
+        metadata.i64(9, chunk.offset as i64);
+        metadata.end_struct();
+        metadata.end_struct();
+    }
+    let total: usize = columns.iter().map(|(_, _, chunk)| chunk.size).sum();
+    metadata.i64(2, total as i64);
+    metadata.i64(3, rows as i64);
+    metadata.end_struct();
+
+    metadata.string(6, CREATED_BY);
+    metadata.end_struct();
+    metadata.buffer
+}
+
+/// Encoder for Thrift's compact protocol. Field ids are written as deltas to the previous
+/// field of the same struct, so the last id of every enclosing struct is kept on a stack.
+#[derive(Default)]
+struct Compact {
+    buffer: Vec<u8>,
+    last_ids: Vec<i16>,
+    last_id: i16,
+}
+
+impl Compact {
+    fn field(&mut self, id: i16, kind: u8) {
+        let delta = id - self.last_id;
+        if (1..=15).contains(&delta) {
+            self.buffer.push(((delta as u8) << 4) | kind);
+        } else {
+            self.buffer.push(kind);
+            varint(&mut self.buffer, zigzag(id as i64));
+        }
+        self.last_id = id;
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, COMPACT_I32);
+        varint(&mut self.buffer, zigzag(value as i64));
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, COMPACT_I64);
+        varint(&mut self.buffer, zigzag(value));
+    }
+
+    fn string(&mut self, id: i16, value: &str) {
+        self.field(id, COMPACT_BINARY);
+        self.list_string(value);
+    }
+
+    fn begin_struct(&mut self, id: i16) {
+        self.field(id, COMPACT_STRUCT);
+        self.last_ids.push(self.last_id);
+        self.last_id = 0;
+    }
+
+    /// Ends the innermost struct or list element
+    fn end_struct(&mut self) {
+        self.buffer.push(0);
+        self.last_id = self.last_ids.pop().unwrap_or_default();
+    }
+
+    /// Starts a list field, its elements follow without field headers
+    fn begin_list(&mut self, id: i16, kind: u8, size: usize) {
+        self.field(id, COMPACT_LIST);
+        if size < 15 {
+            self.buffer.push(((size as u8) << 4) | kind);
+        } else {
+            self.buffer.push(0xf0 | kind);
+            varint(&mut self.buffer, size as u64);
+        }
+    }
+
+    /// Starts a struct that is an element of a list, ended with `end_struct`
+    fn begin_element(&mut self) {
+        self.last_ids.push(self.last_id);
+        self.last_id = 0;
+    }
+
+    fn list_i32(&mut self, value: i32) {
+        varint(&mut self.buffer, zigzag(value as i64));
+    }
+
+    fn list_string(&mut self, value: &str) {
+        varint(&mut self.buffer, value.len() as u64);
+        self.buffer.extend_from_slice(value.as_bytes());
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
@@ -0,0 +1,160 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+const APP_NAME: &str = "findimg";
+
+/// Where an opened bundle was unpacked, it takes the place of the config and data directories
+static BUNDLE_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// Where an encrypted index was decrypted to, it holds the index files of this run
+static UNLOCKED_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Directory that held the images, database and settings before the XDG layout
+pub const LEGACY_DIR: &str = "images";
+
+/// Directory for settings, `$XDG_CONFIG_HOME/findimg` on Linux
+pub fn config_dir() -> PathBuf {
+    if let Some(dir) = BUNDLE_DIR.get() {
+        return dir.clone();
+    }
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(APP_NAME)
+}
+
+/// Directory for the index and other state, `$XDG_DATA_HOME/findimg` on Linux
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = BUNDLE_DIR.get() {
+        return dir.clone();
+    }
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(APP_NAME)
+}
+
+/// Directory for data that can be regenerated, `$XDG_CACHE_HOME/findimg` on Linux
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(APP_NAME)
+}
+
+/// Uses the directory of a bundle for the settings, index and state of this run,
+/// see `bundle::open`
+pub fn use_bundle_dir(dir: PathBuf) {
+    let _ = BUNDLE_DIR.set(dir);
+}
+
+/// Uses the decrypted index in `dir` for this run, see `encryption::unlock`
+pub fn use_unlocked_dir(dir: PathBuf) {
+    let _ = UNLOCKED_DIR.set(dir);
+}
+
+/// Whether the index is decrypted for this run, see `use_unlocked_dir`
+pub fn index_unlocked() -> bool {
+    UNLOCKED_DIR.get().is_some()
+}
+
+/// Directory of the embeddings and metadata, the data directory unless the index is encrypted
+fn index_dir() -> PathBuf {
+    UNLOCKED_DIR.get().cloned().unwrap_or_else(data_dir)
+}
+
+pub fn config_file() -> PathBuf {
+    config_dir().join("config.json")
+}
+
+pub fn database_file() -> PathBuf {
+    index_dir().join("embeddings.db")
+}
+
+/// Index of an additional model from the config, see `Config::models`
+pub fn model_database_file(name: &str) -> PathBuf {
+    index_dir().join("indexes").join(format!("{}.db", name))
+}
+
+/// Metadata of the images, shared by the indexes of all models
+pub fn catalog_file() -> PathBuf {
+    index_dir().join("metadata.sqlite")
+}
+
+/// The embeddings and metadata encrypted with a passphrase, see `encryption::Unlocked`
+pub fn encrypted_index_file() -> PathBuf {
+    data_dir().join("index.enc")
+}
+
+/// The last search and its results, kept with the index so they are encrypted along with it
+pub fn session_file() -> PathBuf {
+    index_dir().join("session.json")
+}
+
+/// Results pinned to the tray
+pub fn pins_file() -> PathBuf {
+    data_dir().join("pins.json")
+}
+
+/// Images on the web that are indexed like the library, see `remote::Remotes`
+pub fn remote_file() -> PathBuf {
+    data_dir().join("remote.json")
+}
+
+/// Thumbnails of the remote items, the only local copy of them
+pub fn thumbnails_dir() -> PathBuf {
+    data_dir().join("thumbnails")
+}
+
+/// Album images downloaded before and where they ended up, see `google_photos::scrape`
+pub fn scraped_file() -> PathBuf {
+    data_dir().join("scraped.json")
+}
+
+/// Shared albums the library is kept in step with, see `sync::Albums`
+pub fn albums_file() -> PathBuf {
+    data_dir().join("albums.json")
+}
+
+/// Rhai scripts that adjust the ranking and add result actions, see `plugins::Plugins`
+pub fn plugins_dir() -> PathBuf {
+    config_dir().join("plugins")
+}
+
+/// Liked and disliked results with the queries they were rated for, kept with the index
+pub fn feedback_file() -> PathBuf {
+    index_dir().join("feedback.json")
+}
+
+/// Graphics capabilities of the terminals findimg ran in
+pub fn terminals_file() -> PathBuf {
+    cache_dir().join("terminals.json")
+}
+
+/// Where a drawn or pasted sketch is stored for the sketch mode
+pub fn sketch_file() -> PathBuf {
+    cache_dir().join("sketch.png")
+}
+
+pub fn trash_dir() -> PathBuf {
+    data_dir().join("trash")
+}
+
+/// Where images are stored if no library directory is configured
+pub fn default_library_dir() -> PathBuf {
+    data_dir().join("images")
+}
+
+/// The absolute path of `path` with symlinks resolved. Unlike `fs::canonicalize` it leaves
+/// out the `\\?\` prefix on Windows, which the index and other programs don't use.
+pub fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+    let canonical = fs::canonicalize(path)?;
+    #[cfg(windows)]
+    if let Some(plain) = canonical
+        .to_str()
+        .and_then(|path| path.strip_prefix(r"\\?\"))
+        .filter(|path| !path.starts_with(r"UNC\"))
+    {
+        return Ok(PathBuf::from(plain));
+    }
+    Ok(canonical)
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{atomic, paths, remote};
+
+/// Results pinned to the tray, kept across searches and sessions
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Pins {
+    paths: Vec<String>,
+}
+
+impl Pins {
+    /// Loads the pins. A missing or broken file results in an empty tray, pinned images that
+    /// were deleted since are left out.
+    pub fn load() -> Self {
+        let mut pins: Self = fs::read_to_string(paths::pins_file())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        pins.paths
+            .retain(|path| remote::is_remote(path) || Path::new(path).exists());
+        pins
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(paths::data_dir())?;
+        let content = serde_json::to_string(self)?;
+        atomic::write(&paths::pins_file(), content)
+    }
+
+    /// Pins an image or unpins it if it is pinned already. Returns whether it is pinned now.
+    pub fn toggle(&mut self, path: &str) -> bool {
+        if let Some(index) = self.paths.iter().position(|pinned| pinned == path) {
+            self.paths.remove(index);
+            false
+        } else {
+            self.paths.push(path.to_string());
+            true
+        }
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.paths.iter().any(|pinned| pinned == path)
+    }
+
+    /// Paths of the pinned images in the order they were pinned
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    pub fn clear(&mut self) {
+        self.paths.clear();
+    }
+}
+
+/// Opens the images in the default viewer of the desktop without waiting for it,
+/// remote items in the browser
+pub fn open(paths: &[String]) -> io::Result<()> {
+    if cfg!(target_os = "macos") {
+        return spawn(Command::new("open").args(paths));
+    }
+    if cfg!(windows) {
+        return paths.iter().try_for_each(|path| start(path));
+    }
+    // xdg-open only takes a single file
+    for path in paths {
+        spawn(Command::new("xdg-open").arg(path))?;
+    }
+    Ok(())
+}
+
+/// Opens a file or URL with `start` of cmd, which picks the default application
+#[cfg(windows)]
+fn start(path: &str) -> io::Result<()> {
+    use std::os::windows::process::CommandExt;
+
+    // quoted by hand, cmd would split a URL at its `&` otherwise. The empty argument is
+    // the window title, `start` would take the quoted path for it.
+    spawn(Command::new("cmd").raw_arg(format!("/C start \"\" \"{}\"", path)))
+}
+
+#[cfg(not(windows))]
+fn start(_: &str) -> io::Result<()> {
+    Err(io::Error::other("only supported on Windows"))
+}
+
+fn spawn(command: &mut Command) -> io::Result<()> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
@@ -0,0 +1,83 @@
+use cliprs::ClipModel;
+use std::{
+    io::{self, BufRead},
+    path::Path,
+};
+
+use crate::{
+    cache::{VectorCache, content_hash},
+    config::Config,
+    engine::Similarity,
+    expansion, paths, query,
+    store::VectorStore,
+};
+
+/// Reads the paths piped to `--stdin`, one per line like `fd` and `find` print them
+pub fn read_paths(input: impl BufRead) -> io::Result<Vec<String>> {
+    let mut paths = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            paths.push(line);
+        }
+    }
+    Ok(paths)
+}
+
+/// Ranks only the given files by similarity to the query, from best match to worst.
+///
+/// Images of the library use their embeddings from the index, other files are looked up in
+/// the vector cache by content hash and embedded and cached if they aren't there yet, so
+/// piping the same files again is fast. Files that can't be embedded are skipped with a
+/// warning on stderr, which keeps stdout clean for the next command in the pipeline.
+pub fn rank(
+    model: &ClipModel,
+    database: &dyn VectorStore,
+    cache: &VectorCache,
+    config: &Config,
+    similarity: Similarity,
+    text: &str,
+    paths: &[String],
+) -> Result<Vec<(String, f32)>, String> {
+    let query = query::parse(text);
+    if !query.filters.is_empty() {
+        return Err(
+            "Search filters need the metadata of the library and don't work with --stdin".into(),
+        );
+    }
+    let embedding = expansion::embed_query(model, &query.text, config)?;
+
+    let mut ranked = Vec::new();
+    for path in paths {
+        match vector(model, database, cache, path) {
+            Ok(vector) => ranked.push((path.clone(), similarity.score(&embedding, &vector))),
+            Err(e) => eprintln!("Skipping {}: {}", path, e),
+        }
+    }
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    Ok(ranked)
+}
+
+fn vector(
+    model: &ClipModel,
+    database: &dyn VectorStore,
+    cache: &VectorCache,
+    path: &str,
+) -> Result<Vec<f32>, String> {
+    // the index names images by the path they were found at, which may not be canonical
+    let canonical = paths::canonicalize(Path::new(path)).map_err(|e| e.to_string())?;
+    let ids = [path.to_string(), canonical.display().to_string()];
+    if let Some(data) = database.get(&ids).first() {
+        return Ok(data.vector.clone());
+    }
+
+    let hash = content_hash(path).map_err(|e| e.to_string())?;
+    if let Some(vector) = cache.get(&hash) {
+        return Ok(vector);
+    }
+    let vector = model.embed_image(path)?;
+    if let Err(e) = cache.put(&hash, &vector) {
+        eprintln!("Failed to cache the embedding of {}: {}", path, e);
+    }
+    Ok(vector)
+}
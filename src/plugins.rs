@@ -0,0 +1,316 @@
+use cliprs::log_warning;
+use rhai::{
+    AST, Array, Dynamic, Engine, EvalAltResult, Scope, module_resolvers::DummyModuleResolver,
+};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{paths, pins};
+
+/// Operations a single call into a plugin may take before it's stopped, so a plugin stuck
+/// in a loop can't hang a search
+const MAX_OPERATIONS: u64 = 1_000_000;
+/// Best candidates of a search whose score plugins adjust, the rest keep their order
+const ADJUSTED_CANDIDATES: usize = 500;
+
+/// Rhai scripts from the `plugins` directory of the config directory.
+///
+/// A plugin can define any of these functions:
+/// - `adjust_score(path, score, fields)` returns the new score of a search candidate,
+///   `fields` is a map of the metadata of the image
+/// - `actions()` returns an array of action names shown in the command palette
+/// - `run_action(name, path)` runs one of the actions on the selected result, a string it
+///   returns is shown as a notification
+///
+/// Scripts can't read or write files or import modules. Besides the standard library they
+/// get `open(path)`, which opens a file or URL in the default application,
+/// `spawn(program, args)`, which starts a program without waiting for it, and
+/// `file_name(path)` and `parent_dir(path)`. `print` shows a warning, for debugging.
+pub struct Plugins {
+    engine: Engine,
+    scripts: Vec<Script>,
+}
+
+struct Script {
+    /// File name without the extension
+    name: String,
+    ast: AST,
+}
+
+/// A result action registered by a plugin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginAction {
+    pub name: String,
+    /// Index of the plugin in `Plugins::scripts`
+    script: usize,
+}
+
+impl Plugins {
+    /// Compiles every `.rhai` file in the plugins directory. Plugins that fail to compile
+    /// are left out, the errors are returned to be shown.
+    pub fn load() -> (Self, Vec<String>) {
+        let mut plugins = Self {
+            engine: engine(),
+            scripts: Vec::new(),
+        };
+        let mut errors = Vec::new();
+
+        let mut files: Vec<_> = fs::read_dir(paths::plugins_dir())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "rhai")
+            })
+            .collect();
+        files.sort();
+        for file in files {
+            let name = file
+                .file_stem()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let compiled = fs::read_to_string(&file)
+                .map_err(|e| e.to_string())
+                .and_then(|script| plugins.engine.compile(script).map_err(|e| e.to_string()));
+            match compiled {
+                Ok(ast) => plugins.scripts.push(Script { name, ast }),
+                Err(e) => errors.push(format!("Plugin {} failed to load: {}", name, e)),
+            }
+        }
+        (plugins, errors)
+    }
+
+    /// The result actions of all plugins, in the order of the plugin files
+    pub fn actions(&self) -> Vec<PluginAction> {
+        let mut actions = Vec::new();
+        for (index, script) in self.scripts.iter().enumerate() {
+            if !defines(&script.ast, "actions") {
+                continue;
+            }
+            let names = self.call(script, "actions", ()).and_then(|names| {
+                names.try_cast::<Array>().ok_or(format!(
+                    "Plugin {}: actions has to return an array",
+                    script.name
+                ))
+            });
+            match names {
+                Ok(names) => actions.extend(names.into_iter().map(|name| PluginAction {
+                    name: name.to_string(),
+                    script: index,
+                })),
+                Err(e) => log_warning(e),
+            }
+        }
+        actions
+    }
+
+    /// Runs an action on a result. Returns the message of the plugin, if it has one.
+    pub fn run_action(&self, action: &PluginAction, path: &str) -> Result<Option<String>, String> {
+        let script = &self.scripts[action.script];
+        let message = self.call(
+            script,
+            "run_action",
+            (action.name.clone(), path.to_string()),
+        )?;
+        Ok(message.try_cast::<String>())
+    }
+
+    /// Lets every plugin with an `adjust_score` function re-score the best candidates of a
+    /// ranking, then sorts them by the new scores. A plugin that fails for a candidate is
+    /// skipped for the whole ranking, so its scores don't mix with the ones it didn't adjust.
+    pub fn adjust_scores(
+        &self,
+        ranking: &mut [(String, f32)],
+        fields: impl Fn(&str) -> HashMap<String, Value>,
+    ) {
+        let scripts: Vec<&Script> = self
+            .scripts
+            .iter()
+            .filter(|script| defines(&script.ast, "adjust_score"))
+            .collect();
+        if scripts.is_empty() {
+            return;
+        }
+
+        let count = ranking.len().min(ADJUSTED_CANDIDATES);
+        let candidates = &mut ranking[..count];
+        let fields: Vec<Dynamic> = candidates
+            .iter()
+            .map(|(path, _)| rhai::serde::to_dynamic(fields(path)).unwrap_or_default())
+            .collect();
+        for script in &scripts {
+            let adjusted: Result<Vec<f32>, String> = candidates
+                .iter()
+                .zip(&fields)
+                .map(|((path, score), fields)| {
+                    let adjusted = self.call(
+                        script,
+                        "adjust_score",
+                        (path.clone(), *score as f64, fields.clone()),
+                    )?;
+                    adjusted
+                        .as_float()
+                        .or(adjusted.as_int().map(|int| int as f64))
+                        .map(|adjusted| adjusted as f32)
+                        .map_err(|_| {
+                            format!(
+                                "Plugin {}: adjust_score has to return a number",
+                                script.name
+                            )
+                        })
+                })
+                .collect();
+            match adjusted {
+                Ok(adjusted) => {
+                    for ((_, score), adjusted) in candidates.iter_mut().zip(adjusted) {
+                        *score = adjusted;
+                    }
+                }
+                Err(e) => log_warning(e),
+            }
+        }
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+    }
+
+    fn call(
+        &self,
+        script: &Script,
+        function: &str,
+        args: impl rhai::FuncArgs,
+    ) -> Result<Dynamic, String> {
+        self.engine
+            .call_fn(&mut Scope::new(), &script.ast, function, args)
+            .map_err(|e| format!("Plugin {}: {}", script.name, e))
+    }
+}
+
+fn defines(ast: &AST, function: &str) -> bool {
+    ast.iter_functions()
+        .any(|metadata| metadata.name == function)
+}
+
+/// The engine plugins run in, limited to the documented API
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_module_resolver(DummyModuleResolver::new())
+        .on_print(|text| log_warning(text.to_string()))
+        .on_debug(|text, _, _| log_warning(text.to_string()));
+
+    engine.register_fn("open", |path: &str| -> Result<(), Box<EvalAltResult>> {
+        pins::open(&[path.to_string()]).map_err(|e| e.to_string().into())
+    });
+    engine.register_fn(
+        "spawn",
+        |program: &str, args: Array| -> Result<(), Box<EvalAltResult>> {
+            Command::new(program)
+                .args(args.iter().map(|arg| arg.to_string()))
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map(|_| ())
+                .map_err(|e| e.to_string().into())
+        },
+    );
+    engine.register_fn("file_name", |path: &str| -> String {
+        Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    engine.register_fn("parent_dir", |path: &str| -> String {
+        Path::new(path)
+            .parent()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default()
+    });
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plugins with an `adjust_score` function of the given body each
+    fn plugins(scripts: &[(&str, &str)]) -> Plugins {
+        let engine = engine();
+        let scripts = scripts
+            .iter()
+            .map(|(name, body)| Script {
+                name: name.to_string(),
+                ast: engine
+                    .compile(format!(
+                        "fn adjust_score(path, score, fields) {{ {} }}",
+                        body
+                    ))
+                    .unwrap(),
+            })
+            .collect();
+        Plugins { engine, scripts }
+    }
+
+    fn ranking() -> Vec<(String, f32)> {
+        vec![
+            ("a.jpg".into(), 0.9),
+            ("b.jpg".into(), 0.8),
+            ("c.jpg".into(), 0.7),
+        ]
+    }
+
+    #[test]
+    fn plugins_adjust_the_scores_in_turn_and_reorder_the_ranking() {
+        let plugins = plugins(&[
+            (
+                "favor_c",
+                r#"if path == "c.jpg" { score + 0.5 } else { score }"#,
+            ),
+            ("halve", "score / 2.0"),
+        ]);
+        let mut ranking = ranking();
+        plugins.adjust_scores(&mut ranking, |_| HashMap::new());
+
+        assert_eq!(
+            ranking,
+            [
+                ("c.jpg".to_string(), 0.6),
+                ("a.jpg".to_string(), 0.45),
+                ("b.jpg".to_string(), 0.4)
+            ]
+        );
+    }
+
+    #[test]
+    fn a_plugin_failing_for_a_candidate_is_skipped_for_the_whole_ranking() {
+        let plugins = plugins(&[
+            (
+                "throws",
+                r#"if path == "b.jpg" { throw "broken" } 1.0 - score"#,
+            ),
+            (
+                "not_a_number",
+                r#"if path == "c.jpg" { "high" } else { 0.0 }"#,
+            ),
+            ("favor_c", r#"if path == "c.jpg" { 1.0 } else { score }"#),
+        ]);
+        let mut ranking = ranking();
+        plugins.adjust_scores(&mut ranking, |_| HashMap::new());
+
+        assert_eq!(
+            ranking,
+            [
+                ("c.jpg".to_string(), 1.0),
+                ("a.jpg".to_string(), 0.9),
+                ("b.jpg".to_string(), 0.8)
+            ]
+        );
+    }
+}
@@ -0,0 +1,21 @@
+use image::{DynamicImage, imageops::FilterType};
+use std::path::{Path, PathBuf};
+
+/// Side in pixels private images are scaled down to before they are blown up again,
+/// coarse enough that nothing in them can be made out
+const PIXELATED_SIZE: u32 = 12;
+
+/// Whether `path` is inside one of the directories marked private in the config
+pub fn is_private(private_dirs: &[PathBuf], path: &str) -> bool {
+    let path = Path::new(path);
+    private_dirs.iter().any(|dir| path.starts_with(dir))
+}
+
+/// A blocky version of `image` at its original size, drawn in place of a private image
+/// until it's revealed
+pub fn pixelate(image: &DynamicImage) -> DynamicImage {
+    let (width, height) = (image.width().max(1), image.height().max(1));
+    image
+        .resize(PIXELATED_SIZE, PIXELATED_SIZE, FilterType::Triangle)
+        .resize_exact(width, height, FilterType::Nearest)
+}
@@ -0,0 +1,115 @@
+use serde_json::{Value, json};
+use std::path::Path;
+
+use crate::{
+    engine::{Hit, HitSource},
+    locale::Locale,
+    paths, remote,
+};
+
+/// Number of matches handed to the launcher
+pub const RESULTS: usize = 20;
+
+/// Launchers `provider` prints the matches for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LauncherFormat {
+    /// Items of a Raycast list, for a script or extension showing them
+    Raycast,
+    /// The JSON of an Alfred script filter
+    Alfred,
+    /// Matches of a KRunner D-Bus runner, for the script answering `Match`
+    Krunner,
+}
+
+impl LauncherFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "raycast" => Some(LauncherFormat::Raycast),
+            "alfred" => Some(LauncherFormat::Alfred),
+            "krunner" => Some(LauncherFormat::Krunner),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the matches of a query in the schema the launcher expects. Local images are
+/// their own thumbnail, remote items use the stored one and open their URL. Launchers run
+/// from anywhere, so local paths are made absolute.
+pub fn render(format: LauncherFormat, matches: &[Hit], locale: Locale) -> Value {
+    let items = matches.iter().map(|hit| {
+        let remote = hit.source == HitSource::Remote;
+        let score = hit.score;
+        let path = &if remote {
+            hit.path.clone()
+        } else {
+            paths::canonicalize(Path::new(&hit.path))
+                .map_or_else(|_| hit.path.clone(), |path| path.display().to_string())
+        };
+        let thumbnail = remote::local_file(path);
+        let title = Path::new(path)
+            .file_name()
+            .map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned());
+        let location = if remote {
+            path.clone()
+        } else {
+            Path::new(path)
+                .parent()
+                .map(|parent| parent.display().to_string())
+                .unwrap_or_default()
+        };
+        let subtitle = match hit.dims {
+            Some((width, height)) => format!(
+                "{} · {}×{} · {}",
+                locale.percent(score as f64),
+                width,
+                height,
+                location
+            ),
+            None => format!("{} · {}", locale.percent(score as f64), location),
+        };
+        let url = if remote {
+            path.clone()
+        } else {
+            format!("file://{}", path)
+        };
+
+        match format {
+            LauncherFormat::Raycast => json!({
+                "id": path,
+                "title": title,
+                "subtitle": subtitle,
+                "icon": thumbnail,
+                "path": path,
+                "url": url,
+                "score": score,
+                "keywords": hit.tags,
+            }),
+            LauncherFormat::Alfred => json!({
+                "uid": path,
+                "type": if remote { "default" } else { "file" },
+                "title": title,
+                "subtitle": subtitle,
+                "arg": path,
+                "icon": { "path": thumbnail },
+                "quicklookurl": url,
+            }),
+            // the fields of the `Match` tuple of the runner D-Bus interface, with its
+            // relevance from 0 to 1
+            LauncherFormat::Krunner => json!({
+                "id": path,
+                "text": title,
+                "icon": thumbnail,
+                "relevance": score.clamp(0.0, 1.0),
+                "properties": {
+                    "subtext": subtitle,
+                    "urls": [url],
+                },
+            }),
+        }
+    });
+
+    match format {
+        LauncherFormat::Alfred => json!({ "items": items.collect::<Vec<_>>() }),
+        LauncherFormat::Raycast | LauncherFormat::Krunner => Value::Array(items.collect()),
+    }
+}
@@ -0,0 +1,238 @@
+use cliprs::log_warning;
+use nano_vectordb_rs::Data;
+use reqwest::{
+    StatusCode,
+    blocking::{Client, RequestBuilder},
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, io};
+
+use crate::{
+    engine::Similarity,
+    store::{Entry, VectorStore},
+};
+
+/// Number of points sent or requested at once
+const BATCH_SIZE: usize = 500;
+
+/// A collection on a Qdrant server, accessed through its REST API.
+///
+/// Qdrant only accepts numbers and UUIDs as point ids, so every image gets a UUID made
+/// from the hash of its path. The path and the metadata are kept in the payload.
+pub struct QdrantStore {
+    client: Client,
+    url: String,
+    api_key: Option<String>,
+    collection: String,
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    result: T,
+}
+
+#[derive(Deserialize)]
+struct Point {
+    #[serde(default)]
+    payload: Payload,
+    #[serde(default)]
+    vector: Vec<f32>,
+    #[serde(default)]
+    score: f32,
+}
+
+#[derive(Deserialize, Default)]
+struct Payload {
+    #[serde(default)]
+    path: String,
+    #[serde(default)]
+    fields: HashMap<String, Value>,
+}
+
+impl QdrantStore {
+    /// Connects to the collection, creating it if it doesn't exist yet
+    pub fn open(
+        url: &str,
+        api_key: Option<String>,
+        collection: String,
+        dimensions: usize,
+        similarity: Similarity,
+    ) -> Result<Self, String> {
+        let store = Self {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            api_key,
+            collection,
+        };
+
+        let response = store
+            .request(store.client.get(store.collection_url("")))
+            .send()
+            .map_err(|e| e.to_string())?;
+        if response.status() == StatusCode::NOT_FOUND {
+            // Qdrant normalizes the vectors of cosine collections,
+            // the other similarities need them as they are
+            let distance = if similarity == Similarity::Cosine {
+                "Cosine"
+            } else {
+                "Dot"
+            };
+            store
+                .request(store.client.put(store.collection_url("")))
+                .body(
+                    json!({ "vectors": { "size": dimensions, "distance": distance } }).to_string(),
+                )
+                .send()
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| {
+                    format!(
+                        "Failed to create the collection {}: {}",
+                        store.collection, e
+                    )
+                })?;
+            return Ok(store);
+        }
+
+        let info = response
+            .error_for_status()
+            .and_then(|response| response.text())
+            .map_err(|e| e.to_string())?;
+        let info: Value = serde_json::from_str(&info).map_err(|e| e.to_string())?;
+        let size = info["result"]["config"]["params"]["vectors"]["size"].as_u64();
+        if size.is_some_and(|size| size as usize != dimensions) {
+            return Err(format!(
+                "The collection {} holds vectors with {} dimensions instead of {}",
+                store.collection,
+                size.unwrap_or_default(),
+                dimensions
+            ));
+        }
+        Ok(store)
+    }
+
+    fn collection_url(&self, path: &str) -> String {
+        format!("{}/collections/{}{}", self.url, self.collection, path)
+    }
+
+    fn request(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = request.header("Content-Type", "application/json");
+        match &self.api_key {
+            Some(key) => request.header("api-key", key),
+            None => request,
+        }
+    }
+
+    /// Sends a JSON body and parses the `result` of the answer
+    fn call<T: for<'de> Deserialize<'de>>(
+        &self,
+        request: RequestBuilder,
+        body: Value,
+    ) -> io::Result<T> {
+        let response = self
+            .request(request)
+            .body(body.to_string())
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text())
+            .map_err(io::Error::other)?;
+        let response: Response<T> = serde_json::from_str(&response).map_err(io::Error::other)?;
+        Ok(response.result)
+    }
+}
+
+/// Point id of an image, a UUID made from the hash of its path
+fn point_id(path: &str) -> String {
+    let hash = Sha256::digest(path.as_bytes());
+    let hex: String = hash[..16]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+impl VectorStore for QdrantStore {
+    fn get(&self, ids: &[String]) -> Vec<Entry<'_>> {
+        let mut data = Vec::new();
+        for ids in ids.chunks(BATCH_SIZE) {
+            let point_ids: Vec<String> = ids.iter().map(|id| point_id(id)).collect();
+            let points: Vec<Point> = match self.call(
+                self.client.post(self.collection_url("/points")),
+                json!({ "ids": point_ids, "with_payload": true, "with_vector": true }),
+            ) {
+                Ok(points) => points,
+                Err(e) => {
+                    log_warning(format!("Failed to get images from Qdrant: {}", e));
+                    return data;
+                }
+            };
+            data.extend(points.into_iter().map(|point| {
+                Entry::Owned(Data {
+                    id: point.payload.path,
+                    vector: point.vector,
+                    fields: point.payload.fields,
+                })
+            }));
+        }
+        data
+    }
+
+    fn upsert(&mut self, data: Vec<Data>) -> io::Result<()> {
+        for data in data.chunks(BATCH_SIZE) {
+            let points: Vec<Value> = data
+                .iter()
+                .map(|data| {
+                    json!({
+                        "id": point_id(&data.id),
+                        "vector": data.vector,
+                        "payload": { "path": data.id, "fields": data.fields },
+                    })
+                })
+                .collect();
+            self.call::<Value>(
+                self.client.put(self.collection_url("/points?wait=true")),
+                json!({ "points": points }),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, ids: &[String]) -> io::Result<()> {
+        let point_ids: Vec<String> = ids.iter().map(|id| point_id(id)).collect();
+        self.call::<Value>(
+            self.client
+                .post(self.collection_url("/points/delete?wait=true")),
+            json!({ "points": point_ids }),
+        )?;
+        Ok(())
+    }
+
+    fn query(&self, query: &[f32], top_k: usize) -> Vec<(String, f32)> {
+        let points: Vec<Point> = match self.call(
+            self.client.post(self.collection_url("/points/search")),
+            json!({ "vector": query, "limit": top_k, "with_payload": true }),
+        ) {
+            Ok(points) => points,
+            Err(e) => {
+                log_warning(format!("Failed to search Qdrant: {}", e));
+                return Vec::new();
+            }
+        };
+        points
+            .into_iter()
+            .map(|point| (point.payload.path, point.score))
+            .collect()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
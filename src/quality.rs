@@ -0,0 +1,70 @@
+use image::DynamicImage;
+
+/// Longest side images are scaled down to before they are measured, so the measures don't
+/// depend on the resolution
+const MEASURE_SIZE: u32 = 512;
+/// Sharpness at which an image scores about two thirds for sharpness
+const SHARPNESS_SCALE: f32 = 100.0;
+/// Brightness at or below which a pixel counts as crushed to black
+const BLACK_CLIP: u8 = 2;
+/// Brightness at or above which a pixel counts as blown out to white
+const WHITE_CLIP: u8 = 253;
+/// Share of clipped pixels at which an image scores 0 for exposure
+const MAX_CLIPPED: f32 = 0.3;
+
+/// How technically usable a photo is, measured while indexing
+#[derive(Debug, Clone, Copy)]
+pub struct Quality {
+    /// Variance of the Laplacian of the brightness. Edges make the Laplacian swing, a blurry
+    /// image has few and scores low.
+    pub sharpness: f32,
+    /// Share of pixels crushed to black or blown out to white, from 0 to 1
+    pub clipped: f32,
+}
+
+impl Quality {
+    /// The quality from 0 to 1: sharp and well exposed photos score close to 1, blurry or
+    /// badly clipped ones close to 0
+    pub fn score(&self) -> f32 {
+        let sharp = 1.0 - (-self.sharpness / SHARPNESS_SCALE).exp();
+        let exposed = 1.0 - (self.clipped / MAX_CLIPPED).min(1.0);
+        sharp * exposed
+    }
+}
+
+/// Measures the sharpness and the exposure clipping of an image
+pub fn measure(image: &DynamicImage) -> Quality {
+    let gray = image.thumbnail(MEASURE_SIZE, MEASURE_SIZE).to_luma8();
+    let (width, height) = gray.dimensions();
+    if width < 3 || height < 3 {
+        return Quality {
+            sharpness: 0.0,
+            clipped: 0.0,
+        };
+    }
+
+    let clipped = gray
+        .pixels()
+        .filter(|pixel| pixel[0] <= BLACK_CLIP || pixel[0] >= WHITE_CLIP)
+        .count() as f32
+        / (width * height) as f32;
+
+    let pixel = |x: u32, y: u32| gray.get_pixel(x, y)[0] as f32;
+    let mut values = Vec::with_capacity(((width - 2) * (height - 2)) as usize);
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            values.push(
+                pixel(x - 1, y) + pixel(x + 1, y) + pixel(x, y - 1) + pixel(x, y + 1)
+                    - 4.0 * pixel(x, y),
+            );
+        }
+    }
+    let mean = values.iter().sum::<f32>() / values.len() as f32;
+    let sharpness = values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f32>()
+        / values.len() as f32;
+
+    Quality { sharpness, clipped }
+}
@@ -0,0 +1,451 @@
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    ops::{Bound, RangeBounds, RangeInclusive},
+    path::Path,
+};
+
+use crate::{
+    colors::{self, from_value, parse_color},
+    index::{
+        AESTHETIC_FIELD, CAPTION_FIELD, COLORS_FIELD, DATE_FIELD, HEIGHT_FIELD, NOTE_FIELD,
+        QUALITY_FIELD, TAGS_FIELD, WIDTH_FIELD,
+    },
+    metadata::{MAX_RATING, rating},
+};
+
+/// Share of an image a color has to cover to pass a `color:` filter
+const COLOR_FILTER_SCORE: f32 = 0.15;
+
+/// A constraint on the metadata of an image, written as `key:value` in the search bar.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// `caption:text` keeps images whose caption contains the text
+    Caption(String),
+    /// `tag:name` keeps images with this tag
+    Tag(String),
+    /// `note:text` keeps images whose note contains the text
+    Note(String),
+    /// `stars:>=4`, `stars:<3` or `stars:5` keeps images rated within the range,
+    /// unrated images count as 0 stars
+    Stars(RangeInclusive<u8>),
+    /// `color:#ff6600` or `color:blue` keeps images where the color is dominant
+    Color([u8; 3]),
+    /// `portrait:`, `landscape:` or `square:` keeps images with that orientation
+    Orientation(Orientation),
+    /// `minres:1920x1080` keeps images at least this wide and high
+    MinResolution(u32, u32),
+    /// `quality:>0.6`, `quality:<=0.3` or `quality:0.6` (at least 0.6) keeps images whose
+    /// quality score is within the range, images that weren't measured don't pass
+    Quality(Bound<f32>, Bound<f32>),
+    /// `aesthetic:>6` and the like keeps images the aesthetic predictor rates within the range,
+    /// images it didn't rate don't pass
+    Aesthetic(Bound<f32>, Bound<f32>),
+    /// `in:/home/me/Photos/2023` keeps images whose path starts with the text
+    PathPrefix(String),
+    /// `format:png` keeps images with one of these extensions, lowercase and without the dot
+    Format(Vec<String>),
+    /// `date:2023`, `date:>=2023-05` or `date:<2020-01-15` keeps images taken within the range,
+    /// images without a date don't pass
+    Date(Bound<String>, Bound<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+    Square,
+}
+
+/// How far the aspect ratio may be from 1 for an image to count as square
+pub const SQUARE_TOLERANCE: f64 = 0.05;
+
+/// Orders the results can be sorted by, written as `sort:key` in the search bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// `sort:stars` puts the best rated images first, equally rated ones stay in ranking order
+    Stars,
+    /// `sort:aesthetic` puts the best looking images first, unrated ones last
+    Aesthetic,
+}
+
+/// A search split into the text that gets embedded and the filters applied to the results.
+#[derive(Debug, Default)]
+pub struct Query {
+    pub text: String,
+    pub filters: Vec<Filter>,
+    /// Order of the results, `None` for the ranking order
+    pub sort: Option<SortKey>,
+}
+
+/// Splits the search bar input into text and filters. Values can be quoted to include spaces,
+/// e.g. `caption:"red car"`. Unknown `key:value` tokens are kept as text.
+pub fn parse(input: &str) -> Query {
+    let mut query = Query::default();
+    let mut text: Vec<String> = Vec::new();
+
+    for token in tokenize(input) {
+        match token.split_once(':') {
+            Some(("sort", "stars")) => {
+                query.sort = Some(SortKey::Stars);
+                continue;
+            }
+            Some(("sort", "aesthetic")) => {
+                query.sort = Some(SortKey::Aesthetic);
+                continue;
+            }
+            _ => {}
+        }
+        match token
+            .split_once(':')
+            .and_then(|(key, value)| parse_filter(key, value))
+        {
+            Some(filter) => query.filters.push(filter),
+            None => text.push(token),
+        }
+    }
+
+    query.text = text.join(" ");
+    query
+}
+
+fn parse_filter(key: &str, value: &str) -> Option<Filter> {
+    let value = value.trim_matches('"');
+
+    // orientation filters don't take a value
+    match key.to_lowercase().as_str() {
+        "portrait" => return Some(Filter::Orientation(Orientation::Portrait)),
+        "landscape" => return Some(Filter::Orientation(Orientation::Landscape)),
+        "square" => return Some(Filter::Orientation(Orientation::Square)),
+        _ => {}
+    }
+
+    if value.is_empty() {
+        return None;
+    }
+
+    match key.to_lowercase().as_str() {
+        "caption" => Some(Filter::Caption(value.to_lowercase())),
+        "tag" => Some(Filter::Tag(value.to_lowercase())),
+        "note" => Some(Filter::Note(value.to_lowercase())),
+        "stars" => parse_stars(value).map(Filter::Stars),
+        "quality" => parse_bounds(value).map(|(min, max)| Filter::Quality(min, max)),
+        "aesthetic" => parse_bounds(value).map(|(min, max)| Filter::Aesthetic(min, max)),
+        "color" | "colour" => parse_color(value).map(Filter::Color),
+        "minres" => {
+            let (width, height) = value
+                .to_lowercase()
+                .split_once('x')
+                .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)))?;
+            Some(Filter::MinResolution(width, height))
+        }
+        "in" => Some(Filter::PathPrefix(value.to_string())),
+        "format" => Some(Filter::Format(extensions(&value.to_lowercase()))),
+        "date" => parse_dates(value).map(|(min, max)| Filter::Date(min, max)),
+        _ => None,
+    }
+}
+
+/// Parses a star range like `>=4`, `<3` or `5`
+fn parse_stars(value: &str) -> Option<RangeInclusive<u8>> {
+    let (operator, number) = value
+        .find(|c: char| c.is_ascii_digit())
+        .map(|index| value.split_at(index))?;
+    let number: u8 = number.trim().parse().ok().filter(|n| *n <= MAX_RATING)?;
+
+    match operator.trim() {
+        "" | "=" => Some(number..=number),
+        ">=" => Some(number..=MAX_RATING),
+        ">" => Some(number.checked_add(1)?..=MAX_RATING),
+        "<=" => Some(0..=number),
+        "<" => Some(0..=number.checked_sub(1)?),
+        _ => None,
+    }
+}
+
+/// Parses a score range like `>0.6` or `<=0.3`, a bare number is the minimum
+fn parse_bounds(value: &str) -> Option<(Bound<f32>, Bound<f32>)> {
+    let (operator, number) = value
+        .find(|c: char| c.is_ascii_digit() || c == '.')
+        .map(|index| value.split_at(index))?;
+    let number: f32 = number.trim().parse().ok().filter(|n: &f32| n.is_finite())?;
+
+    match operator.trim() {
+        "" | ">=" => Some((Bound::Included(number), Bound::Unbounded)),
+        ">" => Some((Bound::Excluded(number), Bound::Unbounded)),
+        "<=" => Some((Bound::Unbounded, Bound::Included(number))),
+        "<" => Some((Bound::Unbounded, Bound::Excluded(number))),
+        _ => None,
+    }
+}
+
+/// The extensions of a format, which some formats have more than one of
+fn extensions(format: &str) -> Vec<String> {
+    let format = format.trim_start_matches('.');
+    let aliases: &[&str] = match format {
+        "jpg" | "jpeg" => &["jpg", "jpeg"],
+        "tif" | "tiff" => &["tif", "tiff"],
+        format => &[format],
+    };
+    aliases.iter().map(|alias| alias.to_string()).collect()
+}
+
+/// Parses a date range like `2023`, `>=2023-05` or `<2020-01-15`. A bare date is the whole
+/// year, month or day it names.
+fn parse_dates(value: &str) -> Option<(Bound<String>, Bound<String>)> {
+    let (operator, date) = value
+        .find(|c: char| c.is_ascii_digit())
+        .map(|index| value.split_at(index))?;
+    let valid = matches!(date.len(), 4 | 7 | 10)
+        && date.chars().enumerate().all(|(i, c)| {
+            if i == 4 || i == 7 {
+                c == '-'
+            } else {
+                c.is_ascii_digit()
+            }
+        });
+    if !valid {
+        return None;
+    }
+
+    // dates are stored as `YYYY-MM-DD` and compared as text, every day within a shorter
+    // date like `2023` sorts after it and before it followed by `~`
+    let start = date.to_string();
+    let end = format!("{}~", date);
+    match operator.trim() {
+        "" | "=" => Some((Bound::Included(start), Bound::Excluded(end))),
+        ">=" => Some((Bound::Included(start), Bound::Unbounded)),
+        ">" => Some((Bound::Excluded(end), Bound::Unbounded)),
+        "<=" => Some((Bound::Unbounded, Bound::Excluded(end))),
+        "<" => Some((Bound::Unbounded, Bound::Excluded(start))),
+        _ => None,
+    }
+}
+
+/// Splits on whitespace, keeping quoted parts together
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+impl Filter {
+    /// Checks whether an image with the given path and metadata passes the filter
+    pub fn matches(&self, path: &str, fields: &HashMap<String, Value>) -> bool {
+        match self {
+            Filter::Caption(text) => fields
+                .get(CAPTION_FIELD)
+                .and_then(Value::as_str)
+                .is_some_and(|caption| caption.to_lowercase().contains(text)),
+            Filter::Tag(tag) => fields
+                .get(TAGS_FIELD)
+                .and_then(Value::as_array)
+                .is_some_and(|tags| tags.iter().any(|t| t.as_str() == Some(tag))),
+            Filter::Note(text) => fields
+                .get(NOTE_FIELD)
+                .and_then(Value::as_str)
+                .is_some_and(|note| note.to_lowercase().contains(text)),
+            Filter::Stars(range) => range.contains(&rating(fields)),
+            Filter::Color(target) => fields.get(COLORS_FIELD).is_some_and(|value| {
+                colors::color_score(&from_value(value), *target) >= COLOR_FILTER_SCORE
+            }),
+            Filter::Orientation(orientation) => dimensions(fields).is_some_and(|(w, h)| {
+                let ratio = w as f64 / h.max(1) as f64;
+                match orientation {
+                    Orientation::Square => (ratio - 1.0).abs() <= SQUARE_TOLERANCE,
+                    Orientation::Landscape => ratio > 1.0 + SQUARE_TOLERANCE,
+                    Orientation::Portrait => ratio < 1.0 - SQUARE_TOLERANCE,
+                }
+            }),
+            Filter::MinResolution(min_width, min_height) => {
+                dimensions(fields).is_some_and(|(w, h)| w >= *min_width && h >= *min_height)
+            }
+            Filter::Quality(min, max) => fields
+                .get(QUALITY_FIELD)
+                .and_then(Value::as_f64)
+                .is_some_and(|quality| (*min, *max).contains(&(quality as f32))),
+            Filter::Aesthetic(min, max) => fields
+                .get(AESTHETIC_FIELD)
+                .and_then(Value::as_f64)
+                .is_some_and(|score| (*min, *max).contains(&(score as f32))),
+            Filter::PathPrefix(prefix) => path.starts_with(prefix.as_str()),
+            Filter::Format(extensions) => Path::new(path)
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    extensions
+                        .iter()
+                        .any(|format| extension.eq_ignore_ascii_case(format))
+                }),
+            Filter::Date(min, max) => fields
+                .get(DATE_FIELD)
+                .and_then(Value::as_str)
+                .is_some_and(|date| (min.clone(), max.clone()).contains(&date.to_string())),
+        }
+    }
+}
+
+/// Width and height of an image as stored in the index
+fn dimensions(fields: &HashMap<String, Value>) -> Option<(u32, u32)> {
+    let width = fields.get(WIDTH_FIELD)?.as_u64()?;
+    let height = fields.get(HEIGHT_FIELD)?.as_u64()?;
+    Some((width as u32, height as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn filters(input: &str) -> Vec<Filter> {
+        parse(input).filters
+    }
+
+    #[test]
+    fn parse_splits_text_filters_and_sort() {
+        let query = parse(r#"red car tag:Street caption:"at night" sort:stars unknown:key"#);
+
+        assert_eq!(query.text, "red car unknown:key");
+        assert_eq!(
+            query.filters,
+            [
+                Filter::Tag("street".into()),
+                Filter::Caption("at night".into())
+            ]
+        );
+        assert_eq!(query.sort, Some(SortKey::Stars));
+        assert_eq!(parse("sort:aesthetic").sort, Some(SortKey::Aesthetic));
+    }
+
+    #[test]
+    fn filters_without_a_value_stay_text() {
+        let query = parse("tag: stars:");
+
+        assert!(query.filters.is_empty());
+        assert_eq!(query.text, "tag: stars:");
+        assert_eq!(
+            filters("portrait: Landscape: square:"),
+            [
+                Filter::Orientation(Orientation::Portrait),
+                Filter::Orientation(Orientation::Landscape),
+                Filter::Orientation(Orientation::Square)
+            ]
+        );
+    }
+
+    #[test]
+    fn stars_take_a_range_within_the_rating_scale() {
+        assert_eq!(filters("stars:5"), [Filter::Stars(5..=5)]);
+        assert_eq!(filters("stars:>=4"), [Filter::Stars(4..=MAX_RATING)]);
+        assert_eq!(filters("stars:>3"), [Filter::Stars(4..=MAX_RATING)]);
+        assert_eq!(filters("stars:<=2"), [Filter::Stars(0..=2)]);
+        assert_eq!(filters("stars:<3"), [Filter::Stars(0..=2)]);
+        assert!(filters("stars:6").is_empty());
+        assert!(filters("stars:<0").is_empty());
+        assert!(filters("stars:!3").is_empty());
+    }
+
+    #[test]
+    fn scores_take_bounds_and_a_bare_number_is_the_minimum() {
+        assert_eq!(
+            filters("quality:0.6"),
+            [Filter::Quality(Bound::Included(0.6), Bound::Unbounded)]
+        );
+        assert_eq!(
+            filters("quality:<=0.3"),
+            [Filter::Quality(Bound::Unbounded, Bound::Included(0.3))]
+        );
+        assert_eq!(
+            filters("aesthetic:>6"),
+            [Filter::Aesthetic(Bound::Excluded(6.0), Bound::Unbounded)]
+        );
+        assert!(filters("quality:high").is_empty());
+    }
+
+    #[test]
+    fn minres_path_and_format_filters() {
+        assert_eq!(
+            filters("minres:1920X1080"),
+            [Filter::MinResolution(1920, 1080)]
+        );
+        assert!(filters("minres:1920").is_empty());
+        assert_eq!(
+            filters(r#"in:"/home/me/My Photos""#),
+            [Filter::PathPrefix("/home/me/My Photos".into())]
+        );
+        assert_eq!(
+            filters("format:JPEG format:.png"),
+            [
+                Filter::Format(vec!["jpg".into(), "jpeg".into()]),
+                Filter::Format(vec!["png".into()])
+            ]
+        );
+    }
+
+    #[test]
+    fn dates_cover_the_whole_period_they_name() {
+        let range = match filters("date:2023-05").as_slice() {
+            [Filter::Date(min, max)] => (min.clone(), max.clone()),
+            other => panic!("expected a date filter, got {other:?}"),
+        };
+
+        assert!(range.contains(&"2023-05-01".to_string()));
+        assert!(range.contains(&"2023-05-31".to_string()));
+        assert!(!range.contains(&"2023-06-01".to_string()));
+        assert!(!range.contains(&"2023-04-30".to_string()));
+
+        assert_eq!(
+            filters("date:<2020-01-15"),
+            [Filter::Date(
+                Bound::Unbounded,
+                Bound::Excluded("2020-01-15".into())
+            )]
+        );
+        assert!(filters("date:2023-5").is_empty());
+        assert!(filters("date:yesterday").is_empty());
+    }
+
+    #[test]
+    fn filters_match_the_metadata() {
+        let fields: HashMap<String, Value> = [
+            (TAGS_FIELD.to_string(), json!(["street"])),
+            (WIDTH_FIELD.to_string(), json!(1920)),
+            (HEIGHT_FIELD.to_string(), json!(1080)),
+            (DATE_FIELD.to_string(), json!("2023-05-14")),
+        ]
+        .into_iter()
+        .collect();
+        let path = "/home/me/Photos/street.JPEG";
+        let matching =
+            "tag:street landscape: minres:1920x1080 format:jpg date:2023 stars:0 in:/home/me";
+
+        for filter in filters(matching) {
+            assert!(filter.matches(path, &fields), "{filter:?} should match");
+        }
+        for filter in filters(
+            "tag:beach portrait: minres:3840x2160 format:png date:<2023 stars:>=1 quality:0",
+        ) {
+            assert!(!filter.matches(path, &fields), "{filter:?} shouldn't match");
+        }
+    }
+}
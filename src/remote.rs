@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{fs, io, path::PathBuf};
+
+use crate::{atomic, img_scrape::fetcher::Fetcher, paths};
+
+/// Longest side of the stored thumbnails in pixels, plenty for CLIP and the result cells
+const THUMBNAIL_SIZE: u32 = 512;
+
+/// Whether an index entry is a remote item named by its URL instead of a local file
+pub fn is_remote(path: &str) -> bool {
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
+/// Where the thumbnail of a remote item is stored, named by the hash of its URL
+pub fn thumbnail_file(url: &str) -> PathBuf {
+    let hash: String = Sha256::digest(url.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    paths::thumbnails_dir().join(hash).with_extension("jpg")
+}
+
+/// The file to read for an index entry: the thumbnail for remote items, the path itself
+/// for local images
+pub fn local_file(path: &str) -> String {
+    if is_remote(path) {
+        thumbnail_file(path).display().to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+/// Images that live on the web, indexed and searched together with the library.
+/// Only a thumbnail of each is kept, activating one opens its URL in the browser.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Remotes {
+    urls: Vec<String>,
+}
+
+impl Remotes {
+    /// Loads the remote items. A missing or broken file results in none, items whose
+    /// thumbnail is gone are left out.
+    pub fn load() -> Self {
+        let mut remotes: Self = fs::read_to_string(paths::remote_file())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        remotes.urls.retain(|url| thumbnail_file(url).is_file());
+        remotes
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(paths::data_dir())?;
+        let content = serde_json::to_string(self)?;
+        atomic::write(&paths::remote_file(), content)
+    }
+
+    /// Downloads the thumbnail of an image on the web and adds it as a remote item.
+    /// Items that are known already aren't downloaded again.
+    pub fn add(&mut self, fetcher: &Fetcher, url: &str) -> Result<(), String> {
+        if self.urls.iter().any(|known| known == url) {
+            return Ok(());
+        }
+        let file = thumbnail_file(url);
+        if !file.is_file() {
+            let bytes = fetcher.get(url)?.bytes().map_err(|e| e.to_string())?;
+            let image = image::load_from_memory(&bytes).map_err(|e| e.to_string())?;
+            fs::create_dir_all(paths::thumbnails_dir()).map_err(|e| e.to_string())?;
+            image
+                .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+                .to_rgb8()
+                .save(&file)
+                .map_err(|e| e.to_string())?;
+        }
+        self.urls.push(url.to_string());
+        Ok(())
+    }
+
+    /// URLs of the remote items in the order they were added
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+}
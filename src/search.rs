@@ -0,0 +1,952 @@
+use cliprs::ClipModel;
+use image::DynamicImage;
+use reqwest::blocking::Client;
+use serde_json::Value;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError, RwLock},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    bursts::group_bursts,
+    catalog::Catalog,
+    colors,
+    config::Config,
+    engine::{self, Candidates, Hit, Pipeline, ScoreCache, Similarity},
+    events::AppEvent,
+    expansion,
+    feedback::Feedback,
+    index::{AESTHETIC_FIELD, CAPTION_FIELD, COLORS_FIELD, NOTE_FIELD, WEB_TEXT_FIELD},
+    memory::{self, ImageCache},
+    metadata, outliers,
+    plugins::Plugins,
+    query::{self, Query, SortKey},
+    remote, sketch,
+    store::{Store, VectorStore},
+    translate::translate,
+    ui::{
+        list::SearchEnum,
+        message::{Message, MessageSeverity},
+    },
+};
+
+/// Candidates ranked per result when bursts are folded, see `Config::burst_similarity`
+const BURST_CANDIDATES: usize = 5;
+/// Number of best matches a search passes on to be refined, see `SearchJob::pool`
+const REFINE_POOL: usize = 200;
+
+/// Image paths with their scores, from best match to worst
+type Ranking = Vec<(String, f32)>;
+
+/// The images a search scores and how many of the best it keeps
+struct Scope<'a> {
+    candidates: Candidates<'a>,
+    top_k: usize,
+}
+
+/// Everything a search needs, so it can run on a worker thread while the UI keeps drawing.
+pub struct SearchJob {
+    /// The search bar input, filters included
+    pub input: String,
+    pub mode: SearchEnum,
+    pub model: Arc<ClipModel>,
+    pub database: Arc<RwLock<Store>>,
+    pub catalog: Arc<Catalog>,
+    /// Decoded images shared with the UI and earlier searches
+    pub images: Arc<Mutex<ImageCache>>,
+    /// Longest side in pixels the result cells show images at, see `memory::load`
+    pub display_side: u32,
+    pub image_paths: Vec<String>,
+    pub config: Config,
+    pub feedback: Feedback,
+    pub similarity: Similarity,
+    /// Further models whose rankings are fused with the one of `model`, empty for a normal search
+    pub ensemble: Vec<EnsembleMember>,
+    /// The candidates of the previous search when refining its results. Only these images
+    /// are ranked, `None` searches the whole library.
+    pub pool: Option<HashSet<String>>,
+    /// Scripts that adjust the scores of the best candidates
+    pub plugins: Arc<Plugins>,
+    /// Rankings of earlier searches, `None` while the library is being indexed
+    pub scores: Option<Arc<Mutex<ScoreCache>>>,
+}
+
+/// A model taking part in an ensemble search, together with its own index
+pub struct EnsembleMember {
+    pub model: Arc<ClipModel>,
+    pub database: Arc<RwLock<Store>>,
+    pub similarity: Similarity,
+}
+
+/// What a search found, sent back to the UI.
+pub struct SearchOutcome {
+    /// `None` if the search failed, the reason is in `messages`
+    pub results: Option<Vec<LoadedResult>>,
+    /// Embedding of the query, if the mode has one
+    pub query_embedding: Option<Vec<f32>>,
+    /// Notifications to show
+    pub messages: Vec<Message>,
+    /// Best matches, the pool a refinement of this search ranks. Every refinement keeps
+    /// the better half of its pool, so each step narrows the results down further.
+    pub candidates: Vec<String>,
+    /// Where the time of the search went, shown in the performance HUD
+    pub timings: Timings,
+    /// Ranked images whose file was deleted since they were indexed, left out of the results
+    pub missing: Vec<String>,
+}
+
+/// Time spent in each stage of a search
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Timings {
+    /// Translating the query if enabled and embedding it with the model, for every model of
+    /// an ensemble
+    pub embedding: Duration,
+    /// Scoring the index against the query and everything else that orders the results
+    pub scoring: Duration,
+    /// Reading and decoding the result images
+    pub decoding: Duration,
+    /// Turning the decoded images into terminal graphics, measured by the UI
+    pub encoding: Duration,
+    /// Times the index was ranked against an embedding, for every model and side of a query
+    pub rankings: usize,
+    /// How many of the rankings were taken from the score cache
+    pub cache_hits: usize,
+}
+
+/// Runs `f` and adds the time it took to `elapsed`
+fn timed<T>(elapsed: &mut Duration, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let value = f();
+    *elapsed += started.elapsed();
+    value
+}
+
+/// A decoded result image with its metadata.
+pub struct LoadedResult {
+    pub hit: Hit,
+    /// The image, or why it could not be read. `None` if it isn't read yet, see `undecoded`.
+    pub image: Option<Result<DynamicImage, String>>,
+    /// Whether `image` is the thumbnail embedded in the file, see `memory::Decoded`
+    pub reduced: bool,
+    pub fields: HashMap<String, Value>,
+    /// Near-identical shots hidden behind this result
+    pub burst: Vec<(String, f32)>,
+}
+
+impl SearchJob {
+    pub fn run(self) -> SearchOutcome {
+        let mut outcome = SearchOutcome {
+            results: None,
+            query_embedding: None,
+            messages: Vec::new(),
+            candidates: Vec::new(),
+            timings: Timings::default(),
+            missing: Vec::new(),
+        };
+        let database = self.database.read().unwrap_or_else(PoisonError::into_inner);
+
+        let started = Instant::now();
+        let ranked = self.rank(&**database, &mut outcome);
+        // the embedding is timed on its own while ranking
+        outcome.timings.scoring = started.elapsed().saturating_sub(outcome.timings.embedding);
+        let Some(mut ranked) = ranked else {
+            return outcome;
+        };
+        let started = Instant::now();
+
+        // bursts fold several candidates into one result, so more of them have to be there
+        let needed = match self.config.burst_similarity {
+            Some(_) => self.config.result_count * BURST_CANDIDATES,
+            None => self.config.result_count,
+        };
+        outcome.missing = drop_missing(&mut ranked, needed);
+        if !outcome.missing.is_empty() {
+            outcome.messages.push(Message::new(
+                format!(
+                    "Skipped {} files deleted since they were indexed, remove them in the stats",
+                    outcome.missing.len()
+                ),
+                MessageSeverity::Info,
+                Duration::from_secs(5),
+            ));
+        }
+
+        let candidates = self.pool.as_ref().map_or(REFINE_POOL, |pool| {
+            (pool.len() / 2).max(self.config.result_count)
+        });
+        outcome.candidates = ranked
+            .iter()
+            .take(candidates)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        let results = match self.config.burst_similarity {
+            Some(threshold) => {
+                let bursts = group_bursts(
+                    &ranked,
+                    |path| vector(&**database, path),
+                    threshold,
+                    self.config.result_count,
+                );
+                let representatives: Vec<(String, f32)> = bursts
+                    .iter()
+                    .map(|burst| burst.representative.clone())
+                    .collect();
+
+                let mut results = decode(
+                    &self.catalog,
+                    &self.images,
+                    &representatives,
+                    self.config.result_count,
+                    self.display_side,
+                );
+                for result in &mut results {
+                    if let Some(burst) = bursts
+                        .iter()
+                        .find(|burst| burst.representative.0 == result.hit.path)
+                    {
+                        result.burst = burst.members.clone();
+                    }
+                }
+                results
+            }
+            None => decode(
+                &self.catalog,
+                &self.images,
+                &ranked,
+                self.config.result_count,
+                self.display_side,
+            ),
+        };
+
+        outcome.timings.decoding = started.elapsed();
+        outcome.results = Some(results);
+        outcome
+    }
+
+    /// Ranks the images for the query, from best match to worst.
+    /// Returns `None` and adds a message to the outcome if the query can not be run.
+    fn rank(
+        &self,
+        database: &dyn VectorStore,
+        outcome: &mut SearchOutcome,
+    ) -> Option<Vec<(String, f32)>> {
+        let query = parse_query(self.mode, &self.input);
+        // the pool is checked first, it is much smaller than the library and needs no lookups
+        let mut pipeline = Pipeline::default();
+        if let Some(pool) = &self.pool {
+            pipeline.push(pool);
+        }
+        if !query.filters.is_empty() {
+            pipeline.push(&query.filters);
+        }
+        let candidates = pipeline.candidates(&self.catalog, &self.image_paths);
+        let top_k = if self.config.burst_similarity.is_some() {
+            // bursts are folded into one result, so more candidates are needed to fill the grid
+            self.config.result_count * BURST_CANDIDATES
+        } else {
+            self.config.result_count
+        }
+        .max(REFINE_POOL);
+        let scope = Scope { candidates, top_k };
+
+        let text = if query.text.trim().is_empty() {
+            query.text.clone()
+        } else {
+            timed(&mut outcome.timings.embedding, || {
+                self.translated(&query.text, &mut outcome.messages)
+            })
+        };
+        let mut embed_rank: Vec<(String, f32)>;
+
+        // a search of only filters or a sort lists the whole library
+        if query.text.trim().is_empty()
+            && (!query.filters.is_empty() || query.sort.is_some())
+            && self.mode != SearchEnum::Outliers
+        {
+            embed_rank = scope
+                .candidates
+                .paths()
+                .iter()
+                .map(|path| (path.clone(), 1.0))
+                .collect();
+        } else {
+            let (ranking, query_embedding) = self.rank_with(
+                &self.model,
+                database,
+                self.similarity,
+                &text,
+                &scope,
+                outcome,
+            )?;
+            embed_rank = ranking;
+            outcome.query_embedding = query_embedding;
+
+            // the other models of the ensemble rank on their own indexes, the rankings are fused
+            if !self.ensemble.is_empty()
+                && !matches!(self.mode, SearchEnum::Color | SearchEnum::Outliers)
+            {
+                let mut rankings = vec![embed_rank];
+                for member in &self.ensemble {
+                    let member_database = member
+                        .database
+                        .read()
+                        .unwrap_or_else(PoisonError::into_inner);
+                    let (ranking, _) = self.rank_with(
+                        &member.model,
+                        &**member_database,
+                        member.similarity,
+                        &text,
+                        &scope,
+                        outcome,
+                    )?;
+                    rankings.push(ranking);
+                }
+                // the fused scores only order the images, the confidence stays the similarity
+                // to the query of the active model
+                let fused = engine::reciprocal_rank_fusion(&rankings);
+                embed_rank = match &outcome.query_embedding {
+                    Some(query) => engine::rescore(database, fused, query, self.similarity),
+                    None => fused,
+                };
+            }
+        }
+
+        if self.config.hide_nsfw {
+            let flagged = self.catalog.flagged_nsfw();
+            embed_rank.retain(|(path, _)| !flagged.contains(path));
+        }
+
+        if self.config.hide_blurry {
+            let blurry = self.catalog.blurry(self.config.blur_threshold);
+            embed_rank.retain(|(path, _)| !blurry.contains(path));
+        }
+
+        if let Some(endpoint) = &self.config.rerank_endpoint
+            && !query.text.trim().is_empty()
+            && matches!(self.mode, SearchEnum::Search | SearchEnum::Ranking)
+        {
+            match engine::rerank(&Client::new(), endpoint, &text, embed_rank.clone()) {
+                Ok(reranked) => embed_rank = reranked,
+                Err(e) => outcome.messages.push(
+                    Message::new(
+                        format!("Re-ranking failed: {}", e),
+                        MessageSeverity::Warning,
+                        Duration::from_secs(5),
+                    )
+                    .action('r', "retry", AppEvent::StartSearch),
+                ),
+            }
+        }
+
+        if self.config.personal_ranking
+            && matches!(
+                self.mode,
+                SearchEnum::Search | SearchEnum::Ranking | SearchEnum::Image2Image
+            )
+            && let Some(query_embedding) = &outcome.query_embedding
+        {
+            self.feedback
+                .rerank(query_embedding, &mut embed_rank, |path| {
+                    vector(database, path)
+                });
+        }
+
+        self.plugins
+            .adjust_scores(&mut embed_rank, |path| self.catalog.fields(path));
+
+        match query.sort {
+            Some(SortKey::Stars) => {
+                // the sort is stable, so equally rated images keep their ranking order
+                embed_rank
+                    .sort_by_key(|(path, _)| Reverse(metadata::rating(&self.catalog.fields(path))));
+            }
+            Some(SortKey::Aesthetic) => {
+                // among the best matches, so the best looking photos of the query come first
+                let scores: HashMap<String, f64> = embed_rank
+                    .iter()
+                    .filter_map(|(path, _)| {
+                        let score = self.catalog.fields(path).get(AESTHETIC_FIELD)?.as_f64()?;
+                        Some((path.clone(), score))
+                    })
+                    .collect();
+                let score = |path: &String| scores.get(path).copied().unwrap_or(f64::MIN);
+                embed_rank.sort_by(|a, b| score(&b.0).total_cmp(&score(&a.0)));
+            }
+            None => {}
+        }
+
+        Some(embed_rank)
+    }
+
+    /// The query in English when `Config::translation_endpoint` is set, translation is on and
+    /// the mode embeds text with a model that only knows English. A notification shows what
+    /// was searched for, and the query is searched as written if the service fails.
+    fn translated(&self, text: &str, messages: &mut Vec<Message>) -> String {
+        let Some(endpoint) = &self.config.translation_endpoint else {
+            return text.to_string();
+        };
+        if !self.config.translate_queries
+            || self.config.multilingual
+            || !matches!(
+                self.mode,
+                SearchEnum::Search | SearchEnum::NegativePrompt | SearchEnum::Ranking
+            )
+        {
+            return text.to_string();
+        }
+
+        let client = Client::new();
+        // the two sides of a ranking query are translated on their own so the `-` between them stays
+        let translated: Result<Vec<String>, _> = if self.mode == SearchEnum::Ranking {
+            text.split('-')
+                .map(|part| translate(&client, endpoint, part).map(|part| part.replace('-', " ")))
+                .collect()
+        } else {
+            translate(&client, endpoint, text).map(|text| vec![text])
+        };
+        match translated {
+            Ok(parts) => {
+                let translated = parts.join(" - ");
+                if !translated.eq_ignore_ascii_case(text.trim()) {
+                    messages.push(Message::new(
+                        format!("Searched for \"{}\" (translated)", translated),
+                        MessageSeverity::Info,
+                        Duration::from_secs(3),
+                    ));
+                }
+                translated
+            }
+            Err(e) => {
+                messages.push(
+                    Message::new(
+                        format!("Translation failed, searched as written: {}", e),
+                        MessageSeverity::Warning,
+                        Duration::from_secs(5),
+                    )
+                    .action('r', "retry", AppEvent::StartSearch),
+                );
+                text.to_string()
+            }
+        }
+    }
+
+    /// Ranks the candidates like `engine::rank`, or takes the ranking from the score cache if an
+    /// earlier search ranked them against the same embedding
+    fn scan(
+        &self,
+        database: &dyn VectorStore,
+        candidates: &Candidates,
+        query: &[f32],
+        similarity: Similarity,
+        top_k: usize,
+        timings: &mut Timings,
+    ) -> Ranking {
+        timings.rankings += 1;
+        let Some(scores) = &self.scores else {
+            return engine::rank(database, candidates, query, similarity, top_k);
+        };
+        let key = ScoreCache::key(candidates, query, similarity, top_k);
+        let cached = scores
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(key);
+        if let Some(ranking) = cached {
+            timings.cache_hits += 1;
+            return ranking;
+        }
+        let ranking = engine::rank(database, candidates, query, similarity, top_k);
+        scores
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key, ranking.clone());
+        ranking
+    }
+
+    /// Ranks the images of `database` by the query text for the mode, using `model` to embed it.
+    /// Returns the ranking together with the embedding of the query, if the mode has one.
+    fn rank_with(
+        &self,
+        model: &ClipModel,
+        database: &dyn VectorStore,
+        similarity: Similarity,
+        text: &str,
+        scope: &Scope,
+        outcome: &mut SearchOutcome,
+    ) -> Option<(Ranking, Option<Vec<f32>>)> {
+        let error = |outcome: &mut SearchOutcome, message: String| {
+            outcome.messages.push(Message::new(
+                message,
+                MessageSeverity::Error,
+                Duration::from_secs(3),
+            ));
+        };
+        let mut embed_rank: Vec<(String, f32)> = vec![];
+        let mut query_embedding = None;
+
+        match self.mode {
+            SearchEnum::Search => {
+                let embedded = timed(&mut outcome.timings.embedding, || {
+                    expansion::embed_query(model, text, &self.config)
+                });
+                let text_embedding = match embedded {
+                    Ok(embed) => embed,
+                    Err(e) => {
+                        error(outcome, e);
+                        return None;
+                    }
+                };
+
+                embed_rank = self.scan(
+                    database,
+                    &scope.candidates,
+                    &text_embedding,
+                    similarity,
+                    scope.top_k,
+                    &mut outcome.timings,
+                );
+
+                // notes are written by hand and the text a gallery gave a scraped image by
+                // whoever posted it, so either mentioning the query is a strong hint
+                let mentions = [NOTE_FIELD, WEB_TEXT_FIELD].map(|field| {
+                    lexical_rank(&self.catalog, scope.candidates.paths(), text, field)
+                });
+                embed_rank =
+                    fuse_mentions(database, embed_rank, mentions, &text_embedding, similarity);
+                query_embedding = Some(text_embedding);
+            }
+            SearchEnum::NegativePrompt => {
+                let embedded = timed(&mut outcome.timings.embedding, || {
+                    expansion::embed_query(model, text, &self.config)
+                });
+                let text_embedding = match embedded {
+                    Ok(embed) => embed,
+                    Err(e) => {
+                        error(outcome, e);
+                        return None;
+                    }
+                };
+
+                embed_rank = self.scan(
+                    database,
+                    &scope.candidates,
+                    &text_embedding,
+                    similarity,
+                    usize::MAX,
+                    &mut outcome.timings,
+                );
+                embed_rank.reverse();
+                query_embedding = Some(text_embedding);
+            }
+            SearchEnum::Ranking => {
+                let search_split: Vec<&str> = text.split("-").collect();
+                if search_split.len() != 2 {
+                    return None;
+                }
+                let (positive_embedding, negative_embedding) =
+                    timed(&mut outcome.timings.embedding, || {
+                        (
+                            expansion::embed_query(model, search_split[0], &self.config),
+                            expansion::embed_query(model, search_split[1], &self.config),
+                        )
+                    });
+
+                let (Ok(positive_embedding), Ok(negative_embedding)) =
+                    (positive_embedding, negative_embedding)
+                else {
+                    error(outcome, "Failed to embed text".into());
+                    return None;
+                };
+
+                let results = self.scan(
+                    database,
+                    &scope.candidates,
+                    &positive_embedding,
+                    similarity,
+                    scope.top_k,
+                    &mut outcome.timings,
+                );
+
+                let negative_score = self
+                    .scan(
+                        database,
+                        &scope.candidates,
+                        &negative_embedding,
+                        similarity,
+                        1,
+                        &mut outcome.timings,
+                    )
+                    .first()
+                    .map(|(_, score)| *score)
+                    .unwrap_or_default();
+
+                for (file_id, positive_score) in results {
+                    embed_rank.push((file_id, positive_score - negative_score));
+                }
+
+                embed_rank.sort_by(|a, b| b.1.total_cmp(&a.1));
+                query_embedding = Some(positive_embedding);
+            }
+            SearchEnum::Image2Image => {
+                let embedded = timed(&mut outcome.timings.embedding, || model.embed_image(text));
+                let image_embedding = match embedded {
+                    Ok(embed) => embed,
+                    Err(e) => {
+                        error(outcome, e);
+                        return None;
+                    }
+                };
+
+                embed_rank = self.scan(
+                    database,
+                    &scope.candidates,
+                    &image_embedding,
+                    similarity,
+                    scope.top_k,
+                    &mut outcome.timings,
+                );
+                query_embedding = Some(image_embedding);
+            }
+            SearchEnum::Color => {
+                let Some(target) = colors::parse_color(text) else {
+                    error(
+                        outcome,
+                        "Enter a color name or a hex code like #ff6600".into(),
+                    );
+                    return None;
+                };
+
+                for path in scope.candidates.paths() {
+                    let dominant = self
+                        .catalog
+                        .fields(path)
+                        .get(COLORS_FIELD)
+                        .map(colors::from_value)
+                        .unwrap_or_default();
+                    let score = colors::color_score(&dominant, target);
+                    if score > 0.0 {
+                        embed_rank.push((path.clone(), score));
+                    }
+                }
+
+                embed_rank.sort_by(|a, b| b.1.total_cmp(&a.1));
+            }
+            // the query text isn't used, only the images passing the filters are compared
+            SearchEnum::Outliers => {
+                embed_rank = outliers::rank(database, scope.candidates.paths());
+            }
+            SearchEnum::Sketch => {
+                let path = match text.trim() {
+                    "" | sketch::CLIPBOARD => match sketch::paste_clipboard() {
+                        Ok(path) => path.display().to_string(),
+                        Err(e) => {
+                            error(outcome, e);
+                            return None;
+                        }
+                    },
+                    path => path.to_string(),
+                };
+                let embedded = timed(&mut outcome.timings.embedding, || {
+                    sketch::embed_sketch(model, &path)
+                });
+                let sketch_embedding = match embedded {
+                    Ok(embed) => embed,
+                    Err(e) => {
+                        error(outcome, e);
+                        return None;
+                    }
+                };
+
+                embed_rank = self.scan(
+                    database,
+                    &scope.candidates,
+                    &sketch_embedding,
+                    similarity,
+                    scope.top_k,
+                    &mut outcome.timings,
+                );
+                query_embedding = Some(sketch_embedding);
+            }
+            SearchEnum::TextOnImage => {
+                let embedded = timed(&mut outcome.timings.embedding, || {
+                    expansion::embed_text_on_image(model, text)
+                });
+                let text_embedding = match embedded {
+                    Ok(embed) => embed,
+                    Err(e) => {
+                        error(outcome, e);
+                        return None;
+                    }
+                };
+
+                embed_rank = self.scan(
+                    database,
+                    &scope.candidates,
+                    &text_embedding,
+                    similarity,
+                    scope.top_k,
+                    &mut outcome.timings,
+                );
+
+                // captions often spell out the text of signs and labels, so images whose
+                // caption contains the text are fused in as a second ranking
+                let captioned =
+                    lexical_rank(&self.catalog, scope.candidates.paths(), text, CAPTION_FIELD);
+                embed_rank = fuse_mentions(
+                    database,
+                    embed_rank,
+                    [captioned],
+                    &text_embedding,
+                    similarity,
+                );
+                query_embedding = Some(text_embedding);
+            }
+        }
+
+        Some((embed_rank, query_embedding))
+    }
+}
+
+/// Splits the search bar input of a mode into text and filters. The image modes take a path,
+/// which can have spaces, quotes or colons like a filter, so it is passed on as it is.
+fn parse_query(mode: SearchEnum, input: &str) -> Query {
+    if matches!(mode, SearchEnum::Image2Image | SearchEnum::Sketch) {
+        Query {
+            text: input.trim().to_string(),
+            ..Query::default()
+        }
+    } else {
+        query::parse(input)
+    }
+}
+
+/// Fuses the rankings of the images mentioning the query, see `lexical_rank`, into the ranking
+/// by their embedding. The fusion only orders them, the confidence stays their similarity to
+/// `query`, which the personal ranking and the plugins adjust.
+fn fuse_mentions(
+    database: &dyn VectorStore,
+    ranking: Ranking,
+    mentions: impl IntoIterator<Item = Ranking>,
+    query: &[f32],
+    similarity: Similarity,
+) -> Ranking {
+    let mut rankings = vec![ranking];
+    rankings.extend(mentions.into_iter().filter(|mentions| !mentions.is_empty()));
+    if rankings.len() == 1 {
+        return rankings.remove(0);
+    }
+    engine::rescore(
+        database,
+        engine::reciprocal_rank_fusion(&rankings),
+        query,
+        similarity,
+    )
+}
+
+/// Ranks the images by the share of the query's words found in a text field of their metadata.
+/// Images without any of the words are left out. The catalog finds the images mentioning a
+/// word in one query, they are then narrowed down to `paths`.
+fn lexical_rank(catalog: &Catalog, paths: &[String], text: &str, field: &str) -> Ranking {
+    let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+
+    let shares: HashMap<String, f32> = catalog
+        .mentioning(field, &words)
+        .into_iter()
+        .filter_map(|(path, value)| {
+            let value = value.to_lowercase();
+            let found = words.iter().filter(|word| value.contains(*word)).count();
+            (found > 0).then(|| (path, found as f32 / words.len() as f32))
+        })
+        .collect();
+    if shares.is_empty() {
+        return Vec::new();
+    }
+    let mut ranked: Ranking = paths
+        .iter()
+        .filter_map(|path| Some((path.clone(), *shares.get(path)?)))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked
+}
+
+/// Removes the images whose file is gone from the front of the ranking until `needed` images
+/// that are still there lead it, so deleted files don't take the place of results.
+/// Only the front is checked, the whole library can be ranked. Returns the removed paths.
+fn drop_missing(ranked: &mut Ranking, needed: usize) -> Vec<String> {
+    let mut missing = Vec::new();
+    let mut present = 0;
+    let mut index = 0;
+    while present < needed && index < ranked.len() {
+        if Path::new(&remote::local_file(&ranked[index].0)).exists() {
+            present += 1;
+            index += 1;
+        } else {
+            missing.push(ranked.remove(index).0);
+        }
+    }
+    missing
+}
+
+/// Decodes the images of the best `count` ranked paths for cells showing them at `side`,
+/// going through the image cache. Images that can not be read stay in the results with the
+/// reason, so they show up as a placeholder instead of quietly shrinking the results.
+pub fn decode(
+    catalog: &Catalog,
+    images: &Mutex<ImageCache>,
+    ranked: &[(String, f32)],
+    count: usize,
+    side: u32,
+) -> Vec<LoadedResult> {
+    let mut results = Vec::new();
+    for (path, confidence) in ranked.iter().take(count) {
+        let (image, reduced) = match memory::load(images, path, side) {
+            Ok(decoded) => (Some(Ok(decoded.image)), decoded.reduced),
+            Err(e) => (Some(Err(e)), false),
+        };
+
+        let fields = catalog.fields(path);
+        results.push(LoadedResult {
+            hit: Hit::new(path.clone(), *confidence, &fields),
+            image,
+            reduced,
+            fields,
+            burst: Vec::new(),
+        });
+    }
+    results
+}
+
+/// The best `count` ranked paths with their metadata, without reading the images. They are
+/// read once they are shown, so the results can be shown right away.
+pub fn undecoded(catalog: &Catalog, ranked: &[(String, f32)], count: usize) -> Vec<LoadedResult> {
+    ranked
+        .iter()
+        .take(count)
+        .map(|(path, confidence)| {
+            let fields = catalog.fields(path);
+            LoadedResult {
+                hit: Hit::new(path.clone(), *confidence, &fields),
+                image: None,
+                reduced: false,
+                fields,
+                burst: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+pub fn vector(database: &dyn VectorStore, path: &str) -> Option<Vec<f32>> {
+    database
+        .get(&[path.to_string()])
+        .first()
+        .map(|data| data.vector.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    const QUERY: [f32; 2] = [1.0, 0.0];
+
+    /// A library of three images, `far.jpg` being the worst match for `QUERY`, and a catalog
+    /// where `field` of `far.jpg` mentions the query `beach`
+    fn library(name: &str, field: &str) -> (MemoryStore, Catalog, Vec<String>) {
+        let images = [
+            ("near.jpg", vec![1.0, 0.1]),
+            ("middle.jpg", vec![1.0, 1.0]),
+            ("far.jpg", vec![0.1, 1.0]),
+        ];
+        let store = MemoryStore::new(images.clone());
+
+        let file = std::env::temp_dir().join(format!(
+            "findimg-search-{}-{}.sqlite",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&file);
+        let catalog = Catalog::open(&file).unwrap();
+        let fields = HashMap::from([(field.to_string(), Value::from("Beach at dusk"))]);
+        catalog.set_fields("far.jpg", &fields).unwrap();
+
+        let paths = images.iter().map(|(path, _)| path.to_string()).collect();
+        (store, catalog, paths)
+    }
+
+    /// Ranks the library for the query `beach` the way a text search does
+    fn search(store: &MemoryStore, catalog: &Catalog, paths: &[String]) -> Ranking {
+        let ranking = store.query(&QUERY, usize::MAX);
+        let mentions =
+            [NOTE_FIELD, WEB_TEXT_FIELD].map(|field| lexical_rank(catalog, paths, "beach", field));
+        fuse_mentions(store, ranking, mentions, &QUERY, Similarity::Cosine)
+    }
+
+    fn assert_scores_are_similarities(store: &MemoryStore, ranking: &Ranking) {
+        for (path, score) in ranking {
+            let expected = Similarity::Cosine.score(&QUERY, &vector(store, path).unwrap());
+            assert_eq!(*score, expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn a_note_mentioning_the_query_lifts_the_image_but_keeps_its_similarity() {
+        let (store, catalog, paths) = library("note", NOTE_FIELD);
+        let ranking = search(&store, &catalog, &paths);
+
+        let order: Vec<&str> = ranking.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(order.len(), 3);
+        assert!(
+            order.iter().position(|path| *path == "far.jpg")
+                < order.iter().position(|path| *path == "middle.jpg"),
+            "{order:?}"
+        );
+        assert_scores_are_similarities(&store, &ranking);
+    }
+
+    #[test]
+    fn web_text_mentioning_the_query_keeps_the_similarity_as_the_confidence() {
+        let (store, catalog, paths) = library("web-text", WEB_TEXT_FIELD);
+        let ranking = search(&store, &catalog, &paths);
+
+        assert_ne!(ranking, store.query(&QUERY, usize::MAX));
+        assert_scores_are_similarities(&store, &ranking);
+    }
+
+    #[test]
+    fn image_paths_are_not_parsed_as_filters() {
+        let path = r#"/home/me/My  Photos/"best" tag:beach.jpg"#;
+
+        for mode in [SearchEnum::Image2Image, SearchEnum::Sketch] {
+            let query = parse_query(mode, &format!("{path} "));
+            assert_eq!(query.text, path);
+            assert!(query.filters.is_empty());
+        }
+        let query = parse_query(SearchEnum::Search, "red  car tag:beach");
+        assert_eq!(query.text, "red car");
+        assert_eq!(query.filters.len(), 1);
+    }
+
+    #[test]
+    fn without_mentions_the_ranking_is_left_as_it_is() {
+        let (store, catalog, paths) = library("unmentioned", NOTE_FIELD);
+        let ranking = store.query(&QUERY, usize::MAX);
+        let mentions = [NOTE_FIELD].map(|field| lexical_rank(&catalog, &paths, "forest", field));
+
+        assert_eq!(
+            fuse_mentions(
+                &store,
+                ranking.clone(),
+                mentions,
+                &QUERY,
+                Similarity::Cosine
+            ),
+            ranking
+        );
+    }
+}
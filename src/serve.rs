@@ -0,0 +1,130 @@
+use cliprs::log_warning;
+use std::{
+    collections::hash_map::RandomState,
+    fs,
+    hash::{BuildHasher, Hasher},
+    io::{self, BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+    time::Duration,
+};
+
+use crate::remote;
+
+/// How long a connection may take to send its request, so one that never does can't block
+/// the others
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves the result picked last over HTTP on localhost, so it can be opened in the browser
+/// of the machine an SSH session comes from through a forwarded port, e.g.
+/// `ssh -L 8765:localhost:8765`. Only that one file is served, under a random token so other
+/// users of the machine can't guess the address. It stops with findimg.
+pub struct Server {
+    port: u16,
+    token: String,
+    /// Path of the served result, remote items are redirected to their URL
+    current: Arc<Mutex<Option<String>>>,
+}
+
+impl Server {
+    pub fn start(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u16(port);
+        let server = Self {
+            port: listener.local_addr()?.port(),
+            token: format!("{:016x}", hasher.finish()),
+            current: Arc::default(),
+        };
+
+        let token = server.token.clone();
+        let current = server.current.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let current = current
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone();
+                if let Err(e) = respond(stream, &token, current.as_deref()) {
+                    log_warning(format!("Failed to serve the result: {}", e));
+                }
+            }
+        });
+        Ok(server)
+    }
+
+    /// Serves `path` instead of the previous result. Returns its address.
+    pub fn serve(&self, path: &str) -> String {
+        *self.current.lock().unwrap_or_else(PoisonError::into_inner) = Some(path.to_string());
+        let name = path.rsplit(['/', '\\']).find(|part| !part.is_empty());
+        format!(
+            "http://localhost:{}/{}/{}",
+            self.port,
+            self.token,
+            name.unwrap_or("image").replace(' ', "%20")
+        )
+    }
+}
+
+/// Answers a single request: the served file if the path starts with the token, 404 otherwise
+fn respond(stream: TcpStream, token: &str, current: Option<&str>) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // the headers don't matter, but the browser expects them to be read
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (method, target) = (parts.next(), parts.next().unwrap_or_default());
+    let authorized = target
+        .strip_prefix('/')
+        .and_then(|target| target.strip_prefix(token))
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+    let mut stream = &stream;
+
+    match current {
+        Some(path) if authorized && method == Some("GET") => {
+            if remote::is_remote(path) {
+                return write!(
+                    stream,
+                    "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    path
+                );
+            }
+            let content = fs::read(path)?;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+                content_type(Path::new(path)),
+                content.len()
+            )?;
+            stream.write_all(&content)
+        }
+        _ => write!(
+            stream,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        ),
+    }
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("bmp") => "image/bmp",
+        Some("tif" | "tiff") => "image/tiff",
+        Some("avif") => "image/avif",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, io};
+
+use crate::{atomic, paths, ui::list::SearchEnum};
+
+/// State of the app that is saved on exit and restored on the next launch.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Session {
+    /// Text of the search bar
+    pub query: String,
+    /// Selected search mode
+    pub mode: SearchEnum,
+    /// Paths and scores of the results, from best match to worst
+    pub results: Vec<(String, f32)>,
+    /// Index of the selected result in `results`
+    #[serde(default)]
+    pub selected_result: usize,
+}
+
+impl Session {
+    /// Loads the last session. Returns `None` if there is none or it can not be parsed.
+    pub fn load() -> Option<Self> {
+        let content = fs::read_to_string(paths::session_file()).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Writes the session to disk
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(paths::data_dir())?;
+        let content = serde_json::to_string(self)?;
+        atomic::write(&paths::session_file(), content)
+    }
+}
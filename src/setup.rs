@@ -0,0 +1,128 @@
+use reqwest::blocking::Client;
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{Config, ImageProtocol},
+    paths,
+};
+
+/// Walks through choosing a model, the library directory and the image protocol on the
+/// command line and saves the answers to the config. Returns the path of the chosen model.
+/// The library is indexed afterwards like on every other launch.
+pub fn run_wizard() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut config = Config::load();
+    println!("Welcome to findimg! A few questions before the first search.\n");
+
+    let model_path = loop {
+        let answer = ask(
+            "Path of a CLIP model file, or a URL to download one from",
+            None,
+        )?;
+        if answer.starts_with("http://") || answer.starts_with("https://") {
+            match download_model(&answer) {
+                Ok(path) => break path,
+                Err(e) => println!("Failed to download the model: {}", e),
+            }
+        } else if Path::new(&answer).is_file() {
+            break std::path::absolute(&answer)?;
+        } else {
+            println!("{} is not a file", answer);
+        }
+    };
+    config.model_path = Some(model_path.clone());
+
+    let suggested = config
+        .library_dir
+        .clone()
+        .or_else(dirs::picture_dir)
+        .unwrap_or_else(paths::default_library_dir);
+    let library_dir = loop {
+        let answer = ask(
+            "Directory with the images to search",
+            Some(&suggested.display().to_string()),
+        )?;
+        let dir = PathBuf::from(answer);
+        match fs::create_dir_all(&dir) {
+            Ok(()) => break std::path::absolute(dir)?,
+            Err(e) => println!("Can not use {}: {}", dir.display(), e),
+        }
+    };
+    config.library_dir = Some(library_dir);
+
+    println!("How should images be drawn?");
+    for (index, protocol) in ImageProtocol::ALL.iter().enumerate() {
+        println!("  {}) {}", index + 1, protocol.name());
+    }
+    config.protocol = loop {
+        let answer = ask("Protocol", Some("1"))?;
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=ImageProtocol::ALL.len()).contains(&number) => {
+                break ImageProtocol::ALL[number - 1];
+            }
+            _ => println!("Pick a number from 1 to {}", ImageProtocol::ALL.len()),
+        }
+    };
+
+    config.save()?;
+    println!(
+        "\nSaved the settings to {}, indexing the library now.",
+        paths::config_file().display()
+    );
+    Ok(model_path)
+}
+
+/// Asks a question and returns the trimmed answer, or the default for an empty answer.
+/// Questions without a default are asked again until something is entered.
+fn ask(question: &str, default: Option<&str>) -> io::Result<String> {
+    loop {
+        match default {
+            Some(default) => print!("{} [{}]: ", question, default),
+            None => print!("{}: ", question),
+        }
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if io::stdin().lock().read_line(&mut answer)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Setup was cancelled",
+            ));
+        }
+        match (answer.trim(), default) {
+            ("", Some(default)) => return Ok(default.to_string()),
+            ("", None) => continue,
+            (answer, _) => return Ok(answer.to_string()),
+        }
+    }
+}
+
+/// Downloads a model into the data directory. The file only gets its final name once it is
+/// complete, so an interrupted download is never mistaken for a model.
+fn download_model(url: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let name = url
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.split('?').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("model.bin");
+    let dir = paths::data_dir().join("models");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(name);
+    let partial = dir.join(format!("{}.part", name));
+
+    println!("Downloading {} to {}...", url, path.display());
+    // models are large, so the default timeout of the blocking client would cut the download off
+    let client = Client::builder().timeout(None).build()?;
+    let mut response = client.get(url).send()?.error_for_status()?;
+    let mut file = File::create(&partial)?;
+    let size = io::copy(&mut response, &mut file)?;
+    file.sync_all()?;
+    fs::rename(&partial, &path)?;
+
+    println!("Downloaded {} MB", size / 1_000_000);
+    Ok(path)
+}
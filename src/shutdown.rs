@@ -0,0 +1,72 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+#[cfg(unix)]
+use crate::paths;
+
+/// Catches SIGINT and SIGTERM while it lives, so a long job on the command line can stop at a
+/// safe point instead of being killed in the middle of a write. A second signal ends the
+/// process right away, for when the job doesn't get to a safe point. Not while the index is
+/// decrypted for the run though: ending right away would leave it decrypted on disk, so the
+/// job always gets to the safe point and the index is encrypted again.
+pub struct Shutdown {
+    requested: Arc<AtomicBool>,
+    #[cfg(unix)]
+    handlers: Vec<signal_hook::SigId>,
+}
+
+impl Shutdown {
+    #[cfg(unix)]
+    pub fn catch() -> Self {
+        use signal_hook::{
+            consts::{SIGINT, SIGTERM},
+            flag,
+        };
+
+        let requested = Arc::new(AtomicBool::new(false));
+        let mut handlers = Vec::new();
+        for signal in [SIGINT, SIGTERM] {
+            // the exit runs before the flag is set, so it only fires on the second signal
+            if !paths::index_unlocked()
+                && let Ok(exit) = flag::register_conditional_shutdown(
+                    signal,
+                    128 + signal,
+                    Arc::clone(&requested),
+                )
+            {
+                handlers.push(exit);
+            }
+            if let Ok(handler) = flag::register(signal, Arc::clone(&requested)) {
+                handlers.push(handler);
+            }
+        }
+        Self {
+            requested,
+            handlers,
+        }
+    }
+
+    /// Signals are left to their default handling where they aren't caught
+    #[cfg(not(unix))]
+    pub fn catch() -> Self {
+        Self {
+            requested: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Whether a signal asked the process to stop
+    pub fn requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Shutdown {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        for handler in self.handlers.drain(..) {
+            signal_hook::low_level::unregister(handler);
+        }
+    }
+}
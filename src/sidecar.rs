@@ -0,0 +1,247 @@
+use image::{ImageDecoder, ImageReader};
+use regex::Regex;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+    time::UNIX_EPOCH,
+};
+
+use crate::metadata::MAX_RATING;
+
+/// IPTC dataset holding one keyword: record 2, dataset 25
+const IPTC_KEYWORD: [u8; 3] = [0x1c, 0x02, 0x19];
+
+const DC_NAMESPACE: &str = "http://purl.org/dc/elements/1.1/";
+const XMP_NAMESPACE: &str = "http://ns.adobe.com/xap/1.0/";
+
+static SUBJECT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)\s*<dc:subject>.*?</dc:subject>").unwrap());
+static LIST_ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<rdf:li[^>]*>(.*?)</rdf:li>").unwrap());
+static RATING_ATTRIBUTE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\s*xmp:Rating="(-?\d+)""#).unwrap());
+static RATING_ELEMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s*<xmp:Rating>(-?\d+)</xmp:Rating>").unwrap());
+static DESCRIPTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<rdf:Description\b[^>]*?(/?)>").unwrap());
+
+/// Tags and rating kept by other photo managers
+#[derive(Debug, Default, PartialEq)]
+pub struct Sidecar {
+    pub tags: Vec<String>,
+    /// Stars from 1 to 5, 0 for unrated or rejected
+    pub rating: u8,
+}
+
+/// Sidecar files other tools write next to an image: `photo.jpg.xmp` like darktable
+/// and digiKam, and `photo.xmp` like Lightroom
+fn sidecar_files(image: &str) -> [PathBuf; 2] {
+    [
+        PathBuf::from(format!("{}.xmp", image)),
+        Path::new(image).with_extension("xmp"),
+    ]
+}
+
+/// When the image or one of its sidecars was last changed, as seconds since the epoch.
+/// Tells whether the metadata has to be read again.
+pub fn modified(image: &str) -> u64 {
+    std::iter::once(PathBuf::from(image))
+        .chain(sidecar_files(image))
+        .filter_map(|path| fs::metadata(path).ok()?.modified().ok())
+        .filter_map(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|time| time.as_secs())
+        .max()
+        .unwrap_or_default()
+}
+
+/// Reads the tags and rating of an image from its XMP sidecars and the XMP and IPTC
+/// keywords embedded in the file. Returns `None` if there are none.
+pub fn read(image: &str) -> Option<Sidecar> {
+    let mut sidecar = Sidecar::default();
+
+    let mut packets: Vec<String> = sidecar_files(image)
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .collect();
+    let mut decoder = ImageReader::open(image)
+        .ok()
+        .and_then(|reader| reader.with_guessed_format().ok())
+        .and_then(|reader| reader.into_decoder().ok());
+    if let Some(decoder) = &mut decoder {
+        if let Ok(Some(xmp)) = decoder.xmp_metadata() {
+            packets.push(String::from_utf8_lossy(&xmp).into_owned());
+        }
+        if let Ok(Some(iptc)) = decoder.iptc_metadata() {
+            sidecar.tags.extend(iptc_keywords(&iptc));
+        }
+    }
+
+    for packet in &packets {
+        sidecar.tags.extend(xmp_tags(packet));
+        if sidecar.rating == 0 {
+            sidecar.rating = xmp_rating(packet);
+        }
+    }
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in sidecar.tags {
+        let tag = tag.trim().to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    sidecar.tags = tags;
+
+    (!sidecar.tags.is_empty() || sidecar.rating > 0).then_some(sidecar)
+}
+
+fn xmp_tags(packet: &str) -> Vec<String> {
+    let Some(subject) = SUBJECT.find(packet) else {
+        return Vec::new();
+    };
+    LIST_ITEM
+        .captures_iter(subject.as_str())
+        .map(|item| unescape(&item[1]))
+        .collect()
+}
+
+fn xmp_rating(packet: &str) -> u8 {
+    RATING_ATTRIBUTE
+        .captures(packet)
+        .or_else(|| RATING_ELEMENT.captures(packet))
+        .and_then(|rating| rating[1].parse::<i64>().ok())
+        // -1 marks rejected images
+        .map_or(0, |rating| rating.clamp(0, MAX_RATING as i64) as u8)
+}
+
+/// Keywords of an IPTC block, each stored as a dataset with a two byte length
+fn iptc_keywords(iptc: &[u8]) -> Vec<String> {
+    let mut keywords = Vec::new();
+    let mut index = 0;
+    while index + 5 <= iptc.len() {
+        if iptc[index..index + 3] != IPTC_KEYWORD {
+            index += 1;
+            continue;
+        }
+        let length = u16::from_be_bytes([iptc[index + 3], iptc[index + 4]]) as usize;
+        let start = index + 5;
+        let Some(keyword) = iptc.get(start..start + length) else {
+            break;
+        };
+        keywords.push(String::from_utf8_lossy(keyword).into_owned());
+        index = start + length;
+    }
+    keywords
+}
+
+/// Writes the tags and rating to the XMP sidecar of an image. An existing sidecar keeps
+/// everything else it contains, otherwise a new one is created next to the image.
+pub fn write(image: &str, tags: &[String], rating: u8) -> io::Result<()> {
+    let existing = sidecar_files(image)
+        .into_iter()
+        .find_map(|path| Some((fs::read_to_string(&path).ok()?, path)));
+
+    let (packet, path) = match existing {
+        Some((packet, path)) if DESCRIPTION.is_match(&packet) => {
+            (update_packet(&packet, tags, rating), path)
+        }
+        // a sidecar that can't be understood is left for the tool that wrote it
+        Some((_, path)) => {
+            return Err(io::Error::other(format!(
+                "{} has no description to update",
+                path.display()
+            )));
+        }
+        None => (new_packet(tags, rating), sidecar_files(image)[0].clone()),
+    };
+    fs::write(path, packet)
+}
+
+fn subject_element(tags: &[String]) -> String {
+    let items: String = tags
+        .iter()
+        .map(|tag| format!("\n     <rdf:li>{}</rdf:li>", escape(tag)))
+        .collect();
+    format!(
+        "\n   <dc:subject>\n    <rdf:Bag>{}\n    </rdf:Bag>\n   </dc:subject>",
+        items
+    )
+}
+
+fn new_packet(tags: &[String], rating: u8) -> String {
+    let rating = if rating > 0 {
+        format!("\n   xmp:Rating=\"{}\"", rating)
+    } else {
+        String::new()
+    };
+    format!(
+        "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  <rdf:Description rdf:about=\"\"\n   xmlns:dc=\"{}\"\n   xmlns:xmp=\"{}\"{}>{}\n  </rdf:Description>\n </rdf:RDF>\n</x:xmpmeta>\n",
+        DC_NAMESPACE,
+        XMP_NAMESPACE,
+        rating,
+        if tags.is_empty() {
+            String::new()
+        } else {
+            subject_element(tags)
+        }
+    )
+}
+
+/// Replaces the tags and rating in an existing packet, adding them to its first description
+fn update_packet(packet: &str, tags: &[String], rating: u8) -> String {
+    let packet = SUBJECT.replace_all(packet, "");
+    let packet = RATING_ELEMENT.replace_all(&packet, "");
+    let packet = RATING_ATTRIBUTE.replace_all(&packet, "").into_owned();
+
+    let Some(description) = DESCRIPTION.captures(&packet) else {
+        return packet;
+    };
+    let whole = description.get(0).expect("group 0 is the whole match");
+    let self_closing = !description[1].is_empty();
+    let tag = whole.as_str();
+    let mut opening = tag
+        .strip_suffix("/>")
+        .or_else(|| tag.strip_suffix('>'))
+        .unwrap_or(tag)
+        .to_string();
+
+    if !opening.contains("xmlns:dc=") {
+        opening.push_str(&format!("\n   xmlns:dc=\"{}\"", DC_NAMESPACE));
+    }
+    if !opening.contains("xmlns:xmp=") {
+        opening.push_str(&format!("\n   xmlns:xmp=\"{}\"", XMP_NAMESPACE));
+    }
+    if rating > 0 {
+        opening.push_str(&format!("\n   xmp:Rating=\"{}\"", rating));
+    }
+    opening.push('>');
+    if !tags.is_empty() {
+        opening.push_str(&subject_element(tags));
+    }
+    if self_closing {
+        opening.push_str("\n  </rdf:Description>");
+    }
+
+    format!(
+        "{}{}{}",
+        &packet[..whole.start()],
+        opening,
+        &packet[whole.end()..]
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
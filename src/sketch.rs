@@ -0,0 +1,64 @@
+use cliprs::ClipModel;
+use std::{path::PathBuf, process::Command};
+
+use crate::{expansion::normalized, paths};
+
+/// Query of the sketch mode that takes the sketch from the clipboard
+pub const CLIPBOARD: &str = "clipboard";
+
+/// Describes what a sketch looks like, its direction is removed from the sketch embedding
+const SKETCH_PROMPT: &str = "a black and white line drawing";
+/// Describes what the results look like, its direction is added to the sketch embedding
+const PHOTO_PROMPT: &str = "a photo";
+
+/// Commands that print the image in the clipboard as PNG, tried in order
+const PASTE_COMMANDS: [&[&str]; 2] = [
+    &["wl-paste", "--no-newline", "--type", "image/png"],
+    &[
+        "xclip",
+        "-selection",
+        "clipboard",
+        "-target",
+        "image/png",
+        "-out",
+    ],
+];
+
+/// Embeds a sketch so that it matches photos with the same composition.
+///
+/// CLIP places drawings close to other drawings, so the difference between a line
+/// drawing and a photo is moved out of the embedding before ranking.
+pub fn embed_sketch(model: &ClipModel, path: &str) -> Result<Vec<f32>, String> {
+    let sketch = normalized(model.embed_image(path)?);
+    let drawing = normalized(model.embed_text(SKETCH_PROMPT)?);
+    let photo = normalized(model.embed_text(PHOTO_PROMPT)?);
+
+    Ok(normalized(
+        sketch
+            .iter()
+            .zip(drawing.iter().zip(&photo))
+            .map(|(sketch, (drawing, photo))| sketch - drawing + photo)
+            .collect(),
+    ))
+}
+
+/// Saves the image in the clipboard to the sketch file and returns its path
+pub fn paste_clipboard() -> Result<PathBuf, String> {
+    let bytes = PASTE_COMMANDS
+        .iter()
+        .filter_map(|command| Command::new(command[0]).args(&command[1..]).output().ok())
+        .find(|output| output.status.success() && !output.stdout.is_empty())
+        .map(|output| output.stdout)
+        .ok_or("There is no image in the clipboard, wl-paste or xclip is needed to read it")?;
+
+    let image = image::load_from_memory(&bytes)
+        .map_err(|e| format!("Failed to read the clipboard image: {}", e))?;
+    let path = paths::sketch_file();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    image
+        .save(&path)
+        .map_err(|e| format!("Failed to save the clipboard image: {}", e))?;
+    Ok(path)
+}
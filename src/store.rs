@@ -0,0 +1,237 @@
+use nano_vectordb_rs::{Data, NanoVectorDB, constants};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+use crate::{atomic, engine::Similarity, qdrant::QdrantStore};
+
+/// A vector store holding the embeddings of the library, opened with `index::open_database`
+pub type Store = Box<dyn VectorStore>;
+
+/// Where the embeddings and metadata of the images are kept.
+///
+/// Every model has its own store, named after its index file. Ids are image paths.
+pub trait VectorStore: Send + Sync {
+    /// The images with the given ids, leaving out the ones that aren't stored
+    fn get(&self, ids: &[String]) -> Vec<Entry<'_>>;
+
+    /// Adds the images or replaces the ones with the same ids
+    fn upsert(&mut self, data: Vec<Data>) -> io::Result<()>;
+
+    fn delete(&mut self, ids: &[String]) -> io::Result<()>;
+
+    /// The `top_k` images with the highest cosine similarity to `query`, best match first
+    fn query(&self, query: &[f32], top_k: usize) -> Vec<(String, f32)>;
+
+    /// Persists the changes. Remote stores write every change right away.
+    fn save(&self) -> io::Result<()>;
+}
+
+/// An image read from a store. Local stores lend their entries, remote ones fetch a
+/// copy, and `Data` can't be cloned, so this stands in for a `Cow`.
+pub enum Entry<'a> {
+    Borrowed(&'a Data),
+    Owned(Data),
+}
+
+impl Entry<'_> {
+    /// The entry as `Data` of its own, copying it if it's borrowed
+    pub fn into_owned(self) -> Data {
+        match self {
+            Entry::Borrowed(data) => Data {
+                id: data.id.clone(),
+                vector: data.vector.clone(),
+                fields: data.fields.clone(),
+            },
+            Entry::Owned(data) => data,
+        }
+    }
+}
+
+impl Deref for Entry<'_> {
+    type Target = Data;
+
+    fn deref(&self) -> &Data {
+        match self {
+            Entry::Borrowed(data) => data,
+            Entry::Owned(data) => data,
+        }
+    }
+}
+
+/// Which vector store holds the embeddings, see `Config::vector_store`
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum StoreConfig {
+    /// A NanoVectorDB file per model in the data directory
+    #[default]
+    Local,
+    /// A Qdrant server, so several machines can share one index
+    Qdrant {
+        /// Base URL of the REST API, e.g. `http://localhost:6333`
+        url: String,
+        #[serde(default)]
+        api_key: Option<String>,
+        /// Prefix of the collection names, the index name is appended to it
+        #[serde(default = "default_collection_prefix")]
+        collection_prefix: String,
+    },
+}
+
+fn default_collection_prefix() -> String {
+    "findimg".into()
+}
+
+impl StoreConfig {
+    /// Opens the store of one index. `file` is where the local backend keeps it, the
+    /// remote backends name their collection after it.
+    pub fn open(
+        &self,
+        file: &Path,
+        dimensions: usize,
+        similarity: Similarity,
+    ) -> Result<Store, String> {
+        match self {
+            StoreConfig::Local => {
+                let database = NanoVectorDB::new(dimensions, &file.to_string_lossy())
+                    .map_err(|e| e.to_string())?;
+                Ok(Box::new(LocalStore {
+                    database,
+                    file: file.to_path_buf(),
+                }))
+            }
+            StoreConfig::Qdrant {
+                url,
+                api_key,
+                collection_prefix,
+            } => {
+                let name = file
+                    .file_stem()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let store = QdrantStore::open(
+                    url,
+                    api_key.clone(),
+                    format!("{}_{}", collection_prefix, name),
+                    dimensions,
+                    similarity,
+                )?;
+                Ok(Box::new(store))
+            }
+        }
+    }
+}
+
+/// The default store, a NanoVectorDB saved to a file in the data directory
+pub struct LocalStore {
+    database: NanoVectorDB,
+    file: PathBuf,
+}
+
+impl VectorStore for LocalStore {
+    fn get(&self, ids: &[String]) -> Vec<Entry<'_>> {
+        self.database
+            .get(ids)
+            .into_iter()
+            .map(Entry::Borrowed)
+            .collect()
+    }
+
+    fn upsert(&mut self, data: Vec<Data>) -> io::Result<()> {
+        self.database
+            .upsert(data)
+            .map(|_| ())
+            .map_err(io::Error::other)
+    }
+
+    fn delete(&mut self, ids: &[String]) -> io::Result<()> {
+        self.database.delete(ids);
+        Ok(())
+    }
+
+    fn query(&self, query: &[f32], top_k: usize) -> Vec<(String, f32)> {
+        self.database
+            .query(query, top_k, None, None)
+            .into_iter()
+            .map(|result| {
+                (
+                    result[constants::F_ID]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    result[constants::F_METRICS].as_f64().unwrap_or_default() as f32,
+                )
+            })
+            .collect()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        atomic::save_database(&self.database, &self.file)
+    }
+}
+
+/// Embeddings kept in memory, for tests that rank images without an index file
+#[cfg(test)]
+pub struct MemoryStore(Vec<Data>);
+
+#[cfg(test)]
+impl MemoryStore {
+    pub fn new<'a>(vectors: impl IntoIterator<Item = (&'a str, Vec<f32>)>) -> Self {
+        Self(
+            vectors
+                .into_iter()
+                .map(|(path, vector)| Data {
+                    id: path.to_string(),
+                    vector,
+                    fields: Default::default(),
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+impl VectorStore for MemoryStore {
+    fn get(&self, ids: &[String]) -> Vec<Entry<'_>> {
+        self.0
+            .iter()
+            .filter(|data| ids.contains(&data.id))
+            .map(Entry::Borrowed)
+            .collect()
+    }
+
+    fn upsert(&mut self, data: Vec<Data>) -> io::Result<()> {
+        let ids: Vec<String> = data.iter().map(|data| data.id.clone()).collect();
+        self.delete(&ids)?;
+        self.0.extend(data);
+        Ok(())
+    }
+
+    fn delete(&mut self, ids: &[String]) -> io::Result<()> {
+        self.0.retain(|data| !ids.contains(&data.id));
+        Ok(())
+    }
+
+    fn query(&self, query: &[f32], top_k: usize) -> Vec<(String, f32)> {
+        let mut ranked: Vec<(String, f32)> = self
+            .0
+            .iter()
+            .map(|data| {
+                (
+                    data.id.clone(),
+                    Similarity::Cosine.score(query, &data.vector),
+                )
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.truncate(top_k);
+        ranked
+    }
+
+    fn save(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
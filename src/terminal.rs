@@ -0,0 +1,81 @@
+use crossterm::{
+    cursor::Show,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::DefaultTerminal;
+use std::{
+    env,
+    fs::File,
+    io::{self, stdout},
+    process::{Command, ExitStatus},
+};
+
+/// Gives the terminal back to the shell or another program
+pub fn leave() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(stdout(), DisableMouseCapture, LeaveAlternateScreen, Show)
+}
+
+/// Takes the terminal over again after `leave` and redraws everything on the next frame
+pub fn enter(terminal: &mut DefaultTerminal) -> io::Result<()> {
+    enable_raw_mode()?;
+    execute!(stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    terminal.clear()
+}
+
+/// Whether this is the old Windows console host instead of Windows Terminal or a terminal
+/// that sets `TERM`. It prints escape sequences beyond the ones crossterm translates as
+/// text and can't draw Braille characters in its default font.
+pub fn is_legacy_console() -> bool {
+    cfg!(windows)
+        && ["WT_SESSION", "TERM", "TERM_PROGRAM"]
+            .iter()
+            .all(|name| env::var_os(name).is_none())
+}
+
+/// Stops the process like Ctrl+Z does in a normal shell program and takes the terminal
+/// over again once it is continued with `fg`.
+#[cfg(unix)]
+pub fn suspend(terminal: &mut DefaultTerminal) -> io::Result<()> {
+    use signal_hook::{consts::SIGTSTP, low_level::emulate_default_handler};
+
+    leave()?;
+    // returns once the process receives SIGCONT
+    emulate_default_handler(SIGTSTP)?;
+    enter(terminal)
+}
+
+/// Points stdout at the terminal, so the interface is drawn there even if stdout is a pipe
+/// or a command substitution. Returns the original stdout to write the result to.
+#[cfg(unix)]
+pub fn redirect_stdout() -> io::Result<File> {
+    use std::os::fd::{AsRawFd, FromRawFd};
+
+    let tty = File::options().read(true).write(true).open("/dev/tty")?;
+    // SAFETY: only file descriptors are duplicated, the new one is owned by the returned file
+    unsafe {
+        let original = libc::dup(libc::STDOUT_FILENO);
+        if original < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::dup2(tty.as_raw_fd(), libc::STDOUT_FILENO) < 0 {
+            let error = io::Error::last_os_error();
+            libc::close(original);
+            return Err(error);
+        }
+        Ok(File::from_raw_fd(original))
+    }
+}
+
+/// Runs a program in the terminal, e.g. an editor, and waits for it to exit
+pub fn run_external(
+    terminal: &mut DefaultTerminal,
+    command: &mut Command,
+) -> io::Result<ExitStatus> {
+    leave()?;
+    let status = command.status();
+    enter(terminal)?;
+    status
+}
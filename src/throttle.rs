@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often the power supply and the network are checked while indexing
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How background indexing holds back so the machine stays usable, see `Config::index_throttle`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct IndexThrottle {
+    /// Index at the lowest CPU priority, other programs get the processor first. Linux only.
+    pub low_priority: bool,
+    /// Most processor cores the model embeds with, all of them if `None`. Linux only.
+    pub max_threads: Option<usize>,
+    /// Wait after every image in milliseconds
+    pub pause_ms: u64,
+    /// Stop indexing while the machine runs on battery
+    pub pause_on_battery: bool,
+    /// Stop indexing while the network connection is metered, e.g. a phone hotspot
+    pub pause_on_metered: bool,
+}
+
+/// Applies an `IndexThrottle` to the thread indexing the library
+pub struct Throttle {
+    config: IndexThrottle,
+    /// When the power supply and the network were last checked, with why indexing is paused
+    checked: Option<(Instant, Option<&'static str>)>,
+}
+
+impl Throttle {
+    /// Lowers the priority of the calling thread and keeps it to `max_threads` cores. The
+    /// threads the model starts from it inherit both.
+    pub fn new(config: IndexThrottle) -> Self {
+        if config.low_priority {
+            lower_priority();
+        }
+        if let Some(threads) = config.max_threads {
+            limit_cores(threads);
+        }
+        Self {
+            config,
+            checked: None,
+        }
+    }
+
+    /// Called after every image: waits `pause_ms`, then blocks while indexing is paused on
+    /// battery or on a metered connection. `paused` is told why each time it is checked.
+    pub fn wait(&mut self, mut paused: impl FnMut(&str)) {
+        if self.config.pause_ms > 0 {
+            thread::sleep(Duration::from_millis(self.config.pause_ms));
+        }
+        if !self.config.pause_on_battery && !self.config.pause_on_metered {
+            return;
+        }
+        loop {
+            let reason = match self.checked {
+                Some((at, reason)) if at.elapsed() < CHECK_INTERVAL => reason,
+                _ => {
+                    let reason = self.pause_reason();
+                    self.checked = Some((Instant::now(), reason));
+                    reason
+                }
+            };
+            let Some(reason) = reason else {
+                return;
+            };
+            paused(reason);
+            thread::sleep(CHECK_INTERVAL);
+        }
+    }
+
+    fn pause_reason(&self) -> Option<&'static str> {
+        if self.config.pause_on_battery && on_battery() {
+            Some("Indexing paused while on battery")
+        } else if self.config.pause_on_metered && on_metered() {
+            Some("Indexing paused on a metered connection")
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether a battery is discharging
+#[cfg(target_os = "linux")]
+fn on_battery() -> bool {
+    let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    supplies.flatten().any(|supply| {
+        let read =
+            |name: &str| std::fs::read_to_string(supply.path().join(name)).unwrap_or_default();
+        read("type").trim() == "Battery" && read("status").trim() == "Discharging"
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn on_battery() -> bool {
+    output("pmset", &["-g", "batt"]).is_some_and(|batt| batt.contains("'Battery Power'"))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn on_battery() -> bool {
+    false
+}
+
+/// Whether NetworkManager considers the connection metered
+fn on_metered() -> bool {
+    output("nmcli", &["-t", "-f", "METERED", "general"])
+        .is_some_and(|metered| metered.trim_start().starts_with("yes"))
+}
+
+/// What a command prints, `None` if it isn't there or fails
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Sets the nice value of the calling thread to the lowest priority
+#[cfg(target_os = "linux")]
+fn lower_priority() {
+    // SAFETY: on Linux the nice value of `PRIO_PROCESS` 0 is the one of the calling thread
+    unsafe {
+        libc::setpriority(libc::PRIO_PROCESS, 0, 19);
+    }
+}
+
+// elsewhere the priority belongs to the whole process, which would slow down the interface too
+#[cfg(not(target_os = "linux"))]
+fn lower_priority() {}
+
+/// Keeps the calling thread, and the threads it starts, to the first `threads` cores
+#[cfg(target_os = "linux")]
+fn limit_cores(threads: usize) {
+    // SAFETY: the set is zeroed and filled with valid core numbers before it is passed on
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for core in 0..threads.clamp(1, libc::CPU_SETSIZE as usize) {
+            libc::CPU_SET(core, &mut set);
+        }
+        libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &set);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn limit_cores(_threads: usize) {}
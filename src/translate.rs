@@ -0,0 +1,32 @@
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranslateResponse {
+    translated_text: String,
+}
+
+/// Translates a query into English with a translation service, so queries in any language
+/// work with an English-only CLIP model.
+///
+/// The request follows the LibreTranslate API, which can run on the same machine: it receives
+/// `{"q": "...", "source": "auto", "target": "en", "format": "text"}` and answers with
+/// `{"translatedText": "..."}`.
+pub fn translate(
+    client: &Client,
+    endpoint: &str,
+    text: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let response = client
+        .post(endpoint)
+        .header("Content-Type", "application/json")
+        .body(json!({ "q": text, "source": "auto", "target": "en", "format": "text" }).to_string())
+        .send()?
+        .error_for_status()?
+        .text()?;
+
+    let response: TranslateResponse = serde_json::from_str(&response)?;
+    Ok(response.translated_text.trim().to_string())
+}
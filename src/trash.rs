@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{atomic, paths};
+
+pub const JOURNAL_FILE: &str = "journal.json";
+
+/// A file that has been moved into the trash.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TrashedFile {
+    /// Where the file was before it got deleted
+    pub original: String,
+    /// Where the file is stored inside the trash
+    pub staged: String,
+    /// Embedding of the image, so it can be restored without embedding it again
+    pub vector: Vec<f32>,
+}
+
+/// One undoable action. Deleting several files at once is a single operation.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Operation {
+    pub files: Vec<TrashedFile>,
+}
+
+/// Staging folder for deleted images, backed by a journal of operations so they can be undone.
+pub struct Trash {
+    dir: PathBuf,
+    journal: Vec<Operation>,
+}
+
+impl Trash {
+    /// Opens the trash and reads its journal. A missing or broken journal results in an empty one.
+    pub fn open() -> Self {
+        Self::at(paths::trash_dir())
+    }
+
+    /// Opens the trash in `dir`
+    fn at(dir: PathBuf) -> Self {
+        let journal = fs::read_to_string(dir.join(JOURNAL_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { dir, journal }
+    }
+
+    /// Moves the given files into the trash as one operation.
+    ///
+    /// # Arguments
+    ///
+    /// - files: Paths of the files together with their embeddings.
+    pub fn delete(&mut self, files: Vec<(String, Vec<f32>)>) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+
+        let mut operation = Operation { files: Vec::new() };
+        for (index, (original, vector)) in files.into_iter().enumerate() {
+            let name = Path::new(&original)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let staged = self.dir.join(format!("{}_{}_{}", stamp, index, name));
+
+            if let Err(e) = move_file(Path::new(&original), &staged) {
+                // keep the journal in sync with the files that did get moved
+                if !operation.files.is_empty() {
+                    self.journal.push(operation);
+                    self.save()?;
+                }
+                return Err(e);
+            }
+
+            operation.files.push(TrashedFile {
+                original,
+                staged: staged.display().to_string(),
+                vector,
+            });
+        }
+
+        self.journal.push(operation);
+        self.save()
+    }
+
+    /// Moves the files of the last operation back to where they were.
+    /// Returns the restored operation or `None` if there is nothing to undo. If a file can't
+    /// be moved back, the operation keeps the files that are still in the trash, so undoing
+    /// again picks up where this one stopped.
+    pub fn undo(&mut self) -> io::Result<Option<Operation>> {
+        let Some(operation) = self.journal.last_mut() else {
+            return Ok(None);
+        };
+
+        let failed = operation
+            .files
+            .iter()
+            .enumerate()
+            .find_map(|(index, file)| restore(file).err().map(|e| (index, e)));
+        if let Some((restored, e)) = failed {
+            // keep the journal in sync with the files that did get moved back
+            operation.files.drain(..restored);
+            self.save()?;
+            return Err(e);
+        }
+
+        let operation = self.journal.pop();
+        self.save()?;
+        Ok(operation)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let content = serde_json::to_string(&self.journal)?;
+        atomic::write(&self.dir.join(JOURNAL_FILE), content)
+    }
+}
+
+/// Moves a file of the trash back to where it was
+fn restore(file: &TrashedFile) -> io::Result<()> {
+    if let Some(parent) = Path::new(&file.original).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    move_file(Path::new(&file.staged), Path::new(&file.original))
+}
+
+/// Renames a file, falling back to copying it if both paths are on different filesystems
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory for a test, the test removes it again
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("findimg-trash-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn undo_restores_the_last_operation() {
+        let dir = scratch("undo");
+        let image = dir.join("library/a.jpg");
+        fs::create_dir_all(image.parent().unwrap()).unwrap();
+        fs::write(&image, "a").unwrap();
+
+        let mut trash = Trash::at(dir.join("trash"));
+        trash
+            .delete(vec![(image.display().to_string(), vec![1.0])])
+            .unwrap();
+        assert!(!image.exists());
+
+        let operation = trash.undo().unwrap().unwrap();
+        assert_eq!(operation.files[0].vector, vec![1.0]);
+        assert_eq!(fs::read_to_string(&image).unwrap(), "a");
+        assert!(trash.undo().unwrap().is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_undo_keeps_the_files_still_in_the_trash() {
+        let dir = scratch("partial");
+        let first = dir.join("library/a.jpg");
+        let second = dir.join("library/album/b.jpg");
+        fs::create_dir_all(second.parent().unwrap()).unwrap();
+        fs::write(&first, "a").unwrap();
+        fs::write(&second, "b").unwrap();
+
+        let mut trash = Trash::at(dir.join("trash"));
+        trash
+            .delete(vec![
+                (first.display().to_string(), Vec::new()),
+                (second.display().to_string(), Vec::new()),
+            ])
+            .unwrap();
+        // a file where the folder of the second image was, so it can't be moved back
+        fs::remove_dir(second.parent().unwrap()).unwrap();
+        fs::write(second.parent().unwrap(), "").unwrap();
+
+        assert!(trash.undo().is_err());
+        assert!(first.exists());
+
+        // the journal on disk only lists the file that is still in the trash
+        let mut reopened = Trash::at(dir.join("trash"));
+        assert_eq!(reopened.journal.len(), 1);
+        assert_eq!(reopened.journal[0].files.len(), 1);
+        assert_eq!(
+            reopened.journal[0].files[0].original,
+            second.display().to_string()
+        );
+
+        fs::remove_file(second.parent().unwrap()).unwrap();
+        let operation = reopened.undo().unwrap().unwrap();
+        assert_eq!(operation.files.len(), 1);
+        assert_eq!(fs::read_to_string(&second).unwrap(), "b");
+        assert!(reopened.undo().unwrap().is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -4,19 +4,51 @@
 
 use ratatui::{
     buffer::Buffer,
-    layout::Rect,
+    layout::{Position, Rect},
     style::{Color, Style},
-    text::Line,
+    text::{Line, Span},
     widgets::Widget,
 };
+use serde::{Deserialize, Serialize};
 
 /// A custom widget that renders a button with a label, theme and state.
 #[derive(Debug, Clone)]
 pub struct Button<'a> {
     label: Line<'a>,
+    icon: Option<&'a str>,
     theme: Theme,
     state: ButtonState,
     padding: (u16, u16, u16, u16),
+    min_width: u16,
+}
+
+/// Accent colors the interface can be drawn in, selectable in the settings
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThemeColor {
+    #[default]
+    Blue,
+    Green,
+    Red,
+}
+
+impl ThemeColor {
+    pub const ALL: [ThemeColor; 3] = [ThemeColor::Blue, ThemeColor::Green, ThemeColor::Red];
+
+    pub const fn theme(self) -> Theme {
+        match self {
+            ThemeColor::Blue => BLUE,
+            ThemeColor::Green => GREEN,
+            ThemeColor::Red => RED,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            ThemeColor::Blue => "Blue",
+            ThemeColor::Green => "Green",
+            ThemeColor::Red => "Red",
+        }
+    }
 }
 
 /// Button states
@@ -25,6 +57,8 @@ pub enum ButtonState {
     Normal,
     Selected,
     Active,
+    /// Dimmed, the button can't be used right now
+    Disabled,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -61,12 +95,43 @@ impl<'a> Button<'a> {
     pub fn new<T: Into<Line<'a>>>(label: T) -> Self {
         Button {
             label: label.into(),
+            icon: None,
             theme: BLUE,
             state: ButtonState::Normal,
             padding: (0, 0, 0, 0),
+            min_width: 0,
         }
     }
 
+    /// Sets an icon or emoji shown in front of the label
+    pub const fn icon(mut self, icon: &'a str) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    /// Sets the width the button takes up at least, see `Button::width`
+    pub const fn min_width(mut self, min_width: u16) -> Self {
+        self.min_width = min_width;
+        self
+    }
+
+    /// Width the button needs for its icon, label and padding, at least the minimum width
+    pub fn width(&self) -> u16 {
+        let (_, right, _, left) = self.padding;
+        let content = self.content().width() as u16 + 2;
+        (content + left + right).max(self.min_width)
+    }
+
+    /// Whether `position` is on the button when it is rendered in `area`, padding excluded.
+    /// Disabled buttons never contain a position, so clicks on them are ignored.
+    pub fn contains(&self, area: Rect, position: Position) -> bool {
+        self.state != ButtonState::Disabled && self.inner(area).contains(position)
+    }
+
+    pub const fn is_disabled(&self) -> bool {
+        matches!(self.state, ButtonState::Disabled)
+    }
+
     /// Sets the theme of the button
     pub const fn theme(mut self, theme: Theme) -> Self {
         self.theme = theme;
@@ -89,15 +154,7 @@ impl<'a> Button<'a> {
 impl<'a> Widget for Button<'a> {
     #[allow(clippy::cast_possible_truncation)]
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let mut area = area;
-
-        {
-            let (top, right, bottom, left) = self.padding;
-            area.width -= left + right;
-            area.x += left;
-            area.height -= top + bottom;
-            area.y += top;
-        }
+        let area = self.inner(area);
 
         let (background, text, shadow, highlight) = self.colors();
         buf.set_style(area, Style::new().bg(background).fg(text));
@@ -121,10 +178,11 @@ impl<'a> Widget for Button<'a> {
             );
         }
         // render label centered
+        let content = self.content();
         buf.set_line(
-            area.x + (area.width.saturating_sub(self.label.width() as u16)) / 2,
+            area.x + (area.width.saturating_sub(content.width() as u16)) / 2,
             area.y + (area.height.saturating_sub(1)) / 2,
-            &self.label,
+            &content,
             area.width,
         );
     }
@@ -138,6 +196,27 @@ impl Button<'_> {
             ButtonState::Normal => (theme.background, theme.text, theme.shadow, theme.highlight),
             ButtonState::Selected => (theme.highlight, theme.text, theme.shadow, theme.highlight),
             ButtonState::Active => (theme.background, theme.text, theme.highlight, theme.shadow),
+            ButtonState::Disabled => (theme.shadow, Color::DarkGray, theme.shadow, theme.shadow),
+        }
+    }
+
+    /// The area inside the padding
+    fn inner(&self, area: Rect) -> Rect {
+        let (top, right, bottom, left) = self.padding;
+        Rect {
+            x: area.x + left,
+            y: area.y + top,
+            width: area.width.saturating_sub(left + right),
+            height: area.height.saturating_sub(top + bottom),
+        }
+    }
+
+    /// The label with the icon in front of it
+    fn content(&self) -> Line<'_> {
+        let mut content = self.label.clone();
+        if let Some(icon) = self.icon {
+            content.spans.insert(0, Span::raw(format!("{} ", icon)));
         }
+        content
     }
 }
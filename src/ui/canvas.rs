@@ -0,0 +1,208 @@
+use image::{GrayImage, Luma};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, HorizontalAlignment, Layout, Position, Rect},
+    style::{Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Clear, Paragraph},
+};
+use std::path::Path;
+
+use crate::ui::button::Theme;
+
+/// Size of the canvas in terminal cells
+const COLUMNS: usize = 64;
+const ROWS: usize = 24;
+/// Braille characters have 2 by 4 dots per cell
+const DOT_COLUMNS: usize = COLUMNS * 2;
+const DOT_ROWS: usize = ROWS * 4;
+/// Pixels per dot in the rasterized sketch
+const PIXELS_PER_DOT: u32 = 4;
+/// Width of the strokes in the rasterized sketch, in pixels
+const STROKE_WIDTH: u32 = 8;
+
+/// Bit of each dot in a braille cell, indexed by `[y][x]`
+const BRAILLE_BITS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// A canvas to draw a sketch on with the mouse or the arrow keys, shown in braille dots
+#[derive(Debug)]
+pub struct SketchCanvas {
+    /// Set dots, indexed by `y * DOT_COLUMNS + x`
+    dots: Vec<bool>,
+    /// Dot moved with the arrow keys
+    cursor: (usize, usize),
+    /// Whether moving the cursor draws
+    pen_down: bool,
+    /// Last dot drawn with the mouse, so a drag draws a connected line
+    last_mouse_dot: Option<(usize, usize)>,
+    /// Where the dots were drawn last, to map mouse positions to dots
+    area: Rect,
+}
+
+impl Default for SketchCanvas {
+    fn default() -> Self {
+        Self {
+            dots: vec![false; DOT_COLUMNS * DOT_ROWS],
+            cursor: (DOT_COLUMNS / 2, DOT_ROWS / 2),
+            pen_down: false,
+            last_mouse_dot: None,
+            area: Rect::default(),
+        }
+    }
+}
+
+impl SketchCanvas {
+    pub fn is_empty(&self) -> bool {
+        !self.dots.contains(&true)
+    }
+
+    pub fn clear(&mut self) {
+        self.dots.fill(false);
+    }
+
+    /// Lifts or lowers the pen, a lowered pen draws at the cursor right away
+    pub fn toggle_pen(&mut self) {
+        self.pen_down = !self.pen_down;
+        if self.pen_down {
+            self.set(self.cursor);
+        }
+    }
+
+    /// Moves the cursor by one dot, drawing on the way if the pen is down
+    pub fn move_cursor(&mut self, dx: isize, dy: isize) {
+        let x = self.cursor.0.saturating_add_signed(dx).min(DOT_COLUMNS - 1);
+        let y = self.cursor.1.saturating_add_signed(dy).min(DOT_ROWS - 1);
+        self.cursor = (x, y);
+        if self.pen_down {
+            self.set(self.cursor);
+        }
+    }
+
+    /// Draws at a mouse position. `dragging` connects it to the previous position.
+    pub fn mouse(&mut self, position: Position, dragging: bool) {
+        if !self.area.contains(position) {
+            self.last_mouse_dot = None;
+            return;
+        }
+        // the middle of the cell, the mouse is only reported per cell
+        let dot = (
+            (position.x - self.area.x) as usize * 2,
+            (position.y - self.area.y) as usize * 4 + 1,
+        );
+        match self.last_mouse_dot {
+            Some(last) if dragging => self.line(last, dot),
+            _ => self.set(dot),
+        }
+        self.last_mouse_dot = Some(dot);
+        self.cursor = dot;
+    }
+
+    /// Ends a mouse stroke
+    pub fn release(&mut self) {
+        self.last_mouse_dot = None;
+    }
+
+    fn set(&mut self, (x, y): (usize, usize)) {
+        if x < DOT_COLUMNS && y < DOT_ROWS {
+            self.dots[y * DOT_COLUMNS + x] = true;
+        }
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.dots[y * DOT_COLUMNS + x]
+    }
+
+    /// Sets the dots on the straight line between two dots
+    fn line(&mut self, from: (usize, usize), to: (usize, usize)) {
+        let (x0, y0) = (from.0 as isize, from.1 as isize);
+        let (x1, y1) = (to.0 as isize, to.1 as isize);
+        let steps = (x1 - x0).abs().max((y1 - y0).abs()).max(1);
+        for step in 0..=steps {
+            let x = x0 + (x1 - x0) * step / steps;
+            let y = y0 + (y1 - y0) * step / steps;
+            self.set((x as usize, y as usize));
+        }
+    }
+
+    /// Saves the sketch as black strokes on a white image
+    pub fn save(&self, path: &Path) -> image::ImageResult<()> {
+        let width = DOT_COLUMNS as u32 * PIXELS_PER_DOT;
+        let height = DOT_ROWS as u32 * PIXELS_PER_DOT;
+        let mut image = GrayImage::from_pixel(width, height, Luma([255]));
+
+        let offset = (STROKE_WIDTH - PIXELS_PER_DOT) / 2;
+        for y in 0..DOT_ROWS {
+            for x in 0..DOT_COLUMNS {
+                if !self.get(x, y) {
+                    continue;
+                }
+                let left = (x as u32 * PIXELS_PER_DOT).saturating_sub(offset);
+                let top = (y as u32 * PIXELS_PER_DOT).saturating_sub(offset);
+                for py in top..(top + STROKE_WIDTH).min(height) {
+                    for px in left..(left + STROKE_WIDTH).min(width) {
+                        image.put_pixel(px, py, Luma([0]));
+                    }
+                }
+            }
+        }
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        image.save(path)
+    }
+
+    /// Draws the canvas in the middle of the screen
+    pub fn draw(&mut self, frame: &mut Frame, theme: Theme) {
+        let [area] = Layout::vertical([Constraint::Length(ROWS as u16 + 2)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let [area] = Layout::horizontal([Constraint::Length(COLUMNS as u16 + 2)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let pen = if self.pen_down { "pen down" } else { "pen up" };
+        let block = Block::bordered()
+            .title(format!("Sketch ({})", pen))
+            .title_alignment(HorizontalAlignment::Center)
+            .title_bottom(
+                "Drag or arrows + Space to draw, c to clear, Enter to search, Esc to close",
+            )
+            .border_type(BorderType::Rounded)
+            .fg(theme.background);
+        self.area = block.inner(area);
+
+        let cursor_cell = (self.cursor.0 / 2, self.cursor.1 / 4);
+        let lines: Vec<Line> = (0..ROWS)
+            .map(|row| {
+                let spans: Vec<Span> = (0..COLUMNS)
+                    .map(|column| {
+                        let mut bits = 0;
+                        for (dy, row_bits) in BRAILLE_BITS.iter().enumerate() {
+                            for (dx, bit) in row_bits.iter().enumerate() {
+                                if self.get(column * 2 + dx, row * 4 + dy) {
+                                    bits |= bit;
+                                }
+                            }
+                        }
+                        let symbol = char::from_u32(0x2800 + bits).unwrap_or(' ').to_string();
+                        if (column, row) == cursor_cell {
+                            Span::styled(
+                                symbol,
+                                Style::new()
+                                    .bg(theme.highlight)
+                                    .add_modifier(Modifier::BOLD),
+                            )
+                        } else {
+                            Span::raw(symbol)
+                        }
+                    })
+                    .collect();
+                Line::from(spans)
+            })
+            .collect();
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
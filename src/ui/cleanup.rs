@@ -0,0 +1,149 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Clear, Paragraph},
+};
+use std::collections::HashMap;
+
+use crate::{cleanup::Group, ui::button::Theme};
+
+/// What happens to an image when the clean-up is finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Keep,
+    Delete,
+}
+
+/// A clean-up of the library going through the groups found by `cleanup::find_groups` one
+/// by one. Nothing is deleted until it is finished and confirmed.
+#[derive(Debug)]
+pub struct CleanupSession {
+    groups: Vec<Group>,
+    /// Index of the group shown in the results
+    pub current: usize,
+    /// Decisions on the images of the groups shown so far, starting at the suggestion
+    decisions: HashMap<String, Decision>,
+    /// Decisions changed by hand with what they were before, most recent last
+    undo: Vec<(usize, String, Decision)>,
+}
+
+impl CleanupSession {
+    pub fn new(groups: Vec<Group>) -> Self {
+        let mut session = Self {
+            groups,
+            current: 0,
+            decisions: HashMap::new(),
+            undo: Vec::new(),
+        };
+        session.visit();
+        session
+    }
+
+    /// The group shown in the results
+    pub fn group(&self) -> &Group {
+        &self.groups[self.current]
+    }
+
+    /// Fills in the suggested decisions of the current group where there is none yet
+    fn visit(&mut self) {
+        let group = &self.groups[self.current];
+        for (index, path) in group.paths.iter().enumerate() {
+            let suggested = if group.suggests_deleting(index) {
+                Decision::Delete
+            } else {
+                Decision::Keep
+            };
+            self.decisions.entry(path.clone()).or_insert(suggested);
+        }
+    }
+
+    /// Moves to the next group, or the previous one for a negative step. Returns whether
+    /// there was one.
+    pub fn step(&mut self, step: isize) -> bool {
+        let Some(next) = self
+            .current
+            .checked_add_signed(step)
+            .filter(|next| *next < self.groups.len())
+        else {
+            return false;
+        };
+        self.current = next;
+        self.visit();
+        true
+    }
+
+    pub fn decision(&self, path: &str) -> Option<Decision> {
+        self.decisions.get(path).copied()
+    }
+
+    /// Flips an image of the current group between keep and delete
+    pub fn toggle(&mut self, path: &str) {
+        let Some(decision) = self.decisions.get_mut(path) else {
+            return;
+        };
+        self.undo.push((self.current, path.to_string(), *decision));
+        *decision = match decision {
+            Decision::Keep => Decision::Delete,
+            Decision::Delete => Decision::Keep,
+        };
+    }
+
+    /// Takes back the last decision changed by hand and moves to its group. Returns whether
+    /// there was one.
+    pub fn undo(&mut self) -> bool {
+        let Some((group, path, decision)) = self.undo.pop() else {
+            return false;
+        };
+        self.decisions.insert(path, decision);
+        self.current = group;
+        true
+    }
+
+    /// The images marked for deletion in the groups shown so far
+    pub fn marked(&self) -> Vec<String> {
+        let mut marked: Vec<String> = self
+            .decisions
+            .iter()
+            .filter(|(_, decision)| **decision == Decision::Delete)
+            .map(|(path, _)| path.clone())
+            .collect();
+        marked.sort();
+        marked
+    }
+
+    /// Draws the progress and the keys over the help line
+    pub fn draw(&self, frame: &mut Frame, area: Rect, theme: Theme) {
+        let group = self.group();
+        let mut spans = vec![
+            format!(" Clean up {}/{} ", self.current + 1, self.groups.len())
+                .bold()
+                .fg(theme.highlight),
+            format!("{} ", group.kind.name()).bold(),
+        ];
+        if let Some(date) = &group.date {
+            spans.push(format!("{} ", date).into());
+        }
+        spans.extend([
+            format!("· {} marked for deletion · ", self.marked().len()).into(),
+            "Space".bold(),
+            " keep/delete, ".into(),
+            "n".bold(),
+            "/".into(),
+            "b".bold(),
+            " next/previous group, ".into(),
+            "u".bold(),
+            " undo, ".into(),
+            "c".bold(),
+            " finish, ".into(),
+            "q".bold(),
+            " stop".into(),
+        ]);
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(Line::from(spans)).style(Style::default()),
+            area,
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use ratatui::layout::{Constraint, Layout, Rect};
+use serde::{Deserialize, Serialize};
+
+/// Minimum height of a row in the list layout
+const LIST_ROW_HEIGHT: u16 = 12;
+/// Widest and tallest an image is laid out as, so a panorama doesn't squeeze the others of
+/// its row into slivers
+const MAX_ASPECT: f64 = 3.0;
+/// Aspect ratio of a square image in cells about twice as tall as wide, for results of
+/// unknown size
+const SQUARE: f64 = 2.0;
+
+/// Width over height of an image in terminal cells, which are about twice as tall as wide.
+/// Images of unknown size are laid out as squares.
+pub fn aspect(dims: Option<(u32, u32)>, font_size: (u16, u16)) -> f64 {
+    let (font_width, font_height) = font_size;
+    let image = match dims {
+        Some((width, height)) if width > 0 && height > 0 => width as f64 / height as f64,
+        _ => 1.0,
+    };
+    let cell = if font_width > 0 && font_height > 0 {
+        font_height as f64 / font_width as f64
+    } else {
+        SQUARE
+    };
+    image.clamp(1.0 / MAX_ASPECT, MAX_ASPECT) * cell
+}
+
+/// The ways the result cells can be arranged inside the images block.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GridLayout {
+    /// The best match on the left half, the other results in a grid on the right half
+    #[default]
+    Featured,
+    /// A uniform grid of equally sized cells
+    Grid,
+    /// The selected image large on top, every result in a strip below it
+    Filmstrip,
+    /// A single column of large previews
+    List,
+}
+
+impl GridLayout {
+    /// Returns the next layout, wrapping around after the last one
+    pub const fn next(self) -> Self {
+        match self {
+            GridLayout::Featured => GridLayout::Grid,
+            GridLayout::Grid => GridLayout::Filmstrip,
+            GridLayout::Filmstrip => GridLayout::List,
+            GridLayout::List => GridLayout::Featured,
+        }
+    }
+
+    /// Name of the layout as shown in the UI
+    pub const fn name(self) -> &'static str {
+        match self {
+            GridLayout::Featured => "Featured",
+            GridLayout::Grid => "Grid",
+            GridLayout::Filmstrip => "Filmstrip",
+            GridLayout::List => "List",
+        }
+    }
+
+    /// Splits `area` into cells for `count` results.
+    ///
+    /// The returned areas are indexed by result. Layouts which can not fit every result
+    /// return fewer areas than `count`.
+    ///
+    /// # Arguments
+    ///
+    /// - area: The area the cells will be placed in.
+    /// - count: The number of results to place.
+    /// - aspects: Width over height of the results in terminal cells, see `aspect`. Results
+    ///   past its end are laid out as squares.
+    /// - columns: Number of columns for the uniform grid, `None` picks one automatically.
+    /// - selected: The index of the selected result, used as the large image of the filmstrip.
+    pub fn areas(
+        self,
+        area: Rect,
+        count: usize,
+        aspects: &[f64],
+        columns: Option<usize>,
+        selected: usize,
+    ) -> Vec<Rect> {
+        if count == 0 {
+            return Vec::new();
+        }
+        if count == 1 {
+            return vec![area];
+        }
+
+        let aspects: Vec<f64> = (0..count)
+            .map(|i| aspects.get(i).copied().unwrap_or(SQUARE))
+            .collect();
+        match self {
+            GridLayout::Featured => featured(area, &aspects),
+            GridLayout::Grid => {
+                let columns = columns
+                    .unwrap_or_else(|| (count as f64).sqrt().ceil() as usize)
+                    .clamp(1, count);
+                uniform(area, &aspects, columns)
+            }
+            GridLayout::Filmstrip => filmstrip(area, &aspects, selected),
+            GridLayout::List => {
+                let fitting = (area.height / LIST_ROW_HEIGHT).max(1) as usize;
+                uniform(area, &aspects[..count.min(fitting)], 1)
+            }
+        }
+    }
+}
+
+/// Weight of a `Constraint::Fill` for a share of a row or column
+fn weight(share: f64) -> Constraint {
+    Constraint::Fill((share * 100.0).round().max(1.0) as u16)
+}
+
+/// Splits `area` into a cell per image, as wide as their aspect ratios ask for at the height
+/// of the row
+fn row(area: Rect, aspects: &[f64]) -> Vec<Rect> {
+    Layout::horizontal(aspects.iter().map(|&aspect| weight(aspect)))
+        .split(area)
+        .to_vec()
+}
+
+/// Rows of `columns` images, like a justified photo gallery: a row of wide images is lower
+/// than a row of tall ones, so both fill the width with little letterboxing
+fn uniform(area: Rect, aspects: &[f64], columns: usize) -> Vec<Rect> {
+    let rows: Vec<Vec<f64>> = aspects
+        .chunks(columns)
+        .map(|row| {
+            // keep cells of the last row about as wide as the ones above
+            let mut row = row.to_vec();
+            row.resize(columns, SQUARE);
+            row
+        })
+        .collect();
+    // a row as wide as the area is its width over the summed aspect ratios high
+    let heights = rows.iter().map(|row| weight(1.0 / row.iter().sum::<f64>()));
+    let row_areas = Layout::vertical(heights).split(area);
+
+    let mut areas = Vec::with_capacity(aspects.len());
+    for (row_area, row_aspects) in row_areas.iter().zip(&rows) {
+        let n = (aspects.len() - areas.len()).min(columns);
+        let mut cells = row(*row_area, row_aspects);
+        cells.truncate(n);
+        areas.extend(cells);
+    }
+    areas
+}
+
+fn filmstrip(area: Rect, aspects: &[f64], selected: usize) -> Vec<Rect> {
+    let selected = selected.min(aspects.len() - 1);
+    let [main_area, strip_area] =
+        Layout::vertical([Constraint::Percentage(75), Constraint::Percentage(25)]).areas(area);
+
+    // the selected result is shown large, the strip holds the others in their order
+    let others: Vec<f64> = aspects
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| *index != selected)
+        .map(|(_, aspect)| *aspect)
+        .collect();
+    let mut areas = row(strip_area, &others);
+    areas.insert(selected, main_area);
+    areas
+}
+
+fn featured(area: Rect, aspects: &[f64]) -> Vec<Rect> {
+    let count = aspects.len();
+    let mut areas = Vec::with_capacity(count);
+
+    let main_split =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).split(area);
+
+    areas.push(main_split[0]);
+
+    let right_area = main_split[1];
+    let remaining = count - 1;
+
+    let r_constraints = if remaining <= 2 {
+        vec![Constraint::Percentage(100)]
+    } else if remaining <= 5 {
+        vec![Constraint::Percentage(50), Constraint::Percentage(50)]
+    } else {
+        vec![
+            Constraint::Percentage(33),
+            Constraint::Percentage(33),
+            Constraint::Percentage(34),
+        ]
+    };
+
+    let r_rows = Layout::vertical(r_constraints).split(right_area);
+
+    let mut r_idx = 0;
+
+    if remaining > 0 {
+        let n = if remaining <= 2 { remaining } else { 2 };
+        areas.extend(row(r_rows[0], &aspects[1..1 + n]));
+        r_idx += n;
+    }
+
+    if remaining > r_idx {
+        let rem = remaining - r_idx;
+        let limit = if remaining <= 5 { rem } else { 3 };
+        let n = rem.min(limit);
+
+        areas.extend(row(r_rows[1], &aspects[1 + r_idx..1 + r_idx + n]));
+        r_idx += n;
+    }
+
+    if remaining > r_idx {
+        areas.extend(row(r_rows[2], &aspects[1 + r_idx..]));
+    }
+
+    areas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rect = Rect::new(0, 0, 120, 40);
+
+    fn overlaps(a: Rect, b: Rect) -> bool {
+        !a.intersection(b).is_empty()
+    }
+
+    /// Every result gets a cell inside the area and no two cells overlap
+    fn assert_tiled(areas: &[Rect]) {
+        for (index, cell) in areas.iter().enumerate() {
+            assert!(!cell.is_empty(), "cell {} is empty", index);
+            assert_eq!(AREA.intersection(*cell), *cell);
+            for other in &areas[index + 1..] {
+                assert!(!overlaps(*cell, *other), "{:?} overlaps {:?}", cell, other);
+            }
+        }
+    }
+
+    #[test]
+    fn every_layout_places_every_result() {
+        for layout in [
+            GridLayout::Featured,
+            GridLayout::Grid,
+            GridLayout::Filmstrip,
+        ] {
+            for count in 1..=10 {
+                let areas = layout.areas(AREA, count, &[], None, 0);
+                assert_eq!(
+                    areas.len(),
+                    count,
+                    "{} with {} results",
+                    layout.name(),
+                    count
+                );
+                assert_tiled(&areas);
+            }
+        }
+    }
+
+    #[test]
+    fn filmstrip_shows_the_selected_result_large_and_the_others_in_the_strip() {
+        for selected in 0..5 {
+            let areas = GridLayout::Filmstrip.areas(AREA, 5, &[], None, selected);
+            assert_eq!(areas.len(), 5);
+            assert_tiled(&areas);
+            assert_eq!(areas[selected].width, AREA.width);
+            // the strip is filled by the other four without a gap
+            let strip: Vec<&Rect> = areas
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != selected)
+                .map(|(_, area)| area)
+                .collect();
+            assert_eq!(strip.iter().map(|area| area.width).sum::<u16>(), AREA.width);
+            assert!(strip.windows(2).all(|pair| pair[0].x < pair[1].x));
+        }
+    }
+
+    #[test]
+    fn featured_keeps_the_best_match_on_the_left_half() {
+        for count in 2..=10 {
+            let areas = GridLayout::Featured.areas(AREA, count, &[], None, 0);
+            assert_eq!(areas[0], Rect::new(0, 0, 60, 40));
+            assert!(areas[1..].iter().all(|area| area.x >= 60));
+        }
+    }
+
+    #[test]
+    fn featured_rows_take_the_aspects_of_their_own_results() {
+        // the results in the second row of the right half are a portrait and a panorama
+        let mut aspects = vec![SQUARE; 7];
+        aspects[3] = aspect(Some((2, 3)), (1, 2));
+        aspects[4] = aspect(Some((3, 1)), (1, 2));
+        let areas = GridLayout::Featured.areas(AREA, 7, &aspects, None, 0);
+        assert_eq!(areas[1].width, areas[2].width);
+        assert_eq!(areas[3].y, areas[4].y);
+        assert!(areas[4].width > 2 * areas[3].width);
+    }
+
+    #[test]
+    fn grid_gives_wide_images_wide_cells_and_low_rows() {
+        let portrait = aspect(Some((2, 3)), (1, 2));
+        let panorama = aspect(Some((3, 1)), (1, 2));
+        let areas = GridLayout::Grid.areas(
+            AREA,
+            4,
+            &[portrait, panorama, panorama, panorama],
+            Some(2),
+            0,
+        );
+        assert_tiled(&areas);
+        assert!(areas[1].width > areas[0].width);
+        assert!(areas[2].height < areas[0].height);
+    }
+
+    #[test]
+    fn aspect_is_in_cells_and_clamped() {
+        assert_eq!(aspect(None, (8, 16)), SQUARE);
+        assert_eq!(aspect(Some((200, 100)), (8, 16)), 4.0);
+        assert_eq!(aspect(Some((1000, 100)), (8, 16)), MAX_ASPECT * 2.0);
+        assert_eq!(aspect(Some((100, 1000)), (8, 16)), 2.0 / MAX_ASPECT);
+    }
+}
@@ -0,0 +1,83 @@
+use ratatui::{
+    Frame,
+    layout::{HorizontalAlignment, Rect},
+    style::Stylize,
+    text::Line,
+    widgets::{Block, BorderType, Clear, Paragraph},
+};
+use std::time::Duration;
+
+use crate::{locale::Locale, search::Timings, ui::button::Theme};
+
+const WIDTH: u16 = 30;
+const HEIGHT: u16 = 10;
+
+/// Draws the performance HUD in the top right corner: where the time of the last search
+/// went, how many of its rankings came from the score cache and how much memory findimg
+/// uses right now
+pub fn draw(frame: &mut Frame, timings: Option<&Timings>, locale: Locale, theme: Theme) {
+    let frame_area = frame.area();
+    let area = Rect::new(
+        frame_area.width.saturating_sub(WIDTH),
+        0,
+        WIDTH.min(frame_area.width),
+        HEIGHT.min(frame_area.height),
+    );
+
+    let row =
+        |name: &str, value: String| Line::from(vec![format!(" {:<11}", name).into(), value.bold()]);
+    let milliseconds = |duration: Duration| {
+        format!(
+            "{:>8} ms",
+            locale.decimal(duration.as_secs_f64() * 1000.0, 1)
+        )
+    };
+    let mut lines = match timings {
+        Some(timings) => vec![
+            row("Embedding", milliseconds(timings.embedding)),
+            row("Scoring", milliseconds(timings.scoring)),
+            row("Decoding", milliseconds(timings.decoding)),
+            row("Encoding", milliseconds(timings.encoding)),
+            row(
+                "Total",
+                milliseconds(
+                    timings.embedding + timings.scoring + timings.decoding + timings.encoding,
+                ),
+            ),
+            row(
+                "Cache hits",
+                format!("{:>8} / {}", timings.cache_hits, timings.rankings),
+            ),
+        ],
+        None => vec![Line::from(" No search yet")],
+    };
+    lines.push(Line::default());
+    lines.push(row(
+        "Memory",
+        resident_memory().map_or("unknown".into(), |bytes| locale.size(bytes)),
+    ));
+
+    let block = Block::bordered()
+        .title("Last search")
+        .title_alignment(HorizontalAlignment::Center)
+        .title_bottom("F2 to close")
+        .border_type(BorderType::Rounded)
+        .fg(theme.highlight);
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Resident set size of the process in bytes, `None` where it can't be read
+#[cfg(target_os = "linux")]
+fn resident_memory() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: sysconf only reads a system setting
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * u64::try_from(page_size).ok()?)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_memory() -> Option<u64> {
+    None
+}
@@ -0,0 +1,84 @@
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, HorizontalAlignment, Layout},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, BorderType, Clear, LineGauge, Paragraph},
+};
+use std::sync::Arc;
+
+use crate::{
+    jobs::{Job, JobState},
+    ui::button::Theme,
+};
+
+/// The background jobs popup, with the progress of every job
+#[derive(Debug, Default)]
+pub struct JobsView {
+    /// Index of the selected job
+    pub selected: usize,
+}
+
+impl JobsView {
+    pub fn draw(&mut self, frame: &mut Frame, jobs: &[Arc<Job>], theme: Theme) {
+        self.selected = self.selected.min(jobs.len().saturating_sub(1));
+
+        let height = jobs.len().max(1) as u16 + 2;
+        let [area] = Layout::vertical([Constraint::Length(height)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let [area] = Layout::horizontal([Constraint::Percentage(70)])
+            .flex(Flex::Center)
+            .areas(area);
+        let block = Block::bordered()
+            .title(format!("Background jobs ({})", jobs.len()))
+            .title_bottom("Space pause/resume, x cancel, Esc to close")
+            .title_alignment(HorizontalAlignment::Center)
+            .border_type(BorderType::Rounded)
+            .fg(theme.background);
+        let inner = block.inner(area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+
+        if jobs.is_empty() {
+            frame.render_widget(
+                Paragraph::new(" Nothing is running in the background"),
+                inner,
+            );
+            return;
+        }
+        let rows = Layout::vertical(vec![Constraint::Length(1); jobs.len()]).split(inner);
+        for (index, (job, row)) in jobs.iter().zip(rows.iter()).enumerate() {
+            let [name_area, gauge_area, state_area] = Layout::horizontal([
+                Constraint::Percentage(40),
+                Constraint::Fill(1),
+                Constraint::Length(11),
+            ])
+            .areas(*row);
+
+            let mut name = Line::from(format!(" {}", job.name));
+            if index == self.selected {
+                name = name.bold().bg(theme.highlight);
+            }
+            frame.render_widget(name, name_area);
+
+            match job.progress() {
+                Some((done, total)) => frame.render_widget(
+                    LineGauge::default()
+                        .ratio((done as f64 / total as f64).clamp(0.0, 1.0))
+                        .label(format!("{}/{}", done, total))
+                        .filled_style(Style::default().fg(theme.highlight)),
+                    gauge_area,
+                ),
+                None => frame.render_widget(Line::from(" in progress"), gauge_area),
+            }
+
+            let state = match job.state() {
+                JobState::Running => "running",
+                JobState::Paused => "paused",
+                JobState::Cancelled => "stopping",
+            };
+            frame.render_widget(Line::from(format!(" {}", state)), state_area);
+        }
+    }
+}
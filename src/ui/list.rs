@@ -13,6 +13,8 @@
 //! [examples]: https://github.com/ratatui/ratatui/blob/main/examples
 //! [examples readme]: https://github.com/ratatui/ratatui/blob/main/examples/README.md
 
+use serde::{Deserialize, Serialize};
+
 use ratatui::{
     style::{
         Color,
@@ -26,6 +28,7 @@ const NORMAL_ROW_BG: Color = SLATE.c950;
 const ALT_ROW_BG_COLOR: Color = SLATE.c900;
 const TEXT_FG_COLOR: Color = SLATE.c200;
 const CHECKED_TEXT_FG_COLOR: Color = GREEN.c500;
+const DISABLED_TEXT_FG_COLOR: Color = SLATE.c600;
 
 /// This struct holds the current state of the app. In particular, it has the `todo_list` field
 /// which is a wrapper around `ListState`. Keeping track of the state lets us render the
@@ -34,25 +37,72 @@ const CHECKED_TEXT_FG_COLOR: Color = GREEN.c500;
 /// Check the event handling at the bottom to see how to change the state on incoming events. Check
 /// the drawing logic for items on how to specify the highlighting style for selected items.
 
-#[derive(Debug, Default)]
-pub struct OptionList {
-    pub items: Vec<OptionItem>,
+#[derive(Debug)]
+pub struct OptionList<T = SearchEnum> {
+    pub items: Vec<OptionItem<T>>,
     pub state: ListState,
+    /// Whether one or several items can be checked at once
+    pub selection: Selection,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SearchEnum {
     Search,
     NegativePrompt,
     Ranking,
     Image2Image,
+    Color,
+    TextOnImage,
+    Sketch,
+    Outliers,
+}
+
+impl SearchEnum {
+    pub const ALL: [SearchEnum; 8] = [
+        SearchEnum::Search,
+        SearchEnum::NegativePrompt,
+        SearchEnum::Ranking,
+        SearchEnum::Image2Image,
+        SearchEnum::Color,
+        SearchEnum::TextOnImage,
+        SearchEnum::Sketch,
+        SearchEnum::Outliers,
+    ];
+
+    /// Name of the mode as shown in the mode list
+    pub const fn name(self) -> &'static str {
+        match self {
+            SearchEnum::Search => "Search",
+            SearchEnum::NegativePrompt => "Negative Prompt",
+            SearchEnum::Ranking => "Ranking",
+            SearchEnum::Image2Image => "Image 2 Image",
+            SearchEnum::Color => "Color",
+            SearchEnum::TextOnImage => "Text on image",
+            SearchEnum::Sketch => "Sketch",
+            SearchEnum::Outliers => "Outliers",
+        }
+    }
+}
+
+/// How many items of an `OptionList` can be checked
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    /// Checking an item unchecks all others, like radio buttons
+    #[default]
+    Single,
+    /// Every item is toggled on its own, like checkboxes
+    #[allow(unused)]
+    Multi,
 }
 
 #[derive(Debug)]
-pub struct OptionItem {
+pub struct OptionItem<T = SearchEnum> {
     pub option: String,
     pub status: OptionStatus,
-    pub search_type: SearchEnum,
+    /// The value the item stands for, returned when it is checked
+    pub value: T,
+    /// Disabled items are dimmed and can't be checked or selected
+    pub disabled: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -61,25 +111,132 @@ pub enum OptionStatus {
     Checked,
 }
 
-impl FromIterator<(OptionStatus, &'static str, SearchEnum)> for OptionList {
-    fn from_iter<I: IntoIterator<Item = (OptionStatus, &'static str, SearchEnum)>>(
-        iter: I,
-    ) -> Self {
+impl<T> Default for OptionList<T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            state: ListState::default(),
+            selection: Selection::default(),
+        }
+    }
+}
+
+impl<T> FromIterator<(OptionStatus, &'static str, T)> for OptionList<T> {
+    fn from_iter<I: IntoIterator<Item = (OptionStatus, &'static str, T)>>(iter: I) -> Self {
         let items = iter
             .into_iter()
-            .map(|(status, option, search_type)| OptionItem::new(status, option, search_type))
+            .map(|(status, option, value)| OptionItem::new(status, option, value))
             .collect();
-        let state = ListState::default();
-        Self { items, state }
+        Self {
+            items,
+            ..Self::default()
+        }
     }
 }
 
-impl OptionItem {
-    fn new(status: OptionStatus, todo: &str, search_type: SearchEnum) -> Self {
+impl<T: Copy + PartialEq> OptionList<T> {
+    /// Turns the list into a multi-select list
+    #[allow(unused)]
+    pub fn multi(mut self) -> Self {
+        self.selection = Selection::Multi;
+        self
+    }
+
+    /// Enables or disables the item holding `value`. Disabled items are unchecked.
+    #[allow(unused)]
+    pub fn set_disabled(&mut self, value: T, disabled: bool) {
+        for item in self.items.iter_mut().filter(|item| item.value == value) {
+            item.disabled = disabled;
+            if disabled {
+                item.status = OptionStatus::Unchecked;
+            }
+        }
+    }
+
+    /// Toggles the highlighted item. In single-select mode all other items are unchecked.
+    /// Returns the value of the toggled item, `None` if nothing is highlighted or it is disabled.
+    pub fn toggle_selected(&mut self) -> Option<T> {
+        let index = self.state.selected()?;
+        let item = self.items.get(index).filter(|item| !item.disabled)?;
+        let status = match item.status {
+            OptionStatus::Checked => OptionStatus::Unchecked,
+            OptionStatus::Unchecked => OptionStatus::Checked,
+        };
+
+        if self.selection == Selection::Single {
+            for item in &mut self.items {
+                item.status = OptionStatus::Unchecked;
+            }
+        }
+        self.items[index].status = status;
+        Some(self.items[index].value)
+    }
+
+    /// Checks only the item holding `value`
+    pub fn set_checked(&mut self, value: T) {
+        for item in &mut self.items {
+            item.status = if item.value == value && !item.disabled {
+                OptionStatus::Checked
+            } else {
+                OptionStatus::Unchecked
+            };
+        }
+    }
+
+    /// Values of all checked items
+    pub fn checked(&self) -> impl Iterator<Item = T> + '_ {
+        self.items
+            .iter()
+            .filter(|item| item.status == OptionStatus::Checked)
+            .map(|item| item.value)
+    }
+
+    /// Value of the first checked item, the selection of a single-select list
+    pub fn checked_value(&self) -> Option<T> {
+        self.checked().next()
+    }
+
+    /// Highlights the next item that isn't disabled
+    pub fn select_next(&mut self) {
+        self.step(1);
+    }
+
+    /// Highlights the previous item that isn't disabled
+    pub fn select_previous(&mut self) {
+        self.step(-1);
+    }
+
+    /// Highlights the first item that isn't disabled
+    pub fn select_first(&mut self) {
+        let first = self.items.iter().position(|item| !item.disabled);
+        self.state.select(first);
+    }
+
+    /// Moves the highlight by `direction` until it lands on an enabled item, stopping at the ends
+    fn step(&mut self, direction: isize) {
+        let Some(mut index) = self.state.selected() else {
+            return self.select_first();
+        };
+        while let Some(next) = index
+            .checked_add_signed(direction)
+            .filter(|next| *next < self.items.len())
+        {
+            index = next;
+            if !self.items[index].disabled {
+                self.state.select(Some(index));
+                return;
+            }
+        }
+    }
+}
+
+impl<T> OptionItem<T> {
+    fn new(status: OptionStatus, option: &str, value: T) -> Self {
         Self {
             status,
-            option: todo.to_string(),
-            search_type,
+            option: option.to_string(),
+            value,
+            disabled: false,
         }
     }
 }
@@ -92,9 +249,12 @@ pub const fn alternate_colors(i: usize) -> Color {
     }
 }
 
-impl From<&OptionItem> for ListItem<'_> {
-    fn from(value: &OptionItem) -> Self {
+impl<T> From<&OptionItem<T>> for ListItem<'_> {
+    fn from(value: &OptionItem<T>) -> Self {
         let line = match value.status {
+            _ if value.disabled => {
+                Line::styled(format!(" ☐ {}", value.option), DISABLED_TEXT_FG_COLOR)
+            }
             OptionStatus::Unchecked => Line::styled(format!(" ☐ {}", value.option), TEXT_FG_COLOR),
             OptionStatus::Checked => {
                 Line::styled(format!(" ✓ {}", value.option), CHECKED_TEXT_FG_COLOR)
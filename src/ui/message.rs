@@ -7,6 +7,9 @@ use ratatui::{
     text::Line,
     widgets::{Block, Paragraph},
 };
+use serde::{Deserialize, Serialize};
+
+use crate::{colors::parse_color, events::AppEvent};
 
 impl Messages {
     /// Creates a new message and displays it
@@ -20,6 +23,33 @@ impl Messages {
         self.messages.push(msg);
     }
 
+    /// Removes the newest message with an action bound to `key` and returns the action's event
+    pub fn take_action(&mut self, key: char) -> Option<AppEvent> {
+        let index = self.messages.iter().rposition(|msg| {
+            msg.time.elapsed() < msg.duration
+                && msg.action.as_ref().is_some_and(|action| action.key == key)
+        })?;
+        self.messages
+            .remove(index)
+            .action
+            .map(|action| action.event)
+    }
+
+    /// Creates an empty message list that draws messages with the given colors
+    pub fn with_theme(theme: MessageTheme) -> Self {
+        Self {
+            messages: Vec::new(),
+            theme,
+            reduced_motion: false,
+        }
+    }
+
+    /// Leaves out the countdown of the messages, which changes every frame
+    pub const fn reduced_motion(mut self, reduced_motion: bool) -> Self {
+        self.reduced_motion = reduced_motion;
+        self
+    }
+
     /// Draws all unexpired messages to the screen. Multiple messages will be stacked from oldest to newest.
     ///
     /// # Example
@@ -33,42 +63,45 @@ impl Messages {
             .retain(|msg| msg.time.elapsed() < msg.duration);
 
         for (index, message) in self.messages.iter().enumerate() {
-            let width = message.text.len() as u16 + 4;
+            let msg_title = format!("{} {}", message.severity.symbol(), message.severity.title());
+            let hint = message
+                .action
+                .as_ref()
+                .map(|action| format!("  [{}] {}", action.key, action.label))
+                .unwrap_or_default();
+            let text_width = message.text.chars().count() + hint.chars().count();
+            let width = text_width.max(msg_title.chars().count()) as u16 + 4;
             let height = 3;
             let (size_x, _) = crossterm::terminal::size().unwrap_or((80, 24));
-            let x = size_x - width - 1; // 1 is the distance from the right edge
+            let x = size_x.saturating_sub(width + 1); // 1 is the distance from the right edge
             let y = index as u16 * (height + 1) + 1;
 
-            let msg_title = match message.severity {
-                MessageSeverity::Error => "Error",
-                MessageSeverity::Warning => "Warning",
-                MessageSeverity::Info => "Info",
-            };
-            let msg_color = match message.severity {
-                MessageSeverity::Error => Color::Red,
-                MessageSeverity::Warning => Color::Yellow,
-                MessageSeverity::Info => Color::White,
-            };
-            let msg_block = Block::bordered()
+            let (msg_color, text_color) = self.theme.colors(message.severity);
+            let mut msg_block = Block::bordered()
                 .title(msg_title)
                 .title_alignment(HorizontalAlignment::Center)
-                .title_bottom(format!(
+                .bg(msg_color)
+                .fg(text_color);
+            if !self.reduced_motion {
+                msg_block = msg_block.title_bottom(format!(
                     "{:.1}s",
                     message
                         .duration
                         .saturating_sub(message.time.elapsed())
                         .as_millis() as f64
                         / 1000.0
-                ))
-                .bg(msg_color)
-                .fg(Color::Black);
+                ));
+            }
 
             let area = Rect::new(x, y, width, height);
             let block_area = msg_block.inner(area);
 
-            let msg = Paragraph::new(Line::from(format!(" {}", message.text)))
-                .bg(msg_color)
-                .fg(Color::Black);
+            let msg = Paragraph::new(Line::from(vec![
+                format!(" {}", message.text).into(),
+                hint.bold(),
+            ]))
+            .bg(msg_color)
+            .fg(text_color);
 
             frame.render_widget(msg_block, area);
             frame.render_widget(msg, block_area);
@@ -97,24 +130,133 @@ impl Message {
     pub fn new(text: impl Into<String>, severity: MessageSeverity, duration: Duration) -> Self {
         Self {
             text: text.into(),
-            severity: severity,
+            severity,
             duration,
             time: Instant::now(),
+            action: None,
         }
     }
+
+    /// Adds an action the user can trigger with `key` while the message is shown.
+    /// The event is dispatched through the event loop and the message is dismissed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let msg = Message::new("Deleted 3 files", MessageSeverity::Info, Duration::from_secs(5))
+    ///     .action('u', "undo", AppEvent::Undo);
+    /// ```
+    pub fn action(mut self, key: char, label: impl Into<String>, event: AppEvent) -> Self {
+        self.action = Some(MessageAction {
+            key,
+            label: label.into(),
+            event,
+        });
+        self
+    }
+}
+
+/// Something the user can do from a message, shown as a key hint.
+pub struct MessageAction {
+    /// Key that triggers the action
+    pub key: char,
+    /// Short description shown next to the key
+    pub label: String,
+    /// Event sent to the event loop when the key is pressed
+    pub event: AppEvent,
 }
 
 #[allow(unused)]
-/// Severity of a message. This controls the background color and title.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Severity of a message. This controls the symbol, title and colors.
 pub enum MessageSeverity {
-    /// Info, the Background is white and the title is "Info"
+    /// Info, the title is "i Info"
     Info,
-    /// Warning, the Background is yellow and the title is "Warning"
+    /// Warning, the title is "! Warning"
     Warning,
-    /// Error, the Background is red and the title is "Error"
+    /// Error, the title is "✖ Error"
     Error,
 }
 
+impl MessageSeverity {
+    /// Symbol shown in front of the title, so the severity doesn't depend on colors alone
+    pub const fn symbol(self) -> &'static str {
+        match self {
+            MessageSeverity::Info => "i",
+            MessageSeverity::Warning => "!",
+            MessageSeverity::Error => "✖",
+        }
+    }
+
+    pub const fn title(self) -> &'static str {
+        match self {
+            MessageSeverity::Info => "Info",
+            MessageSeverity::Warning => "Warning",
+            MessageSeverity::Error => "Error",
+        }
+    }
+}
+
+/// Colors of messages with one severity, as `#rrggbb` or a color name.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SeverityColors {
+    pub background: String,
+    pub foreground: String,
+}
+
+impl SeverityColors {
+    fn new(background: &str, foreground: &str) -> Self {
+        Self {
+            background: background.into(),
+            foreground: foreground.into(),
+        }
+    }
+}
+
+/// Colors of the messages per severity, configurable in the config file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct MessageTheme {
+    pub info: SeverityColors,
+    pub warning: SeverityColors,
+    pub error: SeverityColors,
+}
+
+impl Default for MessageTheme {
+    /// Colors that stay readable on dark and light terminals
+    fn default() -> Self {
+        Self {
+            info: SeverityColors::new("#304890", "#ffffff"),
+            warning: SeverityColors::new("#e6b41e", "#000000"),
+            error: SeverityColors::new("#b42828", "#ffffff"),
+        }
+    }
+}
+
+impl MessageTheme {
+    /// Background and text color of a severity. Colors that can't be parsed fall back to the default.
+    fn colors(&self, severity: MessageSeverity) -> (Color, Color) {
+        let pick = |theme: &MessageTheme| match severity {
+            MessageSeverity::Info => theme.info.clone(),
+            MessageSeverity::Warning => theme.warning.clone(),
+            MessageSeverity::Error => theme.error.clone(),
+        };
+        let configured = pick(self);
+        let fallback = pick(&MessageTheme::default());
+
+        let color = |value: &str, fallback: &str| {
+            let [r, g, b] = parse_color(value)
+                .or_else(|| parse_color(fallback))
+                .unwrap_or_default();
+            Color::Rgb(r, g, b)
+        };
+        (
+            color(&configured.background, &fallback.background),
+            color(&configured.foreground, &fallback.foreground),
+        )
+    }
+}
+
 /// Structure containing messages. Implementation handles adding and displaying them.
 ///
 /// # Example
@@ -125,6 +267,10 @@ pub enum MessageSeverity {
 pub struct Messages {
     /// Vector containing the messages
     messages: Vec<Message>,
+    /// Colors of the messages
+    theme: MessageTheme,
+    /// Whether the countdown is left out
+    reduced_motion: bool,
 }
 
 /// Message struct. This includes text, severity, duration, and creation time.
@@ -137,14 +283,14 @@ pub struct Message {
     pub duration: Duration,
     /// Time the message has been created
     pub time: Instant,
+    /// Optional action triggered by a key while the message is shown
+    pub action: Option<MessageAction>,
 }
 
 impl Default for Messages {
     /// Default Messages struct containing an empty Vector
     fn default() -> Self {
-        Self {
-            messages: Vec::new(),
-        }
+        Self::with_theme(MessageTheme::default())
     }
 }
 
@@ -156,6 +302,7 @@ impl Default for Message {
             severity: MessageSeverity::Info,
             duration: Duration::from_secs(3),
             time: Instant::now(),
+            action: None,
         }
     }
 }
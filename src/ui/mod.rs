@@ -1,3 +1,14 @@
 pub mod button;
+pub mod canvas;
+pub mod cleanup;
+pub mod grid;
+pub mod hud;
+pub mod jobs;
 pub mod list;
 pub mod message;
+pub mod modal;
+pub mod palette;
+pub mod settings;
+pub mod stats;
+pub mod throbber;
+pub mod timeline;
@@ -0,0 +1,59 @@
+use std::mem;
+
+use crate::ui::{
+    jobs::JobsView, palette::CommandPalette, settings::SettingsPopup, stats::StatsView,
+};
+
+/// A popup drawn over the interface, which gets the keys while it is on top
+#[derive(Debug)]
+pub enum Modal {
+    /// The mode selector, its list is `App::modesel_list`
+    ModeSelector,
+    /// Boxed since it holds a copy of the whole config
+    Settings(Box<SettingsPopup>),
+    Stats(StatsView),
+    Jobs(JobsView),
+    Palette(CommandPalette),
+    /// Asks before the files are moved to the trash
+    ConfirmDelete(Vec<String>),
+}
+
+/// The open popups, the one opened last on top. Keys go to the top one only, and they are
+/// drawn from the bottom up, so a confirmation opened from a popup shows over it and closing
+/// it gives the keys back to the popup below.
+#[derive(Debug, Default)]
+pub struct Modals {
+    stack: Vec<Modal>,
+}
+
+impl Modals {
+    /// Opens a popup on top of the others. A popup of the same kind that is open already is
+    /// replaced, so it never shows twice.
+    pub fn open(&mut self, modal: Modal) {
+        self.stack
+            .retain(|open| mem::discriminant(open) != mem::discriminant(&modal));
+        self.stack.push(modal);
+    }
+
+    /// Closes the popup on top and returns it
+    pub fn close(&mut self) -> Option<Modal> {
+        self.stack.pop()
+    }
+
+    pub fn top(&self) -> Option<&Modal> {
+        self.stack.last()
+    }
+
+    pub fn top_mut(&mut self) -> Option<&mut Modal> {
+        self.stack.last_mut()
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.stack.is_empty()
+    }
+
+    /// The open popups from the bottom up, in the order they are drawn
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Modal> {
+        self.stack.iter_mut()
+    }
+}
@@ -0,0 +1,171 @@
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, HorizontalAlignment, Layout},
+    style::{Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, BorderType, Clear, List, ListItem, ListState, Paragraph},
+};
+
+use crate::{
+    actions::Action,
+    plugins::PluginAction,
+    ui::{button::Theme, list::alternate_colors},
+};
+
+/// Score bonus for a matched character that directly follows the previous match
+const CONSECUTIVE_BONUS: i32 = 5;
+/// Score bonus for a matched character at the start of a word
+const WORD_START_BONUS: i32 = 3;
+
+/// Something the command palette can run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    Action(Action),
+    /// An action of a plugin, run on the selected result
+    Plugin(PluginAction),
+}
+
+impl Entry {
+    fn name(&self) -> &str {
+        match self {
+            Entry::Action(action) => action.name(),
+            Entry::Plugin(action) => &action.name,
+        }
+    }
+
+    fn key(&self) -> &'static str {
+        match self {
+            Entry::Action(action) => action.key().unwrap_or_default(),
+            Entry::Plugin(_) => "plugin",
+        }
+    }
+}
+
+/// The command palette: every action, narrowed down by typing part of its name
+#[derive(Debug, Default)]
+pub struct CommandPalette {
+    pub query: String,
+    pub state: ListState,
+    /// Actions registered by plugins, listed after the built-in ones
+    plugin_actions: Vec<PluginAction>,
+}
+
+impl CommandPalette {
+    pub fn new(plugin_actions: Vec<PluginAction>) -> Self {
+        Self {
+            query: String::new(),
+            state: ListState::default().with_selected(Some(0)),
+            plugin_actions,
+        }
+    }
+
+    /// The entries matching the query, best match first
+    pub fn matches(&self) -> Vec<Entry> {
+        let entries = Action::ALL
+            .iter()
+            .map(|action| Entry::Action(*action))
+            .chain(self.plugin_actions.iter().cloned().map(Entry::Plugin));
+        let mut scored: Vec<(i32, Entry)> = entries
+            .filter_map(|entry| Some((fuzzy_score(&self.query, entry.name())?, entry)))
+            .collect();
+        // stable, so equally good matches keep the order of the registry
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// The highlighted entry, `None` if nothing matches
+    pub fn selected(&self) -> Option<Entry> {
+        self.matches()
+            .get(self.state.selected().unwrap_or_default())
+            .cloned()
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.state.select_first();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.state.select_first();
+    }
+
+    /// Draws the palette in the upper middle of the screen
+    pub fn draw(&mut self, frame: &mut Frame, theme: Theme) {
+        let matches = self.matches();
+        let [_, area] = Layout::vertical([
+            Constraint::Percentage(15),
+            Constraint::Length((Action::ALL.len() + self.plugin_actions.len()) as u16 + 5),
+        ])
+        .areas(frame.area());
+        let [area] = Layout::horizontal([Constraint::Percentage(50)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [input_area, list_area] =
+            Layout::vertical([Constraint::Length(3), Constraint::Fill(1)]).areas(area);
+
+        let input = Paragraph::new(format!("{}▏", self.query)).block(
+            Block::bordered()
+                .title("Commands")
+                .title_alignment(HorizontalAlignment::Center)
+                .border_type(BorderType::Rounded)
+                .fg(theme.background),
+        );
+
+        let width = list_area.width.saturating_sub(4) as usize;
+        let items: Vec<ListItem> = matches
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let key = entry.key();
+                let name_width = width.saturating_sub(key.len());
+                ListItem::new(Line::from(vec![
+                    format!(" {:<1$}", entry.name(), name_width).into(),
+                    key.bold(),
+                ]))
+                .bg(alternate_colors(i))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title_bottom("Type to filter, Enter to run, Esc to close")
+                    .border_type(BorderType::Rounded)
+                    .fg(theme.background),
+            )
+            .highlight_style(
+                Style::new()
+                    .bg(theme.highlight)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol(">");
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(input, input_area);
+        frame.render_stateful_widget(list, list_area, &mut self.state);
+    }
+}
+
+/// Scores how well `query` matches `name` when its characters appear in order, not
+/// necessarily next to each other. Runs of characters and word starts score higher.
+/// Returns `None` if the name doesn't contain the query's characters in order.
+fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + name[position..].iter().position(|n| *n == c)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += CONSECUTIVE_BONUS;
+        }
+        if found == 0 || name[found - 1] == ' ' {
+            score += WORD_START_BONUS;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
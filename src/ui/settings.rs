@@ -0,0 +1,187 @@
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, HorizontalAlignment, Layout},
+    style::{Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, BorderType, Clear, List, ListItem, ListState},
+};
+
+use crate::{
+    config::{Config, ImageProtocol},
+    ui::{
+        button::{Theme, ThemeColor},
+        list::{SearchEnum, alternate_colors},
+    },
+};
+
+/// Amount the result count changes by per key press
+const RESULT_COUNT_STEP: usize = 5;
+const MAX_RESULT_COUNT: usize = 100;
+/// Amount the width of the search bar changes by per key press, in percent
+const SEARCH_BAR_STEP: u16 = 5;
+/// Narrowest and widest the search bar gets, so the buttons stay usable
+const SEARCH_BAR_WIDTHS: (u16, u16) = (30, 80);
+
+/// Settings that can be changed from the settings popup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    ResultCount,
+    Theme,
+    SearchBarWidth,
+    Protocol,
+    DefaultMode,
+    LibraryDir,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 6] = [
+        Setting::ResultCount,
+        Setting::Theme,
+        Setting::SearchBarWidth,
+        Setting::Protocol,
+        Setting::DefaultMode,
+        Setting::LibraryDir,
+    ];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Setting::ResultCount => "Results per search",
+            Setting::Theme => "Theme",
+            Setting::SearchBarWidth => "Search bar width",
+            Setting::Protocol => "Image protocol",
+            Setting::DefaultMode => "Default mode",
+            Setting::LibraryDir => "Library directory",
+        }
+    }
+
+    /// The current value as shown in the popup
+    fn value(self, config: &Config) -> String {
+        match self {
+            Setting::ResultCount => config.result_count.to_string(),
+            Setting::Theme => config.theme.name().into(),
+            Setting::SearchBarWidth => format!("{}%", config.search_bar_width),
+            Setting::Protocol => config.protocol.name().into(),
+            Setting::DefaultMode => config.default_mode.name().into(),
+            Setting::LibraryDir => config.library_dir().display().to_string(),
+        }
+    }
+
+    /// Steps the setting to the next (`step` > 0) or previous value.
+    /// Returns `false` for settings that are typed in instead.
+    pub fn adjust(self, config: &mut Config, step: isize) -> bool {
+        match self {
+            Setting::ResultCount => {
+                config.result_count = if step > 0 {
+                    config.result_count + RESULT_COUNT_STEP
+                } else {
+                    config.result_count.saturating_sub(RESULT_COUNT_STEP)
+                }
+                .clamp(1, MAX_RESULT_COUNT);
+            }
+            Setting::Theme => config.theme = cycle(&ThemeColor::ALL, config.theme, step),
+            Setting::SearchBarWidth => {
+                let (narrowest, widest) = SEARCH_BAR_WIDTHS;
+                config.search_bar_width = if step > 0 {
+                    config.search_bar_width + SEARCH_BAR_STEP
+                } else {
+                    config.search_bar_width.saturating_sub(SEARCH_BAR_STEP)
+                }
+                .clamp(narrowest, widest);
+            }
+            Setting::Protocol => {
+                config.protocol = cycle(&ImageProtocol::ALL, config.protocol, step)
+            }
+            Setting::DefaultMode => {
+                config.default_mode = cycle(&SearchEnum::ALL, config.default_mode, step)
+            }
+            Setting::LibraryDir => return false,
+        }
+        true
+    }
+}
+
+/// Returns the value `step` places after `current` in `all`, wrapping around at the ends
+fn cycle<T: Copy + PartialEq>(all: &[T], current: T, step: isize) -> T {
+    let index = all
+        .iter()
+        .position(|value| *value == current)
+        .unwrap_or_default();
+    all[(index as isize + step).rem_euclid(all.len() as isize) as usize]
+}
+
+/// The settings popup: a list of settings, one of which can be typed in at a time
+#[derive(Debug)]
+pub struct SettingsPopup {
+    pub state: ListState,
+    /// The config as it was when the popup was opened, to tell what changed when it is closed
+    pub initial: Config,
+    /// Text of the setting being typed in, `None` while no setting is edited
+    pub editing: Option<String>,
+}
+
+impl SettingsPopup {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            state: ListState::default().with_selected(Some(0)),
+            initial: config.clone(),
+            editing: None,
+        }
+    }
+
+    /// The highlighted setting
+    pub fn selected(&self) -> Setting {
+        let index = self.state.selected().unwrap_or_default();
+        Setting::ALL[index.min(Setting::ALL.len() - 1)]
+    }
+
+    /// Draws the popup in the middle of the screen
+    pub fn draw(&mut self, frame: &mut Frame, config: &Config, theme: Theme) {
+        let [area] = Layout::vertical([Constraint::Length(Setting::ALL.len() as u16 + 2)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let [area] = Layout::horizontal([Constraint::Percentage(60)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let hint = if self.editing.is_some() {
+            "Type the path, Enter to apply, Esc to cancel"
+        } else {
+            "Up/Down to move, Left/Right to change, Enter to edit, Esc to close"
+        };
+        let block = Block::bordered()
+            .title("Settings")
+            .title_alignment(HorizontalAlignment::Center)
+            .title_bottom(hint)
+            .border_type(BorderType::Rounded)
+            .fg(theme.background);
+
+        let selected = self.selected();
+        let items: Vec<ListItem> = Setting::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, setting)| {
+                let value = match &self.editing {
+                    Some(text) if *setting == selected => format!("{}▏", text),
+                    _ => setting.value(config),
+                };
+                ListItem::new(Line::from(vec![
+                    format!(" {:<20}", setting.label()).into(),
+                    value.bold(),
+                ]))
+                .bg(alternate_colors(i))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(
+                Style::new()
+                    .bg(theme.highlight)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol(">");
+
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(list, area, &mut self.state);
+    }
+}
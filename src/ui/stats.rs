@@ -0,0 +1,97 @@
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, HorizontalAlignment, Layout},
+    style::{Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, BorderType, Clear, List, ListItem, ListState, Paragraph},
+};
+
+use crate::{
+    locale::Locale,
+    ui::{button::Theme, list::alternate_colors},
+};
+
+/// Numbers about the library and the files that could not be read, with the reason
+#[derive(Debug, Default)]
+pub struct StatsView {
+    pub locale: Locale,
+    /// Local images in the library directory
+    pub images: usize,
+    /// Images on the web, see `remote::Remotes`
+    pub remote: usize,
+    /// Images with an embedding of the selected model
+    pub indexed: usize,
+    pub pinned: usize,
+    /// Files that failed to index, this session or before, or to decode for a search, with
+    /// the reason
+    pub unreadable: Vec<(String, String)>,
+    pub state: ListState,
+}
+
+impl StatsView {
+    pub fn draw(&mut self, frame: &mut Frame, theme: Theme) {
+        let [area] = Layout::vertical([Constraint::Percentage(70)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let [area] = Layout::horizontal([Constraint::Percentage(70)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [numbers_area, list_area] =
+            Layout::vertical([Constraint::Length(6), Constraint::Fill(1)]).areas(area);
+
+        let row = |name: &str, value: usize| {
+            Line::from(vec![
+                format!(" {:<20}", name).into(),
+                self.locale.count(value).bold(),
+            ])
+        };
+        let numbers = Paragraph::new(vec![
+            row("Local images", self.images),
+            row("Remote items", self.remote),
+            row("Indexed", self.indexed),
+            row("Pinned", self.pinned),
+        ])
+        .block(
+            Block::bordered()
+                .title("Stats")
+                .title_alignment(HorizontalAlignment::Center)
+                .border_type(BorderType::Rounded)
+                .fg(theme.background),
+        );
+
+        let items: Vec<ListItem> = self
+            .unreadable
+            .iter()
+            .enumerate()
+            .map(|(i, (path, reason))| {
+                ListItem::new(Line::from(vec![
+                    format!(" {} ", path).bold(),
+                    reason.clone().into(),
+                ]))
+                .bg(alternate_colors(i))
+            })
+            .collect();
+        let hint = if self.unreadable.is_empty() {
+            "Esc to close"
+        } else {
+            "r to retry, x to remove them from the index, Esc to close"
+        };
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(format!("Unreadable files ({})", self.unreadable.len()))
+                    .title_bottom(hint)
+                    .border_type(BorderType::Rounded)
+                    .fg(theme.background),
+            )
+            .highlight_style(
+                Style::new()
+                    .bg(theme.highlight)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(numbers, numbers_area);
+        frame.render_stateful_widget(list, list_area, &mut self.state);
+    }
+}
@@ -0,0 +1,62 @@
+use ratatui::{buffer::Buffer, layout::Rect, style::Style, widgets::Widget};
+use std::time::{Duration, Instant};
+
+use crate::terminal;
+
+const FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+/// Frames for the old Windows console, whose font has no Braille characters
+const ASCII_FRAMES: [&str; 4] = ["|", "/", "-", "\\"];
+/// How long each frame of the animation is shown
+const FRAME_DURATION: Duration = Duration::from_millis(80);
+
+/// A one cell spinner showing that something is running.
+/// The frame is picked from the time since it started, so it animates whenever the UI redraws.
+#[derive(Debug, Clone)]
+pub struct Throbber {
+    started: Instant,
+    style: Style,
+    /// Whether the first frame is drawn every time, for `Config::reduced_motion`
+    still: bool,
+}
+
+impl Throbber {
+    /// Creates a throbber for work that started at `started`
+    pub fn new(started: Instant) -> Self {
+        Self {
+            started,
+            style: Style::default(),
+            still: false,
+        }
+    }
+
+    /// Sets the style of the throbber
+    pub const fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Stops the animation, the throbber then only marks where the work is shown
+    pub const fn still(mut self, still: bool) -> Self {
+        self.still = still;
+        self
+    }
+}
+
+impl Widget for Throbber {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.is_empty() {
+            return;
+        }
+        let frames: &[&str] = if terminal::is_legacy_console() {
+            &ASCII_FRAMES
+        } else {
+            &FRAMES
+        };
+        let frame = if self.still {
+            0
+        } else {
+            (self.started.elapsed().as_millis() / FRAME_DURATION.as_millis()) as usize
+        };
+        buf.set_string(area.x, area.y, frames[frame % frames.len()], self.style);
+    }
+}
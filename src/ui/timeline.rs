@@ -0,0 +1,67 @@
+use ratatui::layout::Rect;
+
+/// Height of a row of images in the timeline
+const ROW_HEIGHT: u16 = 10;
+/// Number of images per row
+const COLUMNS: u16 = 4;
+
+/// Result cells grouped under section headers, positioned as if the area was tall enough
+/// for all of them. `scroll` and `visible` map them onto the real area.
+pub struct Timeline {
+    /// Cell of every result, indexed by result
+    pub cells: Vec<Rect>,
+    /// Header line and title of every section
+    pub headers: Vec<(Rect, String)>,
+}
+
+impl Timeline {
+    /// Lays out results which are already sorted by date.
+    ///
+    /// # Arguments
+    ///
+    /// - area: The area the timeline is shown in, only its width and top are used.
+    /// - sections: The section title of every result, equal titles have to be next to each other.
+    pub fn new(area: Rect, sections: &[String]) -> Self {
+        let width = area.width / COLUMNS;
+        let mut cells = Vec::with_capacity(sections.len());
+        let mut headers = Vec::new();
+        let mut y = area.y;
+        let mut column = 0;
+
+        for (i, title) in sections.iter().enumerate() {
+            if i == 0 || sections[i - 1] != *title {
+                if column > 0 {
+                    y += ROW_HEIGHT;
+                }
+                headers.push((Rect::new(area.x, y, area.width, 1), title.clone()));
+                y += 1;
+                column = 0;
+            } else if column == COLUMNS {
+                y += ROW_HEIGHT;
+                column = 0;
+            }
+
+            cells.push(Rect::new(area.x + column * width, y, width, ROW_HEIGHT));
+            column += 1;
+        }
+
+        Self { cells, headers }
+    }
+
+    /// How far the timeline has to be scrolled for the selected cell to fit into `area`
+    pub fn scroll(&self, area: Rect, selected: usize) -> u16 {
+        self.cells
+            .get(selected)
+            .map(|cell| cell.bottom().saturating_sub(area.bottom()))
+            .unwrap_or_default()
+    }
+}
+
+/// Moves a cell up by `offset`. Returns `None` if it is not completely inside `area` afterwards.
+pub fn visible(area: Rect, rect: Rect, offset: u16) -> Option<Rect> {
+    let rect = Rect {
+        y: rect.y.checked_sub(offset)?,
+        ..rect
+    };
+    (rect.y >= area.y && rect.bottom() <= area.bottom()).then_some(rect)
+}
@@ -0,0 +1,35 @@
+use regex::Regex;
+use std::{process::Stdio, sync::mpsc::Sender, thread};
+
+use crate::{events::AppEvent, hooks};
+
+/// Records a spoken query with `command` on a background thread and sends what it printed as
+/// an `AppEvent::Transcribed`. The command records from the microphone and prints the
+/// transcription, e.g. a script around whisper.cpp. It can't use the terminal, which belongs
+/// to findimg: its stdin is closed and its errors are only shown if it fails.
+pub fn listen(command: &str, sender: Sender<AppEvent>) {
+    let command = command.to_string();
+    thread::spawn(move || {
+        let _ = sender.send(AppEvent::Transcribed(transcribe(&command)));
+    });
+}
+
+fn transcribe(command: &str) -> Result<String, String> {
+    let output = hooks::shell(command)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        return Err(error
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .map_or_else(|| output.status.to_string(), str::to_string));
+    }
+    // whisper.cpp marks silence and noises like "[BLANK_AUDIO]" or "(wind blowing)"
+    let noise = Regex::new(r"\[[^\]]*\]|\([^)]*\)").unwrap();
+    let text = String::from_utf8_lossy(&output.stdout);
+    let spoken = noise.replace_all(&text, " ");
+    Ok(spoken.split_whitespace().collect::<Vec<_>>().join(" "))
+}
@@ -0,0 +1,110 @@
+use cliprs::ClipModel;
+use std::{
+    env,
+    path::Path,
+    process::{Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    config::Config,
+    curate::{self, Library},
+    engine::Similarity,
+    paths,
+    query::{self, Filter},
+};
+
+/// Number of best matches the wallpaper is picked from, so it changes between runs
+const CANDIDATES: usize = 10;
+/// Smallest wallpaper unless the prompt has a `minres:` filter
+const MIN_RESOLUTION: (u32, u32) = (1920, 1080);
+
+/// Picks a random image among the best matches of the prompt that are large enough
+/// to be a wallpaper. Filters in the prompt work like in the search bar.
+pub fn pick(
+    model: &ClipModel,
+    library: &Library,
+    config: &Config,
+    similarity: Similarity,
+    prompt: &str,
+) -> Result<String, String> {
+    let mut query = query::parse(prompt);
+    if !query
+        .filters
+        .iter()
+        .any(|filter| matches!(filter, Filter::MinResolution(..)))
+    {
+        let (width, height) = MIN_RESOLUTION;
+        query.filters.push(Filter::MinResolution(width, height));
+    }
+
+    let matches = curate::best_matches(model, library, config, similarity, &query, CANDIDATES)?;
+    if matches.is_empty() {
+        return Err("No image matching the prompt is large enough for a wallpaper".into());
+    }
+    // the time is random enough to pick one of a few images
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.subsec_nanos());
+    Ok(matches[nanos as usize % matches.len()].path.clone())
+}
+
+/// Sets an image as the desktop wallpaper with the tool of the desktop:
+/// AppleScript on macOS, gsettings on GNOME, swaybg on other Wayland compositors and feh on X11
+pub fn set(path: &Path) -> Result<(), String> {
+    let path = paths::canonicalize(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let path = path.to_string_lossy();
+
+    if cfg!(target_os = "macos") {
+        let script = format!(
+            "tell application \"System Events\" to tell every desktop to set picture to \"{}\"",
+            path.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+        return run(Command::new("osascript").args(["-e", &script]));
+    }
+    if cfg!(windows) {
+        return Err("Setting the wallpaper is not supported on Windows yet".into());
+    }
+
+    let desktop = env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    if desktop.to_uppercase().contains("GNOME") {
+        let uri = format!("file://{}", path);
+        for key in ["picture-uri", "picture-uri-dark"] {
+            run(Command::new("gsettings").args([
+                "set",
+                "org.gnome.desktop.background",
+                key,
+                &uri,
+            ]))?;
+        }
+        return Ok(());
+    }
+
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        // swaybg keeps running to draw the wallpaper, so the previous one is replaced
+        let _ = Command::new("pkill").arg("-x").arg("swaybg").status();
+        return Command::new("swaybg")
+            .args(["-m", "fill", "-i", &path])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to start swaybg: {}", e));
+    }
+
+    run(Command::new("feh").args(["--bg-fill", &path]))
+}
+
+fn run(command: &mut Command) -> Result<(), String> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let status = command
+        .stdout(Stdio::null())
+        .status()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} failed with {}", program, status))
+    }
+}